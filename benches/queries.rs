@@ -0,0 +1,106 @@
+//! Leaderboard and summary queries against synthetic databases of 10k–1M sessions.
+//!
+//! `cargo bench --bench queries`; `BENCH_SIZES=10000,100000` picks other sizes.
+
+use chrono::Duration;
+use clockbot::db::Db;
+use clockbot::weeks::WeekId;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::{Connection, params};
+use std::path::Path;
+use tempfile::TempDir;
+
+const USERS: usize = 200;
+const ACTIVITIES: &[&str] = &["backend", "frontend", "code-review", "meetings", "docs", "ops", "research"];
+
+fn sizes() -> Vec<usize> {
+    std::env::var("BENCH_SIZES")
+        .ok()
+        .map(|s| s.split(',').filter_map(|n| n.trim().parse().ok()).collect())
+        .unwrap_or_else(|| vec![10_000, 100_000, 1_000_000])
+}
+
+/// Fill `path` with `sessions` finished sessions spread over the current week, plus a
+/// handful of running ones. Written in one transaction straight through SQLite;
+/// going through `Db` row by row would take minutes at 1M.
+fn populate(path: &Path, sessions: usize) {
+    drop(Db::open(path).unwrap());
+    let mut conn = Connection::open(path).unwrap();
+    let tx = conn.transaction().unwrap();
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO sessions (user_id, username, activity, started_at, ended_at, minutes)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .unwrap();
+        let mut rng = StdRng::seed_from_u64(sessions as u64);
+        let monday = WeekId::current().start();
+        for i in 0..sessions {
+            let user = rng.gen_range(0..USERS);
+            let start = monday + Duration::minutes(rng.gen_range(0..6 * 24 * 60));
+            let minutes = rng.gen_range(5..240);
+            let ended = (i >= USERS / 10).then(|| start + Duration::minutes(minutes));
+            insert
+                .execute(params![
+                    format!("{}", 100_000 + user),
+                    format!("user{user}"),
+                    ACTIVITIES[rng.gen_range(0..ACTIVITIES.len())],
+                    start.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    ended.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    ended.map(|_| minutes),
+                ])
+                .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+}
+
+fn queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queries");
+    group.sample_size(10);
+    for size in sizes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bench.db");
+        populate(&path, size);
+        let db = Db::open(&path).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("leaderboard_weekly", size), &db, |b, db| {
+            b.iter(|| {
+                db.clear_cache();
+                db.leaderboard_weekly(false).unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("leaderboard_alltime", size), &db, |b, db| {
+            b.iter(|| {
+                db.clear_cache();
+                db.leaderboard_alltime(false).unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("activity_breakdown_weekly", size), &db, |b, db| {
+            b.iter(|| {
+                db.clear_cache();
+                db.activity_breakdown_weekly().unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("who_is_working", size), &db, |b, db| {
+            b.iter(|| {
+                db.clear_cache();
+                db.who_is_working().unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("weekly_summary", size), &db, |b, db| {
+            b.iter(|| db.weekly_summary(WeekId::current()).unwrap())
+        });
+        // The same leaderboard as served between writes, for comparison
+        group.bench_with_input(BenchmarkId::new("leaderboard_weekly_cached", size), &db, |b, db| {
+            b.iter(|| db.leaderboard_weekly(false).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
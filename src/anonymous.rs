@@ -0,0 +1,180 @@
+//! Anonymous mode (`/clock config anonymous on`) for communities with strict privacy
+//! norms: public summaries and leaderboards show `Member #1`, `Member #2`, … numbered by
+//! place, so the numbers say nothing about who someone is from one post to the next.
+//! People still see their own identified stats by DM.
+
+use crate::db::{ActivityEntry, ChartData, Db, LeaderboardEntry, WeeklySummary};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+pub fn enabled(db: &Db) -> bool {
+    crate::config::is_on(db, crate::config::ANONYMOUS)
+}
+
+/// Labels numbered in the order names are first seen, so seeing a ranking first numbers
+/// people by place.
+#[derive(Debug, Default)]
+pub struct Labels(HashMap<String, usize>);
+
+impl Labels {
+    pub fn ranked<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut labels = Self::default();
+        for name in names {
+            labels.get(name);
+        }
+        labels
+    }
+
+    pub fn get(&mut self, name: &str) -> String {
+        let next = self.0.len() + 1;
+        format!("Member #{}", *self.0.entry(name.to_string()).or_insert(next))
+    }
+}
+
+/// Replace the name of each row, in ranking order.
+pub fn relabel<T>(rows: &mut [T], name: impl Fn(&mut T) -> &mut String) {
+    let mut labels = Labels::default();
+    for row in rows {
+        let name = name(row);
+        *name = labels.get(name);
+    }
+}
+
+/// Labels by this week's competitive minutes (as on the leaderboard), then anyone left.
+fn summary_labels(summary: &WeeklySummary) -> Labels {
+    let ranking = crate::dashboard::ranking(summary);
+    let mut labels = Labels::ranked(ranking.iter().map(|(name, _)| name.as_str()));
+    for (name, _) in breakdown_ranking(&summary.breakdown) {
+        labels.get(&name);
+    }
+    labels
+}
+
+/// Everyone in `entries` by their total minutes, most first.
+fn breakdown_ranking(entries: &[ActivityEntry]) -> Vec<(String, i64)> {
+    let mut totals: HashMap<&str, i64> = HashMap::new();
+    for e in entries {
+        *totals.entry(&e.username).or_default() += e.total_minutes;
+    }
+    let mut totals: Vec<(String, i64)> = totals.into_iter().map(|(name, m)| (name.to_string(), m)).collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+/// `summary` with every name replaced, the MVP and awards included.
+pub fn anonymize_summary(summary: &WeeklySummary) -> WeeklySummary {
+    let mut labels = summary_labels(summary);
+    let mut out = summary.clone();
+    for e in &mut out.breakdown {
+        e.username = labels.get(&e.username);
+    }
+    if let Some((name, _)) = &mut out.mvp {
+        *name = labels.get(name);
+    }
+    if let Some((name, _, _)) = &mut out.longest_session {
+        *name = labels.get(name);
+    }
+    if let Some((name, _)) = &mut out.most_appreciated {
+        *name = labels.get(name);
+    }
+    out
+}
+
+/// Per-person activity rows with people numbered by their total.
+pub fn anonymize_breakdown(entries: &[ActivityEntry]) -> Vec<ActivityEntry> {
+    let ranking = breakdown_ranking(entries);
+    let mut labels = Labels::ranked(ranking.iter().map(|(name, _)| name.as_str()));
+    entries
+        .iter()
+        .map(|e| ActivityEntry { username: labels.get(&e.username), ..e.clone() })
+        .collect()
+}
+
+/// The weekly summary as posted publicly.
+pub fn public_summary<'a>(db: &Db, summary: &'a WeeklySummary) -> Cow<'a, WeeklySummary> {
+    if enabled(db) { Cow::Owned(anonymize_summary(summary)) } else { Cow::Borrowed(summary) }
+}
+
+/// A leaderboard, in rank order, as posted publicly.
+pub fn public_board(db: &Db, mut entries: Vec<LeaderboardEntry>) -> Vec<LeaderboardEntry> {
+    if enabled(db) {
+        relabel(&mut entries, |e| &mut e.username);
+    }
+    entries
+}
+
+/// Per-person activity rows as posted publicly.
+pub fn public_breakdown(db: &Db, entries: Vec<ActivityEntry>) -> Vec<ActivityEntry> {
+    if enabled(db) { anonymize_breakdown(&entries) } else { entries }
+}
+
+/// Chart data (users largest first) as posted publicly.
+pub fn public_chart(db: &Db, mut data: ChartData) -> ChartData {
+    if enabled(db) {
+        relabel(&mut data.users, |u| &mut u.username);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{make_user, make_week_of_sessions, test_db_at};
+    use chrono::NaiveDate;
+
+    fn turn_on(db: &Db) {
+        crate::config::set(db, crate::config::setting(crate::config::ANONYMOUS).unwrap(), "on").unwrap();
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut labels = Labels::ranked(["Bob", "Alice"]);
+        assert_eq!(labels.get("Alice"), "Member #2");
+        assert_eq!(labels.get("Bob"), "Member #1");
+        assert_eq!(labels.get("Carol"), "Member #3");
+        assert_eq!(labels.get("Carol"), "Member #3");
+
+        let mut rows = vec!["Bob".to_string(), "Alice".to_string()];
+        relabel(&mut rows, |r| r);
+        assert_eq!(rows, ["Member #1", "Member #2"]);
+    }
+
+    #[test]
+    fn test_anonymize_summary() {
+        let (db, _clock) = test_db_at(NaiveDate::from_ymd_opt(2026, 3, 20).unwrap().and_hms_opt(18, 0, 0).unwrap());
+        let (alice, bob) = (make_user(&db, "Alice"), make_user(&db, "Bob"));
+        make_week_of_sessions(&db, &alice, db.clock().week(), "backend", 30);
+        make_week_of_sessions(&db, &bob, db.clock().week(), "frontend", 90);
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+
+        assert!(matches!(public_summary(&db, &summary), Cow::Borrowed(_)));
+        turn_on(&db);
+        let public = public_summary(&db, &summary);
+        assert_eq!(public.mvp.as_ref().unwrap().0, "Member #1");
+        assert_eq!(public.longest_session.as_ref().unwrap().0, "Member #1");
+        assert_eq!(public.total_minutes, summary.total_minutes);
+        for e in &public.breakdown {
+            let expected = if e.activity == "frontend" { "Member #1" } else { "Member #2" };
+            assert_eq!(e.username, expected);
+        }
+        let text = crate::mirror::summary_text(&public, "KW12/2026", &crate::display::DisplayFormat::default());
+        assert!(!text.contains("Alice") && !text.contains("Bob"));
+    }
+
+    #[test]
+    fn test_public_board() {
+        let (db, _clock) = test_db_at(NaiveDate::from_ymd_opt(2026, 3, 20).unwrap().and_hms_opt(18, 0, 0).unwrap());
+        let (alice, bob) = (make_user(&db, "Alice"), make_user(&db, "Bob"));
+        make_week_of_sessions(&db, &alice, db.clock().week(), "backend", 30);
+        make_week_of_sessions(&db, &bob, db.clock().week(), "frontend", 90);
+        let board = db.leaderboard_weekly(false).unwrap();
+        assert_eq!(public_board(&db, board.clone())[0].username, "Bob");
+
+        turn_on(&db);
+        let public = public_board(&db, board.clone());
+        assert_eq!(public.iter().map(|e| e.username.as_str()).collect::<Vec<_>>(), ["Member #1", "Member #2"]);
+        assert_eq!(public[0].total_minutes, board[0].total_minutes);
+        let rows = public_breakdown(&db, db.activity_breakdown_weekly().unwrap());
+        assert!(rows.iter().all(|e| e.username.starts_with("Member #")));
+    }
+}
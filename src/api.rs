@@ -0,0 +1,135 @@
+use crate::db::Db;
+use crate::http::{HttpState, Request, Response};
+use crate::ingest::{api_key, hash_api_key};
+
+/// What a personal token may do. Write tokens can also read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Reading your own sessions.
+    Read,
+    /// Also logging time, e.g. editor heartbeats.
+    Write,
+}
+
+pub const SCOPES: &[Scope] = &[Scope::Read, Scope::Write];
+
+impl Scope {
+    pub fn key(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        SCOPES.iter().copied().find(|s| s.key().eq_ignore_ascii_case(key))
+    }
+
+    pub fn allows(self, needed: Scope) -> bool {
+        self == Scope::Write || needed == Scope::Read
+    }
+}
+
+/// `(user_id, username)` behind the request's token if it grants `needed`, otherwise
+/// the response to send. Keys from `/clock apikey` count as write tokens.
+pub fn authenticate(req: &Request, db: &Db, needed: Scope) -> Result<(String, String), Response> {
+    let Some(key) = api_key(req) else {
+        return Err(Response::text(401, "missing api key"));
+    };
+    let hash = hash_api_key(&key);
+    let owner = match db.use_user_token(&hash) {
+        Ok(Some(token)) => Ok(Some((token.user_id, token.username, Scope::parse(&token.scope).unwrap_or(Scope::Read)))),
+        Ok(None) => db
+            .api_token_user(&hash)
+            .map(|user| user.map(|(user_id, username)| (user_id, username, Scope::Write))),
+        Err(e) => Err(e),
+    };
+    match owner {
+        Ok(Some((user_id, username, scope))) if scope.allows(needed) => Ok((db.primary_account(&user_id), username)),
+        Ok(Some(_)) => Err(Response::text(403, "token is read-only")),
+        Ok(None) => Err(Response::text(401, "invalid api key")),
+        Err(e) => {
+            eprintln!("[clock] API key lookup failed: {e}");
+            Err(Response::text(503, "unavailable"))
+        }
+    }
+}
+
+/// `GET /api/v1/sessions`: the caller's running session and this week's finished ones.
+pub fn sessions(req: &Request, state: &HttpState) -> Response {
+    let (user_id, _) = match authenticate(req, &state.db, Scope::Read) {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let fmt = |t: chrono::NaiveDateTime| t.format("%Y-%m-%dT%H:%M:%S").to_string();
+    let (active, records) = match (state.db.active_session(&user_id), state.db.session_records(Some(&user_id))) {
+        (Ok(active), Ok(records)) => (active, records),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("[clock] Failed to load sessions for the API: {e}");
+            return Response::text(503, "unavailable");
+        }
+    };
+    let active = active.map(|s| {
+        serde_json::json!({
+            "activity": s.activity,
+            "started_at": fmt(s.started_at),
+            "minutes": (state.db.clock().now() - s.started_at).num_minutes(),
+        })
+    });
+    let finished: Vec<serde_json::Value> = records
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "activity": r.activity,
+                "started_at": fmt(r.started_at),
+                "ended_at": fmt(r.ended_at),
+                "minutes": r.elapsed.minutes(),
+                "seconds": r.elapsed.seconds(),
+                "source": r.source,
+            })
+        })
+        .collect();
+    Response::json(200, &serde_json::json!({ "active": active, "sessions": finished }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+
+    fn request(key: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/api/v1/sessions".to_string(),
+            headers: vec![("Authorization".to_string(), format!("Bearer {}", key))],
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_scopes() {
+        assert!(Scope::Write.allows(Scope::Read));
+        assert!(!Scope::Read.allows(Scope::Write));
+        assert_eq!(Scope::parse("WRITE"), Some(Scope::Write));
+        assert_eq!(Scope::parse("admin"), None);
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let db = test_db();
+        db.create_user_token("u1", "Alice", &hash_api_key("read-key"), "read").unwrap();
+        db.set_api_token("u2", "Bob", &hash_api_key("editor-key")).unwrap();
+
+        let user = |key, scope| authenticate(&request(key), &db, scope).ok().map(|(user_id, _)| user_id);
+        assert_eq!(user("read-key", Scope::Read).as_deref(), Some("u1"));
+        assert_eq!(authenticate(&request("read-key"), &db, Scope::Write).unwrap_err().status, 403);
+        assert!(db.user_tokens("u1").unwrap()[0].last_used.is_some());
+        assert_eq!(user("editor-key", Scope::Write).as_deref(), Some("u2"));
+        assert_eq!(authenticate(&request("nope"), &db, Scope::Read).unwrap_err().status, 401);
+
+        let id = db.user_tokens("u1").unwrap()[0].id;
+        assert!(!db.revoke_user_token("u2", id).unwrap());
+        assert!(db.revoke_user_token("u1", id).unwrap());
+        assert_eq!(authenticate(&request("read-key"), &db, Scope::Read).unwrap_err().status, 401);
+    }
+}
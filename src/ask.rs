@@ -0,0 +1,410 @@
+use crate::db::WeekActivity;
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+const TOP_ROWS: usize = 5;
+
+/// What a `/clock ask` question wants to know.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intent {
+    /// Time logged, e.g. "how much did I work on backend last month?"
+    Total,
+    /// People ranked by time, e.g. "who worked most in January?"
+    TopPeople,
+    /// Activities ranked by time, e.g. "what did Alice work on this year?"
+    TopActivities,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    Me,
+    /// A username as it appears in the data.
+    User(String),
+    Everyone,
+}
+
+/// Past data is archived per week, so every period is a set of whole weeks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    ThisWeek,
+    LastWeek,
+    LastWeeks(u32),
+    /// Weeks whose Thursday falls in the month (ISO weeks belong to one month that way).
+    Month(i32, u32),
+    Year(i32),
+    AllTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub intent: Intent,
+    pub subject: Subject,
+    pub activity: Option<String>,
+    pub period: Period,
+}
+
+impl Period {
+    /// The weeks the period covers, `None` for all time.
+    pub fn weeks(&self, today: NaiveDate) -> Option<Vec<WeekId>> {
+        let current = WeekId::containing(today);
+        let in_range = |from: NaiveDate, to: NaiveDate| {
+            let mut week = WeekId::containing(from);
+            let mut weeks = Vec::new();
+            while week.monday() <= to {
+                let thursday = week.monday() + Duration::days(3);
+                if thursday >= from && thursday <= to {
+                    weeks.push(week);
+                }
+                week = week.next();
+            }
+            weeks
+        };
+        match *self {
+            Period::ThisWeek => Some(vec![current]),
+            Period::LastWeek => Some(vec![current.previous()]),
+            Period::LastWeeks(n) => Some(current.last_n(n)),
+            Period::Month(year, month) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let next = NaiveDate::from_ymd_opt(year + month as i32 / 12, month % 12 + 1, 1)?;
+                Some(in_range(first, next - Duration::days(1)))
+            }
+            Period::Year(year) => Some(in_range(
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year, 12, 31)?,
+            )),
+            Period::AllTime => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Period::ThisWeek => "this week".to_string(),
+            Period::LastWeek => "last week".to_string(),
+            Period::LastWeeks(n) => format!("the last {} weeks", n),
+            Period::Month(year, month) => format!("in {} {}", capitalize(MONTHS[month as usize - 1]), year),
+            Period::Year(year) => format!("in {}", year),
+            Period::AllTime => "all time".to_string(),
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Lowercase words with punctuation stripped; hyphens separate words too, so
+/// `code-review` and "code review" read the same.
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|w| w.trim_matches('\'').to_string())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Whether `needle` appears in `haystack` as consecutive words.
+fn contains_words(haystack: &[String], needle: &[String]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn parse_period(words: &[String], today: NaiveDate) -> Period {
+    let has = |w: &str| words.iter().any(|x| x == w);
+    let after = |w: &str| {
+        words
+            .iter()
+            .position(|x| x == w)
+            .and_then(|i| words.get(i + 1))
+            .map(String::as_str)
+    };
+    let year_word = words
+        .iter()
+        .filter_map(|w| w.parse::<i32>().ok())
+        .find(|y| (2000..=2100).contains(y));
+
+    // Full names or abbreviations like "jan" and "sept"
+    if let Some(month) = words
+        .iter()
+        .find_map(|w| MONTHS.iter().position(|m| w.len() >= 3 && m.starts_with(w.as_str())))
+    {
+        let month = month as u32 + 1;
+        // A bare month means its most recent occurrence
+        let year = year_word.unwrap_or(if month <= today.month() { today.year() } else { today.year() - 1 });
+        return Period::Month(year, month);
+    }
+    match (after("this"), after("last"), after("past")) {
+        (Some("week"), _, _) => return Period::ThisWeek,
+        (Some("month"), _, _) => return Period::Month(today.year(), today.month()),
+        (Some("year"), _, _) => return Period::Year(today.year()),
+        (_, Some("week"), _) => return Period::LastWeek,
+        (_, Some("month"), _) => {
+            let last = today.with_day(1).unwrap() - Duration::days(1);
+            return Period::Month(last.year(), last.month());
+        }
+        (_, Some("year"), _) => return Period::Year(today.year() - 1),
+        _ => {}
+    }
+    for w in ["last", "past"] {
+        if let Some(n) = after(w).and_then(|n| n.parse::<u32>().ok())
+            && (1..=52).contains(&n)
+        {
+            return Period::LastWeeks(n);
+        }
+    }
+    if let Some(year) = year_word {
+        return Period::Year(year);
+    }
+    if has("week") {
+        return Period::ThisWeek;
+    }
+    Period::AllTime
+}
+
+/// Understand a question about logged time. `activities` and `usernames` are the
+/// names known to the server; the longest one mentioned wins. `None` if the
+/// question isn't about time at all.
+pub fn parse(question: &str, today: NaiveDate, activities: &[String], usernames: &[String]) -> Option<Query> {
+    let words = words(question);
+    let has = |w: &str| words.iter().any(|x| x == w);
+
+    let mentioned = |names: &[String]| {
+        names
+            .iter()
+            .filter(|name| contains_words(&words, &self::words(name)))
+            .max_by_key(|name| name.len())
+            .cloned()
+    };
+    let activity = mentioned(activities);
+    let user = mentioned(usernames).filter(|u| activity.as_deref() != Some(u.as_str()));
+
+    let intent = if has("who") || has("whom") {
+        Intent::TopPeople
+    } else if (has("what") || has("which")) && activity.is_none() {
+        Intent::TopActivities
+    } else if activity.is_some() || ["how", "much", "hours", "long", "time", "total"].iter().any(|w| has(w)) {
+        Intent::Total
+    } else {
+        return None;
+    };
+    let subject = if let Some(user) = user {
+        Subject::User(user)
+    } else if has("i") || has("me") || has("my") || has("i've") {
+        Subject::Me
+    } else {
+        Subject::Everyone
+    };
+    Some(Query { intent, subject, activity, period: parse_period(&words, today) })
+}
+
+/// Answer `query` from the server's per-week data. `user_id` is the asker; rankings of
+/// people leave out `non_competitive` activities unless the question names one, and
+/// number people by place instead of naming them when `anonymous`.
+pub fn answer(
+    query: &Query,
+    rows: &[WeekActivity],
+    non_competitive: &[String],
+    user_id: &str,
+    today: NaiveDate,
+    fmt: &DisplayFormat,
+    anonymous: bool,
+) -> String {
+    let weeks: Option<Vec<String>> = query.period.weeks(today).map(|w| w.iter().map(WeekId::label).collect());
+    let rows: Vec<&WeekActivity> = rows
+        .iter()
+        .filter(|r| weeks.as_ref().is_none_or(|w| w.contains(&r.week_label)))
+        .filter(|r| query.activity.as_ref().is_none_or(|a| &r.activity == a))
+        .filter(|r| match &query.subject {
+            Subject::Me => r.user_id == user_id,
+            Subject::User(name) => r.username.eq_ignore_ascii_case(name),
+            Subject::Everyone => true,
+        })
+        .collect();
+
+    let who = match &query.subject {
+        Subject::Me => "You".to_string(),
+        Subject::User(name) => format!("**{}**", name),
+        Subject::Everyone => "The server".to_string(),
+    };
+    let on = query
+        .activity
+        .as_ref()
+        .map(|a| format!(" on **{}**", a))
+        .unwrap_or_default();
+    let period = query.period.describe();
+    let span = match &weeks {
+        Some(w) if w.len() > 1 => format!(" ({} – {})", fmt.week(&w[0]), fmt.week(&w[w.len() - 1])),
+        Some(w) if w.len() == 1 && !matches!(query.period, Period::ThisWeek | Period::LastWeek) => {
+            format!(" ({})", fmt.week(&w[0]))
+        }
+        _ => String::new(),
+    };
+
+    let ranked = |rows: &[&WeekActivity], key: fn(&WeekActivity) -> &str| {
+        let mut totals: HashMap<&str, i64> = HashMap::new();
+        for r in rows {
+            *totals.entry(key(r)).or_default() += r.minutes;
+        }
+        let mut totals: Vec<(&str, i64)> = totals.into_iter().collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        totals.into_iter().take(TOP_ROWS).map(|(name, m)| (name.to_string(), m)).collect::<Vec<_>>()
+    };
+    let lines = |totals: Vec<(String, i64)>| {
+        totals
+            .iter()
+            .enumerate()
+            .map(|(i, (name, minutes))| format!("{}. **{}** — {}", i + 1, name, fmt.duration(*minutes)))
+            .collect::<Vec<_>>()
+    };
+
+    match query.intent {
+        Intent::Total => {
+            let total: i64 = rows.iter().map(|r| r.minutes).sum();
+            let verb = if matches!(query.subject, Subject::Me) { "worked" } else { "logged" };
+            format!("{} {} **{}**{} {}{}.", who, verb, fmt.duration(total), on, period, span)
+        }
+        Intent::TopPeople => {
+            let competitive: Vec<&WeekActivity> = rows
+                .iter()
+                .copied()
+                .filter(|r| query.activity.is_some() || !non_competitive.contains(&r.activity))
+                .collect();
+            let mut people = ranked(&competitive, |r| &r.username);
+            if anonymous {
+                crate::anonymous::relabel(&mut people, |p| &mut p.0);
+            }
+            let lines = lines(people);
+            if lines.is_empty() {
+                return format!("Nobody logged time{} {}{}.", on, period, span);
+            }
+            format!("Most time{} {}{}:\n{}", on, period, span, lines.join("\n"))
+        }
+        Intent::TopActivities => {
+            let lines = lines(ranked(&rows, |r| &r.activity));
+            if lines.is_empty() {
+                return format!("{} logged no time {}{}.", who, period, span);
+            }
+            let whose = match &query.subject {
+                Subject::Me => "Your top activities".to_string(),
+                Subject::User(name) => format!("**{}**'s top activities", name),
+                Subject::Everyone => "Top activities".to_string(),
+            };
+            format!("{} {}{}:\n{}", whose, period, span, lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        // Wednesday of KW12/2026
+        NaiveDate::from_ymd_opt(2026, 3, 18).unwrap()
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn ask(question: &str) -> Option<Query> {
+        parse(question, today(), &names(&["backend", "code-review", "docs"]), &names(&["Alice", "Bob"]))
+    }
+
+    #[test]
+    fn test_parse_questions() {
+        assert_eq!(
+            ask("How much did I work on backend last month?"),
+            Some(Query {
+                intent: Intent::Total,
+                subject: Subject::Me,
+                activity: Some("backend".to_string()),
+                period: Period::Month(2026, 2),
+            })
+        );
+        assert_eq!(
+            ask("who worked most in January?"),
+            Some(Query {
+                intent: Intent::TopPeople,
+                subject: Subject::Everyone,
+                activity: None,
+                period: Period::Month(2026, 1),
+            })
+        );
+        let query = ask("what did alice work on in 2025").unwrap();
+        assert_eq!((query.intent, query.subject, query.period), (Intent::TopActivities, Subject::User("Alice".to_string()), Period::Year(2025)));
+        assert_eq!(ask("hours on code review over the last 4 weeks").unwrap().activity.as_deref(), Some("code-review"));
+        assert_eq!(ask("hours on code review over the last 4 weeks").unwrap().period, Period::LastWeeks(4));
+        // A month later in the year than today means last year's
+        assert_eq!(ask("how long in december").unwrap().period, Period::Month(2025, 12));
+        assert_eq!(ask("total time this week").unwrap().period, Period::ThisWeek);
+        assert_eq!(ask("how much ever").unwrap().period, Period::AllTime);
+        assert_eq!(ask("tell me a joke"), None);
+    }
+
+    #[test]
+    fn test_period_weeks() {
+        let labels = |p: Period| p.weeks(today()).unwrap().iter().map(WeekId::label).collect::<Vec<_>>();
+        // February 2026 starts on a Sunday: KW05 (Thu 29.01.) belongs to January
+        assert_eq!(labels(Period::Month(2026, 2)), vec!["KW06/2026", "KW07/2026", "KW08/2026", "KW09/2026"]);
+        assert_eq!(labels(Period::LastWeek), vec!["KW11/2026"]);
+        assert_eq!(labels(Period::LastWeeks(2)), vec!["KW11/2026", "KW12/2026"]);
+        assert_eq!(Period::Year(2026).weeks(today()).unwrap().len(), 53);
+        assert_eq!(Period::AllTime.weeks(today()), None);
+    }
+
+    #[test]
+    fn test_answer() {
+        let row = |user_id: &str, username: &str, week: &str, activity: &str, minutes| WeekActivity {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            week_label: week.to_string(),
+            activity: activity.to_string(),
+            minutes,
+        };
+        let rows = vec![
+            row("a", "Alice", "KW06/2026", "backend", 300),
+            row("a", "Alice", "KW07/2026", "docs", 60),
+            row("b", "Bob", "KW07/2026", "backend", 120),
+            row("b", "Bob", "KW12/2026", "backend", 600),
+        ];
+        let fmt = DisplayFormat::default();
+        let answer = |q: &str, user_id| answer(&ask(q).unwrap(), &rows, &[], user_id, today(), &fmt, false);
+
+        assert_eq!(
+            answer("how much did I work on backend last month?", "a"),
+            "You worked **5h 0m** on **backend** in February 2026 (KW06/2026 – KW09/2026)."
+        );
+        assert_eq!(
+            answer("who worked most in february", "a"),
+            "Most time in February 2026 (KW06/2026 – KW09/2026):\n1. **Alice** — 6h 0m\n2. **Bob** — 2h 0m"
+        );
+        assert_eq!(answer("what did bob work on this week", "a"), "**Bob**'s top activities this week:\n1. **backend** — 10h 0m");
+        assert_eq!(answer("who worked on docs last week", "a"), "Nobody logged time on **docs** last week.");
+
+        // Non-competitive activities drop out of people rankings, unless asked about
+        let docs = ["docs".to_string()];
+        assert_eq!(
+            super::answer(&ask("who worked most in february").unwrap(), &rows, &docs, "a", today(), &fmt, false),
+            "Most time in February 2026 (KW06/2026 – KW09/2026):\n1. **Alice** — 5h 0m\n2. **Bob** — 2h 0m"
+        );
+        assert_eq!(
+            super::answer(&ask("who worked most on docs in february").unwrap(), &rows, &docs, "a", today(), &fmt, false),
+            "Most time on **docs** in February 2026 (KW06/2026 – KW09/2026):\n1. **Alice** — 1h 0m"
+        );
+
+        // Anonymous mode numbers people by place
+        assert_eq!(
+            super::answer(&ask("who worked most in february").unwrap(), &rows, &[], "a", today(), &fmt, true),
+            "Most time in February 2026 (KW06/2026 – KW09/2026):\n1. **Member #1** — 6h 0m\n2. **Member #2** — 2h 0m"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::db::{self, Db, Elapsed};
+use crate::display::DisplayFormat;
+use crate::http::{HttpState, Request, Response};
+use crate::normalize::normalize_activity;
+use chrono::{DateTime, Duration, Utc};
+use std::env;
+
+/// Activity for events that don't name one.
+pub const DEFAULT_ACTIVITY: &str = "on-site";
+/// How far a request's `X-Timestamp` may be from now, either way.
+const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// Shared secret the access system signs its requests with, from
+/// `ATTENDANCE_WEBHOOK_SECRET`. Unset disables the `/attendance` endpoint.
+pub fn webhook_secret() -> Option<String> {
+    env::var("ATTENDANCE_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    In,
+    Out,
+    /// In if not clocked in, otherwise out: a reader with a single tap.
+    Toggle,
+}
+
+/// A badge tap or door event.
+#[derive(Debug, PartialEq)]
+pub struct AttendanceEvent {
+    /// Unique per event, so a replayed request is recognized.
+    pub event_id: String,
+    pub external_id: String,
+    pub action: Action,
+    pub activity: Option<String>,
+}
+
+/// `{"event_id": "evt-1", "external_id": "badge-0042", "action": "in|out|toggle",
+/// "activity": "workshop"}`; `action` defaults to `toggle`, `activity` to `DEFAULT_ACTIVITY`.
+pub fn parse_event(payload: &serde_json::Value) -> Option<AttendanceEvent> {
+    let id = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+    let (event_id, external_id) = (id(&payload["event_id"])?, id(&payload["external_id"])?);
+    let (action, activity) = parse_action(payload)?;
+    Some(AttendanceEvent { event_id, external_id, action, activity })
+}
+
+/// Check that `signature` (`sha256=<hex>`) signs `<timestamp>.<body>` and that the Unix
+/// `timestamp` is within a few minutes of `now`, so a captured request can't be replayed
+/// later. The reason for the rejection otherwise.
+pub fn verify_request(
+    secret: &str,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Result<(), &'static str> {
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return Err("missing signature");
+    };
+    let signed = [timestamp.as_bytes(), b".", body].concat();
+    if !crate::github::verify_signature(secret, &signed, signature) {
+        return Err("bad signature");
+    }
+    let sent: i64 = timestamp.trim().parse().map_err(|_| "bad timestamp")?;
+    if (now.timestamp() - sent).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err("stale timestamp");
+    }
+    Ok(())
+}
+
+/// The optional `action` and `activity` fields of a payload; `None` for an unknown action.
+pub fn parse_action(payload: &serde_json::Value) -> Option<(Action, Option<String>)> {
+    let action = match payload["action"].as_str().map(|a| a.to_lowercase()).as_deref() {
+        Some("in") => Action::In,
+        Some("out") => Action::Out,
+        Some("toggle") | None => Action::Toggle,
+        Some(_) => return None,
+    };
+    let activity = payload["activity"].as_str().map(normalize_activity).filter(|a| !a.is_empty());
+    Some((action, activity))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    ClockedIn(String),
+    ClockedOut(String, Elapsed),
+    /// Already in (for `in`) or not clocked in (for `out`).
+    Unchanged,
+}
+
+/// Clock `user_id` in or out. Sessions started this way get the `bot` source.
+pub fn apply(db: &Db, user_id: &str, username: &str, action: Action, activity: Option<&str>) -> anyhow::Result<Outcome> {
+    let running = db.active_session(user_id)?.is_some();
+    match (action, running) {
+        (Action::In | Action::Toggle, false) => {
+            let activity = activity.unwrap_or(DEFAULT_ACTIVITY).to_string();
+            db.clock_in_from(user_id, username, &activity, db::SOURCE_BOT)?;
+            Ok(Outcome::ClockedIn(activity))
+        }
+        (Action::Out | Action::Toggle, true) => {
+            let (elapsed, activity) = db.clock_out(user_id)?;
+            Ok(Outcome::ClockedOut(activity, elapsed))
+        }
+        _ => Ok(Outcome::Unchanged),
+    }
+}
+
+/// `apply` and the JSON reply saying whether the user is now `in`, `out` or `unchanged`.
+/// `via` names the device in the log, e.g. `badge`.
+pub fn respond(db: &Db, user_id: &str, username: &str, action: Action, activity: Option<&str>, via: &str) -> Response {
+    let (status, activity) = match apply(db, user_id, username, action, activity) {
+        Ok(Outcome::ClockedIn(activity)) => {
+            println!("[clock] {} clocked in on {} by {}", username, activity, via);
+            crate::mirror::spawn(crate::mirror::clock_in_text(username, &activity));
+            ("in", Some(activity))
+        }
+        Ok(Outcome::ClockedOut(activity, elapsed)) => {
+            println!("[clock] {} clocked out of {} by {}", username, activity, via);
+            crate::mirror::spawn(crate::mirror::clock_out_text(username, &activity, elapsed, &DisplayFormat::load(db)));
+            ("out", Some(activity))
+        }
+        Ok(Outcome::Unchanged) => ("unchanged", None),
+        Err(e) => {
+            eprintln!("[clock] Clocking {} by {} failed: {e}", username, via);
+            return Response::text(503, "unavailable");
+        }
+    };
+    Response::json(200, &serde_json::json!({ "user": username, "status": status, "activity": activity }))
+}
+
+pub async fn handle_webhook(req: &Request, state: &HttpState) -> Response {
+    let Some(secret) = webhook_secret() else {
+        return Response::text(404, "not found");
+    };
+    let now = state.db.clock().now_utc();
+    if let Err(reason) = verify_request(&secret, req.header("X-Timestamp"), req.header("X-Signature-256"), &req.body, now) {
+        return Response::text(401, reason);
+    }
+
+    let Some(event) = serde_json::from_slice::<serde_json::Value>(&req.body)
+        .ok()
+        .and_then(|payload| parse_event(&payload))
+    else {
+        return Response::text(400, "bad payload");
+    };
+    match state.db.record_attendance_event(&event.event_id, Duration::seconds(4 * MAX_CLOCK_SKEW_SECS)) {
+        Ok(true) => {}
+        Ok(false) => return Response::text(409, "duplicate event"),
+        Err(e) => {
+            eprintln!("[clock] Recording attendance event failed: {e}");
+            return Response::text(503, "unavailable");
+        }
+    }
+    let user = match state.db.external_id(&event.external_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return Response::text(404, "unknown external id"),
+        Err(e) => {
+            eprintln!("[clock] External ID lookup failed: {e}");
+            return Response::text(503, "unavailable");
+        }
+    };
+    respond(&state.db, &user.user_id, &user.username, event.action, event.activity.as_deref(), "badge")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::fixtures::test_db;
+
+    #[test]
+    fn test_parse_event() {
+        let event = parse_event(&json!({"event_id": "e1", "external_id": " badge-42 ", "action": "IN", "activity": "Workshop"})).unwrap();
+        assert_eq!(event.event_id, "e1");
+        assert_eq!(event.external_id, "badge-42");
+        assert_eq!(event.action, Action::In);
+        assert_eq!(event.activity.as_deref(), Some("workshop"));
+
+        let event = parse_event(&json!({"event_id": 7, "external_id": 1042})).unwrap();
+        assert_eq!(event.external_id, "1042");
+        assert_eq!(event.action, Action::Toggle);
+        assert_eq!(event.activity, None);
+
+        assert_eq!(parse_event(&json!({"event_id": "e1", "external_id": "", "action": "in"})), None);
+        assert_eq!(parse_event(&json!({"event_id": "e1", "external_id": "b1", "action": "maybe"})), None);
+        assert_eq!(parse_event(&json!({"event_id": "e1", "action": "in"})), None);
+        assert_eq!(parse_event(&json!({"external_id": "b1"})), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let db = test_db();
+        db.map_external_id("b1", "u1", "Alice").unwrap();
+        let user = db.external_id("b1").unwrap().unwrap();
+        let apply = |action| apply(&db, &user.user_id, &user.username, action, None).unwrap();
+
+        assert_eq!(apply(Action::Out), Outcome::Unchanged);
+        assert_eq!(apply(Action::Toggle), Outcome::ClockedIn(DEFAULT_ACTIVITY.to_string()));
+        assert_eq!(apply(Action::In), Outcome::Unchanged);
+        assert!(matches!(apply(Action::Toggle), Outcome::ClockedOut(a, _) if a == DEFAULT_ACTIVITY));
+        let records = db.session_records(Some("u1")).unwrap();
+        assert_eq!(records[0].source, db::SOURCE_BOT);
+
+        assert!(db.unmap_external_id("b1").unwrap());
+        assert!(db.external_ids().unwrap().is_empty());
+    }
+
+    fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(&[timestamp.as_bytes(), b".", body].concat());
+        let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={hex}")
+    }
+
+    #[test]
+    fn test_verify_request() {
+        let now = DateTime::from_timestamp(1_773_651_600, 0).unwrap();
+        let body = br#"{"event_id":"e1","external_id":"b1"}"#;
+        let ts = now.timestamp().to_string();
+        let sig = sign("s3cret", &ts, body);
+        assert_eq!(verify_request("s3cret", Some(&ts), Some(&sig), body, now), Ok(()));
+        assert_eq!(verify_request("s3cret", Some(&ts), Some(&sig), body, now + Duration::minutes(4)), Ok(()));
+        // Replayed later, or with the timestamp moved forward without re-signing
+        assert_eq!(verify_request("s3cret", Some(&ts), Some(&sig), body, now + Duration::minutes(6)), Err("stale timestamp"));
+        let later = (now.timestamp() + 600).to_string();
+        assert_eq!(verify_request("s3cret", Some(&later), Some(&sig), body, now + Duration::minutes(10)), Err("bad signature"));
+        assert_eq!(verify_request("s3cret", None, Some(&sig), body, now), Err("missing signature"));
+        assert_eq!(verify_request("other", Some(&ts), Some(&sig), body, now), Err("bad signature"));
+    }
+
+    #[test]
+    fn test_record_attendance_event() {
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 3, 16).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let (db, clock) = crate::fixtures::test_db_at(start);
+        let keep = Duration::minutes(20);
+        assert!(db.record_attendance_event("e1", keep).unwrap());
+        assert!(!db.record_attendance_event("e1", keep).unwrap());
+        assert!(db.record_attendance_event("e2", keep).unwrap());
+        clock.set(start + Duration::hours(1));
+        assert!(db.record_attendance_event("e1", keep).unwrap());
+    }
+}
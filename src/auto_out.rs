@@ -0,0 +1,119 @@
+use crate::db::Db;
+use crate::display::DisplayFormat;
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often scheduled clock-outs are checked. Sessions end at the scheduled time,
+/// not when the check runs.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// `30m`, `90m`, `2h`, `1h30m` or `1.5` (hours); between 1 minute and 24 hours.
+pub fn parse_duration(arg: &str) -> Option<i64> {
+    let arg = arg.trim().to_lowercase();
+    match arg.strip_suffix('m') {
+        Some(minutes) if !minutes.contains('h') => minutes
+            .trim()
+            .parse()
+            .ok()
+            .filter(|m| (1..=24 * 60).contains(m)),
+        _ => crate::commands::parse_hours(&arg),
+    }
+}
+
+/// Split `backend for 2h` into the activity and the session length. Text after the
+/// last ` for ` only counts when it is a duration, so `reading for fun` stays whole.
+pub fn split_for(args: &str) -> (&str, Option<i64>) {
+    if let Some((activity, duration)) = args.rsplit_once(" for ")
+        && let Some(minutes) = parse_duration(duration)
+        && !activity.trim().is_empty()
+    {
+        return (activity.trim(), Some(minutes));
+    }
+    (args, None)
+}
+
+/// End sessions whose scheduled clock-out has passed and tell their owners by DM.
+pub async fn auto_out_loop(db: Arc<Db>, http: Arc<Http>) {
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let due = match db.due_clock_outs(db.clock().now()) {
+            Ok(due) => due,
+            Err(e) => {
+                eprintln!("[clock] Scheduled clock-out query failed: {e}");
+                continue;
+            }
+        };
+        for (user_id, at) in due {
+            let username = db
+                .active_session(&user_id)
+                .ok()
+                .flatten()
+                .map(|s| s.username)
+                .unwrap_or_default();
+            let Ok((elapsed, activity)) = db.clock_out_at(&user_id, at) else {
+                continue;
+            };
+            let Ok(id) = user_id.parse::<u64>() else {
+                continue;
+            };
+            let user = UserId::new(id);
+            let fmt = DisplayFormat::load(&db);
+            crate::focus::revoke(&http, &db, user).await;
+            crate::mirror::spawn(crate::mirror::clock_out_text(&username, &activity, elapsed, &fmt));
+
+            let embed = CreateEmbed::new()
+                .color(0xe74c3c)
+                .title("⏰ Clocked out as scheduled")
+                .description(format!("Finished **{}** after {}.", activity, fmt.elapsed(elapsed)));
+            if let Err(e) = user.direct_message(&http, CreateMessage::new().embed(embed)).await {
+                eprintln!("[clock] Failed to DM scheduled clock-out to {user_id}: {e}");
+            }
+            if let Some(violation) = crate::guardrails::check_clock_out(&db, &user_id, elapsed.minutes()) {
+                crate::guardrails::notify(&http, user, &[violation], false, &fmt).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Some(30));
+        assert_eq!(parse_duration("90M"), Some(90));
+        assert_eq!(parse_duration("2h"), Some(120));
+        assert_eq!(parse_duration("1h30m"), Some(90));
+        assert_eq!(parse_duration("1.5"), Some(90));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("25h"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn test_split_for() {
+        assert_eq!(split_for("backend for 2h"), ("backend", Some(120)));
+        assert_eq!(split_for("code review for 45m"), ("code review", Some(45)));
+        assert_eq!(split_for("reading for fun"), ("reading for fun", None));
+        assert_eq!(split_for("for 2h"), ("for 2h", None));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_durations_round_trip(minutes in 1i64..=24 * 60) {
+            prop_assert_eq!(parse_duration(&DisplayFormat::default().duration(minutes)), Some(minutes));
+            prop_assert_eq!(parse_duration(&format!("{}m", minutes)), Some(minutes));
+            prop_assert_eq!(parse_duration(&format!("{}h{}m", minutes / 60, minutes % 60)), Some(minutes));
+        }
+
+        #[test]
+        fn prop_parse_duration_stays_in_range(arg in "[0-9.ehm -]{0,8}|\\PC{0,12}") {
+            if let Some(minutes) = parse_duration(&arg) {
+                prop_assert!((1..=24 * 60).contains(&minutes));
+            }
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use chrono::{Datelike, Duration, NaiveDateTime};
+use clockbot::commands::csv_field;
+use clockbot::db::{self, Db, ImportRow, SessionRecord};
+use clockbot::display::DisplayFormat;
+use clockbot::{anonymous, challenges, commands, config, dashboard, digest, encryption, health, mirror, quiet, storage};
+use clockbot::normalize::normalize_activity;
+use clockbot::weeks::{self, WeekId};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::path::PathBuf;
+
+const DEFAULT_DB: &str = "/data/clock.db";
+
+const DEMO_NAMES: &[&str] = &[
+    "Ada", "Linus", "Grace", "Ken", "Margaret", "Dennis", "Barbara", "Guido", "Frances", "Bjarne", "Radia", "Niklaus",
+];
+const DEMO_ACTIVITIES: &[&str] = &[
+    "backend", "frontend", "code review", "meetings", "docs", "devops", "design", "research", "support", "testing",
+];
+
+const USAGE: &str = "Usage: clockctl [--db <path>] <command>
+
+Commands:
+  export [user-id]                 this week's finished sessions as CSV on stdout
+  export parquet [dir | --upload]  sessions, weekly activity totals and archived weeks as Parquet
+  backup                           upload a copy of the database and the Parquet export to S3
+  encrypt <new-path>               write an encrypted copy of an unencrypted database (DB_MASTER_KEY)
+  import <file.csv>                add finished sessions of this week (columns as exported)
+  backfill                         rebuild the weekly aggregates from the sessions
+  merge <from-id> <into-id> <name> count one user as another, history included
+  normalize                        re-run activity name normalization
+  archive [week-label]             archive finished sessions as the last closed week
+  simulate-week [week-label]       dry-run the next reset on a copy and print what it would do
+  seed [users] [weeks]             fill an empty database with demo users and sessions
+
+The database defaults to $CLOCK_DB or /data/clock.db.";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut path = std::env::var("CLOCK_DB").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(DEFAULT_DB));
+    if args.first().map(String::as_str) == Some("--db") && args.len() > 1 {
+        path = PathBuf::from(args.remove(1));
+        args.remove(0);
+    }
+    let Some(command) = args.first().cloned() else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+
+    // The database to encrypt can't be opened with the key yet
+    let result = match (command.as_str(), &args[1..]) {
+        ("encrypt", [target]) => encrypt(&path, &PathBuf::from(target)),
+        _ => Db::open(&path).and_then(|db| run(&db, &command, &args[1..])),
+    };
+    if let Err(e) = result {
+        eprintln!("clockctl: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(db: &Db, command: &str, args: &[String]) -> anyhow::Result<()> {
+    match (command, args) {
+        ("export", [format, flag]) if format == "parquet" && flag == "--upload" => {
+            let store = object_store()?;
+            let keys = tokio::runtime::Runtime::new()?.block_on(storage::upload_export(db, &store, db.clock().week()))?;
+            print_uploads(&store, &keys);
+        }
+        ("export", [format, dir @ ..]) if format == "parquet" && dir.len() <= 1 => {
+            let dir = PathBuf::from(dir.first().map_or(".", String::as_str));
+            for (path, rows) in clockbot::parquet::export(db, &dir)? {
+                println!("{}: {rows} rows", path.display());
+            }
+        }
+        ("export", [] | [_]) => {
+            let records = db.session_records(args.first().map(String::as_str))?;
+            print!("{}", export_csv(&records));
+        }
+        ("import", [file]) => {
+            let text = std::fs::read_to_string(file)?;
+            let week_start = WeekId::current().start();
+            let (mut rows, mut old) = (Vec::new(), 0);
+            for (n, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+                if n == 0 && line.starts_with("user_id,") {
+                    continue;
+                }
+                let row = parse_row(line).ok_or_else(|| anyhow::anyhow!("line {}: expected user_id,user,activity,started,ended", n + 1))?;
+                // Earlier weeks only exist as archived totals
+                if row.started_at < week_start {
+                    old += 1;
+                } else {
+                    rows.push(row);
+                }
+            }
+            let added = db.import_sessions(&rows, db::SOURCE_IMPORT)?;
+            let overlapping = rows.len() - added;
+            println!("{added} added, {overlapping} overlapping existing sessions, {old} from before this week skipped");
+        }
+        ("backup", []) => {
+            let store = object_store()?;
+            let keys = tokio::runtime::Runtime::new()?.block_on(storage::backup(db, &store, db.clock().week()))?;
+            print_uploads(&store, &keys);
+        }
+        ("backfill", []) => {
+            db.rebuild_weekly_aggregates()?;
+            println!("Weekly aggregates rebuilt");
+        }
+        ("merge", [from, into, name @ ..]) if !name.is_empty() => {
+            db.link_accounts(from, into, &name.join(" "))?;
+            println!("{from} now counts as {into}");
+        }
+        ("normalize", []) => {
+            if db.normalize_activities()? {
+                println!("Activity names normalized");
+            } else {
+                println!("Activity names already follow the current rules");
+            }
+        }
+        ("archive", [] | [_]) => {
+            let week_label = match args.first() {
+                Some(label) => WeekId::parse(label).ok_or_else(|| anyhow::anyhow!("not a week label: {label}"))?.label(),
+                None => weeks::week_closed_by(WeekId::current().start()).label(),
+            };
+            if health::already_archived(db, &week_label) {
+                anyhow::bail!("{week_label} is already archived");
+            }
+            health::archive_week(db, &week_label)?;
+            println!("Archived {week_label}");
+        }
+        ("simulate-week", [] | [_]) => {
+            let week = match args.first() {
+                Some(label) => WeekId::parse(label).ok_or_else(|| anyhow::anyhow!("not a week label: {label}"))?,
+                None => WeekId::current(),
+            };
+            let copy = std::env::temp_dir().join(format!("clockctl-simulate-{}.db", std::process::id()));
+            let _ = std::fs::remove_file(&copy);
+            db.copy_to(&copy)?;
+            let report = Db::open(&copy).and_then(|sim| simulate_reset(&sim, week));
+            let _ = std::fs::remove_file(&copy);
+            print!("{}", report?);
+        }
+        ("seed", [] | [_] | [_, _]) => {
+            let users: usize = args.first().map(|a| a.parse()).transpose()?.unwrap_or(8);
+            let weeks: u32 = args.get(1).map(|a| a.parse()).transpose()?.unwrap_or(6);
+            if !(1..=DEMO_NAMES.len()).contains(&users) || !(1..=52).contains(&weeks) {
+                anyhow::bail!("seed takes 1–{} users and 1–52 weeks", DEMO_NAMES.len());
+            }
+            if !db.session_records(None)?.is_empty() || !db.leaderboard_alltime(false)?.is_empty() {
+                anyhow::bail!("the database already has data; seed an empty one");
+            }
+            let sessions = seed(db, users, weeks, &mut rand::thread_rng())?;
+            println!("Seeded {sessions} sessions of {users} demo users over {weeks} weeks");
+        }
+        _ => anyhow::bail!("unknown command or arguments\n\n{USAGE}"),
+    }
+    Ok(())
+}
+
+fn encrypt(path: &std::path::Path, target: &std::path::Path) -> anyhow::Result<()> {
+    let key = encryption::key_from_env()?.ok_or_else(|| anyhow::anyhow!("set DB_MASTER_KEY and GUILD_ID to encrypt"))?;
+    encryption::encrypt_file(path, target, &key)?;
+    println!("Wrote {}; replace the database with it while the bot is stopped", target.display());
+    Ok(())
+}
+
+fn object_store() -> anyhow::Result<storage::ObjectStore> {
+    storage::ObjectStore::from_env()
+        .ok_or_else(|| anyhow::anyhow!("set S3_ENDPOINT, S3_BUCKET, S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY to upload"))
+}
+
+fn print_uploads(store: &storage::ObjectStore, keys: &[String]) {
+    for key in keys {
+        println!("Uploaded s3://{}/{key}", store.bucket);
+    }
+}
+
+/// Run the steps of the Monday reset closing `week` against `db` (a throwaway copy)
+/// and describe them. Nothing is posted, mailed or mirrored.
+fn simulate_reset(db: &Db, week: WeekId) -> anyhow::Result<String> {
+    let week_label = week.label();
+    let fmt = DisplayFormat::load(db);
+    let mut out = format!("Dry run of the reset closing {week_label}\n\n");
+    if health::already_archived(db, &week_label) {
+        out += "Already archived; the reset would skip this week.\n";
+        return Ok(out);
+    }
+
+    let summary = db.weekly_summary(week)?;
+    let summary = anonymous::public_summary(db, &summary);
+    if summary.total_sessions == 0 {
+        out += "No sessions to summarize.\n";
+    } else {
+        out += &mirror::summary_text(&summary, &week_label, &fmt);
+        out += "\n\n";
+        let channel = std::env::var("SUMMARY_CHANNEL").ok().filter(|c| !c.is_empty());
+        match channel {
+            Some(channel) => {
+                let pdf = if config::is_on(db, config::PDF_REPORT) { " with a PDF report" } else { "" };
+                let sections = commands::report_sections(db);
+                if config::is_on(db, config::SUMMARY_THREAD) {
+                    let (_, appendix) = commands::build_weekly_summary_thread(db, &summary, &week_label, &fmt, &sections);
+                    out += &format!(
+                        "Would post the report to channel {channel}{pdf}, and {} message(s) in a thread under it\n",
+                        appendix.len()
+                    );
+                } else {
+                    let messages = commands::build_weekly_summary_messages(db, &summary, &week_label, &fmt, &sections).len();
+                    out += &format!("Would post {messages} message(s) to channel {channel}{pdf}\n");
+                }
+            }
+            None => out += "No SUMMARY_CHANNEL; nothing would be posted\n",
+        }
+        if dashboard::enabled() {
+            out += "Would publish the dashboard\n";
+        }
+        if digest::enabled() {
+            let subscribers = db.digest_subscribers()?.len();
+            let admins = digest::admin_emails().len();
+            out += &format!("Would email {subscribers} digest(s) and {admins} admin report(s)\n");
+        }
+        let mirrors: Vec<&str> = mirror::notifiers().iter().map(|n| n.name()).collect();
+        if !mirrors.is_empty() {
+            out += &format!("Would mirror the summary to {}\n", mirrors.join(", "));
+        }
+        if let Some(until) = quiet::quiet_until(db, weeks::next_reset_after(db::now_ch())) {
+            out += &format!("Quiet hours: posts and emails wait until {}\n", fmt.timestamp(until));
+        }
+    }
+
+    let results = challenges::settle_lines(db, week, &fmt);
+    if !results.is_empty() {
+        out += &format!("\nChallenges:\n{}\n", results.join("\n"));
+    }
+
+    let running = db.who_is_working()?.len();
+    health::archive_week(db, &week_label)?;
+    let archived = db.archived_week_total(None, &week_label)?.unwrap_or(0);
+    out += &format!(
+        "\nWould archive {} as {week_label}; {running} running session(s) carry over\n",
+        fmt.duration(archived)
+    );
+    Ok(out)
+}
+
+/// Demo sessions for the current week and `weeks - 1` archived weeks before it. Past
+/// weeks go through the real archive, so charts, streaks and summaries look as after
+/// actual use. Returns how many sessions were logged.
+fn seed(db: &Db, users: usize, weeks: u32, rng: &mut impl Rng) -> anyhow::Result<usize> {
+    let now = db::now_ch();
+    let people: Vec<(String, &str, Vec<&str>)> = DEMO_NAMES[..users]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let count = rng.gen_range(3..=5);
+            let favourites = DEMO_ACTIVITIES.choose_multiple(rng, count).copied().collect();
+            (format!("demo-{}", i + 1), *name, favourites)
+        })
+        .collect();
+
+    let mut logged = 0;
+    let mut week = WeekId::current();
+    for _ in 1..weeks {
+        week = week.previous();
+    }
+    loop {
+        let mut rows = Vec::new();
+        for (user_id, name, favourites) in &people {
+            rows.extend(demo_week(user_id, name, favourites, week, rng).into_iter().take_while(|row| row.ended_at <= now));
+        }
+        logged += db.import_sessions(&rows, db::SOURCE_MANUAL)?;
+        if week == WeekId::current() {
+            break;
+        }
+        db.archive_week(&week.label())?;
+        week = week.next();
+    }
+    Ok(logged)
+}
+
+/// A plausible week for one person: most weekdays with one to four sessions from the
+/// morning on, the odd weekend session, mostly on their favourite activities.
+fn demo_week(user_id: &str, name: &str, favourites: &[&str], week: WeekId, rng: &mut impl Rng) -> Vec<ImportRow> {
+    let mut rows = Vec::new();
+    for day in 0..7 {
+        let date = week.monday() + Duration::days(day);
+        let weekend = date.weekday().num_days_from_monday() >= 5;
+        if !rng.gen_bool(if weekend { 0.15 } else { 0.8 }) {
+            continue;
+        }
+        let mut at = date.and_hms_opt(rng.gen_range(7..=10), rng.gen_range(0..4) * 15, 0).unwrap();
+        for _ in 0..rng.gen_range(1..=if weekend { 1 } else { 4 }) {
+            let activity = if rng.gen_bool(0.85) {
+                favourites.choose(rng).copied().unwrap_or("backend")
+            } else {
+                DEMO_ACTIVITIES.choose(rng).copied().unwrap_or("backend")
+            };
+            let end = at + Duration::minutes(rng.gen_range(2..=12) * 15);
+            if end.date() != date {
+                break;
+            }
+            rows.push(ImportRow {
+                user_id: user_id.to_string(),
+                username: name.to_string(),
+                activity: normalize_activity(activity),
+                started_at: at,
+                ended_at: end,
+            });
+            at = end + Duration::minutes(rng.gen_range(1..=6) * 10);
+        }
+    }
+    rows
+}
+
+/// Like `/clock export`, plus the user id so the file can be imported again.
+fn export_csv(records: &[SessionRecord]) -> String {
+    let mut out = String::from("user_id,user,activity,started,ended,minutes,seconds,source\n");
+    for r in records {
+        out += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            r.user_id,
+            csv_field(&r.username),
+            csv_field(&r.activity),
+            r.started_at.format("%Y-%m-%d %H:%M"),
+            r.ended_at.format("%Y-%m-%d %H:%M"),
+            r.elapsed.minutes(),
+            r.elapsed.seconds(),
+            r.source
+        );
+    }
+    out
+}
+
+/// `user_id,user,activity,started,ended` with Swiss local times; further columns
+/// (minutes, source) are ignored since both are derived on import.
+fn parse_row(line: &str) -> Option<ImportRow> {
+    let fields = split_csv_line(line);
+    let time = |s: &str| {
+        NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M"))
+            .ok()
+    };
+    let [user_id, username, activity, started, ended, ..] = fields.as_slice() else {
+        return None;
+    };
+    let row = ImportRow {
+        user_id: user_id.trim().to_string(),
+        username: username.trim().to_string(),
+        activity: normalize_activity(activity),
+        started_at: time(started)?,
+        ended_at: time(ended)?,
+    };
+    (!row.user_id.is_empty() && !row.activity.is_empty() && row.ended_at > row.started_at).then_some(row)
+}
+
+/// Fields of one CSV line, with `"..."` quoting and `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_simulate_reset() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        let week = WeekId::current();
+        let start = week.start() + chrono::Duration::hours(9);
+        db.import_session("u1", "Alice", "backend", start, start + chrono::Duration::hours(2), db::SOURCE_MANUAL)
+            .unwrap();
+
+        let report = simulate_reset(&db, week).unwrap();
+        assert!(report.contains("Alice"));
+        assert!(report.contains(&format!("Would archive 2h 0m as {}", week.label())));
+        assert!(simulate_reset(&db, week).unwrap().contains("Already archived"));
+    }
+
+    #[test]
+    fn test_seed() {
+        use rand::SeedableRng;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        seed(&db, 4, 3, &mut rng).unwrap();
+
+        let previous = WeekId::current().previous().label();
+        assert!(db.archived_week_total(None, &previous).unwrap().unwrap_or(0) > 0);
+        assert!(db.leaderboard_alltime(false).unwrap().len() <= 4);
+        let now = db::now_ch();
+        assert!(db.session_records(None).unwrap().iter().all(|r| r.ended_at <= now && r.started_at >= WeekId::current().start()));
+    }
+
+    #[test]
+    fn test_demo_week_sessions_do_not_overlap() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let rows = demo_week("demo-1", "Ada", &["backend", "docs"], WeekId::current(), &mut rng);
+        assert!(!rows.is_empty());
+        assert!(rows.windows(2).all(|w| w[0].ended_at < w[1].started_at));
+        assert!(rows.iter().all(|r| r.started_at.date() == r.ended_at.date()));
+    }
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(split_csv_line("a,\"b, c\",\"say \"\"hi\"\"\","), vec!["a", "b, c", "say \"hi\"", ""]);
+    }
+
+    #[test]
+    fn test_export_round_trips_through_import() {
+        let at = |h| NaiveDate::from_ymd_opt(2026, 3, 16).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        let record = SessionRecord {
+            user_id: "42".into(),
+            username: "Alice, B".into(),
+            activity: "backend".into(),
+            started_at: at(9),
+            ended_at: at(11),
+            elapsed: db::Elapsed::from_seconds(7200),
+            source: db::SOURCE_MANUAL.into(),
+        };
+        let csv = export_csv(&[record]);
+        let row = parse_row(csv.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(
+            row,
+            ImportRow {
+                user_id: "42".into(),
+                username: "Alice, B".into(),
+                activity: "backend".into(),
+                started_at: at(9),
+                ended_at: at(11),
+            }
+        );
+        assert_eq!(parse_row("42,Alice,backend,2026-03-16 11:00,2026-03-16 09:00"), None);
+        assert_eq!(parse_row("42,Alice,backend"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::db::{ActivityBudget, Db};
+use crate::display::DisplayFormat;
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often budgets are checked for a burn alert.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Burn levels (percent of the budget) that get an alert.
+const THRESHOLDS: [i64; 3] = [50, 80, 100];
+
+/// `200h`, `200` or `37.5h` into minutes.
+pub fn parse_budget(arg: &str) -> Option<i64> {
+    let hours: f64 = arg.trim().trim_end_matches(['h', 'H']).parse().ok()?;
+    (hours.is_finite() && hours > 0.0).then(|| (hours * 60.0).round() as i64)
+}
+
+pub fn burn_pct(budget: &ActivityBudget) -> i64 {
+    budget.used_min * 100 / budget.budget_min.max(1)
+}
+
+/// The highest threshold reached but not yet alerted. Levels passed together only
+/// alert once.
+pub fn due_alert(budget: &ActivityBudget) -> Option<i64> {
+    let burn = burn_pct(budget);
+    THRESHOLDS
+        .into_iter()
+        .filter(|&t| burn >= t && t > budget.alerted_pct)
+        .max()
+}
+
+/// `3h 0m of 10h 0m used (30%) · 7h 0m left`
+pub fn budget_line(budget: &ActivityBudget, fmt: &DisplayFormat) -> String {
+    let remaining = budget.budget_min - budget.used_min;
+    let left = if remaining >= 0 {
+        format!("{} left", fmt.duration(remaining))
+    } else {
+        format!("{} over", fmt.duration(-remaining))
+    };
+    format!(
+        "{} of {} used ({}%) · {}",
+        fmt.duration(budget.used_min),
+        fmt.duration(budget.budget_min),
+        burn_pct(budget),
+        left
+    )
+}
+
+fn alert_embed(db: &Db, budget: &ActivityBudget, pct: i64, fmt: &DisplayFormat) -> CreateEmbed {
+    let (color, title) = if pct >= 100 {
+        (0xe74c3c, format!("🚨 {} is out of budget", budget.activity))
+    } else {
+        (0xe67e22, format!("⚠️ {} has used {}% of its budget", budget.activity, pct))
+    };
+    CreateEmbed::new()
+        .color(color)
+        .title(title)
+        .description(budget_line(budget, fmt))
+        .footer(CreateEmbedFooter::new(crate::commands::swiss_timestamp(db, fmt)))
+}
+
+/// Post burn alerts to the summary channel as budgets pass 50, 80 and 100%.
+pub async fn alert_loop(db: Arc<Db>, http: Arc<Http>, channel_id: Option<ChannelId>) {
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let budgets = match db.activity_budgets() {
+            Ok(budgets) => budgets,
+            Err(e) => {
+                eprintln!("[clock] Budget check failed: {e}");
+                continue;
+            }
+        };
+        for budget in budgets {
+            let Some(pct) = due_alert(&budget) else {
+                continue;
+            };
+            if let Some(channel_id) = channel_id {
+                let embed = alert_embed(&db, &budget, pct, &DisplayFormat::load(&db));
+                if let Err(e) = channel_id.send_message(&http, CreateMessage::new().embed(embed)).await {
+                    eprintln!("[clock] Failed to post budget alert for {}: {e}", budget.activity);
+                    continue;
+                }
+            }
+            if let Err(e) = db.mark_budget_alerted(&budget.activity, pct) {
+                eprintln!("[clock] Failed to mark budget alert for {}: {e}", budget.activity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(used_min: i64, alerted_pct: i64) -> ActivityBudget {
+        ActivityBudget { activity: "platform".into(), budget_min: 600, used_min, alerted_pct }
+    }
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse_budget("200h"), Some(12000));
+        assert_eq!(parse_budget("37.5"), Some(2250));
+        assert_eq!(parse_budget("0h"), None);
+        assert_eq!(parse_budget("lots"), None);
+    }
+
+    #[test]
+    fn test_due_alert() {
+        assert_eq!(due_alert(&budget(200, 0)), None);
+        assert_eq!(due_alert(&budget(300, 0)), Some(50));
+        assert_eq!(due_alert(&budget(300, 50)), None);
+        assert_eq!(due_alert(&budget(500, 50)), Some(80));
+        // Jumping past several levels alerts for the highest only
+        assert_eq!(due_alert(&budget(700, 0)), Some(100));
+        assert_eq!(due_alert(&budget(900, 100)), None);
+    }
+
+    #[test]
+    fn test_budget_line() {
+        let fmt = DisplayFormat::default();
+        assert_eq!(budget_line(&budget(180, 0), &fmt), "3h 0m of 10h 0m used (30%) · 7h 0m left");
+        assert_eq!(budget_line(&budget(660, 100), &fmt), "11h 0m of 10h 0m used (110%) · 1h 0m over");
+    }
+}
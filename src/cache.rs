@@ -0,0 +1,66 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+type Entry = (Instant, Box<dyn Any + Send>);
+
+/// Short-lived in-memory cache for read-heavy queries, keyed by query name.
+/// Entries expire after `ttl`; writers call `invalidate` to drop everything at once.
+pub struct QueryCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<&'static str, Entry>>,
+}
+
+impl QueryCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get<T: Clone + 'static>(&self, key: &'static str) -> Option<T> {
+        let entries = self.entries.lock().unwrap();
+        let (stored_at, value) = entries.get(key)?;
+        if stored_at.elapsed() >= self.ttl {
+            return None;
+        }
+        value.downcast_ref::<T>().cloned()
+    }
+
+    pub fn put<T: Clone + Send + 'static>(&self, key: &'static str, value: T) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, (Instant::now(), Box::new(value)));
+    }
+
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_and_invalidate() {
+        let cache = QueryCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get::<Vec<i64>>("lb"), None);
+
+        cache.put("lb", vec![1i64, 2, 3]);
+        assert_eq!(cache.get::<Vec<i64>>("lb"), Some(vec![1, 2, 3]));
+        // Wrong type for the key is a miss, not a panic
+        assert_eq!(cache.get::<String>("lb"), None);
+
+        cache.invalidate();
+        assert_eq!(cache.get::<Vec<i64>>("lb"), None);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let cache = QueryCache::new(Duration::ZERO);
+        cache.put("lb", 42i64);
+        assert_eq!(cache.get::<i64>("lb"), None);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::db::{CalendarFeed, Db};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono_tz::{Europe::Zurich, Tz};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+/// How often registered calendars are fetched.
+const SYNC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// Calendars larger than this are refused rather than parsed.
+const MAX_ICS_BYTES: usize = 5 * 1024 * 1024;
+/// Redirects followed per fetch, each checked like the original URL.
+const MAX_REDIRECTS: usize = 5;
+/// Events longer than this are almost certainly not meetings (e.g. multi-day blocks).
+const MAX_EVENT_MINUTES: i64 = 12 * 60;
+
+pub const DEFAULT_ACTIVITY: &str = "meetings";
+
+/// One timed VEVENT, converted to Swiss local time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// UID plus start, so each occurrence of an edited recurring event stays distinct.
+    pub key: String,
+    pub summary: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct Calendar {
+    /// `X-WR-CALNAME`, if the feed sets one.
+    pub name: Option<String>,
+    pub events: Vec<CalendarEvent>,
+}
+
+/// Undo RFC 5545 line folding: continuation lines start with a space or tab.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// `DTSTART;TZID=Europe/Zurich:20260210T090000` style property to Swiss local time.
+/// All-day (`VALUE=DATE`) values give `None`: they aren't time spent.
+fn parse_datetime(params: &str, value: &str) -> Option<NaiveDateTime> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        return None;
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&naive).with_timezone(&Zurich).naive_local());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let tz: Tz = params
+        .split(';')
+        .find_map(|p| p.strip_prefix("TZID="))
+        .and_then(|tz| tz.trim_matches('"').parse().ok())
+        // Floating times and unknown zones are taken as Swiss time
+        .unwrap_or(Zurich);
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Zurich).naive_local())
+}
+
+pub fn parse_ics(text: &str) -> Calendar {
+    let mut calendar = Calendar::default();
+    let mut in_event = false;
+    let (mut uid, mut summary, mut start, mut end, mut start_raw) = (None, None, None, None, None);
+    let mut cancelled = false;
+
+    for line in unfold(text) {
+        let Some((name_params, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name_params.split_once(';').unwrap_or((name_params, ""));
+        match name.to_ascii_uppercase().as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => {
+                in_event = true;
+                (uid, summary, start, end, start_raw) = (None, None, None, None, None);
+                cancelled = false;
+            }
+            "END" if value.eq_ignore_ascii_case("VEVENT") => {
+                in_event = false;
+                if let (Some(uid), Some(start), Some(end), false) = (&uid, start, end, cancelled) {
+                    calendar.events.push(CalendarEvent {
+                        key: format!("{}@{}", uid, start_raw.clone().unwrap_or_default()),
+                        summary: summary.clone().unwrap_or_default(),
+                        start,
+                        end,
+                    });
+                }
+            }
+            "X-WR-CALNAME" if !in_event => calendar.name = Some(unescape(value)),
+            "UID" if in_event => uid = Some(value.to_string()),
+            "SUMMARY" if in_event => summary = Some(unescape(value)),
+            "DTSTART" if in_event => {
+                start = parse_datetime(params, value);
+                start_raw = Some(value.to_string());
+            }
+            "DTEND" if in_event => end = parse_datetime(params, value),
+            "STATUS" if in_event => cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    calendar
+}
+
+/// Whether an event should be imported: no keywords imports everything, otherwise
+/// a keyword must appear in the event title or the calendar's name.
+pub fn matches(keywords: &[String], calendar_name: Option<&str>, summary: &str) -> bool {
+    if keywords.is_empty() {
+        return true;
+    }
+    let summary = summary.to_lowercase();
+    let calendar_name = calendar_name.unwrap_or_default().to_lowercase();
+    keywords.iter().any(|k| {
+        let k = k.to_lowercase();
+        summary.contains(&k) || calendar_name.contains(&k)
+    })
+}
+
+/// Accept `webcal://` links as handed out by most calendar apps.
+pub fn normalize_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_matches(['<', '>']);
+    if let Some(rest) = url.strip_prefix("webcal://") {
+        return Some(format!("https://{}", rest));
+    }
+    (url.starts_with("https://") || url.starts_with("http://")).then(|| url.to_string())
+}
+
+/// Host of a feed URL, for display without leaking the secret path.
+pub fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+/// Whether `ip` is on the public internet. Feeds are user-supplied URLs, so the bot must
+/// not be pointed at itself, the local network or a cloud metadata endpoint.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolve `url`'s host to a public address to connect to, refusing anything else.
+async fn public_address(url: &reqwest::Url) -> anyhow::Result<SocketAddr> {
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("unsupported URL scheme {}", url.scheme());
+    }
+    let host = url.host_str().ok_or_else(|| anyhow::anyhow!("URL without a host"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port)).await?.collect();
+    if addrs.is_empty() || !addrs.iter().all(|a| is_public(a.ip())) {
+        anyhow::bail!("{host} is not a public address");
+    }
+    Ok(addrs[0])
+}
+
+/// GET a feed. Every hop's host must resolve to public addresses only, and the connection
+/// is pinned to the checked address so a second DNS answer can't redirect it.
+async fn fetch(url: &str) -> anyhow::Result<String> {
+    let mut url = reqwest::Url::parse(url)?;
+    let mut redirects = 0;
+    let mut response = loop {
+        let addr = public_address(&url).await?;
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(url.host_str().unwrap_or_default(), addr)
+            .build()?;
+        let response = client.get(url.clone()).send().await?;
+        if !response.status().is_redirection() {
+            break response.error_for_status()?;
+        }
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            anyhow::bail!("too many redirects");
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("redirect without a location"))?;
+        url = url.join(location)?;
+    };
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_ICS_BYTES {
+            anyhow::bail!("calendar larger than {} bytes", MAX_ICS_BYTES);
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Import finished, matching events from the running week. Returns how many became sessions.
+pub async fn sync_feed(db: &Db, feed: &CalendarFeed) -> anyhow::Result<usize> {
+    let calendar = parse_ics(&fetch(&feed.url).await?);
+    Ok(import_events(db, feed, &calendar, db.clock().week().start(), db.clock().now()))
+}
+
+/// Events inside `[from, now]` are imported; older ones would land in the wrong week's
+/// archive and future ones haven't happened yet.
+fn import_events(db: &Db, feed: &CalendarFeed, calendar: &Calendar, from: NaiveDateTime, now: NaiveDateTime) -> usize {
+    calendar
+        .events
+        .iter()
+        .filter(|e| e.start >= from && e.end <= now && e.end > e.start)
+        .filter(|e| (e.end - e.start).num_minutes() <= MAX_EVENT_MINUTES)
+        .filter(|e| matches(&feed.keywords, calendar.name.as_deref(), &e.summary))
+        .filter(|e| {
+            db.import_calendar_event(&feed.user_id, &feed.username, &feed.activity, &e.key, e.start, e.end)
+                .unwrap_or_else(|err| {
+                    eprintln!("[clock] Calendar import failed for {}: {err}", feed.user_id);
+                    false
+                })
+        })
+        .count()
+}
+
+/// Periodically turn registered calendars' events into sessions.
+pub async fn calendar_sync_loop(db: Arc<Db>) {
+    loop {
+        for feed in db.calendar_feeds().unwrap_or_default() {
+            match sync_feed(&db, &feed).await {
+                Ok(0) => {}
+                Ok(n) => println!("[clock] Imported {n} calendar event(s) for {}", feed.username),
+                Err(e) => eprintln!("[clock] Calendar sync failed for {}: {e}", feed.username),
+            }
+        }
+        sleep(SYNC_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+X-WR-CALNAME:Work\r
+BEGIN:VEVENT\r
+UID:standup-1\r
+SUMMARY:Daily standup\r
+DTSTART;TZID=Europe/Zurich:20260210T090000\r
+DTEND;TZID=Europe/Zurich:20260210T091500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:review-1\r
+SUMMARY:Design review\\, part 2 with a very long title that is folded\r
+  across lines\r
+DTSTART:20260210T130000Z\r
+DTEND:20260210T140000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:offsite\r
+SUMMARY:Offsite\r
+DTSTART;VALUE=DATE:20260211\r
+DTEND;VALUE=DATE:20260212\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled\r
+SUMMARY:Cancelled sync\r
+STATUS:CANCELLED\r
+DTSTART:20260210T150000Z\r
+DTEND:20260210T160000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:ny\r
+SUMMARY:Call with NY\r
+DTSTART;TZID=America/New_York:20260210T100000\r
+DTEND;TZID=America/New_York:20260210T103000\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 2, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let calendar = parse_ics(ICS);
+        assert_eq!(calendar.name.as_deref(), Some("Work"));
+        // The all-day and the cancelled event are dropped
+        assert_eq!(calendar.events.len(), 3);
+
+        let standup = &calendar.events[0];
+        assert_eq!(standup.summary, "Daily standup");
+        assert_eq!((standup.start, standup.end), (at(10, 9, 0), at(10, 9, 15)));
+        assert_eq!(standup.key, "standup-1@20260210T090000");
+
+        // UTC converted to CET, folded line and escaped comma restored
+        let review = &calendar.events[1];
+        assert_eq!(review.summary, "Design review, part 2 with a very long title that is folded across lines");
+        assert_eq!((review.start, review.end), (at(10, 14, 0), at(10, 15, 0)));
+
+        // 10:00 in New York is 16:00 in Zurich in February
+        assert_eq!(calendar.events[2].start, at(10, 16, 0));
+    }
+
+    #[test]
+    fn test_matches_keywords_and_calendar_name() {
+        let kw = |k: &[&str]| k.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(matches(&[], None, "anything"));
+        assert!(matches(&kw(&["standup"]), None, "Daily Standup"));
+        assert!(!matches(&kw(&["standup"]), None, "Design review"));
+        assert!(matches(&kw(&["work"]), Some("Work"), "Design review"));
+        assert!(matches(&kw(&["lunch", "review"]), None, "Design review"));
+    }
+
+    #[test]
+    fn test_normalize_url_and_host() {
+        assert_eq!(normalize_url("webcal://example.com/cal.ics").as_deref(), Some("https://example.com/cal.ics"));
+        assert_eq!(normalize_url("<https://example.com/a.ics>").as_deref(), Some("https://example.com/a.ics"));
+        assert_eq!(normalize_url("ftp://example.com/a.ics"), None);
+        assert_eq!(url_host("https://calendar.google.com/calendar/ical/secret/basic.ics"), "calendar.google.com");
+    }
+
+    #[test]
+    fn test_import_events_window_and_dedup() {
+        let db = test_db();
+        let feed = CalendarFeed {
+            user_id: "u1".to_string(),
+            username: "Alice".to_string(),
+            url: "https://example.com/a.ics".to_string(),
+            keywords: Vec::new(),
+            activity: DEFAULT_ACTIVITY.to_string(),
+        };
+        let calendar = parse_ics(ICS);
+
+        // Only events that have ended by `now` are imported
+        assert_eq!(import_events(&db, &feed, &calendar, at(9, 0, 0), at(10, 15, 0)), 2);
+        // Already imported events are skipped on the next sync
+        assert_eq!(import_events(&db, &feed, &calendar, at(9, 0, 0), at(10, 15, 0)), 0);
+        assert_eq!(import_events(&db, &feed, &calendar, at(9, 0, 0), at(11, 0, 0)), 1);
+        // Nothing before the window start
+        assert_eq!(import_events(&db, &feed, &calendar, at(11, 0, 0), at(12, 0, 0)), 0);
+
+        let sessions = db.sessions_between("u1", at(10, 0, 0), at(11, 0, 0)).unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions.iter().all(|s| s.activity == DEFAULT_ACTIVITY));
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.10", "169.254.169.254", "0.0.0.0", "100.100.100.200",
+            "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses_private_hosts() {
+        for url in ["http://127.0.0.1:1/cal.ics", "http://169.254.169.254/latest/meta-data/", "http://[::1]/cal.ics", "file:///etc/passwd"] {
+            let err = fetch(url).await.unwrap_err().to_string();
+            assert!(err.contains("not a public address") || err.contains("unsupported URL scheme"), "{url}: {err}");
+        }
+    }
+}
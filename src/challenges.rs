@@ -0,0 +1,167 @@
+use crate::db::{Challenge, Db};
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDateTime};
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often challenges are checked for their mid-week progress post.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+/// Progress goes out Wednesday noon, halfway through the working week.
+const PROGRESS_AFTER: Duration = Duration::hours(2 * 24 + 12);
+
+/// When this week's progress posts go out.
+pub fn progress_time(week: WeekId) -> NaiveDateTime {
+    week.start() + PROGRESS_AFTER
+}
+
+/// Who won: the one who reached the target, or with both there the one with more time.
+/// `None` for a draw or when nobody reached it.
+pub fn winner(challenge: &Challenge, challenger_min: i64, opponent_min: i64) -> Option<&str> {
+    let reached = |m: i64| m >= challenge.target_min;
+    match (reached(challenger_min), reached(opponent_min)) {
+        (true, false) => Some(&challenge.challenger_name),
+        (false, true) => Some(&challenge.opponent_name),
+        (true, true) if challenger_min > opponent_min => Some(&challenge.challenger_name),
+        (true, true) if opponent_min > challenger_min => Some(&challenge.opponent_name),
+        _ => None,
+    }
+}
+
+/// `**Alice** 6h 0m vs **Bob** 4h 30m · race to 10h 0m`
+pub fn standing_line(challenge: &Challenge, challenger_min: i64, opponent_min: i64, fmt: &DisplayFormat) -> String {
+    format!(
+        "**{}** {} vs **{}** {} · race to {}",
+        challenge.challenger_name,
+        fmt.duration(challenger_min),
+        challenge.opponent_name,
+        fmt.duration(opponent_min),
+        fmt.duration(challenge.target_min)
+    )
+}
+
+/// The standing with the outcome, for the weekly summary.
+pub fn result_line(challenge: &Challenge, challenger_min: i64, opponent_min: i64, fmt: &DisplayFormat) -> String {
+    let outcome = match winner(challenge, challenger_min, opponent_min) {
+        Some(name) => format!("🏆 **{}** wins", name),
+        None if challenger_min >= challenge.target_min => "🤝 draw".to_string(),
+        None => "nobody made it".to_string(),
+    };
+    format!("{} — {}", standing_line(challenge, challenger_min, opponent_min, fmt), outcome)
+}
+
+fn minutes(db: &Db, challenge: &Challenge, week: WeekId) -> (i64, i64) {
+    (
+        db.competitive_minutes(&challenge.challenger_id, week).unwrap_or(0),
+        db.competitive_minutes(&challenge.opponent_id, week).unwrap_or(0),
+    )
+}
+
+/// Current standing of `challenge` in `week`.
+pub fn standing(db: &Db, challenge: &Challenge, week: WeekId, fmt: &DisplayFormat) -> String {
+    let (a, b) = minutes(db, challenge, week);
+    standing_line(challenge, a, b, fmt)
+}
+
+/// Settle the challenges of the closing `week` before it is archived. Returns the
+/// summary message, `None` without challenges.
+pub fn settle(db: &Db, week: WeekId, fmt: &DisplayFormat) -> Option<CreateMessage> {
+    let lines = settle_lines(db, week, fmt);
+    if lines.is_empty() {
+        return None;
+    }
+    let embed = CreateEmbed::new()
+        .color(0x9b59b6)
+        .title(format!("⚔️ Challenges — {}", fmt.week(&week.label())))
+        .description(lines.join("\n"));
+    Some(CreateMessage::new().embed(embed))
+}
+
+/// Decide the challenges of `week` and describe each result.
+pub fn settle_lines(db: &Db, week: WeekId, fmt: &DisplayFormat) -> Vec<String> {
+    let challenges = db.settle_challenges(&week.label()).unwrap_or_default();
+    challenges
+        .iter()
+        .map(|c| {
+            let (a, b) = minutes(db, c, week);
+            result_line(c, a, b, fmt)
+        })
+        .collect()
+}
+
+/// Post each challenge's standing to the channel it was made in once the week is halfway.
+pub async fn progress_loop(db: Arc<Db>, http: Arc<Http>) {
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let now = db.clock().now();
+        let week = WeekId::containing(now.date());
+        if now < progress_time(week) {
+            continue;
+        }
+        let fmt = DisplayFormat::load(&db);
+        for challenge in db.challenges_awaiting_progress(&week.label()).unwrap_or_default() {
+            let Ok(channel_id) = challenge.channel_id.parse::<u64>() else {
+                continue;
+            };
+            let embed = CreateEmbed::new()
+                .color(0x9b59b6)
+                .title("⚔️ Halfway there")
+                .description(standing(&db, &challenge, week, &fmt))
+                .footer(CreateEmbedFooter::new("Decided at the weekly reset"));
+            if let Err(e) = ChannelId::new(channel_id)
+                .send_message(&http, CreateMessage::new().embed(embed))
+                .await
+            {
+                eprintln!("[clock] Failed to post challenge progress: {e}");
+            }
+            let _ = db.mark_challenge_progress_posted(challenge.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge() -> Challenge {
+        Challenge {
+            id: 1,
+            week_label: "KW12/2026".to_string(),
+            channel_id: "1".to_string(),
+            challenger_id: "a".to_string(),
+            challenger_name: "Alice".to_string(),
+            opponent_id: "b".to_string(),
+            opponent_name: "Bob".to_string(),
+            target_min: 600,
+        }
+    }
+
+    #[test]
+    fn test_winner() {
+        let c = challenge();
+        assert_eq!(winner(&c, 650, 300), Some("Alice"));
+        assert_eq!(winner(&c, 300, 600), Some("Bob"));
+        assert_eq!(winner(&c, 700, 650), Some("Alice"));
+        assert_eq!(winner(&c, 650, 650), None);
+        assert_eq!(winner(&c, 500, 590), None);
+    }
+
+    #[test]
+    fn test_result_line() {
+        let c = challenge();
+        let fmt = DisplayFormat::default();
+        assert_eq!(
+            result_line(&c, 650, 300, &fmt),
+            "**Alice** 10h 50m vs **Bob** 5h 0m · race to 10h 0m — 🏆 **Alice** wins"
+        );
+        assert!(result_line(&c, 600, 600, &fmt).ends_with("🤝 draw"));
+        assert!(result_line(&c, 60, 0, &fmt).ends_with("nobody made it"));
+    }
+
+    #[test]
+    fn test_progress_time_is_wednesday_noon() {
+        let week = WeekId::parse("KW12/2026").unwrap();
+        assert_eq!(progress_time(week).format("%a %H:%M").to_string(), "Wed 12:00");
+    }
+}
@@ -1,4 +1,5 @@
-use crate::db::ChartData;
+use crate::db::{ActivityEntry, ChartData};
+use crate::display::DisplayFormat;
 use plotters::prelude::*;
 
 /// Chart display mode.
@@ -9,7 +10,7 @@ pub enum ChartMode {
 }
 
 impl ChartMode {
-    pub fn from_str(s: &str) -> Self {
+    pub fn parse(s: &str) -> Self {
         match s {
             "cumulative" => ChartMode::Cumulative,
             "both" => ChartMode::Both,
@@ -51,10 +52,8 @@ fn nice_ceiling(v: f64) -> f64 {
 
 /// Shorten week labels for display: `"KW14/2026"` → `"W14"`, others unchanged.
 fn short_week_label(s: &str) -> String {
-    if let Some(rest) = s.strip_prefix("KW") {
-        if let Some((num, _year)) = rest.split_once('/') {
-            return format!("W{}", num);
-        }
+    if let Some((num, _year)) = s.strip_prefix("KW").and_then(|rest| rest.split_once('/')) {
+        return format!("W{}", num);
     }
     s.to_string()
 }
@@ -98,7 +97,11 @@ pub fn render_chart(data: &ChartData, mode: ChartMode) -> anyhow::Result<Vec<u8>
             .map_err(|e| anyhow::anyhow!("present error: {:?}", e))?;
     }
 
-    // Encode the raw RGB buffer to PNG in memory.
+    encode_png(width, height, pixel_buf)
+}
+
+/// Encode a raw RGB buffer to PNG in memory.
+fn encode_png(width: u32, height: u32, pixel_buf: Vec<u8>) -> anyhow::Result<Vec<u8>> {
     let img = image::RgbImage::from_raw(width, height, pixel_buf)
         .ok_or_else(|| anyhow::anyhow!("failed to create RGB image from pixel buffer"))?;
     let mut png_bytes: Vec<u8> = Vec::new();
@@ -112,6 +115,97 @@ pub fn render_chart(data: &ChartData, mode: ChartMode) -> anyhow::Result<Vec<u8>
     Ok(png_bytes)
 }
 
+/// What goes on a user's shareable weekly report card.
+#[derive(Debug, PartialEq)]
+pub struct ReportCard {
+    pub username: String,
+    pub week_label: String,
+    pub total_minutes: i64,
+    pub sessions: i64,
+    /// Up to three `(activity, minutes)`, largest first.
+    pub top_activities: Vec<(String, i64)>,
+    /// `(rank, people)` on the week's leaderboard.
+    pub rank: Option<(usize, usize)>,
+    pub streak_days: u32,
+}
+
+impl ReportCard {
+    /// `mine` is the user's per-activity rows for the week; `leaderboard` is everyone's
+    /// `(username, minutes)`, largest first.
+    pub fn new(
+        username: &str,
+        week_label: &str,
+        mine: &[ActivityEntry],
+        leaderboard: &[(String, i64)],
+        streak_days: u32,
+    ) -> Self {
+        let mut top: Vec<(String, i64)> = mine.iter().map(|e| (e.activity.clone(), e.total_minutes)).collect();
+        top.sort_by_key(|(_, minutes)| std::cmp::Reverse(*minutes));
+        top.truncate(3);
+        Self {
+            username: username.to_string(),
+            week_label: week_label.to_string(),
+            total_minutes: mine.iter().map(|e| e.total_minutes).sum(),
+            sessions: mine.iter().map(|e| e.session_count).sum(),
+            top_activities: top,
+            rank: leaderboard
+                .iter()
+                .position(|(name, _)| name == username)
+                .map(|i| (i + 1, leaderboard.len())),
+            streak_days,
+        }
+    }
+}
+
+/// Render `card` as an 800×420 PNG in the chart theme.
+pub fn render_report_card(card: &ReportCard, fmt: &DisplayFormat) -> anyhow::Result<Vec<u8>> {
+    let (width, height): (u32, u32) = (800, 420);
+    let mut pixel_buf = vec![0u8; (width * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixel_buf, (width, height)).into_drawing_area();
+        let err = |e| anyhow::anyhow!("report card: {:?}", e);
+        root.fill(&BG).map_err(err)?;
+        root.draw(&Rectangle::new([(0, 0), (8, height as i32)], PALETTE[0].filled()))
+            .map_err(err)?;
+
+        let text = |s: &str, pos: (i32, i32), size: u32, color: &RGBColor| {
+            root.draw(&Text::new(s.to_string(), pos, ("sans-serif", size).into_font().color(color)))
+        };
+        text(&format!("Weekly report card · {}", fmt.week(&card.week_label)), (40, 30), 18, &MUTED).map_err(err)?;
+        text(&card.username, (40, 60), 38, &FG).map_err(err)?;
+        text(&fmt.duration(card.total_minutes), (40, 125), 64, &PALETTE[0]).map_err(err)?;
+        text(&format!("tracked in {} sessions", card.sessions), (40, 200), 18, &MUTED).map_err(err)?;
+
+        let rank = card
+            .rank
+            .map(|(rank, people)| format!("#{} of {}", rank, people))
+            .unwrap_or_else(|| "—".to_string());
+        let streak = match card.streak_days {
+            1 => "1 day".to_string(),
+            n => format!("{} days", n),
+        };
+        for (i, (label, value)) in [("Rank", rank), ("Streak", streak)].iter().enumerate() {
+            let y = 70 + i as i32 * 75;
+            text(label, (540, y), 16, &MUTED).map_err(err)?;
+            text(value, (540, y + 22), 30, &FG).map_err(err)?;
+        }
+
+        let max = card.top_activities.first().map(|(_, m)| *m).unwrap_or(1).max(1);
+        for (i, (activity, minutes)) in card.top_activities.iter().enumerate() {
+            let y = 260 + i as i32 * 48;
+            let color = PALETTE[i % PALETTE.len()];
+            let bar = (420 * *minutes / max) as i32;
+            text(activity, (40, y), 18, &FG).map_err(err)?;
+            root.draw(&Rectangle::new([(40, y + 26), (40 + bar.max(4), y + 36)], color.filled()))
+                .map_err(err)?;
+            text(&fmt.duration(*minutes), (480, y + 18), 18, &MUTED).map_err(err)?;
+        }
+
+        root.present().map_err(err)?;
+    }
+    encode_png(width, height, pixel_buf)
+}
+
 fn draw_panel<DB>(
     area: &DrawingArea<DB, plotters::coord::Shift>,
     data: &ChartData,
@@ -340,4 +434,33 @@ mod tests {
         };
         assert!(render_chart(&data, ChartMode::Totals).is_err());
     }
+
+    #[test]
+    fn test_report_card() {
+        register_test_font();
+        let entry = |activity: &str, minutes| ActivityEntry {
+            username: "Alice".to_string(),
+            activity: activity.to_string(),
+            total_minutes: minutes,
+            session_count: 2,
+        };
+        let mine = vec![entry("docs", 30), entry("backend", 240), entry("review", 60), entry("ops", 45)];
+        let leaderboard = vec![("Bob".to_string(), 400), ("Alice".to_string(), 375)];
+        let card = ReportCard::new("Alice", "KW07/2026", &mine, &leaderboard, 5);
+        assert_eq!(card.total_minutes, 375);
+        assert_eq!(card.sessions, 8);
+        assert_eq!(card.rank, Some((2, 2)));
+        assert_eq!(
+            card.top_activities,
+            vec![("backend".to_string(), 240), ("review".to_string(), 60), ("ops".to_string(), 45)]
+        );
+
+        let png = render_report_card(&card, &DisplayFormat::default()).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        let img = image::load_from_memory(&png).unwrap();
+        assert_eq!((img.width(), img.height()), (800, 420));
+
+        // Users off the leaderboard still get a card
+        assert!(render_report_card(&ReportCard::new("Carol", "KW07/2026", &[], &leaderboard, 0), &DisplayFormat::default()).is_ok());
+    }
 }
@@ -0,0 +1,96 @@
+use crate::weeks::WeekId;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Europe::Zurich;
+use std::sync::Mutex;
+
+/// Where the bot gets the time from. `Db` holds one and the background loops ask it,
+/// so tests can run Monday rollovers, DST changes and long sessions on a fixed clock.
+pub trait Clock: Send + Sync {
+    fn now_utc(&self) -> DateTime<Utc>;
+
+    /// Swiss local time.
+    fn now(&self) -> NaiveDateTime {
+        self.now_utc().with_timezone(&Zurich).naive_local()
+    }
+
+    /// Swiss local date.
+    fn today(&self) -> NaiveDate {
+        self.now().date()
+    }
+
+    /// The running week in Swiss local time.
+    fn week(&self) -> WeekId {
+        WeekId::containing(self.today())
+    }
+}
+
+/// The real time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests and simulations.
+pub struct FixedClock(Mutex<DateTime<Utc>>);
+
+impl FixedClock {
+    /// A clock standing at `at`, Swiss local time (the earlier instant if DST repeats it).
+    pub fn at(at: NaiveDateTime) -> Self {
+        Self(Mutex::new(swiss_instant(at)))
+    }
+
+    /// Move the clock to `at`, Swiss local time.
+    pub fn set(&self, at: NaiveDateTime) {
+        *self.0.lock().unwrap() = swiss_instant(at);
+    }
+
+    /// Move the clock on by `by`, in real elapsed time.
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// `at` in Swiss local time as an instant; a time skipped by DST counts as an hour later.
+fn swiss_instant(at: NaiveDateTime) -> DateTime<Utc> {
+    Zurich
+        .from_local_datetime(&at)
+        .earliest()
+        .or_else(|| Zurich.from_local_datetime(&(at + Duration::hours(1))).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_fixed_clock() {
+        let clock = FixedClock::at(at(29, 1, 30));
+        assert_eq!(clock.now(), at(29, 1, 30));
+        // Clocks go forward at 02:00 on 29 March
+        clock.advance(Duration::hours(1));
+        assert_eq!(clock.now(), at(29, 3, 30));
+        assert_eq!(clock.week(), WeekId::containing(at(29, 0, 0).date()));
+
+        clock.set(at(30, 0, 0));
+        assert_eq!(clock.today(), at(30, 0, 0).date());
+        assert_eq!(clock.week(), WeekId::containing(at(29, 0, 0).date()).next());
+        // 02:30 doesn't exist that night
+        clock.set(at(29, 2, 30));
+        assert_eq!(clock.now(), at(29, 3, 30));
+    }
+}
@@ -1,15 +1,65 @@
-use crate::db::{self, ActivityEntry, Db, LeaderboardEntry, WeeklySummary};
+use crate::config::Capability;
+use crate::db::{self, ActivityEntry, Db, GithubLink, LeaderboardEntry, TimelineSession, UserRank, WeeklySummary};
+use crate::debounce::Debounce;
+use crate::display::DisplayFormat;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use once_cell::sync::Lazy;
 use serenity::all::*;
 use std::sync::Arc;
+use std::time::Duration;
 
 const HELP: &str = r#"**Commands**
-`/clock in <activity>` — start tracking
+`/clock in <activity> [for 2h]` — start tracking, optionally for a set time
 `/clock out` — stop tracking
+`/clock again` — clock back in on your last activity
+`/clock out in <30m|2h>` · `out cancel` — schedule an automatic clock-out
 `/clock status` — your session
 `/clock who` — who's working
 `/clock leaderboard` — weekly + all-time
-`/clock stats` — activity breakdown
+`/clock leaderboard fair` — this week as % of everyone's own goal
+`/clock event [name|list]` — live standings of an event
+`/clock challenge [@user <hours> this-week]` — race someone to a target; decided at the reset
+`/clock event create "<name>" <start> <end>` · `delete <name>` — edit events (admins)
+`/clock stats [auto|<source>]` — activity breakdown; `auto` leaves out clocked time
+`/clock timeline [day]` — your day in 30-minute blocks
+`/clock goal [hours|off]` — daily goal and today's progress
+`/clock timezone [Area/City]` — timezone your days are counted in
+`/clock link [@account]` · `unlink [@account]` — count another account of yours as you (both confirm)
+`/clock nudge on|off` — evening DM when you're behind your goal
+`/clock tips on|off` — greetings and the odd command tip on clock-in/out posts
+`/clock config [key] [value|off]` — server settings (admins)
+`/clock config perms [<admin|export> add|remove @role]` — which roles may use admin commands and exports
+`/clock menu` — quick-start menu of common activities
+`/clock menu add|remove <activity>` — curate the menu (admins)
+`/clock non-competitive [add|remove <activity>]` — activities left out of rankings (admins edit)
+`/clock weight [<activity> <×0.5|off>]` — score weights for the leaderboard (admins edit)
+`/clock calendar set <ics-url>` — import finished calendar events as sessions (best in DM)
+`/clock calendar filter <words|off>` · `activity <name>` · `sync` · `off`
+`/clock github link <login> [suggest|log]` · `verify` · `unlink` — turn GitHub pushes into sessions
+`/clock github map <repo> > <activity>` · `unmap <repo>` — repo rules (admins)
+`/clock badge [map <external-id> @user|unmap <external-id>]` — badge reader IDs for `/attendance` (admins)
+`/clock kiosk [revoke]` — DM a short code for clocking in and out at a shared kiosk
+`/clock apikey [revoke]` — DM a key for editor heartbeats (WakaTime-compatible)
+`/clock token [create [read|write]|revoke <id>]` — personal API tokens for scripts and integrations
+`/clock digest [email|off]` — weekly report by email every Monday
+`/clock holidays [year]` — holiday calendar
+`/clock holiday add <date> <name>` · `remove <date>` · `import [year]` — edit it (admins)
+`/clock activity <name>` — drill into one activity
+`/clock project stats <name>` — the same, for an activity tracked as a project
+`/clock project budget [<name> <200h|off>]` — hour budgets with burn alerts (admins edit)
+`/clock client [<client>]` — time per client
+`/clock client assign <activity> to <client>` · `unassign <activity>` · `rate <client> <150|off>` — file activities and set rates (admins)
+`/clock invoice <client> <YYYY-MM>` — month of client time as CSV, with amounts at its rate (export permission)
+`/clock export` — this week's sessions with their source as CSV
+`/clock export all` — the same for everyone (export permission)
+`/clock search <term>` — find existing activity names
+`/clock ask <question>` — e.g. "how much did I work on backend last month?"
 `/clock rename <old> > <new>` — rename + merge activity
+`/clock activity describe <activity> "<text>"` · `off` — describe a shared activity, naming its owner with a mention (admins)
+`/clock normalize preview <activity>` — how a name would be normalized and what it groups with
+`/clock clusters` — groups of similar activity names to merge (admins)
+`/clock admin check` — look for implausible sessions and archives that don't add up (admins)
+`/clock delete-activity <name>` — delete one of your activities
 `/clock chart [weeks] [totals|cumulative|both]` — line chart of top 5 weekly hours
 `/clock help`"#;
 
@@ -25,11 +75,126 @@ const BAR_FULL: &str = "█";
 const BAR_EMPTY: &str = "░";
 const BAR_WIDTH: usize = 16;
 const CHART_MEDALS: [&str; 5] = ["🥇", "🥈", "🥉", "▫️", "▫️"];
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ACTIVITY_TREND_WEEKS: u32 = 12;
+const SEARCH_MAX_RESULTS: usize = 10;
+const SHARE_SHIFT_ROWS: usize = 5;
+/// Discord caps select menus at 25 options of up to 100 characters.
+const MENU_MAX_OPTIONS: usize = 25;
+const MENU_MAX_LABEL: usize = 100;
+pub const MENU_CUSTOM_ID: &str = "clock_menu";
+/// Buttons offered after a clock-in shortly after the last session; followed by `:<user id>`.
+pub const MERGE_CUSTOM_ID: &str = "clock_merge";
+pub const KEEP_CUSTOM_ID: &str = "clock_keep";
+/// Buttons of the forgotten clock-out fix: `clock_stale:<user id>:<end|keep>`.
+pub const STALE_CUSTOM_ID: &str = "clock_stale";
+/// Suggested end of a forgotten session when `day-end` isn't set.
+const DEFAULT_DAY_END: (u32, u32) = (18, 0);
+const TIMELINE_SLOT_MINUTES: i64 = 30;
+const TIMELINE_SLOTS: i64 = 24 * 60 / TIMELINE_SLOT_MINUTES;
+const TIMELINE_IDLE: &str = "·";
+
+// Discord embed limits (characters)
+const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+const EMBED_FIELD_LIMIT: usize = 1024;
+/// Breakdowns needing more continuation messages than this are attached as CSV instead.
+const MAX_CONTINUATION_MESSAGES: usize = 3;
+
+/// Commands from a bot or webhook in `TRUSTED_BOTS`. Only `/clock log` is accepted:
+/// it records time that just ended for the person mentioned, marked as logged by a bot.
+pub async fn handle_bot_command(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    const USAGE: &str = "Usage: `/clock log @user <45m|1h30m> <activity>`";
+    let Some(args) = msg.content.strip_prefix("/clock log") else {
+        return;
+    };
+    if is_duplicate(msg) {
+        return;
+    }
+    let fmt = DisplayFormat::load(db);
+
+    let mut words = args.split_whitespace().filter(|w| !w.starts_with("<@"));
+    let minutes = words.next().and_then(crate::auto_out::parse_duration);
+    let activity = crate::normalize::normalize_activity(&words.collect::<Vec<_>>().join(" "));
+    let (Some(user), Some(minutes)) = (msg.mentions.iter().find(|u| !u.bot), minutes) else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    if activity.is_empty() {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let username = match msg.guild_id {
+        Some(guild_id) => user
+            .nick_in(&ctx.http, guild_id)
+            .await
+            .unwrap_or_else(|| user.display_name().to_string()),
+        None => user.display_name().to_string(),
+    };
+    let end = db.clock().now();
+    let start = end - chrono::Duration::minutes(minutes);
+    let reply = match db.import_session(&user.id.to_string(), &username, &activity, start, end, db::SOURCE_BOT) {
+        Ok(true) => format!("🤖 Logged {} of **{}** for <@{}>.", fmt.duration(minutes), activity, user.id),
+        Ok(false) => format!("<@{}> already has time in that stretch; nothing logged.", user.id),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// Message ids already handled; Discord can deliver a message again after a reconnect.
+static SEEN_MESSAGES: Lazy<Debounce> = Lazy::new(|| Debounce::new(Duration::from_secs(10 * 60)));
+/// The same command from the same person in the same channel moments apart is a double-send.
+static RECENT_COMMANDS: Lazy<Debounce> = Lazy::new(|| Debounce::new(Duration::from_secs(3)));
+
+/// Whether `msg` repeats a command that was just handled, so it should be ignored.
+fn is_duplicate(msg: &Message) -> bool {
+    let redelivered = !SEEN_MESSAGES.first(&msg.id.to_string());
+    let fingerprint = format!("{}:{}:{}", msg.author.id, msg.channel_id, msg.content.trim());
+    if redelivered || !RECENT_COMMANDS.first(&fingerprint) {
+        println!("[clock] Ignoring duplicate command from {}: {}", msg.author.id, msg.content.trim());
+        return true;
+    }
+    false
+}
+
+/// The capability `/clock <rest>` needs and what it does, for the refusal; `None` for
+/// commands anyone may use. Changing `config perms` is for `ADMIN_IDS` only, see `handle_perms`.
+fn required_capability(rest: &str) -> Option<(Capability, &'static str)> {
+    let mut words = rest.split_whitespace();
+    let (command, action) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    let has_value = words.next().is_some();
+    match (command, action) {
+        ("config", "perms") => None,
+        ("config", _) if has_value => Some((Capability::Admin, "change settings")),
+        ("menu", a) if !a.is_empty() => Some((Capability::Admin, "edit the menu")),
+        ("non-competitive", a) if !a.is_empty() => Some((Capability::Admin, "change which activities are ranked")),
+        ("weight", a) if !a.is_empty() => Some((Capability::Admin, "change score weights")),
+        ("project", "budget") if has_value => Some((Capability::Admin, "change project budgets")),
+        ("client", "rate") if has_value => Some((Capability::Admin, "set client rates")),
+        ("client", "assign" | "unassign") if has_value => Some((Capability::Admin, "file activities under clients")),
+        ("invoice", _) => Some((Capability::Export, "export invoices")),
+        ("export", "all") => Some((Capability::Export, "export everyone's sessions")),
+        ("holiday", a) if !a.is_empty() => Some((Capability::Admin, "edit the holiday calendar")),
+        ("github", "map" | "unmap") => Some((Capability::Admin, "edit repo rules")),
+        ("badge", _) => Some((Capability::Admin, "map badges")),
+        ("event", "create" | "delete") => Some((Capability::Admin, "create or delete events")),
+        ("activity", "describe") if has_value => Some((Capability::Admin, "describe activities")),
+        ("clusters", "") => Some((Capability::Admin, "see the activity clusters")),
+        ("admin", "check") => Some((Capability::Admin, "run the data checks")),
+        _ => None,
+    }
+}
 
 pub async fn handle_command(ctx: &Context, msg: &Message, db: &Arc<Db>) {
     if !msg.content.starts_with("/clock") {
         return;
     }
+    if is_duplicate(msg) {
+        return;
+    }
 
     let rest = msg.content.strip_prefix("/clock").unwrap().trim();
 
@@ -38,6 +203,17 @@ pub async fn handle_command(ctx: &Context, msg: &Message, db: &Arc<Db>) {
         return;
     }
 
+    if let Some((capability, action)) = required_capability(rest)
+        && !crate::config::allowed(db, msg, capability)
+    {
+        let who = match capability {
+            Capability::Admin => "admins",
+            Capability::Export => "admins and roles with the export permission",
+        };
+        let _ = msg.reply(&ctx.http, format!("Only {who} can {action}.")).await;
+        return;
+    }
+
     if rest.starts_with("in ") {
         let activity = rest.strip_prefix("in ").unwrap().trim();
         if activity.is_empty() {
@@ -46,21 +222,127 @@ pub async fn handle_command(ctx: &Context, msg: &Message, db: &Arc<Db>) {
                 .await;
             return;
         }
+        let (activity, auto_out) = crate::auto_out::split_for(activity);
         let activity = crate::normalize::normalize_activity(activity);
-        handle_clock_in(ctx, msg, db, &activity).await;
+        handle_clock_in(ctx, msg, db, &activity, auto_out, None).await;
+    } else if rest == "again" {
+        handle_again(ctx, msg, db).await;
     } else if rest == "out" {
         handle_clock_out(ctx, msg, db).await;
+    } else if let Some(args) = rest.strip_prefix("out ") {
+        handle_schedule_out(ctx, msg, db, args.trim()).await;
     } else if rest == "status" {
         handle_status(ctx, msg, db).await;
     } else if rest == "who" {
         handle_who(ctx, msg, db).await;
     } else if rest == "leaderboard" || rest == "lb" {
         handle_leaderboard(ctx, msg, db).await;
-    } else if rest == "stats" {
-        handle_stats(ctx, msg, db).await;
+    } else if rest == "leaderboard fair" || rest == "lb fair" {
+        handle_fair_leaderboard(ctx, msg, db).await;
+    } else if rest == "challenge" || rest.starts_with("challenge ") {
+        let args = rest.strip_prefix("challenge").unwrap().trim();
+        handle_challenge(ctx, msg, db, args).await;
+    } else if rest == "event" || rest.starts_with("event ") {
+        let args = rest.strip_prefix("event").unwrap().trim();
+        handle_event(ctx, msg, db, args).await;
+    } else if rest == "stats" || rest.starts_with("stats ") {
+        let source = rest.strip_prefix("stats").unwrap().trim();
+        handle_stats(ctx, msg, db, source).await;
+    } else if rest == "export" || rest == "export all" {
+        handle_export(ctx, msg, db, rest == "export all").await;
+    } else if rest.starts_with("timeline") {
+        let day = rest.strip_prefix("timeline").unwrap().trim();
+        handle_timeline(ctx, msg, db, day).await;
+    } else if rest.starts_with("goal") {
+        let args = rest.strip_prefix("goal").unwrap().trim();
+        handle_goal(ctx, msg, db, args).await;
+    } else if rest == "link" || rest.starts_with("link ") {
+        handle_link(ctx, msg, db).await;
+    } else if rest == "unlink" || rest.starts_with("unlink ") {
+        handle_unlink(ctx, msg, db).await;
+    } else if rest.starts_with("timezone") {
+        let args = rest.strip_prefix("timezone").unwrap().trim();
+        handle_timezone(ctx, msg, db, args).await;
+    } else if rest == "tips" || rest.starts_with("tips ") {
+        let args = rest.strip_prefix("tips").unwrap().trim();
+        handle_tips(ctx, msg, db, args).await;
+    } else if rest.starts_with("nudge") {
+        let args = rest.strip_prefix("nudge").unwrap().trim();
+        handle_nudge(ctx, msg, db, args).await;
+    } else if rest.starts_with("config") {
+        let args = rest.strip_prefix("config").unwrap().trim();
+        handle_config(ctx, msg, db, args).await;
+    } else if rest == "non-competitive" || rest.starts_with("non-competitive ") {
+        let args = rest.strip_prefix("non-competitive").unwrap().trim();
+        handle_non_competitive(ctx, msg, db, args).await;
+    } else if rest == "weight" || rest.starts_with("weight ") {
+        let args = rest.strip_prefix("weight").unwrap().trim();
+        handle_weight(ctx, msg, db, args).await;
+    } else if rest == "menu" || rest.starts_with("menu ") {
+        let args = rest.strip_prefix("menu").unwrap().trim();
+        handle_menu(ctx, msg, db, args).await;
+    } else if rest == "calendar" || rest.starts_with("calendar ") {
+        let args = rest.strip_prefix("calendar").unwrap().trim();
+        handle_calendar(ctx, msg, db, args).await;
+    } else if rest == "kiosk" || rest.starts_with("kiosk ") {
+        let args = rest.strip_prefix("kiosk").unwrap().trim();
+        handle_kiosk(ctx, msg, db, args).await;
+    } else if rest == "badge" || rest.starts_with("badge ") {
+        let args = rest.strip_prefix("badge").unwrap().trim();
+        handle_badge(ctx, msg, db, args).await;
+    } else if rest == "github" || rest.starts_with("github ") {
+        let args = rest.strip_prefix("github").unwrap().trim();
+        handle_github(ctx, msg, db, args).await;
+    } else if rest == "token" || rest.starts_with("token ") {
+        let args = rest.strip_prefix("token").unwrap().trim();
+        handle_token(ctx, msg, db, args).await;
+    } else if rest == "apikey" || rest.starts_with("apikey ") {
+        let args = rest.strip_prefix("apikey").unwrap().trim();
+        handle_apikey(ctx, msg, db, args).await;
+    } else if rest == "digest" || rest.starts_with("digest ") {
+        let args = rest.strip_prefix("digest").unwrap().trim();
+        handle_digest(ctx, msg, db, args).await;
+    } else if rest == "holidays" || rest.starts_with("holidays ") {
+        let args = rest.strip_prefix("holidays").unwrap().trim();
+        handle_holidays(ctx, msg, db, args).await;
+    } else if rest.starts_with("holiday ") {
+        let args = rest.strip_prefix("holiday ").unwrap().trim();
+        handle_holiday(ctx, msg, db, args).await;
+    } else if let Some(name) = rest.strip_prefix("project stats ") {
+        handle_activity(ctx, msg, db, name.trim()).await;
+    } else if rest == "client" || rest.starts_with("client ") {
+        let args = rest.strip_prefix("client").unwrap().trim();
+        handle_client(ctx, msg, db, args).await;
+    } else if rest == "invoice" || rest.starts_with("invoice ") {
+        let args = rest.strip_prefix("invoice").unwrap().trim();
+        handle_invoice(ctx, msg, db, args).await;
+    } else if rest == "project budget" || rest.starts_with("project budget ") {
+        let args = rest.strip_prefix("project budget").unwrap().trim();
+        handle_project_budget(ctx, msg, db, args).await;
+    } else if let Some(args) = rest.strip_prefix("activity describe ") {
+        handle_activity_describe(ctx, msg, db, args.trim()).await;
+    } else if rest.starts_with("activity ") {
+        let name = rest.strip_prefix("activity ").unwrap().trim();
+        handle_activity(ctx, msg, db, name).await;
+    } else if rest == "normalize preview" || rest.starts_with("normalize preview ") {
+        let text = rest.strip_prefix("normalize preview").unwrap().trim();
+        handle_normalize_preview(ctx, msg, db, text).await;
+    } else if rest.starts_with("search ") {
+        let term = rest.strip_prefix("search ").unwrap().trim();
+        handle_search(ctx, msg, db, term).await;
+    } else if rest.starts_with("ask ") {
+        let question = rest.strip_prefix("ask ").unwrap().trim();
+        handle_ask(ctx, msg, db, question).await;
+    } else if rest == "clusters" {
+        handle_clusters(ctx, msg, db).await;
+    } else if rest == "admin check" {
+        handle_admin_check(ctx, msg, db).await;
     } else if rest.starts_with("rename ") {
         let args = rest.strip_prefix("rename ").unwrap().trim();
         handle_rename(ctx, msg, db, args).await;
+    } else if rest.starts_with("delete-activity ") {
+        let args = rest.strip_prefix("delete-activity ").unwrap().trim();
+        handle_delete_activity(ctx, msg, db, args).await;
     } else if rest.starts_with("chart") {
         let args = rest.strip_prefix("chart").unwrap().trim();
         handle_chart(ctx, msg, db, args).await;
@@ -69,16 +351,6 @@ pub async fn handle_command(ctx: &Context, msg: &Message, db: &Arc<Db>) {
     }
 }
 
-fn format_duration(minutes: i64) -> String {
-    let h = minutes / 60;
-    let m = minutes % 60;
-    if h > 0 {
-        format!("{}h {}m", h, m)
-    } else {
-        format!("{}m", m)
-    }
-}
-
 fn make_bar(minutes: i64, max_minutes: i64) -> String {
     let ratio = if max_minutes > 0 {
         (minutes as f64 / max_minutes as f64).min(1.0)
@@ -90,6 +362,21 @@ fn make_bar(minutes: i64, max_minutes: i64) -> String {
     format!("{}{}", BAR_FULL.repeat(filled), BAR_EMPTY.repeat(empty))
 }
 
+fn make_sparkline(values: &[i64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| {
+            if max <= 0 {
+                SPARK_BLOCKS[0]
+            } else {
+                let idx = (v as f64 / max as f64 * (SPARK_BLOCKS.len() - 1) as f64).round() as usize;
+                SPARK_BLOCKS[idx.min(SPARK_BLOCKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
 fn make_pie_slice(minutes: i64, total: i64) -> String {
     let pct = if total > 0 {
         (minutes as f64 / total as f64 * 100.0).round() as i64
@@ -100,7 +387,151 @@ fn make_pie_slice(minutes: i64, total: i64) -> String {
     format!("{} {}%", "▓".repeat(blocks.max(1)), pct)
 }
 
-fn format_board(entries: &[LeaderboardEntry]) -> String {
+/// `today`, `yesterday`, a weekday name (the most recent one, today included),
+/// `YYYY-MM-DD` or `DD.MM.YYYY`. Empty means today.
+fn parse_timeline_day(arg: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match arg.to_lowercase().as_str() {
+        "" | "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        other => {
+            if let Ok(weekday) = other.parse::<Weekday>() {
+                let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+                return Some(today - chrono::Duration::days(back as i64));
+            }
+            NaiveDate::parse_from_str(other, "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(other, "%d.%m.%Y"))
+                .ok()
+        }
+    }
+}
+
+fn timeline_slot_start(day: NaiveDate, slot: i64) -> NaiveDateTime {
+    day.and_hms_opt(0, 0, 0).unwrap() + chrono::Duration::minutes(slot * TIMELINE_SLOT_MINUTES)
+}
+
+/// The activity filling each 30-minute slot of `day` (the one overlapping it most), `None` when idle.
+fn timeline_slots(sessions: &[TimelineSession], day: NaiveDate) -> Vec<Option<String>> {
+    (0..TIMELINE_SLOTS)
+        .map(|slot| {
+            let slot_start = timeline_slot_start(day, slot);
+            let slot_end = timeline_slot_start(day, slot + 1);
+            let mut overlaps: Vec<(&str, i64)> = Vec::new();
+            for s in sessions {
+                let secs = (s.ended_at.min(slot_end) - s.started_at.max(slot_start)).num_seconds();
+                if secs <= 0 {
+                    continue;
+                }
+                match overlaps.iter_mut().find(|(a, _)| *a == s.activity) {
+                    Some((_, total)) => *total += secs,
+                    None => overlaps.push((&s.activity, secs)),
+                }
+            }
+            // First session wins ties
+            overlaps
+                .iter()
+                .fold(None::<(&str, i64)>, |best, &(a, secs)| match best {
+                    Some((_, b)) if b >= secs => best,
+                    _ => Some((a, secs)),
+                })
+                .map(|(a, _)| a.to_string())
+        })
+        .collect()
+}
+
+/// One line per run of equal slots between the first and last worked slot,
+/// e.g. `` `09:00–10:30` ███ backend ``. `None` if nothing was worked.
+fn format_timeline(slots: &[Option<String>], day: NaiveDate, fmt: &DisplayFormat) -> Option<String> {
+    let first = slots.iter().position(Option::is_some)?;
+    let last = slots.iter().rposition(Option::is_some)?;
+
+    let mut out = String::new();
+    let mut i = first;
+    while i <= last {
+        let run = slots[i..=last].iter().take_while(|s| **s == slots[i]).count();
+        let from = fmt.time(timeline_slot_start(day, i as i64).time());
+        let to = match timeline_slot_start(day, (i + run) as i64) {
+            end if end.date() > day && !fmt.twelve_hour => "24:00".to_string(),
+            end => fmt.time(end.time()),
+        };
+        out += &match &slots[i] {
+            Some(activity) => format!("`{}–{}` {} **{}**\n", from, to, BAR_FULL.repeat(run), activity),
+            None => format!("`{}–{}` {} *break*\n", from, to, TIMELINE_IDLE.repeat(run)),
+        };
+        i += run;
+    }
+    Some(out)
+}
+
+/// Minutes from `4`, `4.5`, `4h` or `4h30m` (hours); between 1 minute and 24 hours.
+pub fn parse_hours(arg: &str) -> Option<i64> {
+    let arg = arg.trim().to_lowercase();
+    let minutes = if let Some((h, m)) = arg.split_once('h') {
+        let h: i64 = h.trim().parse().ok()?;
+        let m = m.trim().trim_end_matches('m').trim();
+        let m: i64 = if m.is_empty() { 0 } else { m.parse().ok()? };
+        if !(0..60).contains(&m) {
+            return None;
+        }
+        h * 60 + m
+    } else {
+        let hours: f64 = arg.parse().ok()?;
+        if !hours.is_finite() {
+            return None;
+        }
+        (hours * 60.0).round() as i64
+    };
+    (1..=24 * 60).contains(&minutes).then_some(minutes)
+}
+
+fn format_board(entries: &[LeaderboardEntry], fmt: &DisplayFormat) -> String {
+    format_ranking(entries, |minutes| fmt.duration(minutes))
+}
+
+/// Score leaderboard: weighted minutes shown as points, one point per weighted hour.
+fn format_score_board(entries: &[LeaderboardEntry]) -> String {
+    format_ranking(entries, |minutes| format!("{:.1} pts", minutes as f64 / 60.0))
+}
+
+/// Share of the weekly goal in percent.
+fn goal_percent(minutes: i64, goal: i64) -> i64 {
+    minutes * 100 / goal.max(1)
+}
+
+/// `(username, minutes, weekly goal)` rows, highest share first. Equal shares keep their
+/// order and share a medal, like the other boards.
+fn format_fair_board(rows: &[(String, i64, i64)], fmt: &DisplayFormat) -> String {
+    if rows.is_empty() {
+        return "*Nobody with a goal has logged time yet*".into();
+    }
+    let mut rows: Vec<&(String, i64, i64)> = rows.iter().collect();
+    rows.sort_by_key(|r| std::cmp::Reverse(goal_percent(r.1, r.2)));
+
+    let medals = ["🥇", "🥈", "🥉"];
+    let max_name_len = rows.iter().map(|r| r.0.len()).max().unwrap_or(8);
+    let mut out = String::new();
+    let mut rank = 0;
+    let mut last_percent = None;
+    for (name, minutes, goal) in rows {
+        let percent = goal_percent(*minutes, *goal);
+        if last_percent != Some(percent) {
+            rank += 1;
+            last_percent = Some(percent);
+        }
+        let medal = medals.get(rank - 1).copied().unwrap_or("▫️");
+        out += &format!(
+            "{} `{:<width$} {}` {}% of {}\n",
+            medal,
+            name,
+            make_bar(*minutes, *goal),
+            percent,
+            fmt.duration(*goal),
+            width = max_name_len
+        );
+    }
+    out
+}
+
+fn format_ranking(entries: &[LeaderboardEntry], show: impl Fn(i64) -> String) -> String {
     if entries.is_empty() {
         return "*No data yet*".into();
     }
@@ -111,16 +542,48 @@ fn format_board(entries: &[LeaderboardEntry]) -> String {
      let max_name_len = entries.iter().map(|e| e.username.len()).max().unwrap_or(8);
 
     let mut out = String::new();
-    for (i, e) in entries.iter().enumerate() {
-        let medal = if i < 3 { medals[i] } else { "▫️" };
+    for e in entries {
+        // Ties share a rank, so two people can both get gold
+        let place = medals.get(e.rank as usize - 1).map(|m| m.to_string()).unwrap_or_else(|| format!("{}.", e.rank));
         let bar = make_bar(e.total_minutes, max_min);
-        let dur = format_duration(e.total_minutes);
-        out += &format!("{} `{:<width$} {}` {}\n", medal, e.username, bar, dur, width = max_name_len);
+        let dur = show(e.total_minutes);
+        let live = if e.live { " 🔴 live" } else { "" };
+        out += &format!("{} `{:<width$} {}` {}{}\n", place, e.username, bar, dur, live, width = max_name_len);
     }
     out
 }
 
-fn format_activity_breakdown(entries: &[ActivityEntry]) -> String {
+/// The `midweek-post` standings: this week's leaderboard so far and the server's pace
+/// against last week.
+pub fn midweek_standings_embed(db: &Db, fmt: &DisplayFormat) -> anyhow::Result<CreateEmbed> {
+    let weekly = crate::anonymous::public_board(db, db.leaderboard_weekly(crate::config::live_weekly(db))?);
+    let so_far: i64 = weekly.iter().map(|e| e.total_minutes).sum();
+    Ok(CreateEmbed::new()
+        .color(COLOR_GOLD)
+        .title(format!("⏳ Mid-week Standings — {}", fmt.week(&db.clock().week().label())))
+        .description(format!(
+            "{}\n\n{}",
+            format_board(&weekly, fmt),
+            crate::pace::server_pace(db, so_far, db.clock().now(), fmt)
+        ))
+        .footer(CreateEmbedFooter::new(format!("{} · final results at the Monday reset", swiss_timestamp(db, fmt)))))
+}
+
+/// `You: #17 this week (1h 20m behind #16) · #3 all time` for the leaderboard footer,
+/// `None` if the user has no time on either board.
+fn format_user_rank(weekly: Option<UserRank>, alltime: Option<UserRank>, fmt: &DisplayFormat) -> Option<String> {
+    let place = |rank: UserRank, board: &str| match rank.behind {
+        Some(behind) => format!("#{} {} ({} behind #{})", rank.rank, board, fmt.duration(behind), rank.rank - 1),
+        None => format!("#{} {}", rank.rank, board),
+    };
+    let parts: Vec<String> = [weekly.map(|r| place(r, "this week")), alltime.map(|r| place(r, "all time"))]
+        .into_iter()
+        .flatten()
+        .collect();
+    (!parts.is_empty()).then(|| format!("You: {}", parts.join(" · ")))
+}
+
+fn format_activity_breakdown(entries: &[ActivityEntry], fmt: &DisplayFormat) -> String {
     if entries.is_empty() {
         return "*No data yet*".into();
     }
@@ -148,179 +611,2467 @@ fn format_activity_breakdown(entries: &[ActivityEntry]) -> String {
             "  `{}` {} — {}\n",
             pie,
             e.activity,
-            format_duration(e.total_minutes)
+            fmt.duration(e.total_minutes)
+        );
+    }
+    out
+}
+
+/// Split `text` into chunks of at most `limit` characters, breaking on line
+/// boundaries where possible and hard-splitting lines that are too long on their own.
+fn split_for_embed(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_len = line.chars().count();
+        if current_len + line_len > limit && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if line_len > limit {
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(limit) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        current.push_str(line);
+        current_len += line_len;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Truncate `text` to `limit` characters, marking the cut with an ellipsis.
+pub fn truncate_for_embed(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(limit - 1).collect();
+    out.push('…');
+    out
+}
+
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// `**Acme** — 12h 0m` per client.
+fn format_clients(clients: &[(String, i64)], fmt: &DisplayFormat) -> String {
+    clients
+        .iter()
+        .map(|(client, minutes)| format!("**{}** — {}\n", client, fmt.duration(*minutes)))
+        .collect()
+}
+
+/// CSV rendering of an activity breakdown, used when it is too large for embeds.
+fn breakdown_csv(entries: &[ActivityEntry]) -> String {
+    let mut out = String::from("user,activity,minutes,sessions\n");
+    for e in entries {
+        out += &format!(
+            "{},{},{},{}\n",
+            csv_field(&e.username),
+            csv_field(&e.activity),
+            e.total_minutes,
+            e.session_count
+        );
+    }
+    out
+}
+
+/// Messages carrying `first` followed by the per-person breakdown of `entries`.
+/// The breakdown is split across as many embeds/messages as Discord's limits require,
+/// or attached as `<file_stem>.csv` when that would take too many messages.
+fn breakdown_messages(
+    first: CreateEmbed,
+    title: &str,
+    entries: &[ActivityEntry],
+    fmt: &DisplayFormat,
+    file_stem: &str,
+) -> Vec<CreateMessage> {
+    paged_messages(Some(first), title, &format_activity_breakdown(entries, fmt), entries, file_stem)
+}
+
+/// `text` about `entries` split like `breakdown_messages`, after `first` if given.
+fn paged_messages(
+    first: Option<CreateEmbed>,
+    title: &str,
+    text: &str,
+    entries: &[ActivityEntry],
+    file_stem: &str,
+) -> Vec<CreateMessage> {
+    let chunks = split_for_embed(text, EMBED_DESCRIPTION_LIMIT);
+
+    if chunks.len() > MAX_CONTINUATION_MESSAGES {
+        let note = CreateEmbed::new()
+            .color(COLOR_PURPLE)
+            .title(title)
+            .description(format!(
+                "{} entries — too many to show inline, full breakdown attached.",
+                entries.len()
+            ));
+        let attachment = CreateAttachment::bytes(
+            breakdown_csv(entries).into_bytes(),
+            format!("{}.csv", file_stem),
         );
+        let msg = match first {
+            Some(first) => CreateMessage::new().embed(first),
+            None => CreateMessage::new(),
+        };
+        return vec![msg.embed(note).add_file(attachment)];
+    }
+
+    let total = chunks.len();
+    let mut messages = Vec::with_capacity(total.max(1));
+    let mut pending_first = first;
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let title = if total > 1 {
+            format!("{} ({}/{})", title, i + 1, total)
+        } else {
+            title.to_string()
+        };
+        let embed = CreateEmbed::new()
+            .color(COLOR_PURPLE)
+            .title(title)
+            .description(chunk);
+        let mut msg = CreateMessage::new();
+        if let Some(first) = pending_first.take() {
+            msg = msg.embed(first);
+        }
+        messages.push(msg.embed(embed));
+    }
+    if let Some(first) = pending_first {
+        messages.push(CreateMessage::new().embed(first));
+    }
+    messages
+}
+
+pub fn swiss_timestamp(db: &Db, fmt: &DisplayFormat) -> String {
+    fmt.timestamp(db.clock().now())
+}
+
+/// Running totals of `daily`.
+fn cumulative(daily: &[i64; 7]) -> [i64; 7] {
+    let mut total = 0;
+    daily.map(|minutes| {
+        total += minutes;
+        total
+    })
+}
+
+/// Day-by-day cumulative hours (Monday to Sunday) of the server and the MVP as
+/// sparklines, one row each.
+fn format_burnup(summary: &WeeklySummary, fmt: &DisplayFormat) -> String {
+    let server = cumulative(&summary.daily_minutes);
+    let mut out = format!("`{}` Everyone — {}\n", make_sparkline(&server), fmt.duration(server[6]));
+    if let Some((ref name, _)) = summary.mvp {
+        let mvp = cumulative(&summary.mvp_daily_minutes);
+        out += &format!("`{}` {} — {}\n", make_sparkline(&mvp), name, fmt.duration(mvp[6]));
     }
     out
 }
 
-fn swiss_timestamp() -> String {
-    db::now_ch().format("%d.%m.%Y %H:%M").to_string()
+/// Share of the week's time for the top activities and how it moved in percentage
+/// points against the previous week, e.g. `backend 45% ▲8pp`. `None` without a previous week.
+fn format_share_shifts(summary: &WeeklySummary) -> Option<String> {
+    let previous_total: i64 = summary.previous_activities.iter().map(|(_, m)| m).sum();
+    if previous_total == 0 || summary.total_minutes == 0 {
+        return None;
+    }
+    let (_, activities) = crate::dashboard::totals(summary);
+    let share = |minutes: i64, total: i64| (minutes as f64 * 100.0 / total as f64).round() as i64;
+    let lines: Vec<String> = activities
+        .iter()
+        .take(SHARE_SHIFT_ROWS)
+        .map(|(activity, minutes)| {
+            let now = share(*minutes, summary.total_minutes);
+            let before = summary
+                .previous_activities
+                .iter()
+                .find(|(a, _)| a == activity)
+                .map_or(0, |(_, m)| share(*m, previous_total));
+            let change = match now - before {
+                0 => "±0pp".to_string(),
+                d if d > 0 => format!("▲{}pp", d),
+                d => format!("▼{}pp", -d),
+            };
+            format!("{} {}% {}", activity, now, change)
+        })
+        .collect();
+    Some(lines.join(" · ") + "\n")
+}
+
+/// A part of the weekly report that admins can leave out or move with `report-sections`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportSection {
+    /// MVP, Hot Topic, Marathon and Most Appreciated.
+    Awards,
+    /// Minutes per client.
+    Clients,
+    /// Cumulative hours through the week.
+    Burnup,
+    /// Activity shares compared with last week.
+    Trends,
+    /// Everyone's time per activity, posted after the report.
+    Breakdown,
+}
+
+/// Every section, in the default order.
+pub const REPORT_SECTIONS: &[ReportSection] = &[
+    ReportSection::Awards,
+    ReportSection::Clients,
+    ReportSection::Burnup,
+    ReportSection::Trends,
+    ReportSection::Breakdown,
+];
+
+impl ReportSection {
+    pub fn key(self) -> &'static str {
+        match self {
+            ReportSection::Awards => "awards",
+            ReportSection::Clients => "clients",
+            ReportSection::Burnup => "burnup",
+            ReportSection::Trends => "trends",
+            ReportSection::Breakdown => "breakdown",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        REPORT_SECTIONS.iter().copied().find(|s| s.key().eq_ignore_ascii_case(key.trim()))
+    }
+
+    /// The section as it appears in the report embed, `None` when it has nothing to show
+    /// (or, for the breakdown, lives in its own messages).
+    fn render(self, summary: &WeeklySummary, fmt: &DisplayFormat) -> Option<String> {
+        match self {
+            ReportSection::Awards => {
+                let mut text = String::new();
+                if let Some((ref name, mins)) = summary.mvp {
+                    text += &format!("🏅 **MVP** — {} with {}\n", name, fmt.duration(mins));
+                }
+                if let Some((ref activity, mins)) = summary.top_activity {
+                    text += &format!(
+                        "🔥 **Hot Topic** — {} ({})\n",
+                        activity,
+                        fmt.duration(mins)
+                    );
+                }
+                if let Some((ref name, ref activity, mins)) = summary.longest_session {
+                    text += &format!(
+                        "🏋️ **Marathon** — {} on {} ({})\n",
+                        name,
+                        activity,
+                        fmt.duration(mins)
+                    );
+                }
+                if let Some((ref name, kudos)) = summary.most_appreciated {
+                    text += &format!("💛 **Most Appreciated** — {} ({} kudos)\n", name, kudos);
+                }
+                Some(format!("**━━━ Awards ━━━**\n\n{}", text))
+            }
+            ReportSection::Clients => (!summary.clients.is_empty())
+                .then(|| format!("**━━━ Clients ━━━**\n{}", format_clients(&summary.clients, fmt))),
+            ReportSection::Burnup => (summary.total_minutes > 0)
+                .then(|| format!("**━━━ Burnup ━━━**\n{}", format_burnup(summary, fmt))),
+            ReportSection::Trends => {
+                format_share_shifts(summary).map(|shifts| format!("**━━━ Shifts vs last week ━━━**\n{}", shifts))
+            }
+            ReportSection::Breakdown => None,
+        }
+    }
 }
 
-/// Build weekly summary embeds for auto-posting to a channel.
-pub fn build_weekly_summary_embeds(summary: &WeeklySummary, week_label: &str) -> Vec<CreateEmbed> {
-    let mut embeds = Vec::new();
+/// Report sections in the order set by `report-sections`, all of them by default.
+pub fn report_sections(db: &Db) -> Vec<ReportSection> {
+    match crate::config::get(db, crate::config::REPORT_SECTIONS) {
+        Some(value) => value.split(',').filter_map(ReportSection::parse).collect(),
+        None => REPORT_SECTIONS.to_vec(),
+    }
+}
 
+fn weekly_report_embed(
+    db: &Db,
+    summary: &WeeklySummary,
+    week_label: &str,
+    fmt: &DisplayFormat,
+    sections: &[ReportSection],
+) -> CreateEmbed {
     let mut desc = format!(
         "```\n  {} total  ·  {} sessions  ·  {} people\n```\n",
-        format_duration(summary.total_minutes),
+        fmt.duration(summary.total_minutes),
         summary.total_sessions,
         summary.unique_workers,
     );
 
-    desc += "**━━━ Awards ━━━**\n\n";
-
-    if let Some((ref name, mins)) = summary.mvp {
-        desc += &format!("🏅 **MVP** — {} with {}\n", name, format_duration(mins));
-    }
-    if let Some((ref activity, mins)) = summary.top_activity {
-        desc += &format!(
-            "🔥 **Hot Topic** — {} ({})\n",
-            activity,
-            format_duration(mins)
-        );
-    }
-    if let Some((ref name, ref activity, mins)) = summary.longest_session {
+    if !summary.holidays.is_empty() {
+        let names: Vec<&str> = summary.holidays.iter().map(|(_, name)| name.as_str()).collect();
         desc += &format!(
-            "🏋️ **Marathon** — {} on {} ({})\n",
-            name,
-            activity,
-            format_duration(mins)
+            "📅 **Short week** — {} working days ({})\n\n",
+            5 - summary.holidays.len(),
+            names.join(", ")
         );
     }
 
-    embeds.push(
-        CreateEmbed::new()
-            .color(COLOR_ORANGE)
-            .title(format!("📊 Weekly Report — {}", week_label))
-            .description(desc)
-            .footer(CreateEmbedFooter::new(swiss_timestamp())),
-    );
+    let parts: Vec<String> = sections.iter().filter_map(|s| s.render(summary, fmt)).collect();
+    desc += &parts.join("\n");
 
-    if !summary.breakdown.is_empty() {
-        embeds.push(
-            CreateEmbed::new()
-                .color(COLOR_PURPLE)
-                .title("🔍 Who worked on what")
-                .description(format_activity_breakdown(&summary.breakdown)),
-        );
+    CreateEmbed::new()
+        .color(COLOR_ORANGE)
+        .title(format!("📊 Weekly Report — {}", fmt.week(week_label)))
+        .description(truncate_for_embed(&desc, EMBED_DESCRIPTION_LIMIT))
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, fmt)))
+}
+
+/// The weekly report with `sections`, followed by the breakdown if it's one of them.
+pub fn build_weekly_summary_messages(
+    db: &Db,
+    summary: &WeeklySummary,
+    week_label: &str,
+    fmt: &DisplayFormat,
+    sections: &[ReportSection],
+) -> Vec<CreateMessage> {
+    let report = weekly_report_embed(db, summary, week_label, fmt, sections);
+    if summary.breakdown.is_empty() || !sections.contains(&ReportSection::Breakdown) {
+        return vec![CreateMessage::new().embed(report)];
     }
 
-    embeds
+    breakdown_messages(
+        report,
+        "🔍 Who worked on what",
+        &summary.breakdown,
+        fmt,
+        &format!("breakdown-{}", week_label.replace('/', "-")),
+    )
 }
 
-// ── Command handlers ──────────────────────────────────────
+/// The weekly report for `summary-thread`: the report alone for the channel, and the
+/// appendix to post in a thread under it, with every activity and session count per person.
+/// Leaving out the breakdown section leaves out the appendix.
+pub fn build_weekly_summary_thread(
+    db: &Db,
+    summary: &WeeklySummary,
+    week_label: &str,
+    fmt: &DisplayFormat,
+    sections: &[ReportSection],
+) -> (CreateMessage, Vec<CreateMessage>) {
+    let report = CreateMessage::new().embed(weekly_report_embed(db, summary, week_label, fmt, sections));
+    if summary.breakdown.is_empty() || !sections.contains(&ReportSection::Breakdown) {
+        return (report, Vec::new());
+    }
+    let appendix = paged_messages(
+        None,
+        "🔍 Who worked on what",
+        &format_appendix(&summary.breakdown, fmt),
+        &summary.breakdown,
+        &format!("breakdown-{}", week_label.replace('/', "-")),
+    );
+    (report, appendix)
+}
 
-async fn handle_clock_in(ctx: &Context, msg: &Message, db: &Arc<Db>, activity: &str) {
-    let user_id = msg.author.id.to_string();
-    let username = msg.author.display_name().to_string();
+/// Name of the thread holding a week's appendix.
+pub fn summary_thread_name(week_label: &str, fmt: &DisplayFormat) -> String {
+    format!("📊 {} — details", fmt.week(week_label))
+}
 
-    match db.clock_in(&user_id, &username, activity) {
-        Ok(()) => {
-            let embed = CreateEmbed::new()
-                .color(COLOR_GREEN)
-                .title("🟢 Clocked In")
-                .description(format!(
-                    "**{}** started working on **{}**",
-                    username, activity
-                ))
-                .footer(CreateEmbedFooter::new(format!(
-                    "{} · /clock out when done",
-                    swiss_timestamp()
-                )));
-            let _ = msg
-                .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await;
+/// Everyone's week for the summary thread: a line per person with their total, then
+/// each activity with its time and number of sessions.
+fn format_appendix(entries: &[ActivityEntry], fmt: &DisplayFormat) -> String {
+    let sessions = |n: i64| if n == 1 { "1 session".to_string() } else { format!("{} sessions", n) };
+    let mut out = String::new();
+    let mut users: Vec<&str> = Vec::new();
+    for e in entries {
+        if !users.contains(&e.username.as_str()) {
+            users.push(&e.username);
         }
-        Err(_) => {
-            let session = db.active_session(&user_id).ok().flatten();
-            let desc = match session {
-                Some(s) => format!("Already on **{}**\nUse `/clock out` first", s.activity),
-                None => "Already clocked in. `/clock out` first.".into(),
-            };
-            let embed = CreateEmbed::new()
-                .color(COLOR_RED)
-                .title("⚠️ Already Clocked In")
-                .description(desc);
-            let _ = msg
-                .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await;
+    }
+    for user in users {
+        let own: Vec<&ActivityEntry> = entries.iter().filter(|e| e.username == user).collect();
+        let total: i64 = own.iter().map(|e| e.total_minutes).sum();
+        let count: i64 = own.iter().map(|e| e.session_count).sum();
+        if !out.is_empty() {
+            out += "\n";
+        }
+        out += &format!("👤 **{}** — {} · {}\n", user, fmt.duration(total), sessions(count));
+        for e in own {
+            out += &format!(
+                "  `{}` {} — {} · {}\n",
+                make_pie_slice(e.total_minutes, total),
+                e.activity,
+                fmt.duration(e.total_minutes),
+                sessions(e.session_count)
+            );
         }
     }
+    out
 }
 
-async fn handle_clock_out(ctx: &Context, msg: &Message, db: &Arc<Db>) {
-    let user_id = msg.author.id.to_string();
-    let username = msg.author.display_name().to_string();
+// ── Command handlers ──────────────────────────────────────
 
-    match db.clock_out(&user_id) {
-        Ok((minutes, activity)) => {
-            let embed = CreateEmbed::new()
-                .color(COLOR_RED)
-                .title("🔴 Clocked Out")
-                .description(format!("**{}** finished working on **{}**", username, activity))
-                .field("Duration", format_duration(minutes), true)
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
-            let _ = msg
-                .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await;
-        }
-        Err(_) => {
-            let embed = CreateEmbed::new()
-                .color(COLOR_GRAY)
-                .title("🤷 Not Clocked In")
-                .description("Use `/clock in <activity>` first.");
-            let _ = msg
-                .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await;
-        }
+fn clocked_in_embed(db: &Db, username: &str, activity: &str, greeting: Option<&str>, fmt: &DisplayFormat) -> CreateEmbed {
+    let greeting = greeting.map(|g| format!("{}! ", g)).unwrap_or_default();
+    CreateEmbed::new()
+        .color(COLOR_GREEN)
+        .title("🟢 Clocked In")
+        .description(format!(
+            "{}**{}** started working on **{}**",
+            greeting, username, activity
+        ))
+        .footer(CreateEmbedFooter::new(format!(
+            "{} · /clock out when done",
+            swiss_timestamp(db, fmt)
+        )))
+}
+
+fn already_clocked_in_embed(db: &Db, user_id: &str) -> CreateEmbed {
+    let session = db.active_session(user_id).ok().flatten();
+    let desc = match session {
+        Some(s) => format!("Already on **{}**\nUse `/clock out` first", s.activity),
+        None => "Already clocked in. `/clock out` first.".into(),
+    };
+    CreateEmbed::new()
+        .color(COLOR_RED)
+        .title("⚠️ Already Clocked In")
+        .description(desc)
+}
+
+/// The id `user`'s time is kept under, which differs for accounts linked with `/clock link`.
+pub fn account(db: &Db, user: UserId) -> String {
+    db.primary_account(&user.to_string())
+}
+
+/// Run the guardrail checks for a clock-in, DMing any violations.
+/// Returns `false` if the clock-in must be refused.
+async fn guardrails_allow_clock_in(http: &Http, db: &Db, user_id: UserId) -> bool {
+    if !matches!(db.active_session(&account(db, user_id)), Ok(None)) {
+        return true;
+    }
+    let violations = crate::guardrails::check_clock_in(db, &account(db, user_id));
+    if violations.is_empty() {
+        return true;
     }
+    let refuse = crate::guardrails::mode(db) == crate::guardrails::Mode::Refuse;
+    crate::guardrails::notify(http, user_id, &violations, refuse, &DisplayFormat::load(db)).await;
+    !refuse
 }
 
-async fn handle_status(ctx: &Context, msg: &Message, db: &Arc<Db>) {
-    let user_id = msg.author.id.to_string();
+/// `auto_out` schedules a clock-out that many minutes after clocking in; `last_out`
+/// shows the break since the previous session.
+async fn handle_clock_in(
+    ctx: &Context,
+    msg: &Message,
+    db: &Arc<Db>,
+    activity: &str,
+    auto_out: Option<i64>,
+    last_out: Option<NaiveDateTime>,
+) {
+    let user_id = account(db, msg.author.id);
     let username = msg.author.display_name().to_string();
 
-    match db.active_session(&user_id) {
-        Ok(Some(session)) => {
-            let now = db::now_ch();
-            let elapsed = (now - session.started_at).num_minutes();
-            let started = session.started_at.format("%H:%M").to_string();
+    if let Ok(Some(session)) = db.active_session(&user_id)
+        && session.started_at.date() < db.clock().now().date()
+    {
+        offer_stale_fix(ctx, msg, db, &session, activity).await;
+        return;
+    }
 
-            let embed = CreateEmbed::new()
-                .color(COLOR_GREEN)
-                .title(format!("🟢 {} is working", username))
-                .field("Activity", &session.activity, true)
-                .field("Elapsed", format_duration(elapsed), true)
-                .field("Since", &started, true)
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
-            let _ = msg
-                .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await;
+    if !guardrails_allow_clock_in(&ctx.http, db, msg.author.id).await {
+        // Details went out by DM; keep the channel free of them
+        let _ = msg.react(&ctx.http, '⛔').await;
+        return;
+    }
+
+    let Some(merge_gap) = crate::errors::or_report(&ctx.http, msg, merge_gap(db, &user_id, activity)).await else {
+        return;
+    };
+    match db.clock_in(&user_id, &username, activity) {
+        Ok(()) => {
+            let fmt = DisplayFormat::load(db);
+            let greeting = crate::tips::greeting_for(db, &user_id);
+            let mut embed = clocked_in_embed(db, &username, activity, greeting, &fmt);
+            if let Some(last_out) = last_out {
+                let gap = (db.clock().now() - last_out).num_minutes().max(0);
+                embed = embed.field("Break", format!("{} since {}", fmt.duration(gap), fmt.time(last_out.time())), true);
+            }
+            if let Some(minutes) = auto_out {
+                let at = db.clock().now() + chrono::Duration::minutes(minutes);
+                match db.schedule_clock_out(&user_id, Some(at)) {
+                    Ok(true) => {
+                        embed = embed.field("Auto clock-out", format!("{} · `/clock out cancel`", fmt.time(at.time())), false);
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                    }
+                }
+            }
+            if let Some(tip) = crate::tips::next_tip(db, &user_id, activity) {
+                embed = embed.field("💡 Tip", tip.text(), false);
+            }
+            let mut reply = CreateMessage::new().embed(embed);
+            if let Some(gap) = merge_gap {
+                reply = reply.content(format!(
+                    "Only {} since your last **{}** session. Merge them into one block?",
+                    fmt.duration(gap),
+                    activity
+                ));
+                reply = reply.components(vec![merge_buttons(&user_id)]);
+            }
+            let _ = msg.channel_id.send_message(&ctx.http, reply).await;
+            crate::focus::grant(&ctx.http, db, msg.guild_id, msg.author.id).await;
+            crate::mirror::spawn(crate::mirror::clock_in_text(&username, activity));
         }
-        _ => {
-            let embed = CreateEmbed::new()
-                .color(COLOR_GRAY)
-                .title(format!("😴 {} is offline", username))
-                .description("`/clock in <activity>`");
+        Err(_) if matches!(db.active_session(&user_id), Ok(Some(_))) => {
             let _ = msg
                 .channel_id
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .send_message(&ctx.http, CreateMessage::new().embed(already_clocked_in_embed(db, &user_id)))
                 .await;
         }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+        }
     }
 }
 
-async fn handle_who(ctx: &Context, msg: &Message, db: &Arc<Db>) {
-    match db.who_is_working() {
+/// A plausible end for a session left running since `started`: the user's last message
+/// that day, else `day_end` that day, else midnight.
+fn stale_end(started: NaiveDateTime, last_seen: Option<NaiveDateTime>, day_end: NaiveTime) -> NaiveDateTime {
+    let midnight = (started.date() + chrono::Duration::days(1)).and_hms_opt(0, 0, 0).unwrap();
+    last_seen
+        .filter(|&at| at > started)
+        .or(Some(started.date().and_time(day_end)).filter(|&at| at > started))
+        .unwrap_or(midnight)
+        .min(midnight)
+}
+
+fn pending_clock_in_key(user_id: &str) -> String {
+    format!("stale_fix.{}", user_id)
+}
+
+/// `/clock in` while yesterday's (or older) session is still running: suggest closing it
+/// at a plausible time and starting `activity`, which waits in metadata for the answer.
+async fn offer_stale_fix(ctx: &Context, msg: &Message, db: &Db, session: &db::ActiveSession, activity: &str) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+    let last_seen = db.last_seen_on(&user_id, session.started_at.date());
+    let Some(last_seen) = crate::errors::or_report(&ctx.http, msg, last_seen).await else {
+        return;
+    };
+    let day_end = crate::config::get(db, crate::config::DAY_END)
+        .and_then(|t| NaiveTime::parse_from_str(&t, "%H:%M").ok())
+        .unwrap_or_else(|| NaiveTime::from_hms_opt(DEFAULT_DAY_END.0, DEFAULT_DAY_END.1, 0).unwrap());
+    let end = stale_end(session.started_at, last_seen, day_end);
+    let reason = if last_seen.is_some_and(|at| at == end) {
+        "your last message that day"
+    } else {
+        "end of the working day"
+    };
+
+    if let Err(e) = db.set_metadata(&pending_clock_in_key(&user_id), activity) {
+        crate::errors::report(&ctx.http, msg, &e).await;
+        return;
+    }
+    let embed = CreateEmbed::new()
+        .color(COLOR_ORANGE)
+        .title("🕰️ Forgot to clock out?")
+        .description(format!(
+            "**{}** since {} is still running.
+Close it at **{}** ({}, {}) and start **{}**?",
+            session.activity,
+            fmt.timestamp(session.started_at),
+            fmt.timestamp(end),
+            reason,
+            fmt.duration((end - session.started_at).num_minutes()),
+            activity
+        ));
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:{}:{}", STALE_CUSTOM_ID, user_id, end.format("%Y%m%d%H%M")))
+            .label(format!("Close at {} and start", fmt.time(end.time())))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(format!("{}:{}:keep", STALE_CUSTOM_ID, user_id))
+            .label("Keep it running")
+            .style(ButtonStyle::Secondary),
+    ]);
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed).components(vec![buttons]))
+        .await;
+}
+
+/// Answer the forgotten clock-out buttons; only the session's owner may press them.
+pub async fn handle_stale_button(ctx: &Context, interaction: &ComponentInteraction, db: &Arc<Db>) {
+    let mut parts = interaction.data.custom_id.splitn(3, ':').skip(1);
+    let (Some(owner), Some(choice)) = (parts.next(), parts.next()) else {
+        return;
+    };
+    let user = &interaction.user;
+    let user_id = account(db, user.id);
+    if owner != user_id {
+        let response = CreateInteractionResponseMessage::new()
+            .content("Only the person who clocked in can decide this.")
+            .ephemeral(true);
+        let _ = interaction.create_response(&ctx.http, CreateInteractionResponse::Message(response)).await;
+        return;
+    }
+
+    let key = pending_clock_in_key(&user_id);
+    let pending = db.metadata(&key).ok().flatten();
+    let _ = db.delete_metadata(&key);
+    let end = NaiveDateTime::parse_from_str(choice, "%Y%m%d%H%M").ok();
+    let username = interaction
+        .member
+        .as_ref()
+        .map(|m| m.display_name().to_string())
+        .unwrap_or_else(|| user.display_name().to_string());
+    let fmt = DisplayFormat::load(db);
+
+    let (content, embed) = match (end, pending) {
+        (Some(end), Some(activity)) => match db.clock_out_at(&user_id, end) {
+            Ok((elapsed, old)) => {
+                crate::mirror::spawn(crate::mirror::clock_out_text(&username, &old, elapsed, &fmt));
+                let closed = format!("Closed **{}** at {} ({}).", old, fmt.timestamp(end), fmt.elapsed(elapsed));
+                if !guardrails_allow_clock_in(&ctx.http, db, user.id).await {
+                    crate::focus::revoke(&ctx.http, db, user.id).await;
+                    (format!("{}\n⛔ Clock-in blocked — details in your DMs.", closed), None)
+                } else {
+                    match db.clock_in(&user_id, &username, &activity) {
+                        Ok(()) => {
+                            crate::focus::grant(&ctx.http, db, interaction.guild_id, user.id).await;
+                            crate::mirror::spawn(crate::mirror::clock_in_text(&username, &activity));
+                            (closed, Some(clocked_in_embed(db, &username, &activity, None, &fmt)))
+                        }
+                        Err(_) => (closed, None),
+                    }
+                }
+            }
+            Err(_) => ("That session is no longer running.".to_string(), None),
+        },
+        (Some(_), None) => ("This suggestion has expired.".to_string(), None),
+        (None, _) => ("Left it running. `/clock out` when you're done.".to_string(), None),
+    };
+
+    let response = CreateInteractionResponseMessage::new()
+        .content(content)
+        .embeds(embed.into_iter().collect())
+        .components(vec![]);
+    if let Err(e) = interaction
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(response))
+        .await
+    {
+        eprintln!("[clock] Failed to answer forgotten clock-out: {e}");
+    }
+}
+
+/// Minutes since the user's last session if it was on `activity` and ended within the
+/// `merge-gap` setting, i.e. when clocking in should offer to merge the two.
+fn merge_gap(db: &Db, user_id: &str, activity: &str) -> anyhow::Result<Option<i64>> {
+    let Some(limit) = crate::config::get(db, crate::config::MERGE_GAP).and_then(|v| v.parse::<i64>().ok()) else {
+        return Ok(None);
+    };
+    let Some((last, ended_at)) = db.recent_activities(user_id, 1)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let gap = (db.clock().now() - ended_at).num_minutes();
+    Ok((last == activity && gap <= limit).then_some(gap.max(0)))
+}
+
+fn merge_buttons(user_id: &str) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:{}", MERGE_CUSTOM_ID, user_id))
+            .label("Merge")
+            .style(ButtonStyle::Primary),
+        CreateButton::new(format!("{}:{}", KEEP_CUSTOM_ID, user_id))
+            .label("Keep separate")
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// Answer the merge / keep-separate buttons; only the user who clocked in may press them.
+pub async fn handle_merge_button(ctx: &Context, interaction: &ComponentInteraction, db: &Arc<Db>) {
+    let Some((action, owner)) = interaction.data.custom_id.split_once(':') else {
+        return;
+    };
+    let user_id = account(db, interaction.user.id);
+    let response = if owner != user_id {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content("Only the person who clocked in can decide this.")
+                .ephemeral(true),
+        )
+    } else {
+        let content = if action == MERGE_CUSTOM_ID {
+            match db.merge_with_previous(&user_id) {
+                Ok(true) => "🔗 Merged with your previous session; the gap counts as work.".to_string(),
+                Ok(false) => "Nothing to merge anymore.".to_string(),
+                Err(e) => {
+                    let reference = crate::errors::report_interaction(&ctx.http, interaction, &e).await;
+                    crate::errors::user_text(&reference)
+                }
+            }
+        } else {
+            "Kept as a separate session.".to_string()
+        };
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(vec![]),
+        )
+    };
+
+    if let Err(e) = interaction.create_response(&ctx.http, response).await {
+        eprintln!("[clock] Failed to answer merge button: {e}");
+    }
+}
+
+async fn handle_again(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let user_id = account(db, msg.author.id);
+    let Some(recent) = crate::errors::or_report(&ctx.http, msg, db.recent_activities(&user_id, 1)).await else {
+        return;
+    };
+    match recent.into_iter().next() {
+        Some((activity, ended_at)) => handle_clock_in(ctx, msg, db, &activity, None, Some(ended_at)).await,
+        None => {
+            let _ = msg
+                .reply(&ctx.http, "No finished session this week to pick up. `/clock in <activity>`")
+                .await;
+        }
+    }
+}
+
+/// Activities offered by `/clock menu`: the curated templates, or the most used ones.
+fn menu_activities(db: &Db) -> anyhow::Result<Vec<String>> {
+    let curated = db.activity_templates()?;
+    let activities = if curated.is_empty() {
+        db.top_activities(MENU_MAX_OPTIONS)?
+    } else {
+        curated
+    };
+    Ok(activities
+        .into_iter()
+        .filter(|a| a.chars().count() <= MENU_MAX_LABEL)
+        .take(MENU_MAX_OPTIONS)
+        .collect())
+}
+
+async fn handle_menu(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    if !args.is_empty() {
+        handle_menu_edit(ctx, msg, db, args).await;
+        return;
+    }
+
+    let Some(activities) = crate::errors::or_report(&ctx.http, msg, menu_activities(db)).await else {
+        return;
+    };
+    if activities.is_empty() {
+        let _ = msg
+            .reply(&ctx.http, "No activities yet. `/clock in <activity>` to create the first one.")
+            .await;
+        return;
+    }
+
+    let options = activities
+        .iter()
+        .map(|a| CreateSelectMenuOption::new(a, a))
+        .collect();
+    let menu = CreateSelectMenu::new(MENU_CUSTOM_ID, CreateSelectMenuKind::String { options })
+        .placeholder("Pick an activity to clock in");
+    let embed = CreateEmbed::new()
+        .color(COLOR_GREEN)
+        .title("⚡ Quick Start")
+        .description("Pick what you're working on to clock in right away.")
+        .footer(CreateEmbedFooter::new("/clock out when done"));
+    let _ = msg
+        .channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .embed(embed)
+                .components(vec![CreateActionRow::SelectMenu(menu)]),
+        )
+        .await;
+}
+
+async fn handle_menu_edit(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock menu add <activity>` or `/clock menu remove <activity>`";
+
+    let (action, name) = args.split_once(' ').unwrap_or((args, ""));
+    let activity = crate::normalize::normalize_activity(name.trim());
+    if activity.is_empty() {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let Some(templates) = crate::errors::or_report(&ctx.http, msg, db.activity_templates()).await else {
+        return;
+    };
+    let reply = match action {
+        "add" if activity.chars().count() > MENU_MAX_LABEL => {
+            format!("Menu entries are limited to {} characters.", MENU_MAX_LABEL)
+        }
+        "add" if templates.len() >= MENU_MAX_OPTIONS => {
+            format!("The menu is full ({} entries). Remove one first.", MENU_MAX_OPTIONS)
+        }
+        "add" => match db.add_activity_template(&activity) {
+            Ok(true) => format!("Added **{}** to the menu.", activity),
+            Ok(false) => format!("**{}** is already on the menu.", activity),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "remove" => match db.remove_activity_template(&activity) {
+            Ok(true) => format!("Removed **{}** from the menu.", activity),
+            Ok(false) => format!("**{}** isn't on the menu.", activity),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// ` · Not ranked: meetings, break` when activities are left out of rankings.
+fn non_competitive_note(db: &Db) -> anyhow::Result<String> {
+    let excluded = db.non_competitive_activities()?;
+    Ok(if excluded.is_empty() {
+        String::new()
+    } else {
+        format!(" · Not ranked: {}", excluded.join(", "))
+    })
+}
+
+async fn handle_non_competitive(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock non-competitive add <activity>` or `/clock non-competitive remove <activity>`";
+
+    if args.is_empty() {
+        let Some(excluded) = crate::errors::or_report(&ctx.http, msg, db.non_competitive_activities()).await else {
+            return;
+        };
+        let reply = if excluded.is_empty() {
+            "Every activity counts towards the leaderboards and awards.".to_string()
+        } else {
+            format!(
+                "Left out of leaderboards and awards (still in personal stats): {}",
+                excluded.iter().map(|a| format!("**{}**", a)).collect::<Vec<_>>().join(", ")
+            )
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let (action, name) = args.split_once(' ').unwrap_or((args, ""));
+    let activity = crate::normalize::normalize_activity(name.trim());
+    if activity.is_empty() {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let reply = match action {
+        "add" => match db.add_non_competitive(&activity) {
+            Ok(true) => format!("**{}** no longer counts towards leaderboards and awards.", activity),
+            Ok(false) => format!("**{}** is already non-competitive.", activity),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "remove" => match db.remove_non_competitive(&activity) {
+            Ok(true) => format!("**{}** counts towards leaderboards and awards again.", activity),
+            Ok(false) => format!("**{}** isn't on the list.", activity),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// `0.5`, `x0.5` or `×0.5`; between 0 and 5.
+fn parse_weight(arg: &str) -> Option<f64> {
+    let weight: f64 = arg.trim().trim_start_matches(['x', 'X', '×']).parse().ok()?;
+    (weight.is_finite() && (0.0..=5.0).contains(&weight)).then_some(weight)
+}
+
+async fn handle_weight(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock weight <activity> <×0.5|off>`";
+
+    if args.is_empty() {
+        let Some(weights) = crate::errors::or_report(&ctx.http, msg, db.activity_weights()).await else {
+            return;
+        };
+        let reply = if weights.is_empty() {
+            "No score weights set; every activity counts ×1.0.".to_string()
+        } else {
+            let lines: Vec<String> = weights.iter().map(|(a, w)| format!("**{}** ×{}", a, w)).collect();
+            format!("Score weights (others ×1.0):\n{}", lines.join("\n"))
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let Some((name, value)) = args.rsplit_once(' ') else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    let activity = crate::normalize::normalize_activity(name.trim());
+    let weight = if value.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match parse_weight(value) {
+            Some(w) => Some(w),
+            None => {
+                let _ = msg.reply(&ctx.http, USAGE).await;
+                return;
+            }
+        }
+    };
+    if activity.is_empty() {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let reply = match (db.set_activity_weight(&activity, weight), weight) {
+        (Ok(()), Some(w)) => format!("**{}** now scores ×{}.", activity, w),
+        (Ok(()), None) => format!("**{}** scores ×1.0 again.", activity),
+        (Err(e), _) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// Minutes per client from `activities`, most first, leaving out clients without time.
+fn client_totals(activities: &[db::ClientActivity], minutes: impl Fn(&db::ClientActivity) -> i64) -> Vec<(String, i64)> {
+    let mut totals: Vec<(String, i64)> = Vec::new();
+    for a in activities {
+        match totals.iter_mut().find(|(client, _)| client == &a.client) {
+            Some((_, total)) => *total += minutes(a),
+            None => totals.push((a.client.clone(), minutes(a))),
+        }
+    }
+    totals.retain(|(_, total)| *total > 0);
+    totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+    totals
+}
+
+async fn handle_client(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock client [<client>]`, `/clock client assign <activity> to <client>` or `/clock client unassign <activity>`";
+    let fmt = DisplayFormat::load(db);
+
+    if let Some(rate_args) = args.strip_prefix("rate ") {
+        let rate = match rate_args.trim().rsplit_once(' ') {
+            Some((client, value)) if value.eq_ignore_ascii_case("off") => Some((client.trim(), None)),
+            Some((client, value)) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|r| r.is_finite() && *r > 0.0)
+                .map(|r| (client.trim(), Some(r))),
+            None => None,
+        };
+        let Some((client, rate)) = rate.filter(|(client, _)| !client.is_empty()) else {
+            let _ = msg.reply(&ctx.http, "Usage: `/clock client rate <client> <150|off>`").await;
+            return;
+        };
+        let currency = crate::config::get(db, crate::config::CURRENCY).unwrap_or_else(|| "CHF".to_string());
+        let reply = match (db.set_client_rate(client, rate), rate) {
+            (Ok(()), Some(rate)) => format!("**{}** is billed at {} {:.2} per hour.", client, currency, rate),
+            (Ok(()), None) => format!("**{}** no longer has a rate.", client),
+            (Err(e), _) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    if let Some(assignment) = args.strip_prefix("assign ").or(args.strip_prefix("unassign ")) {
+        let (activity, client) = if args.starts_with("assign ") {
+            match assignment.rsplit_once(" to ") {
+                Some((activity, client)) if !client.trim().is_empty() => (activity, Some(client.trim())),
+                _ => {
+                    let _ = msg.reply(&ctx.http, USAGE).await;
+                    return;
+                }
+            }
+        } else {
+            (assignment, None)
+        };
+        let activity = crate::normalize::normalize_activity(activity.trim());
+        if activity.is_empty() {
+            let _ = msg.reply(&ctx.http, USAGE).await;
+            return;
+        }
+        let reply = match (db.set_activity_client(&activity, client), client) {
+            (Ok(_), Some(client)) => format!("**{}** now counts for **{}**.", activity, client),
+            (Ok(true), None) => format!("**{}** no longer belongs to a client.", activity),
+            (Ok(false), None) => format!("**{}** isn't filed under a client.", activity),
+            (Err(e), _) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let Some(activities) = crate::errors::or_report(&ctx.http, msg, db.client_activities()).await else {
+        return;
+    };
+    let embed = if args.is_empty() {
+        if activities.is_empty() {
+            let _ = msg
+                .reply(&ctx.http, "No clients yet. Admins add one with `/clock client assign <activity> to <client>`.")
+                .await;
+            return;
+        }
+        let week = client_totals(&activities, |a| a.week_minutes);
+        let mut lines = String::new();
+        for (client, total) in client_totals(&activities, |a| a.total_minutes) {
+            let this_week = week.iter().find(|(c, _)| *c == client).map(|(_, m)| *m).unwrap_or(0);
+            let projects: Vec<&str> = activities
+                .iter()
+                .filter(|a| a.client == client)
+                .map(|a| a.activity.as_str())
+                .collect();
+            lines += &format!(
+                "**{}** — {} this week · {} all time\n{}\n",
+                client,
+                fmt.duration(this_week),
+                fmt.duration(total),
+                projects.join(", ")
+            );
+        }
+        CreateEmbed::new()
+            .color(COLOR_PURPLE)
+            .title("🏢 Clients")
+            .description(truncate_for_embed(&lines, EMBED_DESCRIPTION_LIMIT))
+    } else {
+        let mine: Vec<&db::ClientActivity> = activities.iter().filter(|a| a.client.eq_ignore_ascii_case(args)).collect();
+        let Some(first) = mine.first() else {
+            let _ = msg.reply(&ctx.http, format!("No activities are filed under **{}**.", args)).await;
+            return;
+        };
+        let mut lines = String::new();
+        for a in &mine {
+            lines += &format!(
+                "**{}** — {} this week · {} all time\n",
+                a.activity,
+                fmt.duration(a.week_minutes),
+                fmt.duration(a.total_minutes)
+            );
+        }
+        CreateEmbed::new()
+            .color(COLOR_PURPLE)
+            .title(format!("🏢 {}", first.client))
+            .description(truncate_for_embed(&lines, EMBED_DESCRIPTION_LIMIT))
+            .field("This Week", fmt.duration(mine.iter().map(|a| a.week_minutes).sum()), true)
+            .field("All Time", fmt.duration(mine.iter().map(|a| a.total_minutes).sum()), true)
+    };
+    let embed = embed.footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_invoice(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock invoice <client> <YYYY-MM>`";
+    let fmt = DisplayFormat::load(db);
+
+    let Some((client, (year, month))) = args
+        .rsplit_once(' ')
+        .and_then(|(client, month)| Some((client.trim(), crate::invoice::parse_month(month)?)))
+    else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+
+    let Some(filed) = crate::errors::or_report(&ctx.http, msg, db.client_activities()).await else {
+        return;
+    };
+    let filed: Vec<db::ClientActivity> = filed
+        .into_iter()
+        .filter(|a| a.client.eq_ignore_ascii_case(client))
+        .collect();
+    let Some(client) = filed.first().map(|a| a.client.clone()) else {
+        let _ = msg.reply(&ctx.http, format!("No activities are filed under **{}**.", client)).await;
+        return;
+    };
+    let activities: Vec<String> = filed.into_iter().map(|a| a.activity).collect();
+    let Some(week_activities) = crate::errors::or_report(&ctx.http, msg, db.week_activities()).await else {
+        return;
+    };
+    let lines = crate::invoice::lines(
+        &week_activities,
+        &activities,
+        year,
+        month,
+        db.clock().now().date(),
+    );
+    let period = format!("{}-{:02}", year, month);
+    if lines.is_empty() {
+        let _ = msg.reply(&ctx.http, format!("No time logged for **{}** in {}.", client, period)).await;
+        return;
+    }
+
+    let Some(rate) = crate::errors::or_report(&ctx.http, msg, db.client_rate(&client)).await else {
+        return;
+    };
+    let currency = crate::config::get(db, crate::config::CURRENCY).unwrap_or_else(|| "CHF".to_string());
+    let minutes: i64 = lines.iter().map(|l| l.minutes).sum();
+    let total = match rate {
+        Some(rate) => format!("{} · {} {:.2}", fmt.duration(minutes), currency, crate::invoice::amount(minutes, rate)),
+        None => format!("{} · no rate set (`/clock client rate`)", fmt.duration(minutes)),
+    };
+    let embed = CreateEmbed::new()
+        .color(COLOR_BLUE)
+        .title(format!("🧾 {} — {}", client, period))
+        .description(truncate_for_embed(
+            &crate::invoice::format_totals(&lines, rate, &currency, &fmt),
+            EMBED_DESCRIPTION_LIMIT,
+        ))
+        .field("Total", total, false)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} · weeks count toward the month of their Thursday",
+            swiss_timestamp(db, &fmt)
+        )));
+    let attachment = CreateAttachment::bytes(
+        crate::invoice::csv(&lines, rate).into_bytes(),
+        crate::invoice::filename(&client, year, month),
+    );
+    if let Err(e) = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed).add_file(attachment))
+        .await
+    {
+        eprintln!("[clock] Failed to send invoice: {e}");
+    }
+}
+
+fn link_request_key(user_id: &str) -> String {
+    format!("link_request.{}", user_id)
+}
+
+/// `/clock link @other` asks to link; the same command from the other account confirms.
+/// The account that asked first keeps its id and name.
+async fn handle_link(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let me = account(db, msg.author.id);
+    let Some(other) = msg.mentions.first() else {
+        let Some(linked) = crate::errors::or_report(&ctx.http, msg, db.linked_accounts(&me)).await else {
+            return;
+        };
+        let reply = if me != msg.author.id.to_string() {
+            format!("This account counts as <@{}>.", me)
+        } else if linked.is_empty() {
+            "No linked accounts. Use `/clock link @other-account` and confirm from that account.".to_string()
+        } else {
+            let mentions: Vec<String> = linked.iter().map(|id| format!("<@{}>", id)).collect();
+            format!("Linked accounts counting as you: {}", mentions.join(", "))
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    };
+    let other_id = account(db, other.id);
+    if other.bot || other_id == me {
+        let _ = msg.reply(&ctx.http, "Mention another account of yours to link.").await;
+        return;
+    }
+
+    // The other account asked first: this confirms and moves this account's time over
+    let Some(request) = crate::errors::or_report(&ctx.http, msg, db.metadata(&link_request_key(&other_id))).await else {
+        return;
+    };
+    if request.as_deref() == Some(me.as_str()) {
+        if let Err(e) = db.delete_metadata(&link_request_key(&other_id)) {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+        let username = match msg.guild_id {
+            Some(guild_id) => other
+                .nick_in(&ctx.http, guild_id)
+                .await
+                .unwrap_or_else(|| other.display_name().to_string()),
+            None => other.display_name().to_string(),
+        };
+        let reply = match db.link_accounts(&me, &other_id, &username) {
+            Ok(()) => format!("🔗 Linked. Time from this account now counts as <@{}>, history included.", other_id),
+            Err(e) if e.downcast_ref::<db::Refusal>() == Some(&db::Refusal::ClockedIn) => "Clock out on this account first, then confirm again.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let reply = match db.set_metadata(&link_request_key(&me), &other_id) {
+        Ok(()) => format!(
+            "<@{}>, to count that account's time as <@{}>, send `/clock link <@{}>` from it.",
+            other_id, me, me
+        ),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// Unlink this account, or from the main account one of its linked accounts.
+async fn handle_unlink(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let me = account(db, msg.author.id);
+    let target = match msg.mentions.first() {
+        Some(other) if db.primary_account(&other.id.to_string()) == me => other.id.to_string(),
+        Some(_) => {
+            let _ = msg.reply(&ctx.http, "That account isn't linked to you.").await;
+            return;
+        }
+        None => msg.author.id.to_string(),
+    };
+    let reply = match db.unlink_account(&target) {
+        Ok(true) => format!("<@{}> counts on its own again; time already combined stays with <@{}>.", target, me),
+        Ok(false) => "This account isn't linked. Mention the account to unlink from your main one.".to_string(),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_project_budget(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock project budget <activity> <200h|off>`";
+    let fmt = DisplayFormat::load(db);
+
+    if args.is_empty() {
+        let Some(budgets) = crate::errors::or_report(&ctx.http, msg, db.activity_budgets()).await else {
+            return;
+        };
+        let reply = if budgets.is_empty() {
+            "No project budgets set.".to_string()
+        } else {
+            let lines: Vec<String> = budgets
+                .iter()
+                .map(|b| format!("**{}** — {}", b.activity, crate::budgets::budget_line(b, &fmt)))
+                .collect();
+            format!("Project budgets:\n{}", lines.join("\n"))
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let Some((name, value)) = args.rsplit_once(' ') else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    let activity = crate::normalize::normalize_activity(name.trim());
+    let budget = if value.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match crate::budgets::parse_budget(value) {
+            Some(minutes) => Some(minutes),
+            None => {
+                let _ = msg.reply(&ctx.http, USAGE).await;
+                return;
+            }
+        }
+    };
+    if activity.is_empty() {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let reply = match db.set_activity_budget(&activity, budget) {
+        Ok(()) => match db.activity_budget(&activity) {
+            Ok(Some(b)) => format!("**{}** budget set: {}.", activity, crate::budgets::budget_line(&b, &fmt)),
+            Ok(None) => format!("**{}** no longer has a budget.", activity),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// Clock the user in on the activity picked from a `/clock menu` select menu.
+pub async fn handle_menu_select(ctx: &Context, interaction: &ComponentInteraction, db: &Arc<Db>) {
+    let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+        return;
+    };
+    let Some(activity) = values.first() else {
+        return;
+    };
+    let activity = crate::normalize::normalize_activity(activity);
+    let user = &interaction.user;
+    let user_id = account(db, user.id);
+    let username = interaction
+        .member
+        .as_ref()
+        .map(|m| m.display_name().to_string())
+        .unwrap_or_else(|| user.display_name().to_string());
+
+    let response = if !guardrails_allow_clock_in(&ctx.http, db, user.id).await {
+        CreateInteractionResponseMessage::new()
+            .content("⛔ Clock-in blocked — details in your DMs.")
+            .ephemeral(true)
+    } else {
+        match db.clock_in(&user_id, &username, &activity) {
+            Ok(()) => {
+                crate::focus::grant(&ctx.http, db, interaction.guild_id, user.id).await;
+                crate::mirror::spawn(crate::mirror::clock_in_text(&username, &activity));
+                CreateInteractionResponseMessage::new().embed(clocked_in_embed(
+                    db,
+                    &username,
+                    &activity,
+                    crate::tips::greeting_for(db, &user_id),
+                    &DisplayFormat::load(db),
+                ))
+            }
+            Err(e) if e.downcast_ref::<db::Refusal>() == Some(&db::Refusal::ClockedIn) => {
+                CreateInteractionResponseMessage::new()
+                    .embed(already_clocked_in_embed(db, &user_id))
+                    .ephemeral(true)
+            }
+            Err(e) => {
+                let reference = crate::errors::report_interaction(&ctx.http, interaction, &e).await;
+                CreateInteractionResponseMessage::new()
+                    .embed(crate::errors::user_embed(&reference))
+                    .ephemeral(true)
+            }
+        }
+    };
+
+    if let Err(e) = interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+    {
+        eprintln!("[clock] Failed to answer menu pick: {e}");
+    }
+}
+
+async fn handle_clock_out(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+    let username = msg.author.display_name().to_string();
+
+    match db.clock_out(&user_id) {
+        Ok((elapsed, activity)) => {
+            let mut embed = CreateEmbed::new()
+                .color(COLOR_RED)
+                .title("🔴 Clocked Out")
+                .description(format!("**{}** finished working on **{}**", username, activity))
+                .field("Duration", fmt.elapsed(elapsed), true)
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+            if let Some(tip) = crate::tips::next_tip(db, &user_id, &activity) {
+                embed = embed.field("💡 Tip", tip.text(), false);
+            }
+            if let Ok(sent) = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await
+            {
+                let week = db.clock().week().label();
+                if let Err(e) = db.add_kudos_target(&sent.id.to_string(), &user_id, &username, &week) {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                }
+            }
+            crate::focus::revoke(&ctx.http, db, msg.author.id).await;
+            crate::mirror::spawn(crate::mirror::clock_out_text(&username, &activity, elapsed, &fmt));
+            if let Some(violation) = crate::guardrails::check_clock_out(db, &user_id, elapsed.minutes()) {
+                crate::guardrails::notify(&ctx.http, msg.author.id, &[violation], false, &fmt).await;
+            }
+        }
+        Err(_) if matches!(db.active_session(&user_id), Ok(None)) => {
+            let embed = CreateEmbed::new()
+                .color(COLOR_GRAY)
+                .title("🤷 Not Clocked In")
+                .description("Use `/clock in <activity>` first.");
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await;
+        }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+        }
+    }
+}
+
+/// A reaction with the `kudos-emoji` on a clock-out post gives (or takes back) kudos
+/// for its owner.
+pub fn handle_kudos_reaction(db: &Db, reaction: &Reaction, added: bool) {
+    let Some(emoji) = crate::config::get(db, crate::config::KUDOS_EMOJI) else {
+        return;
+    };
+    let Some(giver) = reaction.user_id.map(|u| account(db, u)) else {
+        return;
+    };
+    if reaction.emoji.to_string() != emoji || reaction.member.as_ref().is_some_and(|m| m.user.bot) {
+        return;
+    }
+    let message_id = reaction.message_id.to_string();
+    let result = if added {
+        db.give_kudos(&message_id, &giver)
+    } else {
+        db.take_back_kudos(&message_id, &giver)
+    };
+    if let Err(e) = result {
+        eprintln!("[clock] Failed to record kudos: {e}");
+    }
+}
+
+async fn handle_schedule_out(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock out in <30m|2h|1h30m>` or `/clock out cancel`";
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+
+    let reply = if args == "cancel" {
+        match db.scheduled_clock_out(&user_id) {
+            Ok(Some(_)) => match db.schedule_clock_out(&user_id, None) {
+                Ok(_) => "Scheduled clock-out cancelled. You stay clocked in.".to_string(),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            },
+            Ok(None) => "No clock-out is scheduled.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        }
+    } else if let Some(minutes) = args.strip_prefix("in ").and_then(crate::auto_out::parse_duration) {
+        let at = db.clock().now() + chrono::Duration::minutes(minutes);
+        match db.schedule_clock_out(&user_id, Some(at)) {
+            Ok(true) => format!(
+                "⏰ You'll be clocked out at {} (in {}). `/clock out cancel` to keep going.",
+                fmt.time(at.time()),
+                fmt.duration(minutes)
+            ),
+            Ok(false) => "You're not clocked in. Use `/clock in <activity>` first.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        }
+    } else {
+        USAGE.to_string()
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_status(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+    let username = msg.author.display_name().to_string();
+
+    match db.active_session(&user_id) {
+        Ok(Some(session)) => {
+            let now = db.clock().now();
+            let elapsed = (now - session.started_at).num_minutes();
+            let started = fmt.time(session.started_at.time());
+
+            let mut embed = CreateEmbed::new()
+                .color(COLOR_GREEN)
+                .title(format!("🟢 {} is working", username))
+                .field("Activity", &session.activity, true)
+                .field("Elapsed", fmt.duration(elapsed), true)
+                .field("Since", &started, true);
+            let Some(scheduled) = crate::errors::or_report(&ctx.http, msg, db.scheduled_clock_out(&user_id)).await else {
+                return;
+            };
+            if let Some(at) = scheduled {
+                embed = embed.field(
+                    "Auto clock-out",
+                    format!(
+                        "{} (in {}) · `/clock out cancel`",
+                        fmt.time(at.time()),
+                        fmt.duration((at - now).num_minutes().max(0))
+                    ),
+                    false,
+                );
+            }
+            let embed = embed
+                .field("This week", crate::pace::user_pace(db, &user_id, now, &fmt), false)
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await;
+        }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+        }
+        Ok(None) => {
+            let embed = CreateEmbed::new()
+                .color(COLOR_GRAY)
+                .title(format!("😴 {} is offline", username))
+                .description("`/clock in <activity>`")
+                .field("This week", crate::pace::user_pace(db, &user_id, db.clock().now(), &fmt), false);
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await;
+        }
+    }
+}
+
+async fn handle_timeline(ctx: &Context, msg: &Message, db: &Arc<Db>, arg: &str) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+    let username = msg.author.display_name().to_string();
+
+    let Some(day) = parse_timeline_day(arg, db.clock().now().date()) else {
+        let _ = msg
+            .reply(&ctx.http, "Which day? `/clock timeline [today|yesterday|monday|YYYY-MM-DD]`")
+            .await;
+        return;
+    };
+
+    let Some(sessions) = crate::errors::or_report(&ctx.http, msg, db.sessions_on_day(&user_id, day)).await else {
+        return;
+    };
+    let title = format!("🕒 {} · {}", username, day.format("%a %d.%m.%Y"));
+
+    let Some(timeline) = format_timeline(&timeline_slots(&sessions, day), day, &fmt) else {
+        let embed = CreateEmbed::new()
+            .color(COLOR_GRAY)
+            .title(title)
+            .description("*No sessions that day*\nOnly the current week is kept; older weeks are archived as totals.")
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    };
+
+    // Clip sessions crossing midnight to the requested day
+    let day_start = timeline_slot_start(day, 0);
+    let day_end = timeline_slot_start(day, TIMELINE_SLOTS);
+    let worked: i64 = sessions
+        .iter()
+        .map(|s| (s.ended_at.min(day_end) - s.started_at.max(day_start)).num_minutes().max(0))
+        .sum();
+
+    let embed = CreateEmbed::new()
+        .color(COLOR_BLUE)
+        .title(title)
+        .description(truncate_for_embed(&timeline, EMBED_DESCRIPTION_LIMIT))
+        .field("Worked", fmt.duration(worked), true)
+        .field("Sessions", sessions.len().to_string(), true)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} · 1 block = {} min",
+            swiss_timestamp(db, &fmt),
+            TIMELINE_SLOT_MINUTES
+        )));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_goal(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+
+    if args.eq_ignore_ascii_case("off") {
+        let reply = match db.set_daily_goal(&user_id, None) {
+            Ok(()) => "Daily goal cleared.",
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    if !args.is_empty() {
+        let Some(minutes) = parse_hours(args) else {
+            let _ = msg
+                .reply(&ctx.http, "Usage: `/clock goal <hours>` — e.g. `4`, `4.5` or `4h30m` (max 24h), or `/clock goal off`")
+                .await;
+            return;
+        };
+        if let Err(e) = db.set_daily_goal(&user_id, Some(minutes)) {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    }
+
+    let Some(settings) = crate::errors::or_report(&ctx.http, msg, db.user_settings(&user_id)).await else {
+        return;
+    };
+    let Some(goal) = settings.daily_goal_min else {
+        let _ = msg
+            .reply(&ctx.http, "No daily goal set. `/clock goal <hours>` to set one.")
+            .await;
+        return;
+    };
+
+    let worked = crate::nudge::worked_minutes_today(db, &settings);
+    let Some(worked) = crate::errors::or_report(&ctx.http, msg, worked).await else {
+        return;
+    };
+    let (color, title) = if worked >= goal {
+        (COLOR_GREEN, "🎯 Daily goal reached")
+    } else {
+        (COLOR_BLUE, "🎯 Daily goal")
+    };
+    let nudges = if settings.nudges {
+        format!("on, from {:02}:00", crate::nudge::nudge_hour())
+    } else {
+        "off".to_string()
+    };
+
+    let embed = CreateEmbed::new()
+        .color(color)
+        .title(title)
+        .description(format!(
+            "`{}` {} / {}",
+            make_bar(worked, goal),
+            fmt.duration(worked),
+            fmt.duration(goal)
+        ))
+        .field("Timezone", &settings.timezone, true)
+        .field("Nudges", nudges, true)
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_timezone(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let user_id = account(db, msg.author.id);
+
+    if args.is_empty() {
+        let reply = match db.user_settings(&user_id) {
+            Ok(s) => format!("Your days are counted in **{}**. `/clock timezone <Area/City>` to change.", s.timezone),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    let Ok(tz) = args.parse::<chrono_tz::Tz>() else {
+        let _ = msg
+            .reply(&ctx.http, format!("Unknown timezone **{}**. Use an IANA name like `Europe/Zurich` or `America/New_York`.", args))
+            .await;
+        return;
+    };
+    let reply = match db.set_timezone(&user_id, tz.name()) {
+        Ok(()) => format!("Timezone set to **{}**.", tz.name()),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_nudge(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            let _ = msg.reply(&ctx.http, "Usage: `/clock nudge on|off`").await;
+            return;
+        }
+    };
+    let reply = match db.set_nudges(&account(db, msg.author.id), enabled) {
+        Ok(()) if enabled => "Nudges on. You'll get a DM in the evening if you're behind your daily goal.",
+        Ok(()) => "Nudges off.",
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_tips(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            let _ = msg.reply(&ctx.http, "Usage: `/clock tips on|off`").await;
+            return;
+        }
+    };
+    let reply = match db.set_tips(&account(db, msg.author.id), enabled) {
+        Ok(()) if enabled => "Tips on. Clock-in posts greet you, and now and then suggest a command.",
+        Ok(()) => "Tips off.",
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_config(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let fmt = DisplayFormat::load(db);
+    if args.is_empty() {
+        let mut embed = CreateEmbed::new()
+            .color(COLOR_BLUE)
+            .title("⚙️ Server Settings")
+            .footer(CreateEmbedFooter::new(format!(
+                "{} · /clock config <key> <value|off>",
+                swiss_timestamp(db, &fmt)
+            )));
+        for setting in crate::config::SETTINGS {
+            let value = crate::config::display(db, setting).unwrap_or_else(|| "off".to_string());
+            embed = embed.field(
+                format!("`{}` — {}", setting.key, value),
+                setting.description,
+                false,
+            );
+        }
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    let (key, value) = args.split_once(' ').unwrap_or((args, ""));
+    if key == "perms" {
+        handle_perms(ctx, msg, db, value.trim()).await;
+        return;
+    }
+    let Some(setting) = crate::config::setting(key) else {
+        let keys: Vec<String> = crate::config::SETTINGS.iter().map(|s| format!("`{}`", s.key)).collect();
+        let _ = msg
+            .reply(&ctx.http, format!("Unknown setting **{}**. Available: {}", key, keys.join(", ")))
+            .await;
+        return;
+    };
+
+    let value = value.trim();
+    if value.is_empty() {
+        let current = crate::config::display(db, setting).unwrap_or_else(|| "off".to_string());
+        let _ = msg
+            .reply(&ctx.http, format!("`{}` is **{}** — {}", setting.key, current, setting.description))
+            .await;
+        return;
+    }
+
+    let reply = match crate::config::set(db, setting, value) {
+        Ok(Some(shown)) => format!("`{}` set to **{}**.", setting.key, shown),
+        Ok(None) => format!("`{}` turned off.", setting.key),
+        Err(e) => e,
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_perms(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    use crate::config::CAPABILITIES;
+    const USAGE: &str = "Usage: `/clock config perms <admin|export> add|remove @role`";
+    let fmt = DisplayFormat::load(db);
+
+    if args.is_empty() {
+        let mut embed = CreateEmbed::new()
+            .color(COLOR_BLUE)
+            .title("🔐 Permissions")
+            .description("Members in `ADMIN_IDS` can do everything; admin roles include the other permissions.")
+            .footer(CreateEmbedFooter::new(format!("{} · {}", swiss_timestamp(db, &fmt), USAGE.trim_start_matches("Usage: "))));
+        for &capability in CAPABILITIES {
+            let roles: Vec<String> = crate::config::capability_roles(db, capability)
+                .iter()
+                .map(|r| r.mention().to_string())
+                .collect();
+            let roles = if roles.is_empty() { "no roles".to_string() } else { roles.join(", ") };
+            embed = embed.field(format!("`{}` — {}", capability.key(), roles), capability.description(), false);
+        }
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    // Roles may only be granted by the owners in ADMIN_IDS, so admin roles can't hand out more
+    if !crate::config::is_admin(msg.author.id) {
+        let _ = msg.reply(&ctx.http, "Only the admins in `ADMIN_IDS` can change permissions.").await;
+        return;
+    }
+    let mut words = args.split_whitespace();
+    let (Some(capability), Some(action)) = (words.next().and_then(Capability::parse), words.next()) else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    if msg.mention_roles.is_empty() || !matches!(action, "add" | "remove") {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    }
+
+    let mut roles = crate::config::capability_roles(db, capability);
+    roles.retain(|r| !msg.mention_roles.contains(r));
+    if action == "add" {
+        roles.extend(msg.mention_roles.iter().copied());
+    }
+    let mentions: Vec<String> = msg.mention_roles.iter().map(|r| r.mention().to_string()).collect();
+    let reply = match crate::config::set_capability_roles(db, capability, &roles) {
+        Ok(()) if action == "add" => format!("{} can now use `{}`.", mentions.join(", "), capability.key()),
+        Ok(()) => format!("{} can no longer use `{}`.", mentions.join(", "), capability.key()),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_holidays(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let fmt = DisplayFormat::load(db);
+    let year = if args.is_empty() {
+        db.clock().now().year()
+    } else {
+        match args.parse::<i32>() {
+            Ok(y) if (2000..=2100).contains(&y) => y,
+            _ => {
+                let _ = msg.reply(&ctx.http, "Usage: `/clock holidays [year]`").await;
+                return;
+            }
+        }
+    };
+
+    let from = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+    let Some(holidays) = crate::errors::or_report(&ctx.http, msg, db.holidays_between(from, to)).await else {
+        return;
+    };
+
+    let desc = if holidays.is_empty() {
+        format!("*No holidays for {}*\nAdmins: `/clock holiday import {}` for Swiss public holidays.", year, year)
+    } else {
+        holidays
+            .iter()
+            .map(|(day, name)| format!("`{}` {}\n", day.format("%a %d.%m."), name))
+            .collect()
+    };
+
+    let embed = CreateEmbed::new()
+        .color(COLOR_BLUE)
+        .title(format!("📅 Holidays {}", year))
+        .description(truncate_for_embed(&desc, EMBED_DESCRIPTION_LIMIT))
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_holiday(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock holiday add <YYYY-MM-DD> <name>`, `/clock holiday remove <YYYY-MM-DD>` or `/clock holiday import [year]`";
+
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+    let today = db.clock().now().date();
+
+    let reply = match action {
+        "add" => {
+            let (date, name) = rest.split_once(' ').unwrap_or((rest, ""));
+            match (parse_timeline_day(date, today), name.trim()) {
+                (Some(day), name) if !name.is_empty() => match db.add_holiday(day, name) {
+                    Ok(()) => format!("Added **{}** on {}.", name, day.format("%a %d.%m.%Y")),
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                },
+                _ => USAGE.to_string(),
+            }
+        }
+        "remove" => match parse_timeline_day(rest, today) {
+            Some(day) => match db.remove_holiday(day) {
+                Ok(true) => format!("Removed the holiday on {}.", day.format("%a %d.%m.%Y")),
+                Ok(false) => format!("No holiday on {}.", day.format("%a %d.%m.%Y")),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            },
+            None => USAGE.to_string(),
+        },
+        "import" => {
+            let year = if rest.is_empty() { Some(today.year()) } else { rest.parse().ok() };
+            match year.filter(|y| (2000..=2100).contains(y)) {
+                Some(year) => {
+                    let holidays = crate::holidays::swiss_public_holidays(year);
+                    if let Err(e) = holidays.iter().try_for_each(|(day, name)| db.add_holiday(*day, name)) {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                    format!(
+                        "Imported {} Swiss public holidays for {}. `/clock holidays {}` to review.",
+                        holidays.len(),
+                        year,
+                        year
+                    )
+                }
+                None => USAGE.to_string(),
+            }
+        }
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_calendar(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock calendar set <ics-url>`, `filter <word, word|off>`, `activity <name>`, `sync` or `off`";
+
+    let user_id = account(db, msg.author.id);
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+
+    let reply = match action {
+        "" => match db.calendar_feed(&user_id) {
+            Ok(Some(feed)) => {
+                let filter = if feed.keywords.is_empty() {
+                    "all events".to_string()
+                } else {
+                    feed.keywords.join(", ")
+                };
+                format!(
+                    "📅 Syncing **{}** every 15 minutes as **{}** ({}).",
+                    crate::calendar::url_host(&feed.url),
+                    feed.activity,
+                    filter
+                )
+            }
+            Ok(None) => format!("No calendar linked. {}", USAGE),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "set" => {
+            // The URL is a secret: don't leave it readable in a server channel
+            if msg.guild_id.is_some() {
+                let _ = msg.delete(&ctx.http).await;
+            }
+            match crate::calendar::normalize_url(rest) {
+                Some(url) => match db.set_calendar_feed(&user_id, msg.author.display_name(), &url) {
+                    Ok(()) => format!(
+                        "📅 Calendar on **{}** linked. Finished events from this week are imported as **{}** every 15 minutes. Tip: send the URL to me in a DM next time.",
+                        crate::calendar::url_host(&url),
+                        db.calendar_feed(&user_id)
+                            .ok()
+                            .flatten()
+                            .map(|f| f.activity)
+                            .unwrap_or_else(|| crate::calendar::DEFAULT_ACTIVITY.to_string())
+                    ),
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                },
+                None => "That doesn't look like an ICS link (`https://…` or `webcal://…`).".to_string(),
+            }
+        }
+        "filter" => {
+            let keywords: Vec<String> = if rest.eq_ignore_ascii_case("off") {
+                Vec::new()
+            } else {
+                rest.split(',')
+                    .map(|k| k.trim().to_lowercase())
+                    .filter(|k| !k.is_empty())
+                    .collect()
+            };
+            if keywords.is_empty() && !rest.eq_ignore_ascii_case("off") {
+                USAGE.to_string()
+            } else {
+                match db.set_calendar_keywords(&user_id, &keywords) {
+                    Ok(true) if keywords.is_empty() => "Importing all events.".to_string(),
+                    Ok(true) => format!("Importing events matching: {}", keywords.join(", ")),
+                    Ok(false) => "No calendar linked. `/clock calendar set <ics-url>` first.".to_string(),
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                }
+            }
+        }
+        "activity" => {
+            let activity = crate::normalize::normalize_activity(rest);
+            if activity.is_empty() {
+                USAGE.to_string()
+            } else {
+                match db.set_calendar_activity(&user_id, &activity) {
+                    Ok(true) => format!("Calendar events will be logged as **{}**.", activity),
+                    Ok(false) => "No calendar linked. `/clock calendar set <ics-url>` first.".to_string(),
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                }
+            }
+        }
+        "sync" => match db.calendar_feed(&user_id) {
+            Ok(Some(feed)) => {
+                match crate::calendar::sync_feed(db, &feed).await {
+                    Ok(n) => format!("Imported {} new event(s).", n),
+                    Err(e) => format!("Sync failed: {}", e),
+                }
+            }
+            Ok(None) => "No calendar linked. `/clock calendar set <ics-url>` first.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "off" => match db.remove_calendar_feed(&user_id) {
+            Ok(true) => "Calendar unlinked. Already imported sessions stay.".to_string(),
+            Ok(false) => "No calendar linked.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => USAGE.to_string(),
+    };
+
+    // A reply would reference the deleted `set` message, so post plainly
+    let _ = msg.channel_id.say(&ctx.http, reply).await;
+}
+
+async fn handle_github(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    use crate::github::{MODE_LOG, MODE_SUGGEST};
+    const USAGE: &str =
+        "Usage: `/clock github link <login> [suggest|log]`, `verify`, `unlink`, `map <repo> > <activity>` or `unmap <repo>`";
+
+    let user_id = account(db, msg.author.id);
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+
+    let reply = match action {
+        "" => {
+            let link = match db.github_link_for_user(&user_id) {
+                Ok(Some(link)) => format!(
+                    "🐙 Linked to **{}** ({}).",
+                    link.login,
+                    if link.mode == MODE_LOG { "pushes are logged as sessions" } else { "clock-in suggestions by DM" }
+                ),
+                Ok(None) => format!("No GitHub account linked. {}", USAGE),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            };
+            let Some(rules) = crate::errors::or_report(&ctx.http, msg, db.github_repo_rules()).await else {
+                return;
+            };
+            if rules.is_empty() {
+                format!("{}\nNo repo rules: activity is the repository name.", link)
+            } else {
+                let rules: Vec<String> = rules
+                    .iter()
+                    .map(|(pattern, activity)| format!("`{}` → **{}**", pattern, activity))
+                    .collect();
+                format!("{}\n**Repo rules**\n{}", link, rules.join("\n"))
+            }
+        }
+        "link" => {
+            let (login, mode) = rest.split_once(' ').unwrap_or((rest, MODE_SUGGEST));
+            let mode = mode.trim().to_lowercase();
+            if !crate::github::valid_login(login) || !matches!(mode.as_str(), MODE_SUGGEST | MODE_LOG) {
+                USAGE.to_string()
+            } else {
+                match db.github_link_by_login(login) {
+                    Ok(Some(other)) if other.user_id != user_id => {
+                        format!("**{}** is already linked to {}.", other.login, other.username)
+                    }
+                    Ok(_) => {
+                        // Anyone could type any login; it only counts once its owner proves it
+                        let link = GithubLink {
+                            login: login.to_string(),
+                            user_id: user_id.clone(),
+                            username: msg.author.display_name().to_string(),
+                            mode,
+                        };
+                        let code = crate::github::verification_code();
+                        match db.set_github_pending(&link, &code) {
+                            Ok(()) => format!(
+                                "To prove **{}** is yours, put `{}` in your GitHub bio or in the description of a \
+                                 public gist, then run `/clock github verify`. You can remove it afterwards.",
+                                login, code
+                            ),
+                            Err(e) => {
+                                crate::errors::report(&ctx.http, msg, &e).await;
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                }
+            }
+        }
+        "verify" => match db.github_pending(&user_id) {
+            Ok(Some((link, code))) => match crate::github::owns_login(&link.login, &code).await {
+                Ok(true) => match db.github_link_by_login(&link.login) {
+                    Ok(Some(other)) if other.user_id != user_id => {
+                        format!("**{}** is already linked to {}.", other.login, other.username)
+                    }
+                    _ => match db.link_github(&link.login, &user_id, &link.username, &link.mode) {
+                        Ok(()) => {
+                            if let Err(e) = db.delete_github_pending(&user_id) {
+                                crate::errors::report(&ctx.http, msg, &e).await;
+                                return;
+                            }
+                            if link.mode == MODE_LOG {
+                                format!(
+                                    "🐙 Linked **{}**. Bursts of pushes and pull requests will be logged as sessions.",
+                                    link.login
+                                )
+                            } else {
+                                format!("🐙 Linked **{}**. I'll DM you a clock-in hint when you start pushing.", link.login)
+                            }
+                        }
+                        Err(e) => {
+                            crate::errors::report(&ctx.http, msg, &e).await;
+                            return;
+                        }
+                    },
+                },
+                Ok(false) => format!(
+                    "`{}` isn't in the bio or a public gist of **{}** yet. GitHub can take a minute to show changes.",
+                    code, link.login
+                ),
+                Err(e) => {
+                    eprintln!("[clock] GitHub verification of {} failed: {e}", link.login);
+                    "Couldn't reach GitHub to check. Try again in a moment.".to_string()
+                }
+            },
+            Ok(None) => "Nothing to verify. Start with `/clock github link <login>`.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "unlink" => match db.unlink_github(&user_id) {
+            Ok(true) => "GitHub account unlinked. Already logged sessions stay.".to_string(),
+            Ok(false) => "No GitHub account linked.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "map" => match rest.split_once('>') {
+            Some((pattern, activity)) => {
+                let pattern = pattern.trim();
+                let activity = crate::normalize::normalize_activity(activity);
+                if pattern.is_empty() || activity.is_empty() {
+                    USAGE.to_string()
+                } else {
+                    match db.set_github_repo_rule(pattern, &activity) {
+                        Ok(()) => format!("Activity on `{}` counts as **{}**.", pattern.to_lowercase(), activity),
+                        Err(e) => {
+                            crate::errors::report(&ctx.http, msg, &e).await;
+                            return;
+                        }
+                    }
+                }
+            }
+            None => USAGE.to_string(),
+        },
+        "unmap" if !rest.is_empty() => match db.remove_github_repo_rule(rest) {
+            Ok(true) => format!("Removed the rule for `{}`.", rest.to_lowercase()),
+            Ok(false) => format!("No rule for `{}`.", rest.to_lowercase()),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_badge(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock badge map <external-id> @user` or `unmap <external-id>`";
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+    let reply = match action {
+        "" => match db.external_ids() {
+            Ok(ids) if ids.is_empty() => format!("No badges mapped. {}", USAGE),
+            Ok(ids) => {
+                let lines: Vec<String> = ids
+                    .iter()
+                    .map(|e| format!("`{}` → {}", e.external_id, e.username))
+                    .collect();
+                format!("**Badges**\n{}", lines.join("\n"))
+            }
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "map" => {
+            let external_id = rest.split_whitespace().next().filter(|id| !id.starts_with("<@"));
+            let user = msg.mentions.iter().find(|u| !u.bot);
+            match (external_id, user) {
+                (Some(external_id), Some(user)) => {
+                    let username = match msg.guild_id {
+                        Some(guild_id) => user
+                            .nick_in(&ctx.http, guild_id)
+                            .await
+                            .unwrap_or_else(|| user.display_name().to_string()),
+                        None => user.display_name().to_string(),
+                    };
+                    match db.map_external_id(external_id, &account(db, user.id), &username) {
+                        Ok(()) => format!("🪪 `{}` now clocks **{}** in and out.", external_id, username),
+                        Err(e) => {
+                            crate::errors::report(&ctx.http, msg, &e).await;
+                            return;
+                        }
+                    }
+                }
+                _ => USAGE.to_string(),
+            }
+        }
+        "unmap" if !rest.is_empty() => match db.unmap_external_id(rest) {
+            Ok(true) => format!("Removed `{}`.", rest),
+            Ok(false) => format!("No badge `{}`.", rest),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_apikey(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let user_id = account(db, msg.author.id);
+    let reply = match args {
+        "" => {
+            let key = crate::ingest::generate_api_key();
+            let saved = db.set_api_token(&user_id, msg.author.display_name(), &crate::ingest::hash_api_key(&key));
+            if let Err(e) = saved {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+            let embed = CreateEmbed::new()
+                .color(COLOR_BLUE)
+                .title("🔑 Editor API key")
+                .description(format!(
+                    "`{}`\n\nIn `~/.wakatime.cfg`:\n```ini\n[settings]\napi_url = https://<bot-host>/api/v1\napi_key = {}\n```\nCoding time outside your clock-ins is logged as sessions named after the project. Any previous key stops working.",
+                    key, key
+                ))
+                .footer(CreateEmbedFooter::new("/clock apikey revoke to disable"));
+            match msg.author.direct_message(&ctx.http, CreateMessage::new().embed(embed)).await {
+                Ok(_) => "🔑 Sent you a new API key by DM.".to_string(),
+                Err(_) => {
+                    // Never post the key in a channel; a key nobody received is useless
+                    if let Err(e) = db.revoke_api_token(&user_id) {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                    "I couldn't DM you. Allow direct messages from server members and try again.".to_string()
+                }
+            }
+        }
+        "revoke" => match db.revoke_api_token(&user_id) {
+            Ok(true) => "API key revoked. Already logged sessions stay.".to_string(),
+            Ok(false) => "You don't have an API key.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => "Usage: `/clock apikey` or `/clock apikey revoke`".to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_kiosk(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let user_id = account(db, msg.author.id);
+    let reply = match args {
+        "" => {
+            let code = crate::kiosk::generate_code();
+            let saved = db.set_kiosk_code(&user_id, msg.author.display_name(), &crate::ingest::hash_api_key(&code));
+            if let Err(e) = saved {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+            let embed = CreateEmbed::new()
+                .color(COLOR_BLUE)
+                .title("🖲️ Kiosk code")
+                .description(format!(
+                    "**`{}`**\n\nType it on the shared kiosk to clock in, and again to clock out. Any previous code stops working.",
+                    code
+                ))
+                .footer(CreateEmbedFooter::new("/clock kiosk revoke to disable"));
+            match msg.author.direct_message(&ctx.http, CreateMessage::new().embed(embed)).await {
+                Ok(_) => "🖲️ Sent you a new kiosk code by DM.".to_string(),
+                Err(_) => {
+                    if let Err(e) = db.revoke_kiosk_code(&user_id) {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                    "I couldn't DM you. Allow direct messages from server members and try again.".to_string()
+                }
+            }
+        }
+        "revoke" => match db.revoke_kiosk_code(&user_id) {
+            Ok(true) => "Kiosk code revoked.".to_string(),
+            Ok(false) => "You don't have a kiosk code.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        _ => "Usage: `/clock kiosk` or `/clock kiosk revoke`".to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_token(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    use crate::api::Scope;
+    const USAGE: &str = "Usage: `/clock token`, `/clock token create [read|write]` or `/clock token revoke <id>`";
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+
+    let reply = match action {
+        "" => match db.user_tokens(&user_id) {
+            Ok(tokens) if tokens.is_empty() => "No API tokens. `/clock token create [read|write]` makes one.".to_string(),
+            Ok(tokens) => {
+                let lines: Vec<String> = tokens
+                    .iter()
+                    .map(|t| {
+                        let used = t.last_used.map(|u| fmt.timestamp(u)).unwrap_or_else(|| "never".to_string());
+                        format!("`#{}` **{}** — created {} · last used {}", t.id, t.scope, fmt.timestamp(t.created_at), used)
+                    })
+                    .collect();
+                format!("🔑 **Your API tokens**\n{}", lines.join("\n"))
+            }
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "create" => {
+            let Some(scope) = (if rest.is_empty() { Some(Scope::Read) } else { Scope::parse(rest) }) else {
+                let _ = msg.reply(&ctx.http, USAGE).await;
+                return;
+            };
+            let token = crate::ingest::generate_api_key();
+            let hash = crate::ingest::hash_api_key(&token);
+            let created = db.create_user_token(&user_id, msg.author.display_name(), &hash, scope.key());
+            let Some(id) = crate::errors::or_report(&ctx.http, msg, created).await else {
+                return;
+            };
+            let embed = CreateEmbed::new()
+                .color(COLOR_BLUE)
+                .title(format!("🔑 API token #{} ({})", id, scope.key()))
+                .description(format!(
+                    "`{}`\n\nSend it as `Authorization: Bearer <token>`. Read tokens can fetch `GET /api/v1/sessions`; write tokens can also log editor heartbeats. It is shown only once.",
+                    token
+                ))
+                .footer(CreateEmbedFooter::new(format!("/clock token revoke {} to disable", id)));
+            match msg.author.direct_message(&ctx.http, CreateMessage::new().embed(embed)).await {
+                Ok(_) => format!("🔑 Sent you token #{} by DM.", id),
+                Err(_) => {
+                    // Never post a token in a channel
+                    if let Err(e) = db.revoke_user_token(&user_id, id) {
+                        crate::errors::report(&ctx.http, msg, &e).await;
+                        return;
+                    }
+                    "I couldn't DM you. Allow direct messages from server members and try again.".to_string()
+                }
+            }
+        }
+        "revoke" => match rest.trim_start_matches('#').parse::<i64>() {
+            Ok(id) => match db.revoke_user_token(&user_id, id) {
+                Ok(true) => format!("Token #{} revoked.", id),
+                Ok(false) => format!("You have no token #{}.", id),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            },
+            Err(_) => USAGE.to_string(),
+        },
+        _ => USAGE.to_string(),
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_digest(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let user_id = account(db, msg.author.id);
+    let reply = match args {
+        "" => match db.digest_email(&user_id) {
+            Ok(Some(email)) => format!("📧 Weekly digest goes to **{}** on Mondays. `/clock digest off` to stop.", email),
+            Ok(None) => "Not subscribed. `/clock digest <email>` to get the weekly report by email (best in DM).".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "off" => match db.unsubscribe_digest(&user_id) {
+            Ok(true) => "Unsubscribed from the weekly digest.".to_string(),
+            Ok(false) => "You're not subscribed.".to_string(),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        email if crate::digest::valid_email(email) => {
+            // Keep addresses out of server channels
+            if msg.guild_id.is_some() {
+                let _ = msg.delete(&ctx.http).await;
+            }
+            match db.subscribe_digest(&user_id, msg.author.display_name(), email) {
+                Ok(()) if crate::digest::enabled() => "📧 Subscribed. The weekly report arrives every Monday.".to_string(),
+                Ok(()) => "📧 Subscribed, but email isn't configured on this bot yet; nothing is sent until it is.".to_string(),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            }
+        }
+        _ => "Usage: `/clock digest <email>` or `/clock digest off`".to_string(),
+    };
+    // A reply would reference a deleted message, so post plainly
+    let _ = msg.channel_id.say(&ctx.http, reply).await;
+}
+
+async fn handle_who(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    match db.who_is_working() {
         Ok(sessions) if !sessions.is_empty() => {
-            let now = db::now_ch();
+            let now = db.clock().now();
             let mut lines = String::new();
             for (i, s) in sessions.iter().enumerate() {
                 let elapsed = (now - s.started_at).num_minutes();
@@ -329,20 +3080,20 @@ async fn handle_who(ctx: &Context, msg: &Message, db: &Arc<Db>) {
                     i + 1,
                     s.username,
                     s.activity,
-                    format_duration(elapsed),
+                    fmt.duration(elapsed),
                 );
             }
             let embed = CreateEmbed::new()
                 .color(COLOR_BLUE)
                 .title(format!("🔨 {} currently working", sessions.len()))
                 .description(lines)
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
             let _ = msg
                 .channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
                 .await;
         }
-        _ => {
+        Ok(_) => {
             let embed = CreateEmbed::new()
                 .color(COLOR_GRAY)
                 .title("😴 Nobody working");
@@ -351,62 +3102,812 @@ async fn handle_who(ctx: &Context, msg: &Message, db: &Arc<Db>) {
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
                 .await;
         }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+        }
+    }
+}
+
+async fn handle_fair_leaderboard(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    let now = db.clock().now();
+    let Some(rows) = crate::errors::or_report(&ctx.http, msg, db.goal_leaderboard_weekly()).await else {
+        return;
+    };
+    let mut rows: Vec<(String, i64, i64)> = rows
+        .into_iter()
+        .map(|(name, minutes, daily)| (name, minutes, crate::pace::week_goal(db, daily, now)))
+        .collect();
+    // Ties by name, and numbered in that order when anonymous
+    rows.sort_by(|a, b| goal_percent(b.1, b.2).cmp(&goal_percent(a.1, a.2)).then_with(|| a.0.cmp(&b.0)));
+    if crate::anonymous::enabled(db) {
+        crate::anonymous::relabel(&mut rows, |r| &mut r.0);
+    }
+
+    let embed = CreateEmbed::new()
+        .color(COLOR_GOLD)
+        .title(format!("⚖️ Fair Leaderboard — {}", fmt.week(&db.clock().week().label())))
+        .description(truncate_for_embed(&format_fair_board(&rows, &fmt), EMBED_DESCRIPTION_LIMIT))
+        .footer(CreateEmbedFooter::new(format!(
+            "{} · Share of each person's weekly goal · /clock goal <hours> to join",
+            swiss_timestamp(db, &fmt)
+        )));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_event(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock event create \"<name>\" <start> <end>` with times as `2026-03-20T09:00` or `2026-03-20`";
+    let fmt = DisplayFormat::load(db);
+    let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+
+    let reply = match action {
+        "create" => match crate::events::parse_create(rest) {
+            Some((name, start, end)) => match db.create_event(&name, start, end) {
+                Ok(true) => format!(
+                    "🏁 **{}** runs {}. `/clock event {}` for standings.",
+                    name,
+                    crate::events::format_window(start, end, &fmt),
+                    name
+                ),
+                Ok(false) => format!("An event called **{}** already exists.", name),
+                Err(e) => {
+                    crate::errors::report(&ctx.http, msg, &e).await;
+                    return;
+                }
+            },
+            None => USAGE.to_string(),
+        },
+        "delete" => match db.delete_event(rest.trim()) {
+            Ok(true) => format!("Deleted **{}**.", rest.trim()),
+            Ok(false) => format!("No event called **{}**.", rest.trim()),
+            Err(e) => {
+                crate::errors::report(&ctx.http, msg, &e).await;
+                return;
+            }
+        },
+        "list" => {
+            let Some(events) = crate::errors::or_report(&ctx.http, msg, db.events()).await else {
+                return;
+            };
+            if events.is_empty() {
+                "No events yet.".to_string()
+            } else {
+                events
+                    .iter()
+                    .map(|e| format!("**{}** — {}", e.name, crate::events::format_window(e.starts_at, e.ends_at, &fmt)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+        _ => {
+            let now = db.clock().now();
+            // Without a name: the running event, else the one that started last
+            let event = if args.is_empty() {
+                let Some(events) = crate::errors::or_report(&ctx.http, msg, db.events()).await else {
+                    return;
+                };
+                events
+                    .iter()
+                    .find(|e| e.starts_at <= now && now < e.ends_at)
+                    .or(events.first())
+                    .cloned()
+            } else {
+                let Some(event) = crate::errors::or_report(&ctx.http, msg, db.event(args)).await else {
+                    return;
+                };
+                event
+            };
+            let Some(event) = event else {
+                let _ = msg.reply(&ctx.http, "No such event. `/clock event list` shows them all.").await;
+                return;
+            };
+            let standings = db.event_standings(&event, now.min(event.ends_at));
+            let Some(standings) = crate::errors::or_report(&ctx.http, msg, standings).await else {
+                return;
+            };
+            let standings = crate::anonymous::public_board(db, standings);
+            let status = if now < event.starts_at {
+                "Not started"
+            } else if now < event.ends_at {
+                "Live"
+            } else {
+                "Final"
+            };
+            let embed = CreateEmbed::new()
+                .color(COLOR_GOLD)
+                .title(format!("🏁 {} · {}", event.name, status))
+                .description(truncate_for_embed(
+                    &crate::events::format_standings(&standings, &fmt),
+                    EMBED_DESCRIPTION_LIMIT,
+                ))
+                .footer(CreateEmbedFooter::new(crate::events::format_window(event.starts_at, event.ends_at, &fmt)));
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_challenge(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock challenge @user <hours> this-week`, e.g. `/clock challenge @Bob 10h`";
+    let fmt = DisplayFormat::load(db);
+    let now = db.clock().now();
+    let week = crate::weeks::WeekId::containing(now.date());
+
+    if args.is_empty() {
+        let Some(challenges) = crate::errors::or_report(&ctx.http, msg, db.challenges(&week.label())).await else {
+            return;
+        };
+        let reply = if challenges.is_empty() {
+            "No challenges this week. `/clock challenge @user 10h` to start one.".to_string()
+        } else {
+            challenges
+                .iter()
+                .map(|c| format!("⚔️ {}", crate::challenges::standing(db, c, week, &fmt)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let _ = msg.reply(&ctx.http, reply).await;
+        return;
+    }
+
+    // Everything but the mention: the target and an optional period
+    let mut words = args.split_whitespace().filter(|w| !w.starts_with("<@"));
+    let target = words.next().and_then(parse_hours);
+    let period_ok = words.all(|w| w.eq_ignore_ascii_case("this-week"));
+    let (Some(opponent), Some(target), true) = (msg.mentions.first(), target, period_ok) else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    if account(db, opponent.id) == account(db, msg.author.id) || opponent.bot {
+        let _ = msg.reply(&ctx.http, "Pick a teammate to challenge.").await;
+        return;
+    }
+
+    let opponent_name = match msg.guild_id {
+        Some(guild_id) => opponent
+            .nick_in(&ctx.http, guild_id)
+            .await
+            .unwrap_or_else(|| opponent.display_name().to_string()),
+        None => opponent.display_name().to_string(),
+    };
+    let challenge = db::Challenge {
+        id: 0,
+        week_label: week.label(),
+        channel_id: msg.channel_id.to_string(),
+        challenger_id: account(db, msg.author.id),
+        challenger_name: msg.author.display_name().to_string(),
+        opponent_id: account(db, opponent.id),
+        opponent_name,
+        target_min: target,
+    };
+    let late = now >= crate::challenges::progress_time(week);
+    let reply = match db.create_challenge(&challenge, late) {
+        Ok(true) => format!(
+            "⚔️ <@{}> challenges <@{}>: race to {} this week.\n{}Decided at the weekly reset.",
+            challenge.challenger_id,
+            challenge.opponent_id,
+            fmt.duration(target),
+            if late { "" } else { "Progress comes Wednesday noon. " }
+        ),
+        Ok(false) => format!("You already challenged **{}** this week.", challenge.opponent_name),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+async fn handle_leaderboard(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    let (live_weekly, live_alltime) = (crate::config::live_weekly(db), crate::config::live_alltime(db));
+    let (weekly, alltime) = match (db.leaderboard_weekly(live_weekly), db.leaderboard_alltime(live_alltime)) {
+        (Ok(weekly), Ok(alltime)) => (weekly, alltime),
+        (Err(e), _) | (_, Err(e)) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let (weekly, alltime) = (crate::anonymous::public_board(db, weekly), crate::anonymous::public_board(db, alltime));
+    let user_id = account(db, msg.author.id);
+    let (weekly_rank, alltime_rank) = match (db.weekly_rank(&user_id, live_weekly), db.alltime_rank(&user_id, live_alltime)) {
+        (Ok(weekly), Ok(alltime)) => (weekly, alltime),
+        (Err(e), _) | (_, Err(e)) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let user_rank = format_user_rank(weekly_rank, alltime_rank, &fmt);
+
+    let week_label = db.clock().week().label();
+    let weekly_text = format_board(&weekly, &fmt);
+    let alltime_text = format_board(&alltime, &fmt);
+
+    let weekly_total: i64 = weekly.iter().map(|e| e.total_minutes).sum();
+    let alltime_total: i64 = alltime.iter().map(|e| e.total_minutes).sum();
+
+    let mut embed = CreateEmbed::new()
+        .color(COLOR_GOLD)
+        .title("🏆 Leaderboard")
+        .field(
+            format!("📅 This Week ({})", fmt.week(&week_label)),
+            format!(
+                "{}\n*Total: {}*",
+                weekly_text,
+                fmt.duration(weekly_total)
+            ),
+            false,
+        )
+        .field("\u{200b}", "\u{200b}", false);
+    // Only worth showing once admins have weighted something
+    let Some(weights) = crate::errors::or_report(&ctx.http, msg, db.activity_weights()).await else {
+        return;
+    };
+    if !weights.is_empty() {
+        let Some(scores) = crate::errors::or_report(&ctx.http, msg, db.score_leaderboard_weekly()).await else {
+            return;
+        };
+        let scores = crate::anonymous::public_board(db, scores);
+        embed = embed
+            .field("⚖️ Score This Week", format_score_board(&scores), false)
+            .field("\u{200b}", "\u{200b}", false);
+    }
+    // Anonymous boards don't say which row is whose; that goes to the author by DM instead
+    let private_rank = crate::anonymous::enabled(db) && msg.guild_id.is_some();
+    let (user_rank, dm_rank) = if private_rank { (None, user_rank) } else { (user_rank, None) };
+    let Some(note) = crate::errors::or_report(&ctx.http, msg, non_competitive_note(db)).await else {
+        return;
+    };
+    let embed = embed
+        .field(
+            "⏳ All Time",
+            format!(
+                "{}\n*Total: {}*",
+                alltime_text,
+                fmt.duration(alltime_total)
+            ),
+            false,
+        )
+        .footer(CreateEmbedFooter::new(format!(
+            "{}{} · Resets every Monday 00:00{}",
+            user_rank.map(|r| r + "\n").unwrap_or_default(),
+            swiss_timestamp(db, &fmt),
+            note
+        )));
+
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+    if let Some(rank) = dm_rank {
+        let embed = CreateEmbed::new().color(COLOR_GOLD).title("🏆 Your Ranking").description(rank);
+        if let Err(e) = msg.author.direct_message(&ctx.http, CreateMessage::new().embed(embed)).await {
+            eprintln!("[clock] Failed to DM ranking to {}: {e}", msg.author.name);
+        }
+    }
+}
+
+/// Sources selected by a `/clock stats` filter: one source, `auto` for everything not
+/// clocked by hand, or all of them when empty. `None` for an unknown filter.
+fn stats_sources(filter: &str) -> Option<Vec<&'static str>> {
+    let filter = filter.to_lowercase();
+    match filter.as_str() {
+        "" => Some(db::SOURCES.to_vec()),
+        "auto" => Some(db::SOURCES.iter().copied().filter(|s| *s != db::SOURCE_MANUAL).collect()),
+        _ => db::SOURCES.iter().find(|s| **s == filter).map(|s| vec![*s]),
+    }
+}
+
+async fn handle_stats(ctx: &Context, msg: &Message, db: &Arc<Db>, filter: &str) {
+    let fmt = DisplayFormat::load(db);
+    let Some(sources) = stats_sources(filter) else {
+        let _ = msg
+            .reply(&ctx.http, format!("Usage: `/clock stats [auto|{}]`", db::SOURCES.join("|")))
+            .await;
+        return;
+    };
+    let weekly = if filter.is_empty() {
+        db.activity_breakdown_weekly()
+    } else {
+        db.activity_breakdown_weekly_from(&sources)
+    };
+    let weekly = match weekly {
+        Ok(weekly) => weekly,
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let weekly = crate::anonymous::public_breakdown(db, weekly);
+    let week_label = db.clock().week().label();
+    let filter = filter.to_lowercase();
+
+    if weekly.is_empty() {
+        let embed = CreateEmbed::new()
+            .color(COLOR_GRAY)
+            .title("📊 No activity data this week")
+            .description(if filter.is_empty() {
+                "Clock in to start tracking.".to_string()
+            } else {
+                format!("Nothing from `{}` sessions yet.", filter)
+            });
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    let breakdown_text = format_activity_breakdown(&weekly, &fmt);
+
+    // Aggregate top activities across all users
+    let mut activity_totals: std::collections::HashMap<String, i64> =
+        std::collections::HashMap::new();
+    for e in &weekly {
+        *activity_totals.entry(e.activity.clone()).or_insert(0) += e.total_minutes;
+    }
+    let mut sorted: Vec<_> = activity_totals.into_iter().collect();
+    sorted.sort_by_key(|(_, mins)| std::cmp::Reverse(*mins));
+
+    let max_act = sorted.first().map(|(_, m)| *m).unwrap_or(1);
+    let mut top_acts = String::new();
+    for (act, mins) in sorted.iter().take(8) {
+        let bar = make_bar(*mins, max_act);
+        top_acts += &format!("`{}` {} — {}\n", bar, act, fmt.duration(*mins));
+    }
+
+    let title = if filter.is_empty() {
+        format!("📊 Activity Stats — {}", fmt.week(&week_label))
+    } else {
+        format!("📊 Activity Stats — {} · {}", fmt.week(&week_label), filter)
+    };
+    let embed = CreateEmbed::new()
+        .color(COLOR_PURPLE)
+        .title(title)
+        .description(crate::pace::server_pace(db, weekly.iter().map(|e| e.total_minutes).sum(), db.clock().now(), &fmt))
+        .field(
+            "🔥 Top Activities",
+            truncate_for_embed(&top_acts, EMBED_FIELD_LIMIT),
+            false,
+        )
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let Some(clients) = crate::errors::or_report(&ctx.http, msg, db.client_activities()).await else {
+        return;
+    };
+    let clients = client_totals(&clients, |a| a.week_minutes);
+    let embed = if clients.is_empty() || !filter.is_empty() {
+        embed
+    } else {
+        embed.field("🏢 Clients", truncate_for_embed(&format_clients(&clients, &fmt), EMBED_FIELD_LIMIT), false)
+    };
+    // Only worth showing once something besides clock-ins is tracked
+    let Some(sources) = crate::errors::or_report(&ctx.http, msg, db.source_totals_weekly()).await else {
+        return;
+    };
+    let embed = if filter.is_empty() && sources.iter().any(|(s, _)| s != db::SOURCE_MANUAL) {
+        embed.field("🛰️ Sources", format_sources(&sources, &fmt), false)
+    } else {
+        embed
+    };
+
+    // Small breakdowns fit into a field; larger ones continue in follow-up embeds
+    let messages = if breakdown_text.chars().count() <= EMBED_FIELD_LIMIT {
+        let embed = embed
+            .field("\u{200b}", "\u{200b}", false)
+            .field("👤 Per Person", &breakdown_text, false);
+        vec![CreateMessage::new().embed(embed)]
+    } else {
+        breakdown_messages(
+            embed,
+            "👤 Per Person",
+            &weekly,
+            &fmt,
+            &format!("stats-{}", week_label.replace('/', "-")),
+        )
+    };
+
+    for message in messages {
+        if let Err(e) = msg.channel_id.send_message(&ctx.http, message).await {
+            eprintln!("[clock] Failed to send stats: {e}");
+            break;
+        }
+    }
+}
+
+/// `manual 20h 0m · calendar-sync 3h 0m`
+fn format_sources(sources: &[(String, i64)], fmt: &DisplayFormat) -> String {
+    sources
+        .iter()
+        .map(|(source, minutes)| format!("{} {}", source, fmt.duration(*minutes)))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// This week's finished sessions as CSV, yours or (with the export permission) everyone's.
+async fn handle_export(ctx: &Context, msg: &Message, db: &Arc<Db>, all: bool) {
+    let user_id = account(db, msg.author.id);
+    let records = db.session_records((!all).then_some(user_id.as_str()));
+    let Some(records) = crate::errors::or_report(&ctx.http, msg, records).await else {
+        return;
+    };
+    if records.is_empty() {
+        let _ = msg.reply(&ctx.http, "No finished sessions this week.").await;
+        return;
+    }
+
+    let week_label = db.clock().week().label().replace('/', "-");
+    let filename = if all {
+        format!("sessions-{}.csv", week_label)
+    } else {
+        format!("sessions-{}-{}.csv", week_label, user_id)
+    };
+    let attachment = CreateAttachment::bytes(sessions_csv(&records).into_bytes(), filename);
+    let reply = CreateMessage::new()
+        .content(format!("{} sessions this week.", records.len()))
+        .reference_message(msg)
+        .add_file(attachment);
+    if let Err(e) = msg.channel_id.send_message(&ctx.http, reply).await {
+        eprintln!("[clock] Failed to send export: {e}");
+    }
+}
+
+/// One line per session with where it came from, so tracked time can be told apart
+/// from time clocked by hand.
+fn sessions_csv(records: &[db::SessionRecord]) -> String {
+    let mut out = String::from("user,activity,started,ended,minutes,seconds,source\n");
+    for r in records {
+        out += &format!(
+            "{},{},{},{},{},{},{}\n",
+            csv_field(&r.username),
+            csv_field(&r.activity),
+            r.started_at.format("%Y-%m-%d %H:%M"),
+            r.ended_at.format("%Y-%m-%d %H:%M"),
+            r.elapsed.minutes(),
+            r.elapsed.seconds(),
+            r.source
+        );
+    }
+    out
+}
+
+async fn handle_activity(ctx: &Context, msg: &Message, db: &Arc<Db>, name: &str) {
+    let fmt = DisplayFormat::load(db);
+    let activity = crate::normalize::normalize_activity(name);
+
+    let mut detail = match db.activity_detail(&activity, ACTIVITY_TREND_WEEKS) {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            let embed = CreateEmbed::new()
+                .color(COLOR_GRAY)
+                .title("⚠️ Activity Not Found")
+                .description(format!("No sessions found for **{}**", activity))
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+            let _ = msg
+                .channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await;
+            return;
+        }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+
+    let average = if detail.session_count > 0 {
+        fmt.duration(detail.total_minutes / detail.session_count)
+    } else {
+        "—".to_string()
+    };
+
+    let first_week = detail.week_labels.first().map(|w| fmt.week(w)).unwrap_or_else(|| "?".to_string());
+    let last_week = detail.week_labels.last().map(|w| fmt.week(w)).unwrap_or_else(|| "?".to_string());
+    let trend = format!(
+        "`{}`\n{} → {}",
+        make_sparkline(&detail.minutes_per_week),
+        first_week,
+        last_week
+    );
+
+    detail.contributors = crate::anonymous::public_board(db, detail.contributors);
+    let max_min = detail.contributors.first().map(|e| e.total_minutes).unwrap_or(1);
+    let mut contributors = String::new();
+    for e in detail.contributors.iter().take(10) {
+        contributors += &format!(
+            "`{}` {} — {}\n",
+            make_bar(e.total_minutes, max_min),
+            e.username,
+            fmt.duration(e.total_minutes)
+        );
+    }
+
+    let mut embed = CreateEmbed::new()
+        .color(COLOR_PURPLE)
+        .title(format!("🔎 {}", activity))
+        .field("Total", fmt.duration(detail.total_minutes), true)
+        .field("Sessions", detail.session_count.to_string(), true)
+        .field("Average", average, true)
+        .field(format!("📈 Last {} Weeks", ACTIVITY_TREND_WEEKS), trend, false)
+        .field("👤 Contributors", contributors, false)
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let Some(budget) = crate::errors::or_report(&ctx.http, msg, db.activity_budget(&activity)).await else {
+        return;
+    };
+    if let Some(budget) = budget {
+        embed = embed.field("💰 Budget", crate::budgets::budget_line(&budget, &fmt), false);
+    }
+    let Some(info) = crate::errors::or_report(&ctx.http, msg, db.activity_info(&activity)).await else {
+        return;
+    };
+    if let Some(info) = info {
+        embed = embed.description(info.description);
+        if let Some(owner) = info.owner_id {
+            embed = embed.field("🧭 Owner", format!("<@{}>", owner), true);
+        }
+    }
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+/// `<activity> "<description>"` or `<activity> off` of `/clock activity describe`:
+/// the normalized activity and the description, `None` for `off`.
+fn parse_describe(args: &str) -> Option<(String, Option<String>)> {
+    if let Some(name) = args.strip_suffix(" off").or_else(|| args.strip_suffix(" OFF")) {
+        let activity = crate::normalize::normalize_activity(name);
+        return (!activity.is_empty()).then_some((activity, None));
+    }
+    let (name, rest) = args.split_once('"')?;
+    let description = rest.strip_suffix('"')?.trim();
+    let activity = crate::normalize::normalize_activity(name);
+    (!activity.is_empty() && !description.is_empty()).then(|| (activity, Some(description.to_string())))
+}
+
+/// The first user mentioned in `text` (`<@123>` or `<@!123>`).
+fn first_mention(text: &str) -> Option<String> {
+    text.split("<@").skip(1).find_map(|rest| {
+        let (id, _) = rest.trim_start_matches('!').split_once('>')?;
+        (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then(|| id.to_string())
+    })
+}
+
+async fn handle_activity_describe(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    const USAGE: &str = "Usage: `/clock activity describe <activity> \"<description, owner @someone>\"` or `… <activity> off`";
+    let Some((activity, description)) = parse_describe(args) else {
+        let _ = msg.reply(&ctx.http, USAGE).await;
+        return;
+    };
+    let info = description.map(|description| db::ActivityInfo {
+        owner_id: first_mention(&description),
+        description,
+    });
+    let reply = match db.set_activity_info(&activity, info.as_ref()) {
+        Ok(_) => match &info {
+            Some(db::ActivityInfo { owner_id: Some(owner), .. }) => {
+                format!("**{}** is now described, owned by <@{}>. See `/clock activity {}`.", activity, owner, activity)
+            }
+            Some(_) => format!("**{}** is now described. See `/clock activity {}`.", activity, activity),
+            None => format!("**{}** no longer has a description.", activity),
+        },
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg.reply(&ctx.http, reply).await;
+}
+
+/// How `/clock in <raw>` would name the activity: each normalization step, the result,
+/// and the existing activity it joins or the similar ones it stays apart from.
+fn format_normalize_preview(raw: &str, totals: &[db::ActivityTotals], fmt: &DisplayFormat) -> String {
+    let name = crate::normalize::normalize_activity(raw);
+    let mut out = format!("**Input** `{}`\n", raw);
+    let steps = crate::normalize::normalization_steps(raw);
+    if steps.is_empty() {
+        out += "Already normalized.\n";
+    }
+    for (i, (step, text)) in steps.iter().enumerate() {
+        out += &format!("{}. {} → `{}`\n", i + 1, step, text);
+    }
+    out += &format!("**Result** `{}`\n\n", name);
+
+    let minutes = |t: &db::ActivityTotals| {
+        let mine = if t.user_minutes > 0 { fmt.duration(t.user_minutes) } else { "—".to_string() };
+        format!("you: {} · server: {}", mine, fmt.duration(t.total_minutes))
+    };
+    match totals.iter().find(|t| t.activity == name) {
+        Some(t) => out += &format!("🔗 Groups with the existing **{}** ({}).\n", t.activity, minutes(t)),
+        None => out += &format!("🆕 No activity is called **{}** yet; clocking in creates it.\n", name),
+    }
+    let similar: Vec<String> = totals
+        .iter()
+        .filter(|t| t.activity != name && crate::normalize::similar_names(&name, &t.activity))
+        .map(|t| format!("**{}** ({})", t.activity, minutes(t)))
+        .collect();
+    if !similar.is_empty() {
+        out += &format!(
+            "Kept apart from the similar {}; `/clock rename` merges your own.\n",
+            similar.join(", ")
+        );
     }
+    out
 }
 
-async fn handle_leaderboard(ctx: &Context, msg: &Message, db: &Arc<Db>) {
-    let weekly = db.leaderboard_weekly().unwrap_or_default();
-    let alltime = db.leaderboard_alltime().unwrap_or_default();
+async fn handle_normalize_preview(ctx: &Context, msg: &Message, db: &Arc<Db>, text: &str) {
+    let fmt = DisplayFormat::load(db);
+    if text.is_empty() {
+        let _ = msg.reply(&ctx.http, "Usage: `/clock normalize preview <activity>`").await;
+        return;
+    }
+    let totals = db.activity_totals(&account(db, msg.author.id));
+    let Some(totals) = crate::errors::or_report(&ctx.http, msg, totals).await else {
+        return;
+    };
+    let embed = CreateEmbed::new()
+        .color(COLOR_BLUE)
+        .title("🔤 Normalization Preview")
+        .description(truncate_for_embed(&format_normalize_preview(text, &totals, &fmt), EMBED_DESCRIPTION_LIMIT))
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
 
-    let week_label = db::swiss_week_label();
-    let weekly_text = format_board(&weekly);
-    let alltime_text = format_board(&alltime);
+async fn handle_search(ctx: &Context, msg: &Message, db: &Arc<Db>, term: &str) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
 
-    let weekly_total: i64 = weekly.iter().map(|e| e.total_minutes).sum();
-    let alltime_total: i64 = alltime.iter().map(|e| e.total_minutes).sum();
+    let Some(totals) = crate::errors::or_report(&ctx.http, msg, db.activity_totals(&user_id)).await else {
+        return;
+    };
+    let mut matches: Vec<(usize, db::ActivityTotals)> = totals
+        .into_iter()
+        .filter_map(|t| crate::normalize::search_score(term, &t.activity).map(|score| (score, t)))
+        .collect();
+    // Best match first; ties keep the server-wide ordering from the query
+    matches.sort_by_key(|(score, _)| *score);
+
+    if matches.is_empty() {
+        let embed = CreateEmbed::new()
+            .color(COLOR_GRAY)
+            .title("🔍 No Matches")
+            .description(format!(
+                "No activity resembles **{}** yet.\nIt will be created on `/clock in {}`.",
+                term,
+                crate::normalize::normalize_activity(term)
+            ))
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    let mut lines = String::new();
+    for (_, t) in matches.iter().take(SEARCH_MAX_RESULTS) {
+        let mine = if t.user_minutes > 0 {
+            fmt.duration(t.user_minutes)
+        } else {
+            "—".to_string()
+        };
+        lines += &format!(
+            "**{}** — you: {} · server: {}\n",
+            t.activity,
+            mine,
+            fmt.duration(t.total_minutes)
+        );
+    }
+    if matches.len() > SEARCH_MAX_RESULTS {
+        lines += &format!("*…and {} more*\n", matches.len() - SEARCH_MAX_RESULTS);
+    }
 
     let embed = CreateEmbed::new()
-        .color(COLOR_GOLD)
-        .title("🏆 Leaderboard")
-        .field(
-            format!("📅 This Week ({})", week_label),
-            format!(
-                "{}\n*Total: {}*",
-                weekly_text,
-                format_duration(weekly_total)
-            ),
-            false,
-        )
-        .field("\u{200b}", "\u{200b}", false)
-        .field(
-            "⏳ All Time",
-            format!(
-                "{}\n*Total: {}*",
-                alltime_text,
-                format_duration(alltime_total)
-            ),
-            false,
-        )
+        .color(COLOR_BLUE)
+        .title(format!("🔍 Activities matching \"{}\"", term))
+        .description(lines)
         .footer(CreateEmbedFooter::new(format!(
-            "{} · Resets every Monday 00:00",
-            swiss_timestamp()
+            "{} · /clock in <name> to reuse one",
+            swiss_timestamp(db, &fmt)
         )));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+async fn handle_ask(ctx: &Context, msg: &Message, db: &Arc<Db>, question: &str) {
+    let fmt = DisplayFormat::load(db);
+    let Some(rows) = crate::errors::or_report(&ctx.http, msg, db.week_activities()).await else {
+        return;
+    };
+    let mut activities: Vec<String> = rows.iter().map(|r| r.activity.clone()).collect();
+    let mut usernames: Vec<String> = rows.iter().map(|r| r.username.clone()).collect();
+    activities.sort();
+    activities.dedup();
+    usernames.sort();
+    usernames.dedup();
+    let Some(excluded) = crate::errors::or_report(&ctx.http, msg, db.non_competitive_activities()).await else {
+        return;
+    };
 
+    let today = db.clock().now().date();
+    let query = crate::ask::parse(question, today, &activities, &usernames);
+    // Anonymous rankings are numbered; answers naming someone, you included, go by DM
+    let anonymous = crate::anonymous::enabled(db) && msg.guild_id.is_some();
+    let subject = query.as_ref().map(|q| &q.subject);
+    if anonymous && matches!(subject, Some(crate::ask::Subject::User(_))) {
+        let _ = msg
+            .reply(&ctx.http, "Anonymous mode is on, so I don't answer questions about other people here.")
+            .await;
+        return;
+    }
+    let private = anonymous && matches!(subject, Some(crate::ask::Subject::Me));
+    let embed = match query {
+        Some(query) => CreateEmbed::new()
+            .color(COLOR_BLUE)
+            .title(format!("💬 {}", truncate_for_embed(question, 200)))
+            .description(crate::ask::answer(
+                &query,
+                &rows,
+                &excluded,
+                &account(db, msg.author.id),
+                today,
+                &fmt,
+                anonymous,
+            )),
+        None => CreateEmbed::new()
+            .color(COLOR_GRAY)
+            .title("💬 I didn't get that")
+            .description(
+                "Ask about time logged, for example:\n\
+                 • how much did I work on backend last month?\n\
+                 • who worked most in January?\n\
+                 • what did Alice work on this year?\n\
+                 • hours on docs over the last 4 weeks",
+            ),
+    }
+    .footer(CreateEmbedFooter::new(format!(
+        "{} · past weeks are counted whole",
+        swiss_timestamp(db, &fmt)
+    )));
+    if private {
+        if let Err(e) = msg.author.direct_message(&ctx.http, CreateMessage::new().embed(embed)).await {
+            eprintln!("[clock] Failed to DM answer to {}: {e}", msg.author.name);
+        }
+        return;
+    }
     let _ = msg
         .channel_id
         .send_message(&ctx.http, CreateMessage::new().embed(embed))
         .await;
 }
 
-async fn handle_stats(ctx: &Context, msg: &Message, db: &Arc<Db>) {
-    let weekly = db.activity_breakdown_weekly().unwrap_or_default();
-    let week_label = db::swiss_week_label();
+async fn handle_clusters(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    // Most-used first, so each cluster leads with the name worth keeping
+    let totals = db.activity_totals(&account(db, msg.author.id));
+    let Some(totals) = crate::errors::or_report(&ctx.http, msg, totals).await else {
+        return;
+    };
+    let names: Vec<String> = totals.iter().map(|t| t.activity.clone()).collect();
+    let clusters = crate::normalize::cluster_activities(&names);
 
-    if weekly.is_empty() {
+    if clusters.is_empty() {
         let embed = CreateEmbed::new()
             .color(COLOR_GRAY)
-            .title("📊 No activity data this week")
-            .description("Clock in to start tracking.");
+            .title("🧩 No Similar Activities")
+            .description(format!("None of the {} activity names look like duplicates.", names.len()))
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
         let _ = msg
             .channel_id
             .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -414,40 +3915,94 @@ async fn handle_stats(ctx: &Context, msg: &Message, db: &Arc<Db>) {
         return;
     }
 
-    let breakdown_text = format_activity_breakdown(&weekly);
-
-    // Aggregate top activities across all users
-    let mut activity_totals: std::collections::HashMap<String, i64> =
-        std::collections::HashMap::new();
-    for e in &weekly {
-        *activity_totals.entry(e.activity.clone()).or_insert(0) += e.total_minutes;
+    let minutes = |name: &str| totals.iter().find(|t| t.activity == name).map_or(0, |t| t.total_minutes);
+    let mut lines = String::new();
+    for cluster in &clusters {
+        let (keep, merge) = cluster.split_first().expect("clusters have at least two names");
+        let merge: Vec<String> = merge
+            .iter()
+            .map(|name| format!("`{}` ({})", name, fmt.duration(minutes(name))))
+            .collect();
+        lines += &format!("**{}** ({}) ← {}\n", keep, fmt.duration(minutes(keep)), merge.join(", "));
     }
-    let mut sorted: Vec<_> = activity_totals.into_iter().collect();
-    sorted.sort_by(|a, b| b.1.cmp(&a.1));
 
-    let max_act = sorted.first().map(|(_, m)| *m).unwrap_or(1);
-    let mut top_acts = String::new();
-    for (act, mins) in sorted.iter().take(8) {
-        let bar = make_bar(*mins, max_act);
-        top_acts += &format!("`{}` {} — {}\n", bar, act, format_duration(*mins));
+    let embed = CreateEmbed::new()
+        .color(COLOR_BLUE)
+        .title(format!("🧩 {} Groups of Similar Activities", clusters.len()))
+        .description(truncate_for_embed(&lines, EMBED_DESCRIPTION_LIMIT))
+        .footer(CreateEmbedFooter::new(format!(
+            "{} · bold: most-used name · /clock rename merges your own",
+            swiss_timestamp(db, &fmt)
+        )));
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
+/// One line per finding of `/clock admin check`: what's wrong, then how to fix it.
+fn format_findings(findings: &[db::Finding], fmt: &DisplayFormat) -> String {
+    let mut out = String::new();
+    for finding in findings {
+        out += &match finding {
+            db::Finding::BadSession { id, username, activity, started_at, minutes } if *minutes < 0 => format!(
+                "⏪ Session #{} of **{}** on {} ({}) ends before it starts.\n→ Correct its end time or delete it in the database.\n",
+                id, username, activity, fmt.timestamp(*started_at)
+            ),
+            db::Finding::BadSession { id, username, activity, started_at, minutes } => format!(
+                "⏱️ Session #{} of **{}** on {} ({}) lasted {}.\n→ If it was left running, shorten it to when they stopped.\n",
+                id, username, activity, fmt.timestamp(*started_at), fmt.duration(*minutes)
+            ),
+            db::Finding::StaleSession { username, activity, started_at } => format!(
+                "🕰️ **{}** has been clocked in on {} since {}.\n→ Their next `/clock in` offers to close it at their last message that day.\n",
+                username, activity, fmt.timestamp(*started_at)
+            ),
+            db::Finding::ArchiveMismatch { week_label, username, total, activities } => format!(
+                "🧮 {}: **{}** has {} archived, but their activities add up to {}.\n→ Set the weekly total to match; leaderboards use it, breakdowns the activities.\n",
+                fmt.week(week_label), username, fmt.duration(*total), fmt.duration(*activities)
+            ),
+            db::Finding::DuplicateArchive { week_label, username, activity, rows } => format!(
+                "📑 {}: **{}**{} is archived {} times.\n→ The week was probably archived twice; merge the rows into one.\n",
+                fmt.week(week_label),
+                username,
+                activity.as_ref().map(|a| format!(" on {}", a)).unwrap_or_default(),
+                rows
+            ),
+        };
     }
+    out
+}
 
-    let embed = CreateEmbed::new()
-        .color(COLOR_PURPLE)
-        .title(format!("📊 Activity Stats — {}", week_label))
-        .field("🔥 Top Activities", &top_acts, false)
-        .field("\u{200b}", "\u{200b}", false)
-        .field("👤 Per Person", &breakdown_text, false)
-        .footer(CreateEmbedFooter::new(swiss_timestamp()));
+async fn handle_admin_check(ctx: &Context, msg: &Message, db: &Arc<Db>) {
+    let fmt = DisplayFormat::load(db);
+    let findings = match db.integrity_findings() {
+        Ok(findings) => findings,
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
 
+    let embed = if findings.is_empty() {
+        CreateEmbed::new()
+            .color(COLOR_GREEN)
+            .title("✅ Data Looks Consistent")
+            .description("No implausible or forgotten sessions, and the archives add up.")
+    } else {
+        CreateEmbed::new()
+            .color(COLOR_ORANGE)
+            .title(format!("🩺 {} Problem(s) Found", findings.len()))
+            .description(truncate_for_embed(&format_findings(&findings, &fmt), EMBED_DESCRIPTION_LIMIT))
+    };
     let _ = msg
         .channel_id
-        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .send_message(&ctx.http, CreateMessage::new().embed(embed.footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)))))
         .await;
 }
 
 async fn handle_rename(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
-    let user_id = msg.author.id.to_string();
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
 
     // Split args on " > " or ">"
     let parts: Vec<&str> = if args.contains(" > ") {
@@ -464,7 +4019,7 @@ async fn handle_rename(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
             .color(COLOR_RED)
             .title("⚠️ Invalid Syntax")
             .description("Usage: `/clock rename <old activity> > <new activity>`")
-            .footer(CreateEmbedFooter::new(swiss_timestamp()));
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
         let _ = msg
             .channel_id
             .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -485,7 +4040,7 @@ async fn handle_rename(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
                 parts[0].trim(),
                 parts[1].trim()
             ))
-            .footer(CreateEmbedFooter::new(swiss_timestamp()));
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
         let _ = msg
             .channel_id
             .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -495,7 +4050,7 @@ async fn handle_rename(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
 
     // Call db.rename_activity
     match db.rename_activity(&user_id, &old_name, &new_name) {
-        Ok((sessions_updated, archive_rows_merged)) => {
+        Ok(Some((sessions_updated, archive_rows_merged))) => {
             let mut details = String::new();
             if sessions_updated > 0 {
                 details.push_str(&format!("✅ {} session(s) updated\n", sessions_updated));
@@ -512,27 +4067,124 @@ async fn handle_rename(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
                 .title("✏️ Activity Renamed")
                 .description(format!("**{}** → **{}**", old_name, new_name))
                 .field("Changes", details, false)
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
             let _ = msg
                 .channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
                 .await;
         }
-        Err(_) => {
+        Ok(None) => {
             let embed = CreateEmbed::new()
                 .color(COLOR_RED)
                 .title("⚠️ Activity Not Found")
                 .description(format!("No sessions found for **{}**", old_name))
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
             let _ = msg
                 .channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
                 .await;
         }
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+        }
     }
 }
 
+async fn handle_delete_activity(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let fmt = DisplayFormat::load(db);
+    let user_id = account(db, msg.author.id);
+
+    let (name, confirmed) = match args.strip_suffix(" confirm") {
+        Some(name) => (name.trim(), true),
+        None => (args, false),
+    };
+
+    if name.is_empty() {
+        let embed = CreateEmbed::new()
+            .color(COLOR_RED)
+            .title("⚠️ Invalid Syntax")
+            .description("Usage: `/clock delete-activity <activity>`")
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    let activity = crate::normalize::normalize_activity(name);
+
+    if !confirmed {
+        let embed = match db.activity_usage(&user_id, &activity) {
+            Ok((sessions, archive_rows, minutes)) if sessions > 0 || archive_rows > 0 => {
+                CreateEmbed::new()
+                    .color(COLOR_ORANGE)
+                    .title("🗑️ Delete Activity?")
+                    .description(format!(
+                        "This permanently removes **{}** from your history:\n\
+                         {} session(s), {} archived week(s), {} total.\n\n\
+                         Run `/clock delete-activity {} confirm` to proceed.",
+                        activity,
+                        sessions,
+                        archive_rows,
+                        fmt.duration(minutes),
+                        activity
+                    ))
+                    .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)))
+            }
+            _ => CreateEmbed::new()
+                .color(COLOR_RED)
+                .title("⚠️ Activity Not Found")
+                .description(format!("No sessions found for **{}**", activity))
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt))),
+        };
+        let _ = msg
+            .channel_id
+            .send_message(&ctx.http, CreateMessage::new().embed(embed))
+            .await;
+        return;
+    }
+
+    let embed = match db.delete_activity(&user_id, &activity) {
+        Ok(Some((sessions_deleted, archive_rows_deleted))) => CreateEmbed::new()
+            .color(COLOR_BLUE)
+            .title("🗑️ Activity Deleted")
+            .description(format!("**{}** was removed from your history", activity))
+            .field(
+                "Changes",
+                format!(
+                    "✅ {} session(s) deleted\n🗄️ {} archive row(s) deleted",
+                    sessions_deleted, archive_rows_deleted
+                ),
+                false,
+            )
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt))),
+        Err(e) if e.downcast_ref::<db::Refusal>() == Some(&db::Refusal::ActivityRunning) => CreateEmbed::new()
+            .color(COLOR_RED)
+            .title("⚠️ Still Clocked In")
+            .description(format!(
+                "You're currently working on **{}**. `/clock out` first.",
+                activity
+            ))
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt))),
+        Ok(None) => CreateEmbed::new()
+            .color(COLOR_RED)
+            .title("⚠️ Activity Not Found")
+            .description(format!("No sessions found for **{}**", activity))
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt))),
+        Err(e) => {
+            crate::errors::report(&ctx.http, msg, &e).await;
+            return;
+        }
+    };
+    let _ = msg
+        .channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await;
+}
+
 async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
+    let fmt = DisplayFormat::load(db);
     // Parse optional positional arguments: [weeks] [mode]
     let mut weeks: u32 = 12;
     let mut mode_str = "totals";
@@ -547,19 +4199,19 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
         }
     }
 
-    let mode = crate::chart::ChartMode::from_str(mode_str);
+    let mode = crate::chart::ChartMode::parse(mode_str);
 
     // Typing indicator while we render.
     let _ = msg.channel_id.broadcast_typing(&ctx.http).await;
 
     let data = match db.weekly_hours_for_chart(weeks) {
-        Ok(d) => d,
+        Ok(d) => crate::anonymous::public_chart(db, d),
         Err(e) => {
             let embed = CreateEmbed::new()
                 .color(COLOR_GRAY)
                 .title("📊 Chart Error")
                 .description(format!("{}", e))
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
             let _ = msg
                 .channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -576,7 +4228,7 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
                 "No time entries found in the last {} week(s).",
                 weeks
             ))
-            .footer(CreateEmbedFooter::new(swiss_timestamp()));
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
         let _ = msg
             .channel_id
             .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -589,7 +4241,7 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
             .color(COLOR_GRAY)
             .title("📊 Not Enough Data")
             .description("Need at least 2 weeks of data to draw a chart.")
-            .footer(CreateEmbedFooter::new(swiss_timestamp()));
+            .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
         let _ = msg
             .channel_id
             .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -604,7 +4256,7 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
                 .color(COLOR_RED)
                 .title("📊 Render Error")
                 .description(format!("Failed to generate chart: {}", e))
-                .footer(CreateEmbedFooter::new(swiss_timestamp()));
+                .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
             let _ = msg
                 .channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
@@ -614,8 +4266,8 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
     };
 
     // Build a summary of the top users for the embed description.
-    let first_week = data.week_labels.first().map(String::as_str).unwrap_or("?");
-    let last_week = data.week_labels.last().map(String::as_str).unwrap_or("?");
+    let first_week = data.week_labels.first().map(|w| fmt.week(w)).unwrap_or_else(|| "?".to_string());
+    let last_week = data.week_labels.last().map(|w| fmt.week(w)).unwrap_or_else(|| "?".to_string());
 
     let mut user_summary = String::new();
     for (i, user) in data.users.iter().enumerate() {
@@ -624,7 +4276,7 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
             "{} **{}** — {}\n",
             CHART_MEDALS[i],
             user.username,
-            format_duration(total_min)
+            fmt.duration(total_min)
         );
     }
 
@@ -636,7 +4288,7 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
             first_week, last_week, mode_str, user_summary
         ))
         .image("attachment://chart.png")
-        .footer(CreateEmbedFooter::new(swiss_timestamp()));
+        .footer(CreateEmbedFooter::new(swiss_timestamp(db, &fmt)));
 
     let attachment = CreateAttachment::bytes(png_bytes, "chart.png");
     let _ = msg
@@ -647,3 +4299,483 @@ async fn handle_chart(ctx: &Context, msg: &Message, db: &Arc<Db>, args: &str) {
         )
         .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Elapsed;
+    use crate::fixtures::{test_db, test_db_at};
+
+    fn entry(username: &str, activity: &str, minutes: i64) -> ActivityEntry {
+        ActivityEntry {
+            username: username.to_string(),
+            activity: activity.to_string(),
+            total_minutes: minutes,
+            session_count: 1,
+        }
+    }
+
+    #[test]
+    fn test_format_share_shifts() {
+        let entry = |activity: &str, minutes| ActivityEntry {
+            username: "Alice".to_string(),
+            activity: activity.to_string(),
+            total_minutes: minutes,
+            session_count: 1,
+        };
+        let mut summary = WeeklySummary {
+            total_minutes: 1000,
+            total_sessions: 3,
+            unique_workers: 1,
+            mvp: None,
+            top_activity: None,
+            longest_session: None,
+            most_appreciated: None,
+            breakdown: vec![entry("backend", 450), entry("meetings", 200), entry("docs", 350)],
+            holidays: Vec::new(),
+            daily_minutes: [0; 7],
+            mvp_daily_minutes: [0; 7],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        };
+        assert_eq!(format_share_shifts(&summary), None);
+
+        summary.previous_activities = vec![("backend".to_string(), 370), ("meetings".to_string(), 250), ("ops".to_string(), 380)];
+        assert_eq!(
+            format_share_shifts(&summary).unwrap(),
+            "backend 45% ▲8pp · docs 35% ▲35pp · meetings 20% ▼5pp\n"
+        );
+    }
+
+    #[test]
+    fn test_format_burnup() {
+        let summary = WeeklySummary {
+            total_minutes: 600,
+            total_sessions: 4,
+            unique_workers: 2,
+            mvp: Some(("Alice".to_string(), 360)),
+            top_activity: None,
+            longest_session: None,
+            most_appreciated: None,
+            breakdown: Vec::new(),
+            holidays: Vec::new(),
+            daily_minutes: [120, 120, 0, 240, 120, 0, 0],
+            mvp_daily_minutes: [0, 120, 0, 240, 0, 0, 0],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        };
+        assert_eq!(cumulative(&summary.daily_minutes), [120, 240, 240, 480, 600, 600, 600]);
+        assert_eq!(
+            format_burnup(&summary, &DisplayFormat::default()),
+            "`▂▄▄▇███` Everyone — 10h 0m\n`▁▃▃████` Alice — 6h 0m\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_describe() {
+        assert_eq!(
+            parse_describe("Platform \"The provisioning engine, owner <@!42>\""),
+            Some(("platform".to_string(), Some("The provisioning engine, owner <@!42>".to_string())))
+        );
+        assert_eq!(parse_describe("Platform off"), Some(("platform".to_string(), None)));
+        assert_eq!(parse_describe("platform The engine"), None);
+        assert_eq!(parse_describe("platform \" \""), None);
+        assert_eq!(parse_describe("\"no activity\""), None);
+
+        assert_eq!(first_mention("owner <@!42>, backup <@7>").as_deref(), Some("42"));
+        assert_eq!(first_mention("the <@&99> role, then <@7>").as_deref(), Some("7"));
+        assert_eq!(first_mention("nobody"), None);
+    }
+
+    #[test]
+    fn test_format_normalize_preview() {
+        let totals = |activity: &str, user_minutes, total_minutes| db::ActivityTotals {
+            activity: activity.to_string(),
+            user_minutes,
+            total_minutes,
+        };
+        let existing = vec![totals("work-school", 60, 120), totals("work-schools", 0, 30)];
+        let fmt = DisplayFormat::default();
+
+        let text = format_normalize_preview("WorkSchool", &existing, &fmt);
+        assert!(text.contains("1. split camelCase → `Work-School`\n2. lowercase → `work-school`\n"));
+        assert!(text.contains("Groups with the existing **work-school** (you: 1h 0m · server: 2h 0m)"));
+        assert!(text.contains("similar **work-schools** (you: — · server: 30m)"));
+
+        let text = format_normalize_preview("ops", &existing, &fmt);
+        assert!(text.contains("Already normalized."));
+        assert!(text.contains("No activity is called **ops** yet"));
+        assert!(!text.contains("similar"));
+    }
+
+    #[test]
+    fn test_format_findings() {
+        let at = NaiveDate::from_ymd_opt(2026, 2, 11).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let findings = vec![
+            db::Finding::BadSession { id: 7, username: "Bob".to_string(), activity: "docs".to_string(), started_at: at, minutes: -30 },
+            db::Finding::BadSession { id: 8, username: "Bob".to_string(), activity: "ops".to_string(), started_at: at, minutes: 1560 },
+            db::Finding::DuplicateArchive {
+                week_label: "KW05/2026".to_string(),
+                username: "Alice".to_string(),
+                activity: Some("backend".to_string()),
+                rows: 2,
+            },
+        ];
+        let text = format_findings(&findings, &DisplayFormat::default());
+        assert_eq!(text.lines().count(), 6);
+        assert!(text.contains("Session #7 of **Bob** on docs (11.02.2026 09:00) ends before it starts."));
+        assert!(text.contains("Session #8 of **Bob** on ops (11.02.2026 09:00) lasted 26h 0m."));
+        assert!(text.contains("KW05/2026: **Alice** on backend is archived 2 times."));
+    }
+
+    #[test]
+    fn test_report_sections() {
+        let summary = WeeklySummary {
+            total_minutes: 600,
+            total_sessions: 4,
+            unique_workers: 2,
+            mvp: Some(("Alice".to_string(), 360)),
+            top_activity: None,
+            longest_session: None,
+            most_appreciated: None,
+            breakdown: vec![entry("Alice", "work", 360), entry("Bob", "work", 240)],
+            holidays: Vec::new(),
+            daily_minutes: [120, 120, 0, 240, 120, 0, 0],
+            mvp_daily_minutes: [0, 120, 0, 240, 0, 0, 0],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        };
+        let fmt = DisplayFormat::default();
+        assert!(ReportSection::Awards.render(&summary, &fmt).unwrap().contains("**MVP** — Alice with 6h 0m"));
+        // Nothing to show without clients or a previous week
+        assert_eq!(ReportSection::Clients.render(&summary, &fmt), None);
+        assert_eq!(ReportSection::Trends.render(&summary, &fmt), None);
+        assert_eq!(ReportSection::parse(" Burnup"), Some(ReportSection::Burnup));
+        assert_eq!(ReportSection::parse("roles"), None);
+
+        let db = test_db();
+        let (_, appendix) = build_weekly_summary_thread(&db, &summary, "KW07/2026", &fmt, REPORT_SECTIONS);
+        assert_eq!(appendix.len(), 1);
+        let (_, appendix) = build_weekly_summary_thread(&db, &summary, "KW07/2026", &fmt, &[ReportSection::Burnup]);
+        assert!(appendix.is_empty());
+
+        assert_eq!(report_sections(&db), REPORT_SECTIONS);
+        crate::config::set(&db, crate::config::setting(crate::config::REPORT_SECTIONS).unwrap(), "trends, awards").unwrap();
+        assert_eq!(report_sections(&db), vec![ReportSection::Trends, ReportSection::Awards]);
+    }
+
+    #[test]
+    fn test_split_for_embed_respects_limit_and_lines() {
+        let text = "aaaa\nbbbb\ncccc\n";
+        assert_eq!(split_for_embed(text, 100), vec![text.to_string()]);
+        assert_eq!(split_for_embed(text, 10), vec!["aaaa\nbbbb\n", "cccc\n"]);
+
+        // A single over-long line is hard-split
+        let chunks = split_for_embed(&"x".repeat(25), 10);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 10));
+    }
+
+    #[test]
+    fn test_split_for_embed_counts_chars_not_bytes() {
+        let text = "👤 ü\n".repeat(100);
+        for chunk in split_for_embed(&text, 50) {
+            assert!(chunk.chars().count() <= 50);
+        }
+    }
+
+    #[test]
+    fn test_truncate_for_embed() {
+        assert_eq!(truncate_for_embed("short", 10), "short");
+        let cut = truncate_for_embed(&"y".repeat(20), 10);
+        assert_eq!(cut.chars().count(), 10);
+        assert!(cut.ends_with('…'));
+    }
+
+    #[test]
+    fn test_client_totals() {
+        let activity = |client: &str, activity: &str, week_minutes, total_minutes| db::ClientActivity {
+            client: client.into(),
+            activity: activity.into(),
+            week_minutes,
+            total_minutes,
+        };
+        let activities = vec![
+            activity("Acme", "platform", 60, 600),
+            activity("Acme", "website", 30, 30),
+            activity("Globex", "audit", 120, 120),
+            activity("Initech", "tps", 0, 90),
+        ];
+        assert_eq!(
+            client_totals(&activities, |a| a.week_minutes),
+            vec![("Globex".to_string(), 120), ("Acme".to_string(), 90)]
+        );
+        assert_eq!(client_totals(&activities, |a| a.total_minutes)[0], ("Acme".to_string(), 630));
+    }
+
+    #[test]
+    fn test_breakdown_csv_escapes() {
+        let csv = breakdown_csv(&[entry("Alice", "work, mostly", 90), entry("Bob \"B\"", "x", 5)]);
+        assert_eq!(
+            csv,
+            "user,activity,minutes,sessions\nAlice,\"work, mostly\",90,1\n\"Bob \"\"B\"\"\",x,5,1\n"
+        );
+    }
+
+    #[test]
+    fn test_stats_sources_and_session_csv() {
+        assert_eq!(stats_sources("").unwrap().len(), db::SOURCES.len());
+        assert!(!stats_sources("Auto").unwrap().contains(&db::SOURCE_MANUAL));
+        assert_eq!(stats_sources("api"), Some(vec![db::SOURCE_API]));
+        assert_eq!(stats_sources("voice"), None);
+
+        let at = |h| NaiveDate::from_ymd_opt(2026, 3, 16).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        let record = db::SessionRecord {
+            user_id: "u1".into(),
+            username: "Alice".into(),
+            activity: "meetings".into(),
+            started_at: at(9),
+            ended_at: at(10),
+            elapsed: Elapsed::from_seconds(3600),
+            source: db::SOURCE_CALENDAR.into(),
+        };
+        assert_eq!(
+            sessions_csv(&[record]),
+            "user,activity,started,ended,minutes,seconds,source\nAlice,meetings,2026-03-16 09:00,2026-03-16 10:00,60,3600,calendar-sync\n"
+        );
+    }
+
+    #[test]
+    fn test_breakdown_messages_fallback_to_csv() {
+        let small = vec![entry("Alice", "work", 60)];
+        assert_eq!(breakdown_messages(CreateEmbed::new(), "t", &small, &DisplayFormat::default(), "f").len(), 1);
+
+        let huge: Vec<ActivityEntry> = (0..2000)
+            .map(|i| entry(&format!("user{}", i), &format!("activity-{}", i), 60))
+            .collect();
+        // Too big for a few continuation messages → one message with a CSV attachment
+        assert_eq!(breakdown_messages(CreateEmbed::new(), "t", &huge, &DisplayFormat::default(), "f").len(), 1);
+
+        let medium: Vec<ActivityEntry> = (0..150)
+            .map(|i| entry(&format!("user{}", i), "activity", 60))
+            .collect();
+        assert!(breakdown_messages(CreateEmbed::new(), "t", &medium, &DisplayFormat::default(), "f").len() > 1);
+    }
+
+    #[test]
+    fn test_format_appendix() {
+        let mut entries = vec![entry("Alice", "work", 90), entry("Bob", "study", 30), entry("Alice", "meetings", 30)];
+        entries[0].session_count = 3;
+        let text = format_appendix(&entries, &DisplayFormat::default());
+        let alice = text.find("**Alice** — 2h 0m · 4 sessions").unwrap();
+        let bob = text.find("**Bob** — 30m · 1 session\n").unwrap();
+        assert!(alice < bob);
+        assert!(text[alice..bob].contains("work — 1h 30m · 3 sessions"));
+        assert!(text[alice..bob].contains("meetings — 30m · 1 session"));
+
+        // Without a first embed the appendix starts right at the breakdown
+        let medium: Vec<ActivityEntry> = (0..80).map(|i| entry(&format!("user{}", i), "activity", 60)).collect();
+        let text = format_appendix(&medium, &DisplayFormat::default());
+        assert!(paged_messages(None, "t", &text, &medium, "f").len() > 1);
+    }
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 2, 11).unwrap() // a Wednesday
+    }
+
+    fn session(activity: &str, from: (u32, u32), to: (u32, u32)) -> TimelineSession {
+        TimelineSession {
+            activity: activity.to_string(),
+            started_at: day().and_hms_opt(from.0, from.1, 0).unwrap(),
+            ended_at: day().and_hms_opt(to.0, to.1, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_timeline_day() {
+        let today = day();
+        assert_eq!(parse_timeline_day("", today), Some(today));
+        assert_eq!(parse_timeline_day("Today", today), Some(today));
+        assert_eq!(parse_timeline_day("yesterday", today), NaiveDate::from_ymd_opt(2026, 2, 10));
+        assert_eq!(parse_timeline_day("monday", today), NaiveDate::from_ymd_opt(2026, 2, 9));
+        assert_eq!(parse_timeline_day("wed", today), Some(today));
+        assert_eq!(parse_timeline_day("thursday", today), NaiveDate::from_ymd_opt(2026, 2, 5));
+        assert_eq!(parse_timeline_day("2026-01-31", today), NaiveDate::from_ymd_opt(2026, 1, 31));
+        assert_eq!(parse_timeline_day("31.01.2026", today), NaiveDate::from_ymd_opt(2026, 1, 31));
+        assert_eq!(parse_timeline_day("someday", today), None);
+    }
+
+    #[test]
+    fn test_timeline_slots_majority_activity() {
+        let sessions = vec![
+            session("backend", (9, 0), (10, 10)),
+            session("meetings", (10, 10), (10, 45)),
+        ];
+        let slots = timeline_slots(&sessions, day());
+        assert_eq!(slots.len(), 48);
+        assert_eq!(slots[17], None); // 08:30
+        assert_eq!(slots[18].as_deref(), Some("backend")); // 09:00
+        assert_eq!(slots[19].as_deref(), Some("backend")); // 09:30
+        assert_eq!(slots[20].as_deref(), Some("meetings")); // 10:00: 10 vs 20 minutes
+        assert_eq!(slots[21].as_deref(), Some("meetings")); // 10:30
+        assert_eq!(slots[22], None);
+    }
+
+    #[test]
+    fn test_timeline_slots_clip_to_day() {
+        let overnight = TimelineSession {
+            activity: "ops".to_string(),
+            started_at: day().and_hms_opt(23, 0, 0).unwrap(),
+            ended_at: day().succ_opt().unwrap().and_hms_opt(2, 0, 0).unwrap(),
+        };
+        let slots = timeline_slots(&[overnight], day());
+        assert_eq!(slots.iter().filter(|s| s.is_some()).count(), 2);
+        assert_eq!(format_timeline(&slots, day(), &DisplayFormat::default()).unwrap(), "`23:00–24:00` ██ **ops**\n");
+    }
+
+    #[test]
+    fn test_format_timeline_runs() {
+        let sessions = vec![
+            session("backend", (9, 0), (10, 0)),
+            session("frontend", (11, 0), (11, 30)),
+        ];
+        let timeline = format_timeline(&timeline_slots(&sessions, day()), day(), &DisplayFormat::default()).unwrap();
+        assert_eq!(
+            timeline,
+            "`09:00–10:00` ██ **backend**\n`10:00–11:00` ·· *break*\n`11:00–11:30` █ **frontend**\n"
+        );
+        assert_eq!(format_timeline(&timeline_slots(&[], day()), day(), &DisplayFormat::default()), None);
+
+        let twelve_hour = DisplayFormat { twelve_hour: true, ..Default::default() };
+        let timeline = format_timeline(&timeline_slots(&sessions, day()), day(), &twelve_hour).unwrap();
+        assert!(timeline.starts_with("`9:00 AM–10:00 AM` ██ **backend**\n"));
+    }
+
+    #[test]
+    fn test_stale_end() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap();
+        let at = |h, m| day.and_hms_opt(h, m, 0).unwrap();
+        let six = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+        // Last message that day wins
+        assert_eq!(stale_end(at(9, 0), Some(at(19, 42)), six), at(19, 42));
+        // A message before the clock-in doesn't count
+        assert_eq!(stale_end(at(9, 0), Some(at(8, 30)), six), at(18, 0));
+        assert_eq!(stale_end(at(9, 0), None, six), at(18, 0));
+        // Started after the end of the working day: close at midnight
+        assert_eq!(stale_end(at(20, 0), None, six), at(0, 0) + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn test_parse_weight_and_score_board() {
+        assert_eq!(parse_weight("0.5"), Some(0.5));
+        assert_eq!(parse_weight("×1.5"), Some(1.5));
+        assert_eq!(parse_weight("x2"), Some(2.0));
+        assert_eq!(parse_weight("6"), None);
+        assert_eq!(parse_weight("-1"), None);
+        assert_eq!(parse_weight("lots"), None);
+
+        let board = format_score_board(&[LeaderboardEntry { username: "Alice".to_string(), total_minutes: 90, rank: 1, live: false }]);
+        assert!(board.ends_with("1.5 pts\n"));
+    }
+
+    #[test]
+    fn test_format_ranking_and_user_rank() {
+        let fmt = DisplayFormat::default();
+        let entry = |name: &str, minutes, rank| LeaderboardEntry { username: name.to_string(), total_minutes: minutes, rank, live: false };
+        let mut board = vec![entry("Alice", 120, 1), entry("Bob", 120, 1), entry("Carol", 60, 2), entry("Dan", 5, 4)];
+        board[2].live = true;
+        let board = format_board(&board, &fmt);
+        let places: Vec<&str> = board.lines().map(|l| l.split(' ').next().unwrap()).collect();
+        assert_eq!(places, vec!["🥇", "🥇", "🥈", "4."]);
+        assert!(board.lines().nth(2).unwrap().ends_with("1h 0m 🔴 live"));
+        assert_eq!(board.matches("live").count(), 1);
+
+        let rank = |rank, behind| Some(UserRank { rank, total_minutes: 60, behind });
+        assert_eq!(
+            format_user_rank(rank(17, Some(80)), rank(1, None), &fmt).as_deref(),
+            Some("You: #17 this week (1h 20m behind #16) · #1 all time")
+        );
+        assert_eq!(format_user_rank(None, rank(3, Some(5)), &fmt).as_deref(), Some("You: #3 all time (5m behind #2)"));
+        assert_eq!(format_user_rank(None, None, &fmt), None);
+    }
+
+    #[test]
+    fn test_swiss_timestamp_follows_db_clock() {
+        let at = NaiveDate::from_ymd_opt(2026, 2, 16).unwrap().and_hms_opt(21, 5, 0).unwrap();
+        let (db, _clock) = test_db_at(at);
+        assert_eq!(swiss_timestamp(&db, &DisplayFormat::default()), "16.02.2026 21:05");
+    }
+
+    #[test]
+    fn test_format_fair_board() {
+        let fmt = DisplayFormat::default();
+        let rows = vec![
+            ("Alice".to_string(), 600, 2400),
+            ("Bob".to_string(), 900, 1200),
+            ("Carol".to_string(), 300, 1200),
+            ("Dave".to_string(), 60, 1200),
+        ];
+        let board = format_fair_board(&rows, &fmt);
+        let lines: Vec<&str> = board.lines().collect();
+        assert!(lines[0].starts_with("🥇 `Bob  ") && lines[0].ends_with("75% of 20h 0m"));
+        assert!(lines[1].starts_with("🥈 `Alice") && lines[1].ends_with("25% of 40h 0m"));
+        // Same share as Alice, so the same medal
+        assert!(lines[2].starts_with("🥈 `Carol") && lines[2].ends_with("25% of 20h 0m"));
+        assert!(lines[3].starts_with("🥉 `Dave "));
+    }
+
+    #[test]
+    fn test_parse_hours() {
+        assert_eq!(parse_hours("4"), Some(240));
+        assert_eq!(parse_hours("4.5"), Some(270));
+        assert_eq!(parse_hours("4h"), Some(240));
+        assert_eq!(parse_hours("4h30m"), Some(270));
+        assert_eq!(parse_hours("4H 30"), Some(270));
+        assert_eq!(parse_hours("0h45m"), Some(45));
+        assert_eq!(parse_hours("24"), Some(1440));
+        assert_eq!(parse_hours("25"), None);
+        assert_eq!(parse_hours("0"), None);
+        assert_eq!(parse_hours("-2"), None);
+        assert_eq!(parse_hours("4h75m"), None);
+        assert_eq!(parse_hours("lots"), None);
+        assert_eq!(parse_hours("NaN"), None);
+    }
+
+    /// The first command of a help line with optional parts kept and placeholders filled in.
+    fn help_example(line: &str) -> Option<String> {
+        let command = line.split('`').nth(1)?.strip_prefix("/clock")?.trim();
+        let mut example = String::new();
+        let mut in_placeholder = false;
+        for c in command.chars().filter(|c| !matches!(c, '[' | ']')) {
+            match c {
+                '<' => in_placeholder = true,
+                '>' if in_placeholder => {
+                    in_placeholder = false;
+                    example.push('x');
+                }
+                _ if !in_placeholder => example.push(c),
+                _ => {}
+            }
+        }
+        Some(example)
+    }
+
+    #[test]
+    fn test_admin_commands_need_capability() {
+        let marked = |line: &str| ["(admins)", "(admins edit)", "(export permission)"].iter().any(|m| line.ends_with(m));
+        for line in HELP.lines() {
+            let Some(example) = help_example(line) else { continue };
+            assert_eq!(required_capability(&example).is_some(), marked(line), "{example}");
+        }
+        assert_eq!(help_example("`/clock github map <repo> > <activity>` · `unmap <repo>`").unwrap(), "github map x > x");
+
+        for public in ["weight", "non-competitive", "project budget", "menu", "config", "config day-end", "event list", "export", "client Acme", "holidays"] {
+            assert_eq!(required_capability(public), None, "{public}");
+        }
+        assert_eq!(required_capability("invoice Acme 2026-03").unwrap().0, Capability::Export);
+        assert_eq!(required_capability("config perms admin add @Leads"), None);
+    }
+}
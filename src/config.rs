@@ -0,0 +1,505 @@
+use crate::commands::{parse_hours, ReportSection};
+use crate::db::Db;
+use crate::display::DisplayFormat;
+use chrono::NaiveTime;
+use serenity::all::{Message, RoleId, UserId};
+use std::env;
+
+/// A server-wide setting admins can change with `/clock config <key> <value>`.
+/// Values are stored normalized in the metadata table under `config.<key>`.
+pub struct Setting {
+    pub key: &'static str,
+    pub description: &'static str,
+    /// Example value shown in the usage hint.
+    pub example: &'static str,
+    /// Normalize user input for storage, `None` if invalid.
+    parse: fn(&str) -> Option<String>,
+    /// Render a stored value for display.
+    show: fn(&str, &DisplayFormat) -> String,
+}
+
+pub const DAILY_LIMIT: &str = "daily-limit";
+pub const MAX_STREAK: &str = "max-streak";
+pub const GUARDRAILS: &str = "guardrails";
+pub const QUIET_HOURS: &str = "quiet-hours";
+pub const PDF_REPORT: &str = "pdf-report";
+pub const SUMMARY_THREAD: &str = "summary-thread";
+pub const REPORT_SECTIONS: &str = "report-sections";
+pub const DURATION_FORMAT: &str = "duration-format";
+pub const TIME_FORMAT: &str = "time-format";
+pub const WEEK_FORMAT: &str = "week-format";
+pub const MERGE_GAP: &str = "merge-gap";
+pub const DAY_END: &str = "day-end";
+pub const KUDOS_EMOJI: &str = "kudos-emoji";
+pub const CURRENCY: &str = "currency";
+pub const LIVE_TOTALS: &str = "live-totals";
+pub const INACTIVE_WEEKS: &str = "inactive-weeks";
+pub const MIDWEEK_POST: &str = "midweek-post";
+pub const ANONYMOUS: &str = "anonymous";
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: DAILY_LIMIT,
+        description: "Hours per day before the guardrails kick in",
+        example: "10",
+        parse: parse_minutes,
+        show: show_minutes,
+    },
+    Setting {
+        key: MAX_STREAK,
+        description: "Consecutive days worked before the guardrails kick in",
+        example: "6",
+        parse: parse_days,
+        show: show_days,
+    },
+    Setting {
+        key: GUARDRAILS,
+        description: "`warn` privately or `refuse` clock-in when a limit is hit",
+        example: "warn",
+        parse: parse_guardrail_mode,
+        show: show_plain,
+    },
+    Setting {
+        key: QUIET_HOURS,
+        description: "Swiss time window with no reminder DMs or non-essential posts",
+        example: "22-07",
+        parse: parse_quiet_hours,
+        show: show_plain,
+    },
+    Setting {
+        key: PDF_REPORT,
+        description: "`on` attaches a PDF of the weekly report to the Monday post",
+        example: "on",
+        parse: parse_on,
+        show: show_plain,
+    },
+    Setting {
+        key: SUMMARY_THREAD,
+        description: "`on` posts the weekly report alone and everyone's details in a thread under it",
+        example: "on",
+        parse: parse_on,
+        show: show_plain,
+    },
+    Setting {
+        key: REPORT_SECTIONS,
+        description: "Sections of the weekly report and their order: `awards`, `clients`, `burnup`, `trends`, `breakdown`",
+        example: "awards,breakdown",
+        parse: parse_sections,
+        show: show_plain,
+    },
+    Setting {
+        key: DURATION_FORMAT,
+        description: "`decimal` shows durations as `7.5h` instead of `7h 30m`",
+        example: "decimal",
+        parse: |v| parse_choice(v, &["hm", "decimal"]),
+        show: show_plain,
+    },
+    Setting {
+        key: TIME_FORMAT,
+        description: "`12h` shows times as `9:30 PM` instead of `21:30`",
+        example: "12h",
+        parse: |v| parse_choice(v, &["24h", "12h"]),
+        show: show_plain,
+    },
+    Setting {
+        key: WEEK_FORMAT,
+        description: "`week` shows weeks as `Week 7, 2026` instead of `KW07/2026`",
+        example: "week",
+        parse: |v| parse_choice(v, &["kw", "week"]),
+        show: show_plain,
+    },
+    Setting {
+        key: MERGE_GAP,
+        description: "Offer to merge a clock-in this many minutes after the last session on the same activity",
+        example: "5",
+        parse: parse_gap,
+        show: show_gap,
+    },
+    Setting {
+        key: DAY_END,
+        description: "Swiss time suggested for closing a session left running overnight (default 18:00)",
+        example: "18:00",
+        parse: parse_time_of_day,
+        show: show_time_of_day,
+    },
+    Setting {
+        key: KUDOS_EMOJI,
+        description: "Reaction on a clock-out post that gives its owner kudos for the weekly award",
+        example: "👏",
+        parse: parse_emoji,
+        show: show_plain,
+    },
+    Setting {
+        key: CURRENCY,
+        description: "Currency of client rates on invoices (default CHF)",
+        example: "EUR",
+        parse: parse_currency,
+        show: show_plain,
+    },
+    Setting {
+        key: LIVE_TOTALS,
+        description: "Running sessions count on the weekly leaderboard (default `weekly`), also all time (`all`) or not at all (`none`)",
+        example: "all",
+        parse: |v| parse_choice(v, &["weekly", "all", "none"]),
+        show: show_plain,
+    },
+    Setting {
+        key: MIDWEEK_POST,
+        description: "Weekday and Swiss time to post the week's standings so far to the summary channel",
+        example: "wed 12:00",
+        parse: parse_midweek,
+        show: show_plain,
+    },
+    Setting {
+        key: INACTIVE_WEEKS,
+        description: "Weeks without any time after which someone who logged time before gets one friendly DM",
+        example: "4",
+        parse: parse_weeks,
+        show: show_weeks,
+    },
+    Setting {
+        key: ANONYMOUS,
+        description: "`on` shows people as `Member #1`, `Member #2`, … in summaries and leaderboards; your own stats come by DM",
+        example: "on",
+        parse: parse_on,
+        show: show_plain,
+    },
+];
+
+fn parse_minutes(value: &str) -> Option<String> {
+    parse_hours(value).map(|m| m.to_string())
+}
+
+fn show_minutes(value: &str, fmt: &DisplayFormat) -> String {
+    value.parse().map(|m| fmt.duration(m)).unwrap_or_else(|_| value.to_string())
+}
+
+fn parse_days(value: &str) -> Option<String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|d| (1..=60).contains(d))
+        .map(|d| d.to_string())
+}
+
+fn show_days(value: &str, _: &DisplayFormat) -> String {
+    format!("{} days", value)
+}
+
+fn parse_weeks(value: &str) -> Option<String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|w| (1..=52).contains(w))
+        .map(|w| w.to_string())
+}
+
+fn show_weeks(value: &str, _: &DisplayFormat) -> String {
+    format!("{} weeks", value)
+}
+
+fn parse_gap(value: &str) -> Option<String> {
+    value
+        .trim_end_matches('m')
+        .parse::<u32>()
+        .ok()
+        .filter(|m| (1..=60).contains(m))
+        .map(|m| m.to_string())
+}
+
+fn show_gap(value: &str, _: &DisplayFormat) -> String {
+    format!("{} min", value)
+}
+
+fn parse_time_of_day(value: &str) -> Option<String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .ok()
+        .or_else(|| value.trim().parse::<u32>().ok().and_then(|h| NaiveTime::from_hms_opt(h, 0, 0)))
+        .map(|t| t.format("%H:%M").to_string())
+}
+
+fn show_time_of_day(value: &str, fmt: &DisplayFormat) -> String {
+    NaiveTime::parse_from_str(value, "%H:%M").map(|t| fmt.time(t)).unwrap_or_else(|_| value.to_string())
+}
+
+fn parse_emoji(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty() && !value.contains(char::is_whitespace)).then(|| value.to_string())
+}
+
+fn parse_currency(value: &str) -> Option<String> {
+    let value = value.trim().to_uppercase();
+    (value.len() == 3 && value.chars().all(|c| c.is_ascii_alphabetic())).then_some(value)
+}
+
+fn parse_guardrail_mode(value: &str) -> Option<String> {
+    let value = value.to_lowercase();
+    matches!(value.as_str(), "warn" | "refuse").then_some(value)
+}
+
+fn parse_quiet_hours(value: &str) -> Option<String> {
+    crate::quiet::QuietHours::parse(value).map(|q| q.to_string())
+}
+
+fn parse_sections(value: &str) -> Option<String> {
+    let mut keys: Vec<&str> = Vec::new();
+    for part in value.split(',').filter(|p| !p.trim().is_empty()) {
+        let key = ReportSection::parse(part)?.key();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    (!keys.is_empty()).then(|| keys.join(","))
+}
+
+fn parse_midweek(value: &str) -> Option<String> {
+    crate::midweek::Schedule::parse(value).map(|s| s.to_string())
+}
+
+fn parse_choice(value: &str, choices: &[&str]) -> Option<String> {
+    let value = value.to_lowercase();
+    choices.contains(&value.as_str()).then_some(value)
+}
+
+fn parse_on(value: &str) -> Option<String> {
+    value.eq_ignore_ascii_case("on").then(|| "on".to_string())
+}
+
+/// Whether an on/off setting is switched on.
+pub fn is_on(db: &Db, key: &str) -> bool {
+    get(db, key).as_deref() == Some("on")
+}
+
+/// Whether running sessions count toward this week's leaderboard: unless `live-totals` is `none`.
+pub fn live_weekly(db: &Db) -> bool {
+    get(db, LIVE_TOTALS).as_deref() != Some("none")
+}
+
+/// Whether running sessions count toward the all-time leaderboard: only with `live-totals` `all`.
+pub fn live_alltime(db: &Db) -> bool {
+    get(db, LIVE_TOTALS).as_deref() == Some("all")
+}
+
+fn show_plain(value: &str, _: &DisplayFormat) -> String {
+    value.to_string()
+}
+
+pub fn setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+fn storage_key(key: &str) -> String {
+    format!("config.{}", key)
+}
+
+/// Raw stored value of `key`, `None` when unset.
+pub fn get(db: &Db, key: &str) -> Option<String> {
+    db.metadata(&storage_key(key)).ok().flatten()
+}
+
+/// Stored value of `setting` rendered for display, `None` when unset.
+pub fn display(db: &Db, setting: &Setting) -> Option<String> {
+    get(db, setting.key).map(|v| (setting.show)(&v, &DisplayFormat::load(db)))
+}
+
+/// Validate and store `value` (or clear the setting for `off`).
+/// Returns the value as displayed, or an error message for the user.
+pub fn set(db: &Db, setting: &Setting, value: &str) -> Result<Option<String>, String> {
+    if value.eq_ignore_ascii_case("off") {
+        db.delete_metadata(&storage_key(setting.key))
+            .map_err(|_| "Failed to clear the setting.".to_string())?;
+        return Ok(None);
+    }
+    let normalized = (setting.parse)(value).ok_or_else(|| {
+        format!(
+            "Invalid value for `{}`. Example: `/clock config {} {}`",
+            setting.key, setting.key, setting.example
+        )
+    })?;
+    db.set_metadata(&storage_key(setting.key), &normalized)
+        .map_err(|_| "Failed to save the setting.".to_string())?;
+    Ok(Some((setting.show)(&normalized, &DisplayFormat::load(db))))
+}
+
+/// Users allowed to change settings, from the comma-separated `ADMIN_IDS`.
+pub fn is_admin(user_id: UserId) -> bool {
+    env::var("ADMIN_IDS")
+        .map(|ids| ids.split(',').any(|id| id.trim() == user_id.to_string()))
+        .unwrap_or(false)
+}
+
+/// Bots and webhooks allowed to log time for people, from the comma-separated
+/// `TRUSTED_BOTS` (bot user ids or webhook ids). Other bot messages are ignored.
+pub fn is_trusted_bot(msg: &Message) -> bool {
+    let Ok(ids) = env::var("TRUSTED_BOTS") else {
+        return false;
+    };
+    let author = msg.author.id.to_string();
+    let webhook = msg.webhook_id.map(|w| w.to_string());
+    ids.split(',')
+        .map(str::trim)
+        .any(|id| !id.is_empty() && (id == author || Some(id) == webhook.as_deref()))
+}
+
+/// What Discord roles can be allowed to do with `/clock config perms`. Members
+/// listed in `ADMIN_IDS` can do everything, and `Admin` roles include the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    /// Admin commands: settings, menus, events, weights, budgets, clients.
+    Admin,
+    /// Exports of everyone's time, such as invoices.
+    Export,
+}
+
+pub const CAPABILITIES: &[Capability] = &[Capability::Admin, Capability::Export];
+
+impl Capability {
+    pub fn key(self) -> &'static str {
+        match self {
+            Capability::Admin => "admin",
+            Capability::Export => "export",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Capability::Admin => "admin commands (settings, menus, events, weights, budgets, clients)",
+            Capability::Export => "exports of everyone's time (invoices)",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        CAPABILITIES.iter().copied().find(|c| c.key().eq_ignore_ascii_case(key))
+    }
+
+    fn storage_key(self) -> String {
+        format!("perms.{}", self.key())
+    }
+}
+
+/// Roles granted `capability`, stored as comma-separated ids.
+pub fn capability_roles(db: &Db, capability: Capability) -> Vec<RoleId> {
+    db.metadata(&capability.storage_key())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse::<u64>().ok().filter(|&id| id != 0))
+        .map(RoleId::new)
+        .collect()
+}
+
+pub fn set_capability_roles(db: &Db, capability: Capability, roles: &[RoleId]) -> anyhow::Result<()> {
+    if roles.is_empty() {
+        return db.delete_metadata(&capability.storage_key());
+    }
+    let ids: Vec<String> = roles.iter().map(|r| r.to_string()).collect();
+    db.set_metadata(&capability.storage_key(), &ids.join(","))
+}
+
+/// Whether a member with `roles` may use `capability`.
+pub fn has_capability(db: &Db, user_id: UserId, roles: &[RoleId], capability: Capability) -> bool {
+    let granted = |c: Capability| capability_roles(db, c).iter().any(|r| roles.contains(r));
+    is_admin(user_id) || granted(Capability::Admin) || granted(capability)
+}
+
+/// Whether the author of `msg` may use `capability`. Roles only count in servers.
+pub fn allowed(db: &Db, msg: &Message, capability: Capability) -> bool {
+    let roles = msg.member.as_ref().map(|m| m.roles.as_slice()).unwrap_or_default();
+    has_capability(db, msg.author.id, roles, capability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+
+    #[test]
+    fn test_set_validates_and_normalizes() {
+        let db = test_db();
+
+        let limit = setting(DAILY_LIMIT).unwrap();
+        assert_eq!(set(&db, limit, "9h30m"), Ok(Some("9h 30m".to_string())));
+        assert_eq!(get(&db, DAILY_LIMIT).as_deref(), Some("570"));
+        assert!(set(&db, limit, "forever").is_err());
+        assert_eq!(get(&db, DAILY_LIMIT).as_deref(), Some("570"));
+        assert_eq!(set(&db, limit, "OFF"), Ok(None));
+        assert_eq!(get(&db, DAILY_LIMIT), None);
+
+        let streak = setting(MAX_STREAK).unwrap();
+        assert!(set(&db, streak, "0").is_err());
+        assert_eq!(set(&db, streak, "6"), Ok(Some("6 days".to_string())));
+
+        let mode = setting(GUARDRAILS).unwrap();
+        assert_eq!(set(&db, mode, "Refuse"), Ok(Some("refuse".to_string())));
+        assert!(set(&db, mode, "shout").is_err());
+
+        let quiet = setting(QUIET_HOURS).unwrap();
+        assert_eq!(set(&db, quiet, "22-7"), Ok(Some("22:00-07:00".to_string())));
+        assert!(set(&db, quiet, "all day").is_err());
+
+        let sections = setting(REPORT_SECTIONS).unwrap();
+        assert_eq!(set(&db, sections, "Breakdown, awards,breakdown"), Ok(Some("breakdown,awards".to_string())));
+        assert!(set(&db, sections, "awards,roles").is_err());
+        assert!(set(&db, sections, ",").is_err());
+
+        let midweek = setting(MIDWEEK_POST).unwrap();
+        assert_eq!(set(&db, midweek, "Thursday 9"), Ok(Some("thu 09:00".to_string())));
+        assert!(set(&db, midweek, "noon").is_err());
+
+        let inactive = setting(INACTIVE_WEEKS).unwrap();
+        assert_eq!(set(&db, inactive, "4"), Ok(Some("4 weeks".to_string())));
+        assert!(set(&db, inactive, "0").is_err());
+
+        let pdf = setting(PDF_REPORT).unwrap();
+        assert!(!is_on(&db, PDF_REPORT));
+        assert_eq!(set(&db, pdf, "ON"), Ok(Some("on".to_string())));
+        assert!(is_on(&db, PDF_REPORT));
+        assert!(set(&db, pdf, "yes").is_err());
+
+        let durations = setting(DURATION_FORMAT).unwrap();
+        assert_eq!(set(&db, durations, "Decimal"), Ok(Some("decimal".to_string())));
+        assert!(set(&db, durations, "minutes").is_err());
+
+        let gap = setting(MERGE_GAP).unwrap();
+        assert_eq!(set(&db, gap, "5m"), Ok(Some("5 min".to_string())));
+        assert!(set(&db, gap, "90").is_err());
+
+        let day_end = setting(DAY_END).unwrap();
+        assert_eq!(set(&db, day_end, "17"), Ok(Some("17:00".to_string())));
+        assert_eq!(set(&db, day_end, "17:30"), Ok(Some("17:30".to_string())));
+        assert!(set(&db, day_end, "25:00").is_err());
+
+        let kudos = setting(KUDOS_EMOJI).unwrap();
+        assert_eq!(set(&db, kudos, " 👏 "), Ok(Some("👏".to_string())));
+        assert!(set(&db, kudos, "thumbs up").is_err());
+
+        let currency = setting(CURRENCY).unwrap();
+        assert_eq!(set(&db, currency, "eur"), Ok(Some("EUR".to_string())));
+        assert!(set(&db, currency, "euros").is_err());
+
+        assert!(setting("nope").is_none());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let db = test_db();
+        let user = UserId::new(42);
+        let (leads, finance) = (RoleId::new(1), RoleId::new(2));
+
+        assert!(!has_capability(&db, user, &[leads], Capability::Admin));
+        set_capability_roles(&db, Capability::Export, &[finance]).unwrap();
+        assert!(has_capability(&db, user, &[finance], Capability::Export));
+        assert!(!has_capability(&db, user, &[finance], Capability::Admin));
+
+        // Admin roles can do everything
+        set_capability_roles(&db, Capability::Admin, &[leads]).unwrap();
+        assert!(has_capability(&db, user, &[leads], Capability::Export));
+        assert_eq!(capability_roles(&db, Capability::Admin), vec![leads]);
+
+        set_capability_roles(&db, Capability::Admin, &[]).unwrap();
+        assert!(capability_roles(&db, Capability::Admin).is_empty());
+        assert_eq!(Capability::parse("EXPORT"), Some(Capability::Export));
+        assert_eq!(Capability::parse("aliases"), None);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::chart::{render_chart, ChartMode};
+use crate::db::{Db, LeaderboardEntry, WeeklySummary};
+use crate::display::DisplayFormat;
+use crate::http::Response;
+use base64::Engine;
+use std::collections::HashMap;
+use std::env;
+
+/// Metadata key holding the most recently generated dashboard.
+const DASHBOARD_KEY: &str = "dashboard.html";
+const CHART_WEEKS: u32 = 12;
+
+/// Whether `GET /dashboard` serves the dashboard, from `DASHBOARD_PUBLIC`.
+pub fn public() -> bool {
+    env::var("DASHBOARD_PUBLIC").is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "on"))
+}
+
+/// Where to `PUT` the dashboard after each reset, from `DASHBOARD_UPLOAD_URL`.
+pub fn upload_url() -> Option<String> {
+    env::var("DASHBOARD_UPLOAD_URL").ok().filter(|s| !s.is_empty())
+}
+
+/// The dashboard is only generated when something will show it.
+pub fn enabled() -> bool {
+    public() || upload_url().is_some()
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `(name, minutes)` rows, largest first.
+pub type Totals = Vec<(String, i64)>;
+
+/// Per-user and per-activity totals of a week.
+pub fn totals(summary: &WeeklySummary) -> (Totals, Totals) {
+    let mut users: HashMap<&str, i64> = HashMap::new();
+    let mut activities: HashMap<&str, i64> = HashMap::new();
+    for entry in &summary.breakdown {
+        *users.entry(&entry.username).or_default() += entry.total_minutes;
+        *activities.entry(&entry.activity).or_default() += entry.total_minutes;
+    }
+    (sorted(users), sorted(activities))
+}
+
+/// Per-user totals for leaderboards, without the week's non-competitive activities.
+pub fn ranking(summary: &WeeklySummary) -> Totals {
+    let mut users: HashMap<&str, i64> = HashMap::new();
+    for entry in summary
+        .breakdown
+        .iter()
+        .filter(|e| !summary.non_competitive.contains(&e.activity))
+    {
+        *users.entry(&entry.username).or_default() += entry.total_minutes;
+    }
+    sorted(users)
+}
+
+fn sorted(map: HashMap<&str, i64>) -> Totals {
+    let mut v: Totals = map.into_iter().map(|(k, m)| (k.to_string(), m)).collect();
+    v.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    v
+}
+
+pub fn table(title: &str, rows: &Totals, fmt: &DisplayFormat) -> String {
+    let max = rows.iter().map(|(_, m)| *m).max().unwrap_or(0).max(1);
+    let body: String = rows
+        .iter()
+        .enumerate()
+        .map(|(i, (name, minutes))| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td><div class=\"bar\" style=\"width:{}%\"></div></td><td>{}</td></tr>\n",
+                i + 1,
+                escape(name),
+                minutes * 100 / max,
+                fmt.duration(*minutes)
+            )
+        })
+        .collect();
+    format!("<section><h2>{}</h2>\n<table>\n{}</table></section>\n", title, body)
+}
+
+/// Self-contained HTML page for the week that just closed.
+pub fn render(
+    week_label: &str,
+    summary: &WeeklySummary,
+    alltime: &[LeaderboardEntry],
+    chart_png: Option<&[u8]>,
+    generated_at: &str,
+    fmt: &DisplayFormat,
+) -> String {
+    let (_, activities) = totals(summary);
+    let users = ranking(summary);
+    let alltime: Totals = alltime.iter().map(|e| (e.username.clone(), e.total_minutes)).collect();
+    let chart = chart_png
+        .map(|png| {
+            format!(
+                "<section><h2>Weekly hours, last {} weeks</h2><img alt=\"Weekly hours chart\" src=\"data:image/png;base64,{}\"></section>\n",
+                CHART_WEEKS,
+                base64::engine::general_purpose::STANDARD.encode(png)
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>ClockBot — {week}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; background: #1e1f22; color: #eaecee; max-width: 960px; margin: 2rem auto; padding: 0 1rem; }}
+h1 {{ margin-bottom: .25rem; }}
+.muted {{ color: #9aa0a6; }}
+table {{ width: 100%; border-collapse: collapse; }}
+td {{ padding: .3rem .5rem; border-bottom: 1px solid #2f3339; }}
+td:last-child {{ text-align: right; white-space: nowrap; }}
+td:nth-child(3) {{ width: 40%; }}
+.bar {{ height: .6rem; background: #4c9aff; border-radius: 3px; }}
+img {{ max-width: 100%; }}
+</style>
+</head>
+<body>
+<h1>📊 Weekly Report — {week}</h1>
+<p class="muted">{total} total · {sessions} sessions · {people} people</p>
+{leaderboard}{activities}{chart}{alltime}<p class="muted">Generated {generated} (Europe/Zurich)</p>
+</body>
+</html>
+"#,
+        week = escape(&fmt.week(week_label)),
+        total = fmt.duration(summary.total_minutes),
+        sessions = summary.total_sessions,
+        people = summary.unique_workers,
+        leaderboard = table("Leaderboard", &users, fmt),
+        activities = table("Activities", &activities, fmt),
+        chart = chart,
+        alltime = table("All time", &alltime, fmt),
+        generated = generated_at,
+    )
+}
+
+/// Render the dashboard for a closed week (after archiving, so all-time totals include it),
+/// keep it for `GET /dashboard` and upload it if configured.
+pub async fn publish(db: &Db, week_label: &str, summary: &WeeklySummary) -> anyhow::Result<()> {
+    let alltime = crate::anonymous::public_board(db, db.leaderboard_alltime(false)?);
+    let chart = db
+        .weekly_hours_for_chart(CHART_WEEKS)
+        .ok()
+        .filter(|data| !data.users.is_empty())
+        .and_then(|data| render_chart(&crate::anonymous::public_chart(db, data), ChartMode::Totals).ok());
+    let fmt = DisplayFormat::load(db);
+    let generated_at = fmt.timestamp(db.clock().now());
+    let summary = crate::anonymous::public_summary(db, summary);
+    let html = render(week_label, &summary, &alltime, chart.as_deref(), &generated_at, &fmt);
+    db.set_metadata(DASHBOARD_KEY, &html)?;
+
+    if let Some(url) = upload_url() {
+        let mut request = crate::calendar::http_client()
+            .put(&url)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(html);
+        if let Ok(token) = env::var("DASHBOARD_UPLOAD_TOKEN") {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+    }
+    Ok(())
+}
+
+/// `GET /dashboard`: the last generated dashboard, 404 unless public and generated.
+pub fn serve(db: &Db) -> Response {
+    match db.metadata(DASHBOARD_KEY) {
+        Ok(Some(html)) if public() => Response::html(200, &html),
+        _ => Response::text(404, "not found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ActivityEntry;
+
+    fn entry(username: &str, activity: &str, total_minutes: i64) -> ActivityEntry {
+        ActivityEntry {
+            username: username.to_string(),
+            activity: activity.to_string(),
+            total_minutes,
+            session_count: 1,
+        }
+    }
+
+    fn summary() -> WeeklySummary {
+        WeeklySummary {
+            total_minutes: 420,
+            total_sessions: 3,
+            unique_workers: 2,
+            mvp: Some(("Alice".to_string(), 300)),
+            top_activity: Some(("backend".to_string(), 360)),
+            longest_session: None,
+            most_appreciated: None,
+            breakdown: vec![
+                entry("<Bob>", "backend", 120),
+                entry("Alice", "backend", 240),
+                entry("Alice", "docs", 60),
+            ],
+            holidays: Vec::new(),
+            daily_minutes: [0; 7],
+            mvp_daily_minutes: [0; 7],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_totals_per_user_and_activity() {
+        let (users, activities) = totals(&summary());
+        assert_eq!(users, vec![("Alice".to_string(), 300), ("<Bob>".to_string(), 120)]);
+        assert_eq!(activities, vec![("backend".to_string(), 360), ("docs".to_string(), 60)]);
+    }
+
+    #[test]
+    fn test_ranking_skips_non_competitive() {
+        let mut summary = summary();
+        assert_eq!(ranking(&summary), totals(&summary).0);
+        summary.non_competitive = vec!["docs".to_string()];
+        assert_eq!(ranking(&summary), vec![("Alice".to_string(), 240), ("<Bob>".to_string(), 120)]);
+        summary.non_competitive = vec!["backend".to_string()];
+        assert_eq!(ranking(&summary), vec![("Alice".to_string(), 60)]);
+    }
+
+    #[test]
+    fn test_render_escapes_and_embeds_chart() {
+        let alltime = vec![LeaderboardEntry { username: "Alice".to_string(), total_minutes: 6000, rank: 1, live: false }];
+        let html = render("KW07/2026", &summary(), &alltime, Some(b"png"), "16.02.2026 00:00", &DisplayFormat::default());
+        assert!(html.contains("Weekly Report — KW07/2026"));
+        assert!(html.contains("7h 0m total · 3 sessions · 2 people"));
+        assert!(html.contains("&lt;Bob&gt;"));
+        assert!(!html.contains("<Bob>"));
+        assert!(html.contains("data:image/png;base64,cG5n"));
+        assert!(html.contains("100h 0m"));
+
+        let without_chart = render("KW07/2026", &summary(), &[], None, "16.02.2026 00:00", &DisplayFormat::default());
+        assert!(!without_chart.contains("<img"));
+
+        let fmt = DisplayFormat { decimal_hours: true, week_words: true, ..DisplayFormat::default() };
+        let formatted = render("KW07/2026", &summary(), &alltime, None, "16.02.2026 00:00", &fmt);
+        assert!(formatted.contains("Weekly Report — Week 7, 2026"));
+        assert!(formatted.contains("7.0h total"));
+        assert!(formatted.contains("100.0h"));
+    }
+}
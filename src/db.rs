@@ -1,30 +1,152 @@
-use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
-use chrono_tz::Europe::Zurich;
+use crate::cache::QueryCache;
+use crate::clock::{Clock, SystemClock};
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use rusqlite::{params, Connection};
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 pub struct Db {
     conn: Mutex<Connection>,
+    cache: QueryCache,
+    clock: Arc<dyn Clock>,
 }
 
-#[derive(Debug)]
+/// How long cached leaderboard / who-is-working / breakdown results stay valid.
+const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Writes refused for a reason the user can fix, told apart from real failures with
+/// `e.downcast_ref::<Refusal>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The user already has a running session.
+    ClockedIn,
+    /// The user's running session is on the activity being changed.
+    ActivityRunning,
+}
+
+impl std::fmt::Display for Refusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Refusal::ClockedIn => "already clocked in",
+            Refusal::ActivityRunning => "currently clocked in on that activity",
+        })
+    }
+}
+
+impl std::error::Error for Refusal {}
+
+#[derive(Debug, Clone)]
 pub struct ActiveSession {
-    pub id: i64,
-    pub user_id: String,
     pub username: String,
     pub activity: String,
     pub started_at: NaiveDateTime,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LeaderboardEntry {
     pub username: String,
     pub total_minutes: i64,
+    /// Dense rank: people with the same total share it and the next total is one lower.
+    pub rank: i64,
+    /// The total includes a session that is still running.
+    pub live: bool,
 }
 
-#[derive(Debug)]
+/// Where one person stands on a leaderboard, wherever that is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserRank {
+    pub rank: i64,
+    pub total_minutes: i64,
+    /// Minutes short of the next rank up; `None` when already first.
+    pub behind: Option<i64>,
+}
+
+/// Competitive minutes per person since `:monday`, for the weekly boards. With `:live`
+/// running sessions count up to `:now`, from Monday if they started before. Seconds are
+/// summed and the total rounded down once, so short sessions still add up.
+const WEEKLY_TOTALS_SQL: &str = "SELECT user_id, username,
+        SUM(CASE WHEN ended_at IS NULL
+                 THEN CAST((julianday(:now) - julianday(MAX(started_at, :monday))) * 86400 AS INTEGER)
+                 ELSE seconds END) / 60 as total,
+        MAX(ended_at IS NULL) as live
+     FROM sessions
+     WHERE ((ended_at IS NOT NULL AND started_at >= :monday) OR (:live AND ended_at IS NULL))
+       AND activity NOT IN (SELECT activity FROM non_competitive)
+     GROUP BY user_id";
+
+/// Competitive minutes per person over unarchived sessions and all archived weeks,
+/// with `:live` including running sessions up to `:now`.
+const ALLTIME_TOTALS_SQL: &str = "SELECT user_id, username, SUM(secs) / 60 as total, MAX(live) as live FROM (
+        SELECT user_id, username,
+               SUM(CASE WHEN ended_at IS NULL
+                        THEN CAST((julianday(:now) - julianday(started_at)) * 86400 AS INTEGER)
+                        ELSE seconds END) as secs,
+               MAX(ended_at IS NULL) as live
+            FROM sessions
+            WHERE (ended_at IS NOT NULL OR :live)
+              AND activity NOT IN (SELECT activity FROM non_competitive)
+            GROUP BY user_id
+        UNION ALL
+        SELECT user_id, username, SUM(total_sec) as secs, 0 as live FROM weekly_archive
+            GROUP BY user_id
+        UNION ALL
+        -- weekly_archive has no activities; take non-competitive time back out
+        SELECT user_id, username, -SUM(total_sec) as secs, 0 as live FROM activity_archive
+            WHERE activity IN (SELECT activity FROM non_competitive)
+            GROUP BY user_id
+     ) GROUP BY user_id HAVING total > 0";
+
+/// The top 15 of a `user_id, username, total, live` query, ranked over everyone in it.
+fn top_ranked(conn: &Connection, totals_sql: &str, params: &[(&str, &dyn rusqlite::ToSql)]) -> anyhow::Result<Vec<LeaderboardEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT username, total, DENSE_RANK() OVER (ORDER BY total DESC), live FROM ({totals_sql})
+         ORDER BY total DESC, username LIMIT 15"
+    ))?;
+    let rows = stmt.query_map(params, |r| {
+        Ok(LeaderboardEntry {
+            username: r.get(0)?,
+            total_minutes: r.get(1)?,
+            rank: r.get(2)?,
+            live: r.get(3)?,
+        })
+    })?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
+}
+
+/// `user_id`'s place in a `user_id, username, total, live` query; `None` if they aren't in it.
+fn rank_of(
+    conn: &Connection,
+    totals_sql: &str,
+    params: &[(&str, &dyn rusqlite::ToSql)],
+    user_id: &str,
+) -> anyhow::Result<Option<UserRank>> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH ranked AS (
+             SELECT user_id, total, DENSE_RANK() OVER (ORDER BY total DESC) as rank FROM ({totals_sql})
+         )
+         SELECT rank, total, (SELECT MIN(above.total) FROM ranked above WHERE above.total > ranked.total)
+         FROM ranked WHERE user_id = :user"
+    ))?;
+    let mut params = params.to_vec();
+    params.push((":user", &user_id));
+    let rank = stmt.query_row(params.as_slice(), |r| {
+        let total: i64 = r.get(1)?;
+        Ok(UserRank {
+            rank: r.get(0)?,
+            total_minutes: total,
+            behind: r.get::<_, Option<i64>>(2)?.map(|above| above - total),
+        })
+    });
+    match rank {
+        Ok(rank) => Ok(Some(rank)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ActivityEntry {
     pub username: String,
     pub activity: String,
@@ -32,7 +154,7 @@ pub struct ActivityEntry {
     pub session_count: i64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WeeklySummary {
     pub total_minutes: i64,
     pub total_sessions: i64,
@@ -40,7 +162,30 @@ pub struct WeeklySummary {
     pub mvp: Option<(String, i64)>,
     pub top_activity: Option<(String, i64)>,
     pub longest_session: Option<(String, String, i64)>,
+    /// Most kudos received: `(username, kudos)`.
+    pub most_appreciated: Option<(String, i64)>,
     pub breakdown: Vec<ActivityEntry>,
+    /// Holidays falling on the week's weekdays.
+    pub holidays: Vec<(NaiveDate, String)>,
+    /// Everyone's minutes per day, Monday first.
+    pub daily_minutes: [i64; 7],
+    /// The MVP's minutes per day, Monday first.
+    pub mvp_daily_minutes: [i64; 7],
+    /// Archived `(activity, minutes)` of the week before, largest first.
+    pub previous_activities: Vec<(String, i64)>,
+    /// Activities left out of rankings and awards (still counted in totals and breakdowns).
+    pub non_competitive: Vec<String>,
+    /// Minutes per client, most first. Activities without a client are left out.
+    pub clients: Vec<(String, i64)>,
+}
+
+/// An activity filed under a client, with its minutes this week and all time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientActivity {
+    pub client: String,
+    pub activity: String,
+    pub week_minutes: i64,
+    pub total_minutes: i64,
 }
 
 #[derive(Debug)]
@@ -57,47 +202,665 @@ pub struct ChartData {
     pub users: Vec<UserWeeklyData>,
 }
 
+#[derive(Debug)]
+pub struct ActivityDetail {
+    pub total_minutes: i64,
+    pub session_count: i64,
+    /// Week labels in chronological order (oldest first).
+    pub week_labels: Vec<String>,
+    /// Server-wide minutes per week, aligned with `week_labels`.
+    pub minutes_per_week: Vec<i64>,
+    /// Users who logged time on the activity, ordered by total minutes (descending).
+    pub contributors: Vec<LeaderboardEntry>,
+}
+
+/// Someone with archived time but none in the last weeks, for a re-engagement DM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InactiveUser {
+    pub user_id: String,
+    pub username: String,
+    /// Label of the last week they logged time in.
+    pub last_week: String,
+    /// Label and minutes of their best week.
+    pub best_week: String,
+    pub best_minutes: i64,
+}
+
+/// What a shared activity is about and who looks after it, set by an admin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityInfo {
+    pub description: String,
+    /// Discord user ID of the owner.
+    pub owner_id: Option<String>,
+}
+
+/// An hour budget on an activity tracked as a project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityBudget {
+    pub activity: String,
+    pub budget_min: i64,
+    /// Finished minutes logged on the activity by everyone, all time.
+    pub used_min: i64,
+    /// Highest burn alert (percent) already posted.
+    pub alerted_pct: i64,
+}
+
+#[derive(Debug)]
+pub struct ActivityTotals {
+    pub activity: String,
+    /// Minutes the requesting user logged on the activity.
+    pub user_minutes: i64,
+    /// Minutes logged by everyone on the activity.
+    pub total_minutes: i64,
+}
+
+/// Minutes one user logged on one activity in one week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeekActivity {
+    pub user_id: String,
+    pub username: String,
+    pub week_label: String,
+    pub activity: String,
+    pub minutes: i64,
+}
+
+/// An archived week as recorded in the `weeks` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedWeek {
+    pub label: String,
+    /// Monday of the week.
+    pub start: NaiveDate,
+    /// Sunday of the week.
+    pub end: NaiveDate,
+    /// When the reset archived it; `None` for weeks archived before this was kept.
+    pub archived_at: Option<NaiveDateTime>,
+    pub total_minutes: i64,
+    pub sessions: i64,
+    pub people: i64,
+}
+
+/// Finished sessions longer than this are reported by `/clock admin check`.
+pub const MAX_PLAUSIBLE_MINUTES: i64 = 24 * 60;
+/// Running sessions older than this are reported by `/clock admin check`.
+pub const STALE_SESSION_DAYS: i64 = 7;
+
+/// Something wrong with the stored data, found by `Db::integrity_findings`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// A finished session that ends before it starts or runs longer than `MAX_PLAUSIBLE_MINUTES`.
+    BadSession { id: i64, username: String, activity: String, started_at: NaiveDateTime, minutes: i64 },
+    /// A session still running after `STALE_SESSION_DAYS`.
+    StaleSession { username: String, activity: String, started_at: NaiveDateTime },
+    /// A person's archived total for a week that differs from the sum of their archived activities.
+    ArchiveMismatch { week_label: String, username: String, total: i64, activities: i64 },
+    /// A person, or one of their activities (`Some`), archived more than once for the same week.
+    DuplicateArchive { week_label: String, username: String, activity: Option<String>, rows: i64 },
+}
+
+/// Exact length of a finished session, to the second. Aggregates and archives keep
+/// seconds too; queries round their totals down to whole minutes only when reading them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Elapsed {
+    seconds: i64,
+}
+
+impl Elapsed {
+    pub fn between(start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self::from_seconds((end - start).num_seconds())
+    }
+
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self { seconds: seconds.max(0) }
+    }
+
+    pub fn seconds(self) -> i64 {
+        self.seconds
+    }
+
+    /// Whole minutes of this session on its own. Totals add up seconds and round down
+    /// once, so they can be more than the sum of these.
+    pub fn minutes(self) -> i64 {
+        self.seconds / 60
+    }
+}
+
+/// A finished session to import, in Swiss local time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub user_id: String,
+    pub username: String,
+    pub activity: String,
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+}
+
+/// Rows per multi-row `INSERT` in bulk paths; 8 columns each stays far below SQLite's
+/// bound-parameter limit.
+const BULK_INSERT_ROWS: usize = 100;
+
+/// A finished session of the current week, as exported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub user_id: String,
+    pub username: String,
+    pub activity: String,
+    pub started_at: NaiveDateTime,
+    pub ended_at: NaiveDateTime,
+    pub elapsed: Elapsed,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineSession {
+    pub activity: String,
+    pub started_at: NaiveDateTime,
+    /// `now_ch()` for a session that is still running.
+    pub ended_at: NaiveDateTime,
+}
+
+/// A personal API token from `/clock token create`; only its hash is stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserToken {
+    pub id: i64,
+    pub user_id: String,
+    pub username: String,
+    /// `read` or `write`.
+    pub scope: String,
+    pub created_at: NaiveDateTime,
+    pub last_used: Option<NaiveDateTime>,
+}
+
+fn user_token_from_row(r: &rusqlite::Row) -> rusqlite::Result<UserToken> {
+    let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+    Ok(UserToken {
+        id: r.get(0)?,
+        user_id: r.get(1)?,
+        username: r.get(2)?,
+        scope: r.get(3)?,
+        created_at: parse(r.get(4)?),
+        last_used: r.get::<_, Option<String>>(5)?.map(parse),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct UserSettings {
+    pub user_id: String,
+    pub daily_goal_min: Option<i64>,
+    /// IANA timezone name the user's days are measured in.
+    pub timezone: String,
+    pub nudges: bool,
+    /// Local date of the last goal nudge sent.
+    pub last_nudged: Option<NaiveDate>,
+    /// Greetings and tips on clock-in/out posts.
+    pub tips: bool,
+    /// Local date of the last tip shown.
+    pub last_tip: Option<NaiveDate>,
+}
+
+impl UserSettings {
+    fn defaults(user_id: &str) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            daily_goal_min: None,
+            timezone: DEFAULT_TIMEZONE.to_string(),
+            nudges: true,
+            last_nudged: None,
+            tips: true,
+            last_tip: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarFeed {
+    pub user_id: String,
+    pub username: String,
+    pub url: String,
+    /// Lowercase keywords an event title or calendar name must contain; empty imports all.
+    pub keywords: Vec<String>,
+    /// Activity imported events are logged under.
+    pub activity: String,
+}
+
+/// A badge, card or device ID from an access system, mapped to a Discord user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalId {
+    pub external_id: String,
+    pub user_id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GithubLink {
+    /// Lowercase GitHub login.
+    pub login: String,
+    pub user_id: String,
+    pub username: String,
+    /// `suggest` (DM a clock-in hint) or `log` (record sessions).
+    pub mode: String,
+}
+
+/// A competition window with its own leaderboard, e.g. a hackathon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub id: i64,
+    pub name: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+}
+
+fn event_from_row(r: &rusqlite::Row) -> rusqlite::Result<Event> {
+    let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+    Ok(Event {
+        id: r.get(0)?,
+        name: r.get(1)?,
+        starts_at: parse(r.get(2)?),
+        ends_at: parse(r.get(3)?),
+    })
+}
+
+/// A head-to-head race to `target_min` within one week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub id: i64,
+    pub week_label: String,
+    /// Where the challenge was made; progress is posted there.
+    pub channel_id: String,
+    pub challenger_id: String,
+    pub challenger_name: String,
+    pub opponent_id: String,
+    pub opponent_name: String,
+    pub target_min: i64,
+}
+
+fn challenge_from_row(r: &rusqlite::Row) -> rusqlite::Result<Challenge> {
+    Ok(Challenge {
+        id: r.get(0)?,
+        week_label: r.get(1)?,
+        channel_id: r.get(2)?,
+        challenger_id: r.get(3)?,
+        challenger_name: r.get(4)?,
+        opponent_id: r.get(5)?,
+        opponent_name: r.get(6)?,
+        target_min: r.get(7)?,
+    })
+}
+
+const CHALLENGE_COLUMNS: &str =
+    "id, week_label, channel_id, challenger_id, challenger_name, opponent_id, opponent_name, target_min";
+
+/// A run of detected activity (GitHub pushes, editor heartbeats) without a long pause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityBurst {
+    pub user_id: String,
+    pub username: String,
+    pub activity: String,
+    pub first_at: NaiveDateTime,
+    pub last_at: NaiveDateTime,
+}
+
+pub const DEFAULT_TIMEZONE: &str = "Europe/Zurich";
+
+/// Where a session came from, kept in `sessions.source`.
+pub const SOURCE_MANUAL: &str = "manual";
+pub const SOURCE_CALENDAR: &str = "calendar-sync";
+pub const SOURCE_API: &str = "api";
+/// Logged by an allowlisted bot or webhook on someone's behalf.
+pub const SOURCE_BOT: &str = "bot";
+/// Loaded from a file with `clockctl import`.
+pub const SOURCE_IMPORT: &str = "import";
+pub const SOURCES: &[&str] = &[SOURCE_MANUAL, SOURCE_CALENDAR, SOURCE_API, SOURCE_BOT, SOURCE_IMPORT];
+
+/// The real Swiss local time, for code without a `Db` at hand; `Db::clock` is the one
+/// tests can control.
 pub fn now_ch() -> NaiveDateTime {
-    Utc::now().with_timezone(&Zurich).naive_local()
+    SystemClock.now()
 }
 
-fn now_ch_str() -> String {
-    now_ch().format("%Y-%m-%d %H:%M:%S").to_string()
+fn non_competitive(conn: &Connection) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT activity FROM non_competitive ORDER BY activity")?;
+    let rows = stmt.query_map([], |r| r.get(0))?;
+    Ok(rows.filter_map(|r| r.ok()).collect())
 }
 
-pub fn swiss_week_label() -> String {
-    let now = Utc::now().with_timezone(&Zurich);
-    now.format("KW%V/%G").to_string()
+/// Recompute `weekly_aggregates` from all completed sessions.
+fn rebuild_aggregates(conn: &Connection) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT user_id, username, activity, started_at, seconds FROM sessions
+         WHERE ended_at IS NOT NULL ORDER BY id ASC",
+    )?;
+    let rows: Vec<(String, String, String, String, i64)> = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?
+        .filter_map(|r| r.ok())
+        .collect();
+    drop(stmt);
+
+    conn.execute("DELETE FROM weekly_aggregates", [])?;
+    for (user_id, username, activity, started_str, seconds) in rows {
+        let started = NaiveDateTime::parse_from_str(&started_str, "%Y-%m-%d %H:%M:%S")?;
+        let week = WeekId::containing(started.date()).label();
+        add_to_aggregates(conn, &week, &user_id, &username, &activity, Elapsed::from_seconds(seconds))?;
+    }
+    Ok(())
 }
 
-fn monday_of_current_week() -> String {
-    let now = now_ch();
-    let wd = now.weekday().num_days_from_monday() as i64;
-    let monday = now.date() - Duration::days(wd);
-    monday.format("%Y-%m-%d 00:00:00").to_string()
+/// Fold one completed session into its (week, user, activity) aggregate row.
+fn add_to_aggregates(
+    conn: &Connection,
+    week_label: &str,
+    user_id: &str,
+    username: &str,
+    activity: &str,
+    elapsed: Elapsed,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO weekly_aggregates
+            (week_label, user_id, username, activity, total_sec, session_count, longest_sec)
+         VALUES (?1, ?2, ?3, ?4, ?5, 1, ?5)
+         ON CONFLICT(week_label, user_id, activity) DO UPDATE SET
+            username = excluded.username,
+            total_sec = total_sec + excluded.total_sec,
+            session_count = session_count + 1,
+            longest_sec = MAX(longest_sec, excluded.longest_sec)",
+        params![week_label, user_id, username, activity, elapsed.seconds()],
+    )?;
+    Ok(())
 }
 
-/// Generate the last `weeks` ISO week labels (oldest first, newest last),
-/// matching the `KW%V/%G` format used by `swiss_week_label()`.
-pub(crate) fn generate_week_labels(weeks: u32) -> Vec<String> {
-    let now = Utc::now().with_timezone(&Zurich);
-    let today = now.date_naive();
-    let wd = today.weekday().num_days_from_monday() as i64;
-    let current_monday: NaiveDate = today - Duration::days(wd);
+fn has_column(conn: &Connection, table: &str, column: &str) -> anyhow::Result<bool> {
+    Ok(conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+        params![column],
+        |r| r.get(0),
+    )?)
+}
 
-    (0..weeks as i64)
-        .rev()
-        .map(|i| {
-            let monday = current_monday - Duration::weeks(i);
-            let iso = monday.iso_week();
-            format!("KW{:02}/{}", iso.week(), iso.year())
-        })
-        .collect()
+/// Add `column` to `table` if an older database was created without it.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> anyhow::Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+    }
+    Ok(())
+}
+
+/// Turn the whole minutes in `table.old` into seconds in `table.new`, for databases from
+/// before totals were kept to the second.
+fn minutes_to_seconds(conn: &Connection, table: &str, old: &str, new: &str) -> anyhow::Result<()> {
+    if has_column(conn, table, old)? {
+        conn.execute_batch(&format!(
+            "BEGIN;
+             ALTER TABLE {table} RENAME COLUMN {old} TO {new};
+             UPDATE {table} SET {new} = {new} * 60;
+             COMMIT;"
+        ))?;
+    }
+    Ok(())
+}
+
+/// `(monday, sunday)` of `week_label` as stored in the archive tables, `None` for a
+/// label that isn't a week.
+fn week_dates(week_label: &str) -> Option<(String, String)> {
+    WeekId::parse(week_label).map(|w| (w.monday().to_string(), w.sunday().to_string()))
+}
+
+/// Fill in the dates of archive rows from before they were stored, and a `weeks` row
+/// for every archived week missing one.
+fn backfill_week_dates(conn: &Connection) -> anyhow::Result<()> {
+    let labels: Vec<String> = conn
+        .prepare(
+            "SELECT week_label FROM weekly_archive WHERE week_start IS NULL
+             UNION SELECT week_label FROM activity_archive WHERE week_start IS NULL",
+        )?
+        .query_map([], |r| r.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    for label in labels {
+        let Some((start, end)) = week_dates(&label) else {
+            continue;
+        };
+        for table in ["weekly_archive", "activity_archive"] {
+            conn.execute(
+                &format!("UPDATE {} SET week_start = ?2, week_end = ?3 WHERE week_label = ?1", table),
+                params![label, start, end],
+            )?;
+        }
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO weeks (label, week_start, week_end, total_sec, sessions, people)
+         SELECT week_label, MIN(week_start), MIN(week_end), 0, 0, 0
+         FROM weekly_archive WHERE week_start IS NOT NULL GROUP BY week_label",
+        [],
+    )?;
+    refresh_week_totals(conn)
+}
+
+/// The weekly reset used to archive a week under the label of the week it was running in,
+/// one week late: Monday 00:00 already belongs to the next week. Databases only the old
+/// reset archived (no last archived week recorded yet) get every label moved back one
+/// week, oldest first so no two weeks meet, and the newest is recorded so the next reset
+/// neither reuses its label nor archives it again.
+fn relabel_legacy_archives(conn: &Connection) -> anyhow::Result<()> {
+    let recorded: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM metadata WHERE key = ?1",
+        params![crate::health::LAST_ARCHIVED_WEEK_KEY],
+        |r| r.get(0),
+    )?;
+    if recorded {
+        return Ok(());
+    }
+    let mut weeks: Vec<WeekId> = conn
+        .prepare("SELECT week_label FROM weekly_archive UNION SELECT week_label FROM activity_archive")?
+        .query_map([], |r| r.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .filter_map(|label| WeekId::parse(&label))
+        .collect();
+    weeks.sort();
+    let Some(newest) = weeks.last().map(WeekId::previous) else {
+        return Ok(());
+    };
+    let tx = conn.unchecked_transaction()?;
+    for week in weeks {
+        let closed = week.previous();
+        let (label, start, end) = (closed.label(), closed.monday().to_string(), closed.sunday().to_string());
+        for table in ["weekly_archive", "activity_archive"] {
+            tx.execute(
+                &format!("UPDATE {table} SET week_label = ?2, week_start = ?3, week_end = ?4 WHERE week_label = ?1"),
+                params![week.label(), label, start, end],
+            )?;
+        }
+        tx.execute(
+            "UPDATE weeks SET label = ?2, week_start = ?3, week_end = ?4 WHERE label = ?1",
+            params![week.label(), label, start, end],
+        )?;
+    }
+    tx.execute(
+        "INSERT INTO metadata (key, value) VALUES (?1, ?2)",
+        params![crate::health::LAST_ARCHIVED_WEEK_KEY, newest.label()],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Recompute the totals of every `weeks` row from the archive tables, after archiving
+/// or after edits to archived time.
+fn refresh_week_totals(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute(
+        "UPDATE weeks SET
+            total_sec = COALESCE((SELECT SUM(total_sec) FROM weekly_archive WHERE week_label = weeks.label), 0),
+            sessions = COALESCE((SELECT SUM(session_count) FROM activity_archive WHERE week_label = weeks.label), 0),
+            people = (SELECT COUNT(DISTINCT user_id) FROM weekly_archive WHERE week_label = weeks.label)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Mark every Swiss date a session from `started` to `ended` touches as worked.
+fn record_work_days(conn: &Connection, user_id: &str, started: NaiveDateTime, ended: NaiveDateTime) -> anyhow::Result<()> {
+    let mut day = started.date();
+    while day <= ended.date() {
+        conn.execute(
+            "INSERT OR IGNORE INTO work_days (user_id, day) VALUES (?1, ?2)",
+            params![user_id, day.format("%Y-%m-%d").to_string()],
+        )?;
+        day += Duration::days(1);
+    }
+    Ok(())
+}
+
+/// The account `user_id`'s time is kept under: itself, or the account it was linked to.
+fn primary_account(conn: &Connection, user_id: &str) -> anyhow::Result<String> {
+    match conn.query_row(
+        "SELECT primary_id FROM account_links WHERE user_id = ?1",
+        params![user_id],
+        |r| r.get(0),
+    ) {
+        Ok(primary) => Ok(primary),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(user_id.to_string()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Run `INSERT <head> VALUES (..), (..) <tail>` over `rows` in chunks of `BULK_INSERT_ROWS`.
+fn insert_rows(conn: &Connection, head: &str, tail: &str, rows: &[Vec<rusqlite::types::Value>]) -> anyhow::Result<()> {
+    for chunk in rows.chunks(BULK_INSERT_ROWS) {
+        let tuple = format!("({})", vec!["?"; chunk[0].len()].join(","));
+        let sql = format!("INSERT {} VALUES {} {}", head, vec![tuple.as_str(); chunk.len()].join(","), tail);
+        conn.prepare_cached(&sql)?
+            .execute(rusqlite::params_from_iter(chunk.iter().flatten()))?;
+    }
+    Ok(())
+}
+
+/// Insert a finished session unless it overlaps one of the user's sessions.
+fn insert_imported_session(
+    conn: &Connection,
+    user_id: &str,
+    username: &str,
+    activity: &str,
+    (start, end): (NaiveDateTime, NaiveDateTime),
+    source: &str,
+    now: &str,
+) -> anyhow::Result<bool> {
+    // Imports are set up per account; linked accounts share one history
+    let user_id = &primary_account(conn, user_id)?;
+    let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+    let overlaps: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sessions
+         WHERE user_id = ?1 AND started_at < ?3 AND COALESCE(ended_at, ?4) > ?2",
+        params![user_id, fmt(start), fmt(end), now],
+        |r| r.get(0),
+    )?;
+    if overlaps {
+        return Ok(false);
+    }
+
+    let elapsed = Elapsed::between(start, end);
+    conn.execute(
+        "INSERT INTO sessions (user_id,username,activity,started_at,ended_at,minutes,seconds,source)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+        params![user_id, username, activity, fmt(start), fmt(end), elapsed.minutes(), elapsed.seconds(), source],
+    )?;
+    add_to_aggregates(conn, &WeekId::containing(start.date()).label(), user_id, username, activity, elapsed)?;
+    record_work_days(conn, user_id, start, end)?;
+    Ok(true)
+}
+
+fn activity_burst_from_row(r: &rusqlite::Row) -> rusqlite::Result<ActivityBurst> {
+    let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+    Ok(ActivityBurst {
+        user_id: r.get(0)?,
+        username: r.get(1)?,
+        activity: r.get(2)?,
+        first_at: parse(r.get(3)?),
+        last_at: parse(r.get(4)?),
+    })
+}
+
+fn calendar_feed_from_row(r: &rusqlite::Row) -> rusqlite::Result<CalendarFeed> {
+    let keywords: String = r.get(3)?;
+    Ok(CalendarFeed {
+        user_id: r.get(0)?,
+        username: r.get(1)?,
+        url: r.get(2)?,
+        keywords: keywords
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(String::from)
+            .collect(),
+        activity: r.get(4)?,
+    })
+}
+
+fn holidays_between(conn: &Connection, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<(NaiveDate, String)>> {
+    let mut stmt = conn.prepare("SELECT day, name FROM holidays WHERE day >= ?1 AND day <= ?2 ORDER BY day")?;
+    let rows = stmt.query_map(
+        params![from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string()],
+        |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)),
+    )?;
+    Ok(rows
+        .filter_map(|r| r.ok())
+        .filter_map(|(day, name)| Some((NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?, name)))
+        .collect())
+}
+
+fn external_id_from_row(r: &rusqlite::Row) -> rusqlite::Result<ExternalId> {
+    Ok(ExternalId {
+        external_id: r.get(0)?,
+        user_id: r.get(1)?,
+        username: r.get(2)?,
+    })
+}
+
+fn user_settings_from_row(r: &rusqlite::Row) -> rusqlite::Result<UserSettings> {
+    Ok(UserSettings {
+        user_id: r.get(0)?,
+        daily_goal_min: r.get(1)?,
+        timezone: r.get(2)?,
+        nudges: r.get(3)?,
+        last_nudged: r
+            .get::<_, Option<String>>(4)?
+            .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+        tips: r.get(5)?,
+        last_tip: r
+            .get::<_, Option<String>>(6)?
+            .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+    })
 }
 
 impl Db {
     pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Self::open_with_clock(path, Arc::new(SystemClock))
+    }
+
+    /// `open` with the time coming from `clock`. With `DB_MASTER_KEY` set the file is
+    /// encrypted (see `encryption`).
+    pub fn open_with_clock(path: &Path, clock: Arc<dyn Clock>) -> anyhow::Result<Self> {
         let conn = Connection::open(path)?;
+        if let Some(key) = crate::encryption::key_from_env()? {
+            crate::encryption::unlock(&conn, &key)?;
+        }
+        Self::with_connection(conn, clock)
+    }
+
+    /// A database that lives only as long as the `Db`, for tests and dry runs.
+    pub fn open_in_memory(clock: Arc<dyn Clock>) -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, clock)
+    }
+
+    /// Create or migrate the schema on `conn`.
+    fn with_connection(conn: Connection, clock: Arc<dyn Clock>) -> anyhow::Result<Self> {
+        // Totals are kept in seconds and only rounded to minutes when read
+        for (table, old, new) in [
+            ("weekly_archive", "total_min", "total_sec"),
+            ("activity_archive", "total_min", "total_sec"),
+            ("weeks", "total_min", "total_sec"),
+            ("weekly_aggregates", "total_min", "total_sec"),
+            ("weekly_aggregates", "longest_min", "longest_sec"),
+            ("event_archive", "minutes", "seconds"),
+        ] {
+            minutes_to_seconds(&conn, table, old, new)?;
+        }
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS sessions (
                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -106,14 +869,17 @@ impl Db {
                 activity    TEXT    NOT NULL,
                 started_at  TEXT    NOT NULL,
                 ended_at    TEXT,
-                minutes     INTEGER
+                minutes     INTEGER,
+                seconds     INTEGER,
+                auto_out_at TEXT,
+                source      TEXT    NOT NULL DEFAULT 'manual'
             );
             CREATE TABLE IF NOT EXISTS weekly_archive (
                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
                 user_id     TEXT    NOT NULL,
                 username    TEXT    NOT NULL,
                 week_label  TEXT    NOT NULL,
-                total_min   INTEGER NOT NULL
+                total_sec   INTEGER NOT NULL
             );
             CREATE TABLE IF NOT EXISTS activity_archive (
                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -121,74 +887,649 @@ impl Db {
                 username    TEXT    NOT NULL,
                 week_label  TEXT    NOT NULL,
                 activity    TEXT    NOT NULL,
-                total_min   INTEGER NOT NULL
+                total_sec   INTEGER NOT NULL,
+                session_count INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS weeks (
+                label       TEXT PRIMARY KEY,
+                week_start  TEXT    NOT NULL,
+                week_end    TEXT    NOT NULL,
+                archived_at TEXT,
+                total_sec   INTEGER NOT NULL,
+                sessions    INTEGER NOT NULL,
+                people      INTEGER NOT NULL
             );
             CREATE TABLE IF NOT EXISTS metadata (
                 key   TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             );
+            CREATE TABLE IF NOT EXISTS weekly_aggregates (
+                week_label    TEXT    NOT NULL,
+                user_id       TEXT    NOT NULL,
+                username      TEXT    NOT NULL,
+                activity      TEXT    NOT NULL,
+                total_sec     INTEGER NOT NULL,
+                session_count INTEGER NOT NULL,
+                longest_sec   INTEGER NOT NULL,
+                PRIMARY KEY (week_label, user_id, activity)
+            );
+            CREATE TABLE IF NOT EXISTS focus_roles (
+                user_id     TEXT PRIMARY KEY,
+                guild_id    TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS user_settings (
+                user_id        TEXT PRIMARY KEY,
+                daily_goal_min INTEGER,
+                timezone       TEXT    NOT NULL DEFAULT 'Europe/Zurich',
+                nudges         INTEGER NOT NULL DEFAULT 1,
+                last_nudged    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS work_days (
+                user_id     TEXT NOT NULL,
+                day         TEXT NOT NULL,
+                PRIMARY KEY (user_id, day)
+            );
+            CREATE TABLE IF NOT EXISTS holidays (
+                day         TEXT PRIMARY KEY,
+                name        TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS activity_templates (
+                activity    TEXT PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS non_competitive (
+                activity    TEXT PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS events (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                name        TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                starts_at   TEXT NOT NULL,
+                ends_at     TEXT NOT NULL,
+                announced   INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS event_archive (
+                event_id    INTEGER NOT NULL,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                seconds     INTEGER NOT NULL,
+                PRIMARY KEY (event_id, user_id)
+            );
+            CREATE TABLE IF NOT EXISTS challenges (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                week_label      TEXT NOT NULL,
+                channel_id      TEXT NOT NULL,
+                challenger_id   TEXT NOT NULL,
+                challenger_name TEXT NOT NULL,
+                opponent_id     TEXT NOT NULL,
+                opponent_name   TEXT NOT NULL,
+                target_min      INTEGER NOT NULL,
+                progress_posted INTEGER NOT NULL DEFAULT 0,
+                settled         INTEGER NOT NULL DEFAULT 0,
+                UNIQUE (week_label, challenger_id, opponent_id)
+            );
+            CREATE TABLE IF NOT EXISTS kudos_targets (
+                message_id  TEXT PRIMARY KEY,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                week_label  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS kudos (
+                message_id  TEXT NOT NULL,
+                giver_id    TEXT NOT NULL,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                week_label  TEXT NOT NULL,
+                PRIMARY KEY (message_id, giver_id)
+            );
+            CREATE TABLE IF NOT EXISTS activity_weights (
+                activity    TEXT PRIMARY KEY,
+                weight      REAL NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS activity_budgets (
+                activity    TEXT PRIMARY KEY,
+                budget_min  INTEGER NOT NULL,
+                alerted_pct INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS external_ids (
+                external_id TEXT PRIMARY KEY,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS attendance_events (
+                event_id    TEXT PRIMARY KEY,
+                received_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS activity_info (
+                activity    TEXT PRIMARY KEY,
+                description TEXT NOT NULL,
+                owner_id    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS activity_clients (
+                activity    TEXT PRIMARY KEY,
+                client      TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS client_rates (
+                client      TEXT PRIMARY KEY COLLATE NOCASE,
+                rate        REAL NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS account_links (
+                user_id     TEXT PRIMARY KEY,
+                primary_id  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS calendar_feeds (
+                user_id     TEXT PRIMARY KEY,
+                username    TEXT NOT NULL,
+                url         TEXT NOT NULL,
+                keywords    TEXT NOT NULL DEFAULT '',
+                activity    TEXT NOT NULL DEFAULT 'meetings'
+            );
+            CREATE TABLE IF NOT EXISTS calendar_imports (
+                user_id     TEXT NOT NULL,
+                event_key   TEXT NOT NULL,
+                PRIMARY KEY (user_id, event_key)
+            );
+            CREATE TABLE IF NOT EXISTS github_links (
+                login       TEXT PRIMARY KEY,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                mode        TEXT NOT NULL DEFAULT 'suggest'
+            );
+            CREATE TABLE IF NOT EXISTS github_pending (
+                user_id     TEXT PRIMARY KEY,
+                login       TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                mode        TEXT NOT NULL,
+                code        TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS github_repo_rules (
+                pattern     TEXT PRIMARY KEY,
+                activity    TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS github_bursts (
+                user_id     TEXT PRIMARY KEY,
+                username    TEXT NOT NULL,
+                activity    TEXT NOT NULL,
+                first_at    TEXT NOT NULL,
+                last_at     TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS heartbeat_spans (
+                user_id     TEXT PRIMARY KEY,
+                username    TEXT NOT NULL,
+                activity    TEXT NOT NULL,
+                first_at    TEXT NOT NULL,
+                last_at     TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS kiosk_codes (
+                code_hash   TEXT PRIMARY KEY,
+                user_id     TEXT NOT NULL UNIQUE,
+                username    TEXT NOT NULL,
+                created_at  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS api_tokens (
+                token_hash  TEXT PRIMARY KEY,
+                user_id     TEXT NOT NULL UNIQUE,
+                username    TEXT NOT NULL,
+                created_at  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS user_tokens (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                token_hash  TEXT NOT NULL UNIQUE,
+                user_id     TEXT NOT NULL,
+                username    TEXT NOT NULL,
+                scope       TEXT NOT NULL,
+                created_at  TEXT NOT NULL,
+                last_used   TEXT
+            );
+            CREATE TABLE IF NOT EXISTS digest_subscriptions (
+                user_id     TEXT PRIMARY KEY,
+                username    TEXT NOT NULL,
+                email       TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS last_seen (
+                user_id     TEXT NOT NULL,
+                day         TEXT NOT NULL,
+                at          TEXT NOT NULL,
+                PRIMARY KEY (user_id, day)
+            );
             CREATE INDEX IF NOT EXISTS idx_sess_user   ON sessions(user_id);
             CREATE INDEX IF NOT EXISTS idx_sess_end    ON sessions(ended_at);
             CREATE INDEX IF NOT EXISTS idx_arch_user   ON weekly_archive(user_id);
             CREATE INDEX IF NOT EXISTS idx_actarch_user ON activity_archive(user_id);",
         )?;
-        Ok(Self {
-            conn: Mutex::new(conn),
-        })
-    }
-
-    pub fn clock_in(&self, user_id: &str, username: &str, activity: &str) -> anyhow::Result<()> {
-        let conn = self.conn.lock().unwrap();
-        let active: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
-            params![user_id],
-            |r| r.get(0),
-        )?;
-        if active {
-            anyhow::bail!("already clocked in");
+        ensure_column(&conn, "activity_archive", "session_count", "INTEGER NOT NULL DEFAULT 0")?;
+        // Week dates next to the labels, so archives can be queried by date range
+        for table in ["weekly_archive", "activity_archive"] {
+            ensure_column(&conn, table, "week_start", "TEXT")?;
+            ensure_column(&conn, table, "week_end", "TEXT")?;
         }
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_arch_start ON weekly_archive(week_start);
+             CREATE INDEX IF NOT EXISTS idx_actarch_start ON activity_archive(week_start);",
+        )?;
+        backfill_week_dates(&conn)?;
+        relabel_legacy_archives(&conn)?;
+        ensure_column(&conn, "sessions", "auto_out_at", "TEXT")?;
+        // Last active week a re-engagement DM went out about
+        ensure_column(&conn, "user_settings", "reengaged_for", "TEXT")?;
+        ensure_column(&conn, "user_settings", "tips", "INTEGER NOT NULL DEFAULT 1")?;
+        ensure_column(&conn, "user_settings", "last_tip", "TEXT")?;
+        ensure_column(&conn, "sessions", "source", "TEXT NOT NULL DEFAULT 'manual'")?;
+        // Exact lengths of sessions finished before seconds were kept, from their timestamps
+        ensure_column(&conn, "sessions", "seconds", "INTEGER")?;
         conn.execute(
-            "INSERT INTO sessions (user_id,username,activity,started_at) VALUES (?1,?2,?3,?4)",
-            params![user_id, username, activity, now_ch_str()],
+            "UPDATE sessions SET seconds = CAST(ROUND((julianday(ended_at) - julianday(started_at)) * 86400) AS INTEGER)
+             WHERE ended_at IS NOT NULL AND seconds IS NULL",
+            [],
         )?;
-        Ok(())
+        // One running session per user, enforced by SQLite so that clockctl or a second
+        // bot instance can't race a clock-in. Extra running sessions from before the index
+        // existed are closed at their start; the newest one keeps running.
+        conn.execute(
+            "UPDATE sessions SET ended_at = started_at, minutes = 0, seconds = 0
+             WHERE ended_at IS NULL
+               AND id NOT IN (SELECT MAX(id) FROM sessions WHERE ended_at IS NULL GROUP BY user_id)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_sess_one_running ON sessions(user_id) WHERE ended_at IS NULL",
+            [],
+        )?;
+        // Days worked before work_days existed, as far as unarchived sessions still show them
+        conn.execute(
+            "INSERT OR IGNORE INTO work_days (user_id, day)
+             SELECT DISTINCT user_id, substr(started_at, 1, 10) FROM sessions WHERE ended_at IS NOT NULL",
+            [],
+        )?;
+        let db = Self {
+            conn: Mutex::new(conn),
+            cache: QueryCache::new(CACHE_TTL),
+            clock,
+        };
+        db.rebuild_weekly_aggregates()?;
+        Ok(db)
     }
 
-    pub fn clock_out(&self, user_id: &str) -> anyhow::Result<(i64, String)> {
+    /// Serve `key` from the query cache or run `load` and cache its result.
+    /// The result is stored while the connection lock is held so it cannot race
+    /// with a writer's invalidation.
+    fn cached<T: Clone + Send + 'static>(
+        &self,
+        key: &'static str,
+        load: impl FnOnce(&Connection) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if let Some(hit) = self.cache.get(key) {
+            return Ok(hit);
+        }
         let conn = self.conn.lock().unwrap();
-        let row: Option<(i64, String, String)> = conn
-            .query_row(
-                "SELECT id,started_at,activity FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
-                params![user_id],
-                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        let value = load(&conn)?;
+        self.cache.put(key, value.clone());
+        Ok(value)
+    }
+
+    /// Drop all cached query results, e.g. so benchmarks time the queries themselves.
+    pub fn clear_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /// Cheap round-trip to verify the database is reachable.
+    pub fn ping(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT 1", [], |r| r.get::<_, i64>(0))?;
+        Ok(())
+    }
+
+    /// Read a value from the `metadata` key/value table.
+    pub fn metadata(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT value FROM metadata WHERE key = ?1",
+            params![key],
+            |r| r.get(0),
+        ) {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write a value to the `metadata` key/value table.
+    pub fn set_metadata(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_metadata(&self, key: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM metadata WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    /// The time everything in the bot is measured against.
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    fn now_str(&self) -> String {
+        self.clock.now().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// `started_at` lower bound for queries over the running week.
+    fn monday_of_current_week(&self) -> String {
+        self.clock.week().start().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Start a session; fails with `Refusal::ClockedIn` if one is running, which the
+    /// unique index on running sessions decides.
+    pub fn clock_in(&self, user_id: &str, username: &str, activity: &str) -> anyhow::Result<()> {
+        self.clock_in_from(user_id, username, activity, SOURCE_MANUAL)
+    }
+
+    /// `clock_in` for a session started by something other than the user, e.g. a badge reader.
+    pub fn clock_in_from(&self, user_id: &str, username: &str, activity: &str, source: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match conn.execute(
+            "INSERT INTO sessions (user_id,username,activity,started_at,source) VALUES (?1,?2,?3,?4,?5)",
+            params![user_id, username, activity, self.now_str(), source],
+        ) {
+            Ok(_) => {}
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+                return Err(Refusal::ClockedIn.into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    pub fn clock_out(&self, user_id: &str) -> anyhow::Result<(Elapsed, String)> {
+        self.clock_out_at(user_id, self.clock.now())
+    }
+
+    /// End the running session at `end` (Swiss local time), e.g. a scheduled clock-out
+    /// that is processed a little late.
+    pub fn clock_out_at(&self, user_id: &str, end: NaiveDateTime) -> anyhow::Result<(Elapsed, String)> {
+        let conn = self.conn.lock().unwrap();
+        let row: Option<(i64, String, String, String)> = conn
+            .query_row(
+                "SELECT id,started_at,activity,username FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
+                params![user_id],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
             )
             .ok();
         match row {
-            Some((id, started_str, activity)) => {
+            Some((id, started_str, activity, username)) => {
                 let started = NaiveDateTime::parse_from_str(&started_str, "%Y-%m-%d %H:%M:%S")?;
-                let now = now_ch();
-                let minutes = (now - started).num_minutes();
+                let end = end.max(started);
+                let elapsed = Elapsed::between(started, end);
                 conn.execute(
-                    "UPDATE sessions SET ended_at=?1, minutes=?2 WHERE id=?3",
-                    params![now_ch_str(), minutes, id],
+                    "UPDATE sessions SET ended_at=?1, minutes=?2, seconds=?3, auto_out_at=NULL WHERE id=?4",
+                    params![end.format("%Y-%m-%d %H:%M:%S").to_string(), elapsed.minutes(), elapsed.seconds(), id],
+                )?;
+                add_to_aggregates(
+                    &conn,
+                    &WeekId::containing(started.date()).label(),
+                    user_id,
+                    &username,
+                    &activity,
+                    elapsed,
                 )?;
-                Ok((minutes, activity))
+                record_work_days(&conn, user_id, started, end)?;
+                self.cache.invalidate();
+                Ok((elapsed, activity))
             }
             None => anyhow::bail!("not clocked in"),
         }
     }
 
+    /// The account `user_id`'s time is kept under: itself, or the account it was linked to.
+    pub fn primary_account(&self, user_id: &str) -> String {
+        let conn = self.conn.lock().unwrap();
+        primary_account(&conn, user_id).unwrap_or_else(|_| user_id.to_string())
+    }
+
+    /// Accounts linked to `primary_id`.
+    pub fn linked_accounts(&self, primary_id: &str) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user_id FROM account_links WHERE primary_id = ?1 ORDER BY user_id")?;
+        let rows = stmt.query_map(params![primary_id], |r| r.get(0))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Link `secondary` to `primary`, moving its history over so stats, streaks and
+    /// archives count both as one person under `username`. Fails with
+    /// `Refusal::ClockedIn` while `secondary` is clocked in.
+    pub fn link_accounts(&self, secondary: &str, primary: &str, username: &str) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let primary = primary_account(&tx, primary)?;
+        let secondary = primary_account(&tx, secondary)?;
+        if primary == secondary {
+            anyhow::bail!("already linked");
+        }
+        let active: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM sessions WHERE user_id = ?1 AND ended_at IS NULL",
+            params![secondary],
+            |r| r.get(0),
+        )?;
+        if active {
+            return Err(Refusal::ClockedIn.into());
+        }
+
+        let ids = params![secondary, primary, username];
+        tx.execute("UPDATE sessions SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        // Archive rows of the same week (and activity) add up into the primary's row
+        tx.execute(
+            "UPDATE weekly_archive SET total_sec = total_sec + (
+                SELECT SUM(s.total_sec) FROM weekly_archive s
+                WHERE s.user_id = ?1 AND s.week_label = weekly_archive.week_label)
+             WHERE user_id = ?2 AND week_label IN (SELECT week_label FROM weekly_archive WHERE user_id = ?1)",
+            params![secondary, primary],
+        )?;
+        tx.execute(
+            "DELETE FROM weekly_archive WHERE user_id = ?1
+             AND week_label IN (SELECT week_label FROM weekly_archive WHERE user_id = ?2)",
+            params![secondary, primary],
+        )?;
+        tx.execute("UPDATE weekly_archive SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        tx.execute(
+            "UPDATE activity_archive SET
+                total_sec = total_sec + (SELECT SUM(s.total_sec) FROM activity_archive s
+                    WHERE s.user_id = ?1 AND s.week_label = activity_archive.week_label
+                    AND s.activity = activity_archive.activity),
+                session_count = session_count + (SELECT SUM(s.session_count) FROM activity_archive s
+                    WHERE s.user_id = ?1 AND s.week_label = activity_archive.week_label
+                    AND s.activity = activity_archive.activity)
+             WHERE user_id = ?2 AND EXISTS (SELECT 1 FROM activity_archive s
+                WHERE s.user_id = ?1 AND s.week_label = activity_archive.week_label
+                AND s.activity = activity_archive.activity)",
+            params![secondary, primary],
+        )?;
+        tx.execute(
+            "DELETE FROM activity_archive WHERE user_id = ?1 AND EXISTS (SELECT 1 FROM activity_archive p
+                WHERE p.user_id = ?2 AND p.week_label = activity_archive.week_label
+                AND p.activity = activity_archive.activity)",
+            params![secondary, primary],
+        )?;
+        tx.execute("UPDATE activity_archive SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        tx.execute(
+            "UPDATE event_archive SET seconds = seconds + (SELECT s.seconds FROM event_archive s
+                WHERE s.user_id = ?1 AND s.event_id = event_archive.event_id)
+             WHERE user_id = ?2 AND event_id IN (SELECT event_id FROM event_archive WHERE user_id = ?1)",
+            params![secondary, primary],
+        )?;
+        tx.execute("UPDATE OR IGNORE event_archive SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        // Event rows left behind were added to the primary's above
+        tx.execute("DELETE FROM event_archive WHERE user_id = ?1", params![secondary])?;
+        for table in ["work_days", "last_seen"] {
+            tx.execute(
+                &format!("UPDATE OR IGNORE {table} SET user_id = ?2 WHERE user_id = ?1"),
+                params![secondary, primary],
+            )?;
+            // Days the primary already has
+            tx.execute(&format!("DELETE FROM {table} WHERE user_id = ?1"), params![secondary])?;
+        }
+        tx.execute("UPDATE kudos_targets SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        tx.execute("UPDATE kudos SET user_id = ?2, username = ?3 WHERE user_id = ?1", ids)?;
+        // Kudos the two accounts gave each other's posts would now be self-kudos
+        tx.execute("UPDATE OR IGNORE kudos SET giver_id = ?2 WHERE giver_id = ?1", params![secondary, primary])?;
+        tx.execute("DELETE FROM kudos WHERE giver_id = ?1 OR giver_id = user_id", params![secondary])?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO account_links (user_id, primary_id) VALUES (?1, ?2)",
+            params![secondary, primary],
+        )?;
+        tx.execute("UPDATE account_links SET primary_id = ?2 WHERE primary_id = ?1", params![secondary, primary])?;
+        refresh_week_totals(&tx)?;
+        rebuild_aggregates(&tx)?;
+        tx.commit()?;
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    /// Stop counting `user_id` as part of the account it was linked to. History that
+    /// was combined stays with that account. Returns `false` if it wasn't linked.
+    pub fn unlink_account(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM account_links WHERE user_id = ?1", params![user_id])?;
+        self.cache.invalidate();
+        Ok(removed > 0)
+    }
+
+    /// Fold the user's last finished session on the running activity into the running
+    /// one, which then starts when that session started so the gap counts as work.
+    /// Returns `false` when there is nothing to merge.
+    pub fn merge_with_previous(&self, user_id: &str) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let running: Option<(i64, String, String)> = match tx.query_row(
+            "SELECT id, activity, started_at FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
+            params![user_id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        ) {
+            Ok(row) => Some(row),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let Some((running_id, activity, started_at)) = running else {
+            return Ok(false);
+        };
+        let previous: Option<(i64, String)> = match tx.query_row(
+            "SELECT id, started_at FROM sessions
+             WHERE user_id=?1 AND activity=?2 AND ended_at IS NOT NULL AND ended_at <= ?3
+             ORDER BY ended_at DESC LIMIT 1",
+            params![user_id, activity, started_at],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        ) {
+            Ok(row) => Some(row),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let Some((previous_id, previous_start)) = previous else {
+            return Ok(false);
+        };
+        tx.execute("DELETE FROM sessions WHERE id=?1", params![previous_id])?;
+        tx.execute(
+            "UPDATE sessions SET started_at=?1 WHERE id=?2",
+            params![previous_start, running_id],
+        )?;
+        rebuild_aggregates(&tx)?;
+        tx.commit()?;
+        self.cache.invalidate();
+        Ok(true)
+    }
+
+    /// Remember `at` as the user's latest message of that day. Days older than two
+    /// weeks are dropped; they only matter for sessions left running.
+    pub fn record_last_seen(&self, user_id: &str, at: NaiveDateTime) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO last_seen (user_id, day, at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(user_id, day) DO UPDATE SET at = excluded.at",
+            params![
+                user_id,
+                at.format("%Y-%m-%d").to_string(),
+                at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+        )?;
+        conn.execute(
+            "DELETE FROM last_seen WHERE user_id = ?1 AND day < ?2",
+            params![user_id, (at.date() - Duration::days(14)).format("%Y-%m-%d").to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// The user's last message on `day`, if any was seen.
+    pub fn last_seen_on(&self, user_id: &str, day: NaiveDate) -> anyhow::Result<Option<NaiveDateTime>> {
+        let conn = self.conn.lock().unwrap();
+        let at: String = match conn.query_row(
+            "SELECT at FROM last_seen WHERE user_id = ?1 AND day = ?2",
+            params![user_id, day.format("%Y-%m-%d").to_string()],
+            |r| r.get(0),
+        ) {
+            Ok(at) => at,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(NaiveDateTime::parse_from_str(&at, "%Y-%m-%d %H:%M:%S").ok())
+    }
+
+    /// Schedule (or with `None` cancel) an automatic clock-out of the running session.
+    /// Returns `false` when not clocked in.
+    pub fn schedule_clock_out(&self, user_id: &str, at: Option<NaiveDateTime>) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE sessions SET auto_out_at=?2 WHERE user_id=?1 AND ended_at IS NULL",
+            params![user_id, at.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())],
+        )?;
+        self.cache.invalidate();
+        Ok(updated > 0)
+    }
+
+    /// When the running session of `user_id` is scheduled to end, if at all.
+    pub fn scheduled_clock_out(&self, user_id: &str) -> anyhow::Result<Option<NaiveDateTime>> {
+        let conn = self.conn.lock().unwrap();
+        let at: Option<String> = match conn.query_row(
+            "SELECT auto_out_at FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
+            params![user_id],
+            |r| r.get(0),
+        ) {
+            Ok(at) => at,
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(at.and_then(|t| NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S").ok()))
+    }
+
+    /// Users whose scheduled clock-out is at or before `now`, with the scheduled time.
+    pub fn due_clock_outs(&self, now: NaiveDateTime) -> anyhow::Result<Vec<(String, NaiveDateTime)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT user_id, auto_out_at FROM sessions
+             WHERE ended_at IS NULL AND auto_out_at IS NOT NULL AND auto_out_at <= ?1",
+        )?;
+        let rows = stmt.query_map(params![now.format("%Y-%m-%d %H:%M:%S").to_string()], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })?;
+        Ok(rows
+            .filter_map(|r| r.ok())
+            .filter_map(|(user_id, at)| {
+                NaiveDateTime::parse_from_str(&at, "%Y-%m-%d %H:%M:%S").ok().map(|at| (user_id, at))
+            })
+            .collect())
+    }
+
     pub fn active_session(&self, user_id: &str) -> anyhow::Result<Option<ActiveSession>> {
         let conn = self.conn.lock().unwrap();
         match conn.query_row(
-            "SELECT id,user_id,username,activity,started_at FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
+            "SELECT username,activity,started_at FROM sessions WHERE user_id=?1 AND ended_at IS NULL",
             params![user_id],
             |r| Ok(ActiveSession {
-                id: r.get(0)?,
-                user_id: r.get(1)?,
-                username: r.get(2)?,
-                activity: r.get(3)?,
-                started_at: NaiveDateTime::parse_from_str(&r.get::<_,String>(4)?, "%Y-%m-%d %H:%M:%S").unwrap(),
+                username: r.get(0)?,
+                activity: r.get(1)?,
+                started_at: NaiveDateTime::parse_from_str(&r.get::<_,String>(2)?, "%Y-%m-%d %H:%M:%S").unwrap(),
             }),
         ) {
             Ok(s) => Ok(Some(s)),
@@ -197,528 +1538,2463 @@ impl Db {
         }
     }
 
-    pub fn leaderboard_weekly(&self) -> anyhow::Result<Vec<LeaderboardEntry>> {
+    /// `user_id`'s sessions overlapping `day` (Swiss local time), including a running one,
+    /// ordered by start. Only covers the current week: older sessions are archived away.
+    pub fn sessions_on_day(&self, user_id: &str, day: NaiveDate) -> anyhow::Result<Vec<TimelineSession>> {
+        let day_start = day.and_hms_opt(0, 0, 0).unwrap();
+        self.sessions_between(user_id, day_start, day_start + Duration::days(1))
+    }
+
+    /// `user_id`'s sessions overlapping `[from, to)` (Swiss local time), including a running one,
+    /// ordered by start.
+    pub fn sessions_between(
+        &self,
+        user_id: &str,
+        day_start: NaiveDateTime,
+        day_end: NaiveDateTime,
+    ) -> anyhow::Result<Vec<TimelineSession>> {
+        let now = self.clock.now();
         let conn = self.conn.lock().unwrap();
-        let monday = monday_of_current_week();
         let mut stmt = conn.prepare(
-            "SELECT username, SUM(minutes) as total FROM sessions
-             WHERE ended_at IS NOT NULL AND started_at >= ?1
-             GROUP BY user_id ORDER BY total DESC LIMIT 15",
+            "SELECT activity,started_at,ended_at FROM sessions
+             WHERE user_id=?1 AND started_at < ?3 AND (ended_at IS NULL OR ended_at > ?2)
+             ORDER BY started_at",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                user_id,
+                day_start.format("%Y-%m-%d %H:%M:%S").to_string(),
+                day_end.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+            |r| {
+                let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+                Ok(TimelineSession {
+                    activity: r.get(0)?,
+                    started_at: parse(r.get(1)?),
+                    ended_at: r.get::<_, Option<String>>(2)?.map(parse).unwrap_or(now),
+                })
+            },
         )?;
-        let rows = stmt.query_map(params![monday], |r| {
-            Ok(LeaderboardEntry {
-                username: r.get(0)?,
-                total_minutes: r.get(1)?,
-            })
-        })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    pub fn leaderboard_alltime(&self) -> anyhow::Result<Vec<LeaderboardEntry>> {
+    /// `user_id`'s most recently finished activities with when each last ended, newest
+    /// first. Only sessions of the current week are kept, so this is empty after the reset.
+    pub fn recent_activities(&self, user_id: &str, limit: usize) -> anyhow::Result<Vec<(String, NaiveDateTime)>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT username, SUM(mins) as total FROM (
-                SELECT user_id, username, SUM(minutes) as mins FROM sessions
-                    WHERE ended_at IS NOT NULL GROUP BY user_id
-                UNION ALL
-                SELECT user_id, username, SUM(total_min) as mins FROM weekly_archive
-                    GROUP BY user_id
-             ) GROUP BY user_id ORDER BY total DESC LIMIT 15",
+            "SELECT activity, MAX(ended_at) AS last FROM sessions
+             WHERE user_id=?1 AND ended_at IS NOT NULL
+             GROUP BY activity ORDER BY last DESC LIMIT ?2",
         )?;
-        let rows = stmt.query_map([], |r| {
-            Ok(LeaderboardEntry {
-                username: r.get(0)?,
-                total_minutes: r.get(1)?,
-            })
+        let rows = stmt.query_map(params![user_id, limit as i64], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
         })?;
+        Ok(rows
+            .filter_map(|r| r.ok())
+            .filter_map(|(activity, at)| {
+                NaiveDateTime::parse_from_str(&at, "%Y-%m-%d %H:%M:%S").ok().map(|at| (activity, at))
+            })
+            .collect())
+    }
+
+    /// This week's top 15; `live` also counts running sessions up to now.
+    pub fn leaderboard_weekly(&self, live: bool) -> anyhow::Result<Vec<LeaderboardEntry>> {
+        let key = if live { "leaderboard_weekly_live" } else { "leaderboard_weekly" };
+        self.cached(key, |conn| {
+            let (monday, now) = (self.monday_of_current_week(), self.now_str());
+            top_ranked(conn, WEEKLY_TOTALS_SQL, &[(":monday", &monday), (":now", &now), (":live", &live)])
+        })
+    }
+
+    /// `user_id`'s place on this week's leaderboard, also outside the top 15.
+    pub fn weekly_rank(&self, user_id: &str, live: bool) -> anyhow::Result<Option<UserRank>> {
+        let conn = self.conn.lock().unwrap();
+        let (monday, now) = (self.monday_of_current_week(), self.now_str());
+        rank_of(&conn, WEEKLY_TOTALS_SQL, &[(":monday", &monday), (":now", &now), (":live", &live)], user_id)
+    }
+
+    /// This week's leaderboard by score: minutes times the activity's weight (1.0 unless
+    /// set), rounded to whole minutes.
+    pub fn score_leaderboard_weekly(&self) -> anyhow::Result<Vec<LeaderboardEntry>> {
+        self.cached("score_leaderboard_weekly", |conn| {
+            let monday = self.monday_of_current_week();
+            let mut stmt = conn.prepare(
+                "SELECT s.username, CAST(ROUND(SUM(s.seconds * COALESCE(w.weight, 1.0)) / 60) AS INTEGER) as score,
+                        DENSE_RANK() OVER (ORDER BY CAST(ROUND(SUM(s.seconds * COALESCE(w.weight, 1.0)) / 60) AS INTEGER) DESC)
+                 FROM sessions s LEFT JOIN activity_weights w ON w.activity = s.activity
+                 WHERE s.ended_at IS NOT NULL AND s.started_at >= ?1
+                   AND s.activity NOT IN (SELECT activity FROM non_competitive)
+                 GROUP BY s.user_id HAVING score > 0 ORDER BY score DESC, s.username LIMIT 15",
+            )?;
+            let rows = stmt.query_map(params![monday], |r| {
+                Ok(LeaderboardEntry {
+                    username: r.get(0)?,
+                    total_minutes: r.get(1)?,
+                    rank: r.get(2)?,
+                    live: false,
+                })
+            })?;
+            Ok(rows.filter_map(|r| r.ok()).collect())
+        })
+    }
+
+    /// This week's competitive minutes of everyone with a daily goal, with that goal:
+    /// `(username, minutes, daily_goal_min)`.
+    pub fn goal_leaderboard_weekly(&self) -> anyhow::Result<Vec<(String, i64, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT s.username, SUM(s.seconds) / 60, u.daily_goal_min
+             FROM sessions s JOIN user_settings u ON u.user_id = s.user_id
+             WHERE s.ended_at IS NOT NULL AND s.started_at >= ?1 AND u.daily_goal_min > 0
+               AND s.activity NOT IN (SELECT activity FROM non_competitive)
+             GROUP BY s.user_id",
+        )?;
+        let rows = stmt.query_map(params![self.monday_of_current_week()], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    pub fn archive_week(&self, week_label: &str) -> anyhow::Result<()> {
+    /// The all-time top 15; `live` also counts running sessions up to now.
+    pub fn leaderboard_alltime(&self, live: bool) -> anyhow::Result<Vec<LeaderboardEntry>> {
+        let key = if live { "leaderboard_alltime_live" } else { "leaderboard_alltime" };
+        self.cached(key, |conn| {
+            top_ranked(conn, ALLTIME_TOTALS_SQL, &[(":now", &self.now_str()), (":live", &live)])
+        })
+    }
+
+    /// `user_id`'s place on the all-time leaderboard, also outside the top 15.
+    pub fn alltime_rank(&self, user_id: &str, live: bool) -> anyhow::Result<Option<UserRank>> {
         let conn = self.conn.lock().unwrap();
+        rank_of(&conn, ALLTIME_TOTALS_SQL, &[(":now", &self.now_str()), (":live", &live)], user_id)
+    }
+
+    pub fn archive_week(&self, week_label: &str) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        // All or nothing: a failure halfway must not delete sessions that weren't archived
+        let conn = conn.transaction()?;
+        let dates = week_dates(week_label);
+        let (start, end) = (dates.as_ref().map(|d| &d.0), dates.as_ref().map(|d| &d.1));
         // Archive totals per user
         conn.execute(
-            "INSERT INTO weekly_archive (user_id,username,week_label,total_min)
-             SELECT user_id,username,?1,SUM(minutes) FROM sessions
+            "INSERT INTO weekly_archive (user_id,username,week_label,total_sec,week_start,week_end)
+             SELECT user_id,username,?1,SUM(seconds),?2,?3 FROM sessions
              WHERE ended_at IS NOT NULL GROUP BY user_id",
-            params![week_label],
+            params![week_label, start, end],
         )?;
         // Archive per-activity breakdown
         conn.execute(
-            "INSERT INTO activity_archive (user_id,username,week_label,activity,total_min)
-             SELECT user_id,username,?1,activity,SUM(minutes) FROM sessions
+            "INSERT INTO activity_archive (user_id,username,week_label,activity,total_sec,session_count,week_start,week_end)
+             SELECT user_id,username,?1,activity,SUM(seconds),COUNT(*),?2,?3 FROM sessions
              WHERE ended_at IS NOT NULL GROUP BY user_id, activity",
-            params![week_label],
+            params![week_label, start, end],
+        )?;
+        // The week itself, with its totals
+        if let Some((start, end)) = &dates {
+            conn.execute(
+                "INSERT INTO weeks (label, week_start, week_end, archived_at, total_sec, sessions, people)
+                 VALUES (?1, ?2, ?3, ?4, 0, 0, 0)
+                 ON CONFLICT(label) DO UPDATE SET archived_at = excluded.archived_at",
+                params![week_label, start, end, self.now_str()],
+            )?;
+            refresh_week_totals(&conn)?;
+        }
+        // Keep the event time of sessions about to be deleted
+        conn.execute(
+            "INSERT INTO event_archive (event_id, user_id, username, seconds)
+             SELECT e.id, s.user_id, s.username,
+                    CAST(ROUND(SUM(julianday(MIN(s.ended_at, e.ends_at)) - julianday(MAX(s.started_at, e.starts_at))) * 86400) AS INTEGER)
+             FROM events e JOIN sessions s ON s.started_at < e.ends_at AND s.ended_at > e.starts_at
+             WHERE s.ended_at IS NOT NULL AND s.activity NOT IN (SELECT activity FROM non_competitive)
+             GROUP BY e.id, s.user_id
+             ON CONFLICT(event_id, user_id) DO UPDATE SET
+                username = excluded.username,
+                seconds = seconds + excluded.seconds",
+            [],
         )?;
         conn.execute("DELETE FROM sessions WHERE ended_at IS NOT NULL", [])?;
+        // Kudos on posts of closed weeks no longer count
+        conn.execute("DELETE FROM kudos_targets WHERE week_label = ?1", params![week_label])?;
+        // Everything aggregated so far now lives in the archive tables
+        conn.execute("DELETE FROM weekly_aggregates", [])?;
+        conn.commit()?;
+        self.cache.invalidate();
         Ok(())
     }
 
-    /// Activity breakdown for current week per user.
-    pub fn activity_breakdown_weekly(&self) -> anyhow::Result<Vec<ActivityEntry>> {
+    /// Returns `false` if an event with that name (any case) exists.
+    pub fn create_event(&self, name: &str, starts_at: NaiveDateTime, ends_at: NaiveDateTime) -> anyhow::Result<bool> {
         let conn = self.conn.lock().unwrap();
-        let monday = monday_of_current_week();
-        let mut stmt = conn.prepare(
-            "SELECT username, activity, SUM(minutes) as total, COUNT(*) as sessions
-             FROM sessions
-             WHERE ended_at IS NOT NULL AND started_at >= ?1
-             GROUP BY user_id, activity
-             ORDER BY username ASC, total DESC",
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO events (name, starts_at, ends_at) VALUES (?1, ?2, ?3)",
+            params![
+                name,
+                starts_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ends_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
         )?;
-        let rows = stmt.query_map(params![monday], |r| {
-            Ok(ActivityEntry {
-                username: r.get(0)?,
-                activity: r.get(1)?,
-                total_minutes: r.get(2)?,
-                session_count: r.get(3)?,
-            })
-        })?;
+        Ok(added > 0)
+    }
+
+    /// All events, latest start first.
+    pub fn events(&self) -> anyhow::Result<Vec<Event>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, starts_at, ends_at FROM events ORDER BY starts_at DESC")?;
+        let rows = stmt.query_map([], event_from_row)?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    /// Activity breakdown for all time (archived + current).
-    pub fn activity_breakdown_alltime(&self) -> anyhow::Result<Vec<ActivityEntry>> {
+    /// The event named `name`, ignoring case.
+    pub fn event(&self, name: &str) -> anyhow::Result<Option<Event>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT id, name, starts_at, ends_at FROM events WHERE name = ?1",
+            params![name],
+            event_from_row,
+        ) {
+            Ok(e) => Ok(Some(e)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn delete_event(&self, name: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM event_archive WHERE event_id IN (SELECT id FROM events WHERE name = ?1)",
+            params![name],
+        )?;
+        let removed = conn.execute("DELETE FROM events WHERE name = ?1", params![name])?;
+        Ok(removed > 0)
+    }
+
+    /// Competitive minutes per person inside the event window, counting running sessions
+    /// up to `now` and sessions already archived by a weekly reset.
+    pub fn event_standings(&self, event: &Event, now: NaiveDateTime) -> anyhow::Result<Vec<LeaderboardEntry>> {
         let conn = self.conn.lock().unwrap();
+        let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
         let mut stmt = conn.prepare(
-            "SELECT username, activity, SUM(mins) as total, SUM(cnt) as sessions FROM (
-                SELECT username, activity, SUM(minutes) as mins, COUNT(*) as cnt
-                    FROM sessions WHERE ended_at IS NOT NULL
-                    GROUP BY user_id, activity
+            "SELECT username, SUM(secs) / 60 as total, DENSE_RANK() OVER (ORDER BY SUM(secs) / 60 DESC), MAX(live) FROM (
+                SELECT user_id, username,
+                       CAST(ROUND((julianday(MIN(COALESCE(ended_at, ?3), ?2)) - julianday(MAX(started_at, ?1))) * 86400) AS INTEGER) as secs,
+                       ended_at IS NULL AND ?3 < ?2 as live
+                FROM sessions
+                WHERE started_at < ?2 AND COALESCE(ended_at, ?3) > ?1
+                  AND activity NOT IN (SELECT activity FROM non_competitive)
                 UNION ALL
-                SELECT username, activity, SUM(total_min) as mins, 0 as cnt
-                    FROM activity_archive
-                    GROUP BY user_id, activity
-             ) GROUP BY username, activity ORDER BY username ASC, total DESC",
+                SELECT user_id, username, seconds as secs, 0 as live FROM event_archive WHERE event_id = ?4
+             ) GROUP BY user_id HAVING total > 0 ORDER BY total DESC",
+        )?;
+        let rows = stmt.query_map(
+            params![fmt(event.starts_at), fmt(event.ends_at), fmt(now), event.id],
+            |r| {
+                Ok(LeaderboardEntry {
+                    username: r.get(0)?,
+                    total_minutes: r.get(1)?,
+                    rank: r.get(2)?,
+                    live: r.get(3)?,
+                })
+            },
         )?;
-        let rows = stmt.query_map([], |r| {
-            Ok(ActivityEntry {
-                username: r.get(0)?,
-                activity: r.get(1)?,
-                total_minutes: r.get(2)?,
-                session_count: r.get(3)?,
-            })
-        })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    /// Weekly summary data for the automated post.
-    pub fn weekly_summary(&self) -> anyhow::Result<WeeklySummary> {
+    /// Events that ended by `now` and whose winners have not been announced.
+    pub fn events_to_announce(&self, now: NaiveDateTime) -> anyhow::Result<Vec<Event>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, starts_at, ends_at FROM events WHERE announced = 0 AND ends_at <= ?1",
+        )?;
+        let rows = stmt.query_map(params![now.format("%Y-%m-%d %H:%M:%S").to_string()], event_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    pub fn mark_event_announced(&self, id: i64) -> anyhow::Result<()> {
         let conn = self.conn.lock().unwrap();
-        let monday = monday_of_current_week();
+        conn.execute("UPDATE events SET announced = 1 WHERE id = ?1", params![id])?;
+        Ok(())
+    }
 
-        // Total hours, total sessions, unique workers
-        let (total_min, total_sessions, unique_workers): (i64, i64, i64) = conn.query_row(
-            "SELECT COALESCE(SUM(minutes),0), COUNT(*), COUNT(DISTINCT user_id)
-             FROM sessions WHERE ended_at IS NOT NULL AND started_at >= ?1",
-            params![monday],
-            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+    /// Returns `false` if `challenger` already challenged `opponent` this week.
+    /// `progress_posted` skips the mid-week update, e.g. for challenges made after it.
+    pub fn create_challenge(&self, challenge: &Challenge, progress_posted: bool) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO challenges
+                (week_label, channel_id, challenger_id, challenger_name, opponent_id, opponent_name, target_min, progress_posted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                challenge.week_label,
+                challenge.channel_id,
+                challenge.challenger_id,
+                challenge.challenger_name,
+                challenge.opponent_id,
+                challenge.opponent_name,
+                challenge.target_min,
+                progress_posted
+            ],
         )?;
+        Ok(added > 0)
+    }
 
-        // MVP (most minutes)
-        let mvp: Option<(String, i64)> = conn
-            .query_row(
-                "SELECT username, SUM(minutes) as total FROM sessions
-             WHERE ended_at IS NOT NULL AND started_at >= ?1
-             GROUP BY user_id ORDER BY total DESC LIMIT 1",
-                params![monday],
-                |r| Ok((r.get(0)?, r.get(1)?)),
-            )
-            .ok();
+    /// Challenges of the week `week_label`, oldest first.
+    pub fn challenges(&self, week_label: &str) -> anyhow::Result<Vec<Challenge>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM challenges WHERE week_label = ?1 ORDER BY id",
+            CHALLENGE_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![week_label], challenge_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
 
-        // Most popular activity
-        let top_activity: Option<(String, i64)> = conn
-            .query_row(
-                "SELECT activity, SUM(minutes) as total FROM sessions
-             WHERE ended_at IS NOT NULL AND started_at >= ?1
-             GROUP BY activity ORDER BY total DESC LIMIT 1",
-                params![monday],
-                |r| Ok((r.get(0)?, r.get(1)?)),
-            )
-            .ok();
+    /// Challenges of `week_label` still waiting for their mid-week progress post.
+    pub fn challenges_awaiting_progress(&self, week_label: &str) -> anyhow::Result<Vec<Challenge>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM challenges WHERE week_label = ?1 AND progress_posted = 0 ORDER BY id",
+            CHALLENGE_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![week_label], challenge_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
 
-        // Longest single session
-        let longest_session: Option<(String, String, i64)> = conn
-            .query_row(
-                "SELECT username, activity, minutes FROM sessions
-             WHERE ended_at IS NOT NULL AND started_at >= ?1
-             ORDER BY minutes DESC LIMIT 1",
-                params![monday],
-                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
-            )
-            .ok();
+    pub fn mark_challenge_progress_posted(&self, id: i64) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE challenges SET progress_posted = 1 WHERE id = ?1", params![id])?;
+        Ok(())
+    }
 
-        // Per-person breakdown
-        let mut stmt = conn.prepare(
-            "SELECT username, activity, SUM(minutes) as total
-             FROM sessions WHERE ended_at IS NOT NULL AND started_at >= ?1
-             GROUP BY user_id, activity ORDER BY username ASC, total DESC",
+    /// Take the unsettled challenges of `week_label`, marking them settled.
+    pub fn settle_challenges(&self, week_label: &str) -> anyhow::Result<Vec<Challenge>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM challenges WHERE week_label = ?1 AND settled = 0 ORDER BY id",
+            CHALLENGE_COLUMNS
+        ))?;
+        let rows: Vec<Challenge> = stmt
+            .query_map(params![week_label], challenge_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        conn.execute("UPDATE challenges SET settled = 1 WHERE week_label = ?1", params![week_label])?;
+        Ok(rows)
+    }
+
+    /// `user_id`'s completed competitive minutes in `week` (sessions still unarchived).
+    pub fn competitive_minutes(&self, user_id: &str, week: WeekId) -> anyhow::Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        let total: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(seconds), 0) / 60 FROM sessions
+             WHERE user_id = ?1 AND ended_at IS NOT NULL AND started_at >= ?2 AND started_at < ?3
+               AND activity NOT IN (SELECT activity FROM non_competitive)",
+            params![user_id, fmt(week.start()), fmt(week.end())],
+            |r| r.get(0),
         )?;
-        let rows = stmt.query_map(params![monday], |r| {
+        Ok(total)
+    }
+
+    /// Remember a clock-out post of `user_id` so reactions on it count as kudos in the
+    /// week it was posted.
+    pub fn add_kudos_target(&self, message_id: &str, user_id: &str, username: &str, week_label: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO kudos_targets (message_id, user_id, username, week_label) VALUES (?1, ?2, ?3, ?4)",
+            params![message_id, user_id, username, week_label],
+        )?;
+        Ok(())
+    }
+
+    /// Count a kudos from `giver_id` on a clock-out post. Returns `false` for other
+    /// messages, self-kudos and repeats.
+    pub fn give_kudos(&self, message_id: &str, giver_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO kudos (message_id, giver_id, user_id, username, week_label)
+             SELECT message_id, ?2, user_id, username, week_label FROM kudos_targets
+             WHERE message_id = ?1 AND user_id != ?2",
+            params![message_id, giver_id],
+        )?;
+        Ok(added > 0)
+    }
+
+    pub fn take_back_kudos(&self, message_id: &str, giver_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM kudos WHERE message_id = ?1 AND giver_id = ?2",
+            params![message_id, giver_id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Activity breakdown for current week per user.
+    pub fn activity_breakdown_weekly(&self) -> anyhow::Result<Vec<ActivityEntry>> {
+        self.cached("activity_breakdown_weekly", |conn| {
+            let monday = self.monday_of_current_week();
+            let mut stmt = conn.prepare(
+                "SELECT username, activity, SUM(seconds) / 60 as total, COUNT(*) as sessions
+                 FROM sessions
+                 WHERE ended_at IS NOT NULL AND started_at >= ?1
+                 GROUP BY user_id, activity
+                 ORDER BY username ASC, total DESC",
+            )?;
+            let rows = stmt.query_map(params![monday], |r| {
+                Ok(ActivityEntry {
+                    username: r.get(0)?,
+                    activity: r.get(1)?,
+                    total_minutes: r.get(2)?,
+                    session_count: r.get(3)?,
+                })
+            })?;
+            Ok(rows.filter_map(|r| r.ok()).collect())
+        })
+    }
+
+    /// This week's breakdown counting only sessions from `sources`.
+    pub fn activity_breakdown_weekly_from(&self, sources: &[&str]) -> anyhow::Result<Vec<ActivityEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let placeholders = vec!["?"; sources.len()].join(",");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT username, activity, SUM(seconds) / 60 as total, COUNT(*) as sessions
+             FROM sessions
+             WHERE ended_at IS NOT NULL AND started_at >= ? AND source IN ({})
+             GROUP BY user_id, activity
+             ORDER BY username ASC, total DESC",
+            placeholders
+        ))?;
+        let monday = self.monday_of_current_week();
+        let mut args: Vec<&dyn rusqlite::ToSql> = vec![&monday];
+        args.extend(sources.iter().map(|s| s as &dyn rusqlite::ToSql));
+        let rows = stmt.query_map(args.as_slice(), |r| {
             Ok(ActivityEntry {
                 username: r.get(0)?,
                 activity: r.get(1)?,
                 total_minutes: r.get(2)?,
-                session_count: 0,
+                session_count: r.get(3)?,
             })
         })?;
-        let breakdown: Vec<ActivityEntry> = rows.filter_map(|r| r.ok()).collect();
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
 
-        Ok(WeeklySummary {
-            total_minutes: total_min,
-            total_sessions,
-            unique_workers,
-            mvp,
-            top_activity,
-            longest_session,
-            breakdown,
-        })
+    /// This week's finished minutes per session source, most first.
+    pub fn source_totals_weekly(&self) -> anyhow::Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT source, SUM(seconds) / 60 as total FROM sessions
+             WHERE ended_at IS NOT NULL AND started_at >= ?1
+             GROUP BY source ORDER BY total DESC, source",
+        )?;
+        let rows = stmt.query_map(params![self.monday_of_current_week()], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    pub fn who_is_working(&self) -> anyhow::Result<Vec<ActiveSession>> {
+    /// Finished sessions of the current week, of `user_id` or everyone, ordered by start.
+    pub fn session_records(&self, user_id: Option<&str>) -> anyhow::Result<Vec<SessionRecord>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id,user_id,username,activity,started_at FROM sessions WHERE ended_at IS NULL",
+            "SELECT user_id, username, activity, started_at, ended_at, seconds, source
+             FROM sessions WHERE ended_at IS NOT NULL AND (?1 IS NULL OR user_id = ?1)
+             ORDER BY started_at, username",
         )?;
-        let rows = stmt.query_map([], |r| {
-            Ok(ActiveSession {
-                id: r.get(0)?,
-                user_id: r.get(1)?,
-                username: r.get(2)?,
-                activity: r.get(3)?,
-                started_at: NaiveDateTime::parse_from_str(
-                    &r.get::<_, String>(4)?,
-                    "%Y-%m-%d %H:%M:%S",
-                )
-                .unwrap(),
+        let rows = stmt.query_map(params![user_id], |r| {
+            let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+            Ok(SessionRecord {
+                user_id: r.get(0)?,
+                username: r.get(1)?,
+                activity: r.get(2)?,
+                started_at: parse(r.get(3)?),
+                ended_at: parse(r.get(4)?),
+                elapsed: Elapsed::from_seconds(r.get(5)?),
+                source: r.get(6)?,
             })
         })?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    /// Normalize all activity names in `sessions` and `activity_archive` tables.
-    /// Call once on startup to clean up historical data.
-    /// Uses a version flag to run only once.
-    pub fn normalize_activities(&self) -> anyhow::Result<()> {
-        let mut conn = self.conn.lock().unwrap();
+    /// Recompute the incremental weekly aggregates from `sessions`.
+    /// Runs on open so databases from older versions (or after manual edits) are consistent.
+    pub fn rebuild_weekly_aggregates(&self) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        rebuild_aggregates(&conn)
+    }
+
+    /// Weekly summary data for the automated post.
+    /// Reads the precomputed `weekly_aggregates` rows instead of scanning `sessions`.
+    pub fn weekly_summary(&self, week: WeekId) -> anyhow::Result<WeeklySummary> {
+        let conn = self.conn.lock().unwrap();
+        let week_label = week.label();
+
+        // Total hours, total sessions, unique workers
+        let (total_minutes, total_sessions, unique_workers): (i64, i64, i64) = conn.query_row(
+            "SELECT COALESCE(SUM(total_sec),0) / 60, COALESCE(SUM(session_count),0), COUNT(DISTINCT user_id)
+             FROM weekly_aggregates WHERE week_label = ?1",
+            params![week_label],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )?;
 
-        // Check if normalization has already been run
-        let already_normalized: bool = conn
+        // MVP (most minutes)
+        let mvp: Option<(String, String, i64)> = conn
             .query_row(
-                "SELECT value FROM metadata WHERE key = 'activities_normalized'",
-                [],
-                |r| {
-                    let val: String = r.get(0)?;
-                    Ok(val == "true")
-                },
+                "SELECT user_id, username, SUM(total_sec) / 60 as total FROM weekly_aggregates
+             WHERE week_label = ?1 AND activity NOT IN (SELECT activity FROM non_competitive)
+             GROUP BY user_id ORDER BY total DESC LIMIT 1",
+                params![week_label],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
             )
-            .unwrap_or(false);
-
-        if already_normalized {
-            return Ok(());
-        }
+            .ok();
 
-        // Use rusqlite transaction for proper RAII and rollback semantics
-        let tx = conn.transaction()?;
+        // Most popular activity
+        let top_activity: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT activity, SUM(total_sec) / 60 as total FROM weekly_aggregates
+             WHERE week_label = ?1
+             GROUP BY activity ORDER BY total DESC LIMIT 1",
+                params![week_label],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .ok();
 
-        // Step 1: Normalize activities in sessions table
-        let mut stmt = tx.prepare("SELECT DISTINCT activity FROM sessions")?;
-        let activities: Vec<String> = stmt
-            .query_map([], |r| r.get(0))?
-            .filter_map(|r| r.ok())
-            .collect();
-        drop(stmt);
+        // Longest single session
+        let longest_session: Option<(String, String, i64)> = conn
+            .query_row(
+                "SELECT username, activity, longest_sec / 60 FROM weekly_aggregates
+             WHERE week_label = ?1 AND activity NOT IN (SELECT activity FROM non_competitive)
+             ORDER BY longest_sec DESC LIMIT 1",
+                params![week_label],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .ok();
 
-        for original in activities {
-            let normalized = crate::normalize::normalize_activity(&original);
-            if normalized != original {
-                tx.execute(
-                    "UPDATE sessions SET activity = ?1 WHERE activity = ?2",
-                    params![normalized, original],
-                )?;
-            }
-        }
+        let most_appreciated: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT username, COUNT(*) as n FROM kudos WHERE week_label = ?1
+             GROUP BY user_id ORDER BY n DESC LIMIT 1",
+                params![week_label],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .ok();
 
-        // Step 2: Normalize activities in activity_archive table
-        let mut stmt = tx.prepare("SELECT DISTINCT activity FROM activity_archive")?;
-        let activities: Vec<String> = stmt
-            .query_map([], |r| r.get(0))?
-            .filter_map(|r| r.ok())
-            .collect();
-        drop(stmt);
+        // Per-person breakdown
+        let mut stmt = conn.prepare(
+            "SELECT username, activity, total_sec / 60, session_count
+             FROM weekly_aggregates WHERE week_label = ?1
+             ORDER BY username ASC, total_sec DESC",
+        )?;
+        let rows = stmt.query_map(params![week_label], |r| {
+            Ok(ActivityEntry {
+                username: r.get(0)?,
+                activity: r.get(1)?,
+                total_minutes: r.get(2)?,
+                session_count: r.get(3)?,
+            })
+        })?;
+        let breakdown: Vec<ActivityEntry> = rows.filter_map(|r| r.ok()).collect();
 
-        for original in activities {
-            let normalized = crate::normalize::normalize_activity(&original);
-            if normalized != original {
-                tx.execute(
-                    "UPDATE activity_archive SET activity = ?1 WHERE activity = ?2",
-                    params![normalized, original],
-                )?;
-            }
-        }
+        let holidays = holidays_between(&conn, week.monday(), week.monday() + Duration::days(4))?;
+        let non_competitive = non_competitive(&conn)?;
 
-        // Step 3: Merge duplicate rows in activity_archive that now have the same (user_id, week_label, activity)
-        // Find groups with duplicates
-        let mut stmt = tx.prepare(
-            "SELECT user_id, week_label, activity, COUNT(*) as cnt
-             FROM activity_archive
-             GROUP BY user_id, week_label, activity
-             HAVING cnt > 1",
+        let mut stmt = conn.prepare(
+            "SELECT c.client, SUM(a.total_sec) / 60 as total FROM weekly_aggregates a
+             JOIN activity_clients c ON c.activity = a.activity
+             WHERE a.week_label = ?1 GROUP BY c.client ORDER BY total DESC",
         )?;
-        let duplicates: Vec<(String, String, String)> = stmt
-            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+        let clients: Vec<(String, i64)> = stmt
+            .query_map(params![week_label], |r| Ok((r.get(0)?, r.get(1)?)))?
             .filter_map(|r| r.ok())
             .collect();
-        drop(stmt);
 
-        // Prepare statements once for all duplicate groups
-        let mut select_stmt = tx.prepare(
-            "SELECT id, total_min FROM activity_archive
-             WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3
-             ORDER BY id ASC",
-        )?;
-        let mut update_stmt = tx.prepare(
-            "UPDATE activity_archive SET total_min = ?1 WHERE id = ?2"
-        )?;
-        let mut delete_stmt = tx.prepare(
-            "DELETE FROM activity_archive WHERE id = ?1"
+        // Day-by-day minutes of the same sessions as the aggregates, split at midnight
+        let mut stmt = conn.prepare(
+            "SELECT user_id, activity, started_at, ended_at FROM sessions
+             WHERE ended_at IS NOT NULL AND started_at >= ?1 AND started_at < ?2",
         )?;
-
-        // For each duplicate group, keep the row with MIN(id), sum total_min into it, delete rest
-        for (user_id, week_label, activity) in duplicates {
-            // Get all ids and total_min for this group
-            let rows: Vec<(i64, i64)> = select_stmt
-                .query_map(params![&user_id, &week_label, &activity], |r| {
-                    Ok((r.get(0)?, r.get(1)?))
-                })?
-                .filter_map(|r| r.ok())
+        let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        let sessions: Vec<(String, TimelineSession)> = stmt
+            .query_map(params![fmt(week.start()), fmt(week.end())], |r| {
+                let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+                Ok((
+                    r.get(0)?,
+                    TimelineSession {
+                        activity: r.get(1)?,
+                        started_at: parse(r.get(2)?),
+                        ended_at: parse(r.get(3)?),
+                    },
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        let daily = |user_id: Option<&str>| {
+            let mine: Vec<TimelineSession> = sessions
+                .iter()
+                .filter(|(uid, s)| match user_id {
+                    Some(u) => u == uid && !non_competitive.contains(&s.activity),
+                    None => true,
+                })
+                .map(|(_, s)| s.clone())
                 .collect();
+            crate::pace::daily_minutes(&mine, week)
+        };
+        let daily_minutes = daily(None);
+        let mvp_daily_minutes = mvp.as_ref().map(|(uid, _, _)| daily(Some(uid))).unwrap_or_default();
 
-            if rows.len() > 1 {
-                let keep_id = rows[0].0;
-                let total_sum: i64 = rows.iter().map(|(_, mins)| mins).sum();
-
-                // Update the kept row with the sum
-                update_stmt.execute(params![total_sum, keep_id])?;
+        let mut stmt = conn.prepare(
+            "SELECT activity, SUM(total_sec) / 60 as total FROM activity_archive
+             WHERE week_label = ?1 GROUP BY activity ORDER BY total DESC",
+        )?;
+        let previous_activities: Vec<(String, i64)> = stmt
+            .query_map(params![week.previous().label()], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
 
-                // Delete the duplicate rows
-                for (id, _) in rows.iter().skip(1) {
-                    delete_stmt.execute(params![id])?;
-                }
-            }
-        }
+        Ok(WeeklySummary {
+            total_minutes,
+            total_sessions,
+            unique_workers,
+            mvp: mvp.map(|(_, name, total)| (name, total)),
+            top_activity,
+            longest_session,
+            most_appreciated,
+            breakdown,
+            holidays,
+            daily_minutes,
+            mvp_daily_minutes,
+            previous_activities,
+            non_competitive,
+            clients,
+        })
+    }
 
-        drop(select_stmt);
-        drop(update_stmt);
-        drop(delete_stmt);
+    pub fn who_is_working(&self) -> anyhow::Result<Vec<ActiveSession>> {
+        self.cached("who_is_working", |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT username,activity,started_at FROM sessions WHERE ended_at IS NULL",
+            )?;
+            let rows = stmt.query_map([], |r| {
+                Ok(ActiveSession {
+                    username: r.get(0)?,
+                    activity: r.get(1)?,
+                    started_at: NaiveDateTime::parse_from_str(
+                        &r.get::<_, String>(2)?,
+                        "%Y-%m-%d %H:%M:%S",
+                    )
+                    .unwrap(),
+                })
+            })?;
+            Ok(rows.filter_map(|r| r.ok()).collect())
+        })
+    }
 
-        // Mark normalization as complete
-        tx.execute(
-            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('activities_normalized', 'true')",
+    /// Number of users currently clocked in.
+    pub fn active_count(&self) -> anyhow::Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let count = conn.query_row(
+            "SELECT COUNT(*) FROM sessions WHERE ended_at IS NULL",
             [],
+            |r| r.get(0),
         )?;
+        Ok(count)
+    }
 
-        // Commit transaction
-        tx.commit()?;
-
+    /// Remember that the focus role was granted to `user_id` in `guild_id`.
+    pub fn record_focus_role(&self, user_id: &str, guild_id: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO focus_roles (user_id, guild_id) VALUES (?1, ?2)",
+            params![user_id, guild_id],
+        )?;
         Ok(())
     }
 
-    /// Rename all of a user's sessions with `old_activity` to `new_activity`.
-    /// In `sessions`: UPDATE activity for all rows matching (user_id, old_activity).
-    /// In `activity_archive`: UPDATE activity, then merge any resulting duplicates
-    /// by summing total_min for the same (user_id, week_label, new_activity).
-    /// Returns (sessions_updated, archive_rows_merged) counts.
-    pub fn rename_activity(&self, user_id: &str, old_activity: &str, new_activity: &str) -> anyhow::Result<(usize, usize)> {
-        let mut conn = self.conn.lock().unwrap();
+    /// Forget a focus role grant. Returns the guild it was granted in, if any.
+    pub fn take_focus_role(&self, user_id: &str) -> anyhow::Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let guild_id: Option<String> = conn
+            .query_row(
+                "SELECT guild_id FROM focus_roles WHERE user_id = ?1",
+                params![user_id],
+                |r| r.get(0),
+            )
+            .ok();
+        conn.execute("DELETE FROM focus_roles WHERE user_id = ?1", params![user_id])?;
+        Ok(guild_id)
+    }
 
-        // Check that the user actually has sessions or archive entries with old_activity
-        let has_sessions: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM sessions WHERE user_id = ?1 AND activity = ?2",
-            params![user_id, old_activity],
-            |r| r.get(0),
+    /// Focus role grants whose holder is no longer clocked in, as (user_id, guild_id).
+    pub fn stale_focus_roles(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT user_id, guild_id FROM focus_roles
+             WHERE user_id NOT IN (SELECT user_id FROM sessions WHERE ended_at IS NULL)",
         )?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
 
-        let has_archive: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM activity_archive WHERE user_id = ?1 AND activity = ?2",
-            params![user_id, old_activity],
-            |r| r.get(0),
+    /// Number of consecutive days with tracked time directly before `day` (Swiss dates).
+    pub fn consecutive_work_days_before(&self, user_id: &str, day: NaiveDate) -> anyhow::Result<u32> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT day FROM work_days WHERE user_id = ?1 AND day < ?2 ORDER BY day DESC",
         )?;
-
-        if !has_sessions && !has_archive {
-            anyhow::bail!("no sessions found with that activity");
+        let days = stmt.query_map(
+            params![user_id, day.format("%Y-%m-%d").to_string()],
+            |r| r.get::<_, String>(0),
+        )?;
+        let mut expected = day;
+        let mut count = 0;
+        for d in days {
+            let Ok(d) = NaiveDate::parse_from_str(&d?, "%Y-%m-%d") else {
+                break;
+            };
+            expected -= Duration::days(1);
+            if d != expected {
+                break;
+            }
+            count += 1;
         }
+        Ok(count)
+    }
 
-        // Start transaction
-        let tx = conn.transaction()?;
+    /// Add or rename the holiday on `day`.
+    pub fn add_holiday(&self, day: NaiveDate, name: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO holidays (day, name) VALUES (?1, ?2)",
+            params![day.format("%Y-%m-%d").to_string(), name],
+        )?;
+        Ok(())
+    }
 
-        // Update sessions table
-        let sessions_updated = tx.execute(
-            "UPDATE sessions SET activity = ?1 WHERE user_id = ?2 AND activity = ?3",
-            params![new_activity, user_id, old_activity],
+    /// Remove the holiday on `day`. Returns whether there was one.
+    pub fn remove_holiday(&self, day: NaiveDate) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM holidays WHERE day = ?1",
+            params![day.format("%Y-%m-%d").to_string()],
         )?;
+        Ok(removed > 0)
+    }
 
-        // Update activity_archive table
-        tx.execute(
-            "UPDATE activity_archive SET activity = ?1 WHERE user_id = ?2 AND activity = ?3",
-            params![new_activity, user_id, old_activity],
+    /// Holidays from `from` to `to` inclusive, ordered by date.
+    pub fn holidays_between(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<(NaiveDate, String)>> {
+        let conn = self.conn.lock().unwrap();
+        holidays_between(&conn, from, to)
+    }
+
+    pub fn is_holiday(&self, day: NaiveDate) -> anyhow::Result<bool> {
+        Ok(!self.holidays_between(day, day)?.is_empty())
+    }
+
+    /// Register (or replace) `user_id`'s calendar URL, keeping their filter and activity.
+    pub fn set_calendar_feed(&self, user_id: &str, username: &str, url: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO calendar_feeds (user_id, username, url) VALUES (?1, ?2, ?3)
+             ON CONFLICT(user_id) DO UPDATE SET username = excluded.username, url = excluded.url",
+            params![user_id, username, url],
         )?;
+        Ok(())
+    }
 
-        // Merge duplicate archive rows for this user
-        // Find groups with duplicates after the rename
-        let mut stmt = tx.prepare(
-            "SELECT user_id, week_label, activity, COUNT(*) as cnt
-             FROM activity_archive
-             WHERE user_id = ?1 AND activity = ?2
-             GROUP BY user_id, week_label, activity
-             HAVING cnt > 1",
+    /// Returns `false` if `user_id` has no calendar registered.
+    pub fn set_calendar_keywords(&self, user_id: &str, keywords: &[String]) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE calendar_feeds SET keywords = ?2 WHERE user_id = ?1",
+            params![user_id, keywords.join(",")],
         )?;
-        let duplicates: Vec<(String, String, String)> = stmt
-            .query_map(params![user_id, new_activity], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
-            .filter_map(|r| r.ok())
-            .collect();
-        drop(stmt);
+        Ok(updated > 0)
+    }
 
-        let mut archive_rows_merged = 0;
+    /// Returns `false` if `user_id` has no calendar registered.
+    pub fn set_calendar_activity(&self, user_id: &str, activity: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE calendar_feeds SET activity = ?2 WHERE user_id = ?1",
+            params![user_id, activity],
+        )?;
+        Ok(updated > 0)
+    }
 
-        // Prepare statements for merging duplicates
-        let mut select_stmt = tx.prepare(
-            "SELECT id, total_min FROM activity_archive
-             WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3
-             ORDER BY id ASC",
+    /// Stop syncing `user_id`'s calendar. Sessions already imported stay.
+    pub fn remove_calendar_feed(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM calendar_feeds WHERE user_id = ?1", params![user_id])?;
+        Ok(removed > 0)
+    }
+
+    pub fn calendar_feed(&self, user_id: &str) -> anyhow::Result<Option<CalendarFeed>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT user_id,username,url,keywords,activity FROM calendar_feeds WHERE user_id = ?1",
+            params![user_id],
+            calendar_feed_from_row,
+        ) {
+            Ok(f) => Ok(Some(f)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn calendar_feeds(&self) -> anyhow::Result<Vec<CalendarFeed>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user_id,username,url,keywords,activity FROM calendar_feeds")?;
+        let rows = stmt.query_map([], calendar_feed_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Log a calendar event as a finished session. Each `event_key` is considered once;
+    /// events overlapping a session the user already has (e.g. clocked manually) are skipped.
+    /// Returns whether a session was added.
+    pub fn import_calendar_event(
+        &self,
+        user_id: &str,
+        username: &str,
+        activity: &str,
+        event_key: &str,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let new = tx.execute(
+            "INSERT OR IGNORE INTO calendar_imports (user_id, event_key) VALUES (?1, ?2)",
+            params![user_id, event_key],
+        )? > 0;
+        if !new {
+            return Ok(false);
+        }
+
+        let added = insert_imported_session(&tx, user_id, username, activity, (start, end), SOURCE_CALENDAR, &self.now_str())?;
+        tx.commit()?;
+        if added {
+            self.cache.invalidate();
+        }
+        Ok(added)
+    }
+
+    /// Log an externally detected stretch of work as a finished session, unless it
+    /// overlaps a session the user already has. `source` records where it came from.
+    /// Returns whether a session was added.
+    pub fn import_session(
+        &self,
+        user_id: &str,
+        username: &str,
+        activity: &str,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        source: &str,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let added = insert_imported_session(&tx, user_id, username, activity, (start, end), source, &self.now_str())?;
+        tx.commit()?;
+        if added {
+            self.cache.invalidate();
+        }
+        Ok(added)
+    }
+
+    /// Map an access-system ID to a Discord user, replacing its previous mapping.
+    pub fn map_external_id(&self, external_id: &str, user_id: &str, username: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO external_ids (external_id, user_id, username) VALUES (?1, ?2, ?3)",
+            params![external_id, user_id, username],
         )?;
-        let mut update_stmt = tx.prepare(
-            "UPDATE activity_archive SET total_min = ?1 WHERE id = ?2"
+        Ok(())
+    }
+
+    /// Remember an attendance webhook's event id. `false` if it was seen before, i.e. the
+    /// request is a replay. Ids older than `keep` are forgotten; their timestamps are
+    /// rejected anyway.
+    pub fn record_attendance_event(&self, event_id: &str, keep: Duration) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let now = self.clock.now();
+        conn.execute(
+            "DELETE FROM attendance_events WHERE received_at < ?1",
+            params![(now - keep).format("%Y-%m-%d %H:%M:%S").to_string()],
         )?;
-        let mut delete_stmt = tx.prepare(
-            "DELETE FROM activity_archive WHERE id = ?1"
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO attendance_events (event_id, received_at) VALUES (?1, ?2)",
+            params![event_id, now.format("%Y-%m-%d %H:%M:%S").to_string()],
         )?;
+        Ok(inserted > 0)
+    }
 
-        // For each duplicate group, keep the row with MIN(id), sum total_min into it, delete rest
-        for (uid, week_label, activity) in duplicates {
-            let rows: Vec<(i64, i64)> = select_stmt
-                .query_map(params![&uid, &week_label, &activity], |r| {
-                    Ok((r.get(0)?, r.get(1)?))
-                })?
-                .filter_map(|r| r.ok())
-                .collect();
+    pub fn unmap_external_id(&self, external_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM external_ids WHERE external_id = ?1", params![external_id])?;
+        Ok(removed > 0)
+    }
 
-            if rows.len() > 1 {
-                let keep_id = rows[0].0;
-                let total_sum: i64 = rows.iter().map(|(_, mins)| mins).sum();
+    pub fn external_id(&self, external_id: &str) -> anyhow::Result<Option<ExternalId>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT external_id, user_id, username FROM external_ids WHERE external_id = ?1",
+            params![external_id],
+            external_id_from_row,
+        ) {
+            Ok(e) => Ok(Some(e)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-                // Update the kept row with the sum
-                update_stmt.execute(params![total_sum, keep_id])?;
+    pub fn external_ids(&self) -> anyhow::Result<Vec<ExternalId>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT external_id, user_id, username FROM external_ids ORDER BY username, external_id")?;
+        let rows = stmt.query_map([], external_id_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
 
-                // Delete the duplicate rows
-                for (id, _) in rows.iter().skip(1) {
-                    delete_stmt.execute(params![id])?;
-                    archive_rows_merged += 1;
+    /// `import_session` for many rows in one transaction, with multi-row inserts for the
+    /// sessions, their weekly aggregates and work days. Rows overlapping an existing
+    /// session or an earlier row of the batch are skipped. Returns how many were added.
+    pub fn import_sessions(&self, rows: &[ImportRow], source: &str) -> anyhow::Result<usize> {
+        use rusqlite::types::Value;
+        let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let mut primary: HashMap<String, String> = HashMap::new();
+        // Accepted spans per user, to catch overlaps within the batch
+        let mut accepted: HashMap<String, Vec<(NaiveDateTime, NaiveDateTime)>> = HashMap::new();
+        let mut sessions = Vec::new();
+        // (week, user, activity) -> (username, seconds, count, longest)
+        type AggregateKey = (String, String, String);
+        let mut aggregates: HashMap<AggregateKey, (String, i64, i64, i64)> = HashMap::new();
+        let mut days = std::collections::BTreeSet::new();
+        {
+            let mut overlaps = tx.prepare_cached(
+                "SELECT COUNT(*) > 0 FROM sessions
+                 WHERE user_id = ?1 AND started_at < ?3 AND COALESCE(ended_at, ?4) > ?2",
+            )?;
+            let now = self.now_str();
+            for row in rows {
+                if !primary.contains_key(&row.user_id) {
+                    primary.insert(row.user_id.clone(), primary_account(&tx, &row.user_id)?);
+                }
+                let user_id = &primary[&row.user_id];
+                let spans = accepted.entry(user_id.clone()).or_default();
+                if spans.iter().any(|&(s, e)| s < row.ended_at && e > row.started_at)
+                    || overlaps.query_row(params![user_id, fmt(row.started_at), fmt(row.ended_at), now], |r| r.get(0))?
+                {
+                    continue;
+                }
+                spans.push((row.started_at, row.ended_at));
+
+                let elapsed = Elapsed::between(row.started_at, row.ended_at);
+                let seconds = elapsed.seconds();
+                sessions.push(vec![
+                    Value::Text(user_id.clone()),
+                    Value::Text(row.username.clone()),
+                    Value::Text(row.activity.clone()),
+                    Value::Text(fmt(row.started_at)),
+                    Value::Text(fmt(row.ended_at)),
+                    Value::Integer(elapsed.minutes()),
+                    Value::Integer(seconds),
+                    Value::Text(source.to_string()),
+                ]);
+                let week = WeekId::containing(row.started_at.date()).label();
+                let entry = aggregates
+                    .entry((week, user_id.clone(), row.activity.clone()))
+                    .or_insert_with(|| (String::new(), 0, 0, 0));
+                *entry = (row.username.clone(), entry.1 + seconds, entry.2 + 1, entry.3.max(seconds));
+                let mut day = row.started_at.date();
+                while day <= row.ended_at.date() {
+                    days.insert((user_id.clone(), day.format("%Y-%m-%d").to_string()));
+                    day += Duration::days(1);
                 }
             }
         }
+        if sessions.is_empty() {
+            return Ok(0);
+        }
 
-        drop(select_stmt);
-        drop(update_stmt);
-        drop(delete_stmt);
+        insert_rows(
+            &tx,
+            "INTO sessions (user_id,username,activity,started_at,ended_at,minutes,seconds,source)",
+            "",
+            &sessions,
+        )?;
+        let aggregates: Vec<Vec<Value>> = aggregates
+            .into_iter()
+            .map(|((week, user_id, activity), (username, seconds, count, longest))| {
+                vec![
+                    Value::Text(week),
+                    Value::Text(user_id),
+                    Value::Text(username),
+                    Value::Text(activity),
+                    Value::Integer(seconds),
+                    Value::Integer(count),
+                    Value::Integer(longest),
+                ]
+            })
+            .collect();
+        insert_rows(
+            &tx,
+            "INTO weekly_aggregates (week_label, user_id, username, activity, total_sec, session_count, longest_sec)",
+            "ON CONFLICT(week_label, user_id, activity) DO UPDATE SET
+                username = excluded.username,
+                total_sec = total_sec + excluded.total_sec,
+                session_count = session_count + excluded.session_count,
+                longest_sec = MAX(longest_sec, excluded.longest_sec)",
+            &aggregates,
+        )?;
+        let days: Vec<Vec<Value>> = days.into_iter().map(|(u, d)| vec![Value::Text(u), Value::Text(d)]).collect();
+        insert_rows(&tx, "OR IGNORE INTO work_days (user_id, day)", "", &days)?;
 
-        // Commit transaction
         tx.commit()?;
+        self.cache.invalidate();
+        Ok(sessions.len())
+    }
 
-        Ok((sessions_updated, archive_rows_merged))
+    /// Link a GitHub login to a Discord user, replacing any previous link of either.
+    pub fn link_github(&self, login: &str, user_id: &str, username: &str, mode: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM github_links WHERE user_id = ?1", params![user_id])?;
+        conn.execute(
+            "INSERT OR REPLACE INTO github_links (login, user_id, username, mode) VALUES (?1, ?2, ?3, ?4)",
+            params![login.to_lowercase(), user_id, username, mode],
+        )?;
+        Ok(())
     }
 
-    /// Return per-user weekly minutes for the last `weeks` weeks, capped to the top 5 users
-    /// by total minutes in that window.  Week labels are in chronological order (oldest first).
-    pub fn weekly_hours_for_chart(&self, weeks: u32) -> anyhow::Result<ChartData> {
-        if weeks == 0 {
-            anyhow::bail!("weeks must be at least 1");
+    /// Remember a link waiting for proof that `user_id` owns `login`, replacing the
+    /// user's previous one.
+    pub fn set_github_pending(&self, link: &GithubLink, code: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO github_pending (user_id, login, username, mode, code) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![link.user_id, link.login.to_lowercase(), link.username, link.mode, code],
+        )?;
+        Ok(())
+    }
+
+    /// The user's link waiting for verification and its code.
+    pub fn github_pending(&self, user_id: &str) -> anyhow::Result<Option<(GithubLink, String)>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT login, user_id, username, mode, code FROM github_pending WHERE user_id = ?1",
+            params![user_id],
+            |r| {
+                let link = GithubLink { login: r.get(0)?, user_id: r.get(1)?, username: r.get(2)?, mode: r.get(3)? };
+                Ok((link, r.get(4)?))
+            },
+        ) {
+            Ok(pending) => Ok(Some(pending)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
         }
+    }
 
-        let week_labels = generate_week_labels(weeks);
-        let current_week_label = swiss_week_label();
-        let monday = monday_of_current_week();
+    pub fn delete_github_pending(&self, user_id: &str) -> anyhow::Result<()> {
         let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM github_pending WHERE user_id = ?1", params![user_id])?;
+        Ok(())
+    }
 
-        // user_id -> (username, week_label -> minutes)
-        let mut user_data: HashMap<String, (String, HashMap<String, i64>)> = HashMap::new();
+    pub fn unlink_github(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM github_bursts WHERE user_id = ?1", params![user_id])?;
+        let removed = conn.execute("DELETE FROM github_links WHERE user_id = ?1", params![user_id])?;
+        Ok(removed > 0)
+    }
 
-        // ── Past weeks: pull from weekly_archive ──────────────────────────
-        let past_labels: Vec<&str> = week_labels
-            .iter()
-            .filter(|wl| wl.as_str() != current_week_label.as_str())
-            .map(String::as_str)
-            .collect();
+    pub fn github_link_by_login(&self, login: &str) -> anyhow::Result<Option<GithubLink>> {
+        self.github_link_where("login", &login.to_lowercase())
+    }
 
-        if !past_labels.is_empty() {
-            let placeholders = past_labels.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-            let sql = format!(
-                "SELECT user_id, username, week_label, SUM(total_min) as total \
-                 FROM weekly_archive WHERE week_label IN ({}) \
-                 GROUP BY user_id, week_label",
-                placeholders
-            );
-            let mut stmt = conn.prepare(&sql)?;
-            let rows = stmt.query_map(rusqlite::params_from_iter(past_labels.iter()), |r| {
-                Ok((
-                    r.get::<_, String>(0)?,
-                    r.get::<_, String>(1)?,
-                    r.get::<_, String>(2)?,
-                    r.get::<_, i64>(3)?,
-                ))
-            })?;
-            for row in rows.filter_map(|r| r.ok()) {
-                let (uid, username, week_label, minutes) = row;
-                let entry = user_data
-                    .entry(uid)
-                    .or_insert_with(|| (username, HashMap::new()));
-                *entry.1.entry(week_label).or_insert(0) += minutes;
-            }
+    pub fn github_link_for_user(&self, user_id: &str) -> anyhow::Result<Option<GithubLink>> {
+        self.github_link_where("user_id", user_id)
+    }
+
+    fn github_link_where(&self, column: &str, value: &str) -> anyhow::Result<Option<GithubLink>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            &format!("SELECT login, user_id, username, mode FROM github_links WHERE {} = ?1", column),
+            params![value],
+            |r| {
+                Ok(GithubLink {
+                    login: r.get(0)?,
+                    user_id: r.get(1)?,
+                    username: r.get(2)?,
+                    mode: r.get(3)?,
+                })
+            },
+        ) {
+            Ok(l) => Ok(Some(l)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
         }
+    }
 
-        // ── Current week: pull from sessions ──────────────────────────────
-        let mut stmt = conn.prepare(
-            "SELECT user_id, username, SUM(minutes) as total FROM sessions \
-             WHERE ended_at IS NOT NULL AND started_at >= ?1 \
-             GROUP BY user_id",
+    /// Repo pattern → activity rules, in the order they were added.
+    pub fn github_repo_rules(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT pattern, activity FROM github_repo_rules ORDER BY rowid")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    pub fn set_github_repo_rule(&self, pattern: &str, activity: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO github_repo_rules (pattern, activity) VALUES (?1, ?2)
+             ON CONFLICT(pattern) DO UPDATE SET activity = excluded.activity",
+            params![pattern.to_lowercase(), activity],
         )?;
-        let rows = stmt.query_map(params![monday], |r| {
-            Ok((
-                r.get::<_, String>(0)?,
-                r.get::<_, String>(1)?,
-                r.get::<_, i64>(2)?,
-            ))
-        })?;
-        for row in rows.filter_map(|r| r.ok()) {
-            let (uid, username, minutes) = row;
-            let entry = user_data
-                .entry(uid)
-                .or_insert_with(|| (username, HashMap::new()));
-            *entry.1.entry(current_week_label.clone()).or_insert(0) += minutes;
-        }
+        Ok(())
+    }
 
-        // ── Find top 5 by total minutes in the window ─────────────────────
-        let mut user_totals: Vec<(String, String, i64)> = user_data
-            .iter()
-            .map(|(uid, (name, weeks_map))| {
-                let total: i64 = weeks_map.values().sum();
-                (uid.clone(), name.clone(), total)
-            })
+    pub fn remove_github_repo_rule(&self, pattern: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM github_repo_rules WHERE pattern = ?1",
+            params![pattern.to_lowercase()],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Fold GitHub activity between `first` and `last` into the user's open burst.
+    /// A different activity or a pause longer than `gap` closes the open burst (returned)
+    /// and starts a new one; the flag says whether a new burst was started.
+    pub fn record_github_activity(
+        &self,
+        user_id: &str,
+        username: &str,
+        activity: &str,
+        first: NaiveDateTime,
+        last: NaiveDateTime,
+        gap: Duration,
+    ) -> anyhow::Result<(bool, Option<ActivityBurst>)> {
+        let burst = ActivityBurst {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            activity: activity.to_string(),
+            first_at: first,
+            last_at: last,
+        };
+        self.record_burst("github_bursts", &burst, gap)
+    }
+
+    /// Remove and return GitHub bursts with no activity since `idle_before`.
+    pub fn take_idle_github_bursts(&self, idle_before: NaiveDateTime) -> anyhow::Result<Vec<ActivityBurst>> {
+        self.take_idle_bursts("github_bursts", idle_before)
+    }
+
+    /// Fold an editor heartbeat into the user's open span, returning the span it closed, if any.
+    pub fn record_heartbeat(
+        &self,
+        user_id: &str,
+        username: &str,
+        activity: &str,
+        at: NaiveDateTime,
+        gap: Duration,
+    ) -> anyhow::Result<Option<ActivityBurst>> {
+        let span = ActivityBurst {
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            activity: activity.to_string(),
+            first_at: at,
+            last_at: at,
+        };
+        let (_, closed) = self.record_burst("heartbeat_spans", &span, gap)?;
+        Ok(closed)
+    }
+
+    /// Remove and return heartbeat spans with no heartbeat since `idle_before`.
+    pub fn take_idle_heartbeat_spans(&self, idle_before: NaiveDateTime) -> anyhow::Result<Vec<ActivityBurst>> {
+        self.take_idle_bursts("heartbeat_spans", idle_before)
+    }
+
+    /// Fold `burst` into the open row of `table` for its user (see `record_github_activity`).
+    fn record_burst(&self, table: &str, burst: &ActivityBurst, gap: Duration) -> anyhow::Result<(bool, Option<ActivityBurst>)> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let open = tx
+            .query_row(
+                &format!("SELECT user_id, username, activity, first_at, last_at FROM {} WHERE user_id = ?1", table),
+                params![burst.user_id],
+                activity_burst_from_row,
+            )
+            .ok();
+
+        let fmt = |t: NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        let result = match open {
+            Some(open) if open.activity == burst.activity && burst.first_at - open.last_at <= gap => {
+                tx.execute(
+                    &format!("UPDATE {} SET first_at = ?2, last_at = ?3 WHERE user_id = ?1", table),
+                    params![
+                        burst.user_id,
+                        fmt(open.first_at.min(burst.first_at)),
+                        fmt(open.last_at.max(burst.last_at))
+                    ],
+                )?;
+                (false, None)
+            }
+            closed => {
+                tx.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {} (user_id, username, activity, first_at, last_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        table
+                    ),
+                    params![burst.user_id, burst.username, burst.activity, fmt(burst.first_at), fmt(burst.last_at)],
+                )?;
+                (true, closed)
+            }
+        };
+        tx.commit()?;
+        Ok(result)
+    }
+
+    fn take_idle_bursts(&self, table: &str, idle_before: NaiveDateTime) -> anyhow::Result<Vec<ActivityBurst>> {
+        let conn = self.conn.lock().unwrap();
+        let cutoff = idle_before.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT user_id, username, activity, first_at, last_at FROM {} WHERE last_at < ?1",
+            table
+        ))?;
+        let bursts: Vec<ActivityBurst> = stmt
+            .query_map(params![cutoff], activity_burst_from_row)?
+            .filter_map(|r| r.ok())
             .collect();
-        user_totals.sort_by(|a, b| b.2.cmp(&a.2));
-        user_totals.truncate(5);
+        conn.execute(&format!("DELETE FROM {} WHERE last_at < ?1", table), params![cutoff])?;
+        Ok(bursts)
+    }
 
-        // ── Build ordered output ───────────────────────────────────────────
-        let users: Vec<UserWeeklyData> = user_totals
-            .into_iter()
-            .map(|(uid, username, _)| {
-                let weeks_map = user_data
-                    .remove(&uid)
-                    .map(|(_, m)| m)
-                    .unwrap_or_default();
-                let minutes_per_week = week_labels
-                    .iter()
-                    .map(|wl| *weeks_map.get(wl).unwrap_or(&0))
-                    .collect();
+    /// Replace `user_id`'s ingest API key with the one hashing to `token_hash`.
+    pub fn set_api_token(&self, user_id: &str, username: &str, token_hash: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM api_tokens WHERE user_id = ?1", params![user_id])?;
+        conn.execute(
+            "INSERT INTO api_tokens (token_hash, user_id, username, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![token_hash, user_id, username, self.now_str()],
+        )?;
+        Ok(())
+    }
+
+    pub fn revoke_api_token(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM heartbeat_spans WHERE user_id = ?1", params![user_id])?;
+        let removed = conn.execute("DELETE FROM api_tokens WHERE user_id = ?1", params![user_id])?;
+        Ok(removed > 0)
+    }
+
+    /// `(user_id, username)` owning the API key that hashes to `token_hash`.
+    pub fn api_token_user(&self, token_hash: &str) -> anyhow::Result<Option<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT user_id, username FROM api_tokens WHERE token_hash = ?1",
+            params![token_hash],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        ) {
+            Ok(u) => Ok(Some(u)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace `user_id`'s kiosk code.
+    pub fn set_kiosk_code(&self, user_id: &str, username: &str, code_hash: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM kiosk_codes WHERE user_id = ?1", params![user_id])?;
+        conn.execute(
+            "INSERT INTO kiosk_codes (code_hash, user_id, username, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![code_hash, user_id, username, self.now_str()],
+        )?;
+        Ok(())
+    }
+
+    pub fn revoke_kiosk_code(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM kiosk_codes WHERE user_id = ?1", params![user_id])?;
+        Ok(removed > 0)
+    }
+
+    /// `(user_id, username)` owning the kiosk code that hashes to `code_hash`.
+    pub fn kiosk_code_user(&self, code_hash: &str) -> anyhow::Result<Option<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT user_id, username FROM kiosk_codes WHERE code_hash = ?1",
+            params![code_hash],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        ) {
+            Ok(u) => Ok(Some(u)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Store a new token for `user_id` and return its id. Users can hold several.
+    pub fn create_user_token(&self, user_id: &str, username: &str, token_hash: &str, scope: &str) -> anyhow::Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_tokens (token_hash, user_id, username, scope, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![token_hash, user_id, username, scope, self.now_str()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// `user_id`'s tokens, oldest first.
+    pub fn user_tokens(&self, user_id: &str) -> anyhow::Result<Vec<UserToken>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, user_id, username, scope, created_at, last_used FROM user_tokens
+             WHERE user_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![user_id], user_token_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Revoke one of `user_id`'s tokens. Returns `false` if they have none with that id.
+    pub fn revoke_user_token(&self, user_id: &str, id: i64) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM user_tokens WHERE id = ?1 AND user_id = ?2", params![id, user_id])?;
+        Ok(removed > 0)
+    }
+
+    /// The token hashing to `token_hash`, marked as used now.
+    pub fn use_user_token(&self, token_hash: &str) -> anyhow::Result<Option<UserToken>> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE user_tokens SET last_used = ?2 WHERE token_hash = ?1",
+            params![token_hash, self.now_str()],
+        )?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        let token = conn.query_row(
+            "SELECT id, user_id, username, scope, created_at, last_used FROM user_tokens WHERE token_hash = ?1",
+            params![token_hash],
+            user_token_from_row,
+        )?;
+        Ok(Some(token))
+    }
+
+    /// Subscribe `user_id` to the weekly email digest, replacing their previous address.
+    pub fn subscribe_digest(&self, user_id: &str, username: &str, email: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO digest_subscriptions (user_id, username, email) VALUES (?1, ?2, ?3)
+             ON CONFLICT(user_id) DO UPDATE SET username = excluded.username, email = excluded.email",
+            params![user_id, username, email],
+        )?;
+        Ok(())
+    }
+
+    pub fn unsubscribe_digest(&self, user_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM digest_subscriptions WHERE user_id = ?1", params![user_id])?;
+        Ok(removed > 0)
+    }
+
+    pub fn digest_email(&self, user_id: &str) -> anyhow::Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT email FROM digest_subscriptions WHERE user_id = ?1",
+            params![user_id],
+            |r| r.get(0),
+        ) {
+            Ok(email) => Ok(Some(email)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// `(user_id, username, email)` of every digest subscriber.
+    pub fn digest_subscribers(&self) -> anyhow::Result<Vec<(String, String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT user_id, username, email FROM digest_subscriptions ORDER BY username")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Archived weeks starting between `from` and `to` (inclusive), oldest first.
+    pub fn archived_weeks(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<ArchivedWeek>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT label, week_start, week_end, archived_at, total_sec / 60, sessions, people FROM weeks
+             WHERE week_start BETWEEN ?1 AND ?2 ORDER BY week_start",
+        )?;
+        let rows = stmt.query_map(params![from.to_string(), to.to_string()], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, Option<String>>(3)?,
+                (r.get(4)?, r.get(5)?, r.get(6)?),
+            ))
+        })?;
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok();
+        Ok(rows
+            .filter_map(|r| r.ok())
+            .filter_map(|(label, start, end, archived_at, (total_minutes, sessions, people))| {
+                Some(ArchivedWeek {
+                    label,
+                    start: date(&start)?,
+                    end: date(&end)?,
+                    archived_at: archived_at.and_then(|t| NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S").ok()),
+                    total_minutes,
+                    sessions,
+                    people,
+                })
+            })
+            .collect())
+    }
+
+    /// Everything `/clock admin check` reports: implausible and forgotten sessions, archived
+    /// totals that don't add up, and weeks archived twice.
+    pub fn integrity_findings(&self) -> anyhow::Result<Vec<Finding>> {
+        let conn = self.conn.lock().unwrap();
+        let parse = |s: String| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut findings = Vec::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, username, activity, started_at,
+                    CAST(ROUND((julianday(ended_at) - julianday(started_at)) * 1440) AS INTEGER) AS mins
+             FROM sessions
+             WHERE ended_at IS NOT NULL AND (ended_at < started_at OR seconds < 0 OR mins > ?1)
+             ORDER BY started_at",
+        )?;
+        let rows = stmt.query_map(params![MAX_PLAUSIBLE_MINUTES], |r| {
+            Ok(Finding::BadSession {
+                id: r.get(0)?,
+                username: r.get(1)?,
+                activity: r.get(2)?,
+                started_at: parse(r.get(3)?),
+                minutes: r.get(4)?,
+            })
+        })?;
+        findings.extend(rows.filter_map(|r| r.ok()));
+
+        let cutoff = (self.clock.now() - Duration::days(STALE_SESSION_DAYS)).format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut stmt = conn.prepare(
+            "SELECT username, activity, started_at FROM sessions
+             WHERE ended_at IS NULL AND started_at < ?1 ORDER BY started_at",
+        )?;
+        let rows = stmt.query_map(params![cutoff], |r| {
+            Ok(Finding::StaleSession { username: r.get(0)?, activity: r.get(1)?, started_at: parse(r.get(2)?) })
+        })?;
+        findings.extend(rows.filter_map(|r| r.ok()));
+
+        let mut stmt = conn.prepare(
+            "SELECT week_label, MAX(username), SUM(total) / 60, SUM(activities) / 60 FROM (
+                SELECT user_id, week_label, username, total_sec AS total, 0 AS activities FROM weekly_archive
+                UNION ALL
+                SELECT user_id, week_label, username, 0, total_sec FROM activity_archive
+             ) GROUP BY user_id, week_label HAVING SUM(total) != SUM(activities)
+             ORDER BY week_label",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok(Finding::ArchiveMismatch {
+                week_label: r.get(0)?,
+                username: r.get(1)?,
+                total: r.get(2)?,
+                activities: r.get(3)?,
+            })
+        })?;
+        findings.extend(rows.filter_map(|r| r.ok()));
+
+        let mut stmt = conn.prepare(
+            "SELECT week_label, MAX(username), NULL, COUNT(*) FROM weekly_archive
+                GROUP BY user_id, week_label HAVING COUNT(*) > 1
+             UNION ALL
+             SELECT week_label, MAX(username), activity, COUNT(*) FROM activity_archive
+                GROUP BY user_id, week_label, activity HAVING COUNT(*) > 1",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok(Finding::DuplicateArchive {
+                week_label: r.get(0)?,
+                username: r.get(1)?,
+                activity: r.get(2)?,
+                rows: r.get(3)?,
+            })
+        })?;
+        findings.extend(rows.filter_map(|r| r.ok()));
+        Ok(findings)
+    }
+
+    /// `user_id`'s per-activity totals for an archived week, largest first.
+    pub fn archived_activities(&self, user_id: &str, week_label: &str) -> anyhow::Result<Vec<ActivityEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT username, activity, total_sec / 60, session_count FROM activity_archive
+             WHERE user_id = ?1 AND week_label = ?2 ORDER BY total_sec DESC",
+        )?;
+        let rows = stmt.query_map(params![user_id, week_label], |r| {
+            Ok(ActivityEntry {
+                username: r.get(0)?,
+                activity: r.get(1)?,
+                total_minutes: r.get(2)?,
+                session_count: r.get(3)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Write a consistent copy of the whole database to `path`, which must not exist.
+    pub fn copy_to(&self, path: &Path) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Delete every row of every table, then vacuum so nothing is left in free pages.
+    /// The schema stays, so the bot keeps working on an empty database.
+    pub fn delete_all_data(&self) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let tables: Vec<String> = tx
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")?
+            .query_map([], |r| r.get(0))?
+            .collect::<Result<_, _>>()?;
+        for table in &tables {
+            tx.execute(&format!("DELETE FROM \"{}\"", table), [])?;
+        }
+        tx.commit()?;
+        conn.execute_batch("VACUUM")?;
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    /// Minutes archived for `week_label`, for one user or (`None`) everyone.
+    /// `None` if nothing was archived for it.
+    pub fn archived_week_total(&self, user_id: Option<&str>, week_label: &str) -> anyhow::Result<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let total = conn.query_row(
+            "SELECT SUM(total_sec) / 60 FROM weekly_archive WHERE week_label = ?1 AND (?2 IS NULL OR user_id = ?2)",
+            params![week_label, user_id],
+            |r| r.get(0),
+        )?;
+        Ok(total)
+    }
+
+    /// Goal, timezone and nudge preferences for `user_id`; defaults if never set.
+    pub fn user_settings(&self, user_id: &str) -> anyhow::Result<UserSettings> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT user_id,daily_goal_min,timezone,nudges,last_nudged,tips,last_tip FROM user_settings WHERE user_id=?1",
+            params![user_id],
+            user_settings_from_row,
+        ) {
+            Ok(s) => Ok(s),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(UserSettings::defaults(user_id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Set or clear (`None`) the daily goal in minutes.
+    pub fn set_daily_goal(&self, user_id: &str, minutes: Option<i64>) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, daily_goal_min) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET daily_goal_min = excluded.daily_goal_min",
+            params![user_id, minutes],
+        )?;
+        Ok(())
+    }
+
+    /// Set the IANA timezone name; callers validate it.
+    pub fn set_timezone(&self, user_id: &str, timezone: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, timezone) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET timezone = excluded.timezone",
+            params![user_id, timezone],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_nudges(&self, user_id: &str, enabled: bool) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, nudges) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET nudges = excluded.nudges",
+            params![user_id, enabled],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_tips(&self, user_id: &str, enabled: bool) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, tips) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET tips = excluded.tips",
+            params![user_id, enabled],
+        )?;
+        Ok(())
+    }
+
+    /// Record that `user_id` was shown a tip on their local `date`.
+    pub fn mark_tip_shown(&self, user_id: &str, date: NaiveDate) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, last_tip) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET last_tip = excluded.last_tip",
+            params![user_id, date.format("%Y-%m-%d").to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Users with a daily goal who have not turned nudges off.
+    pub fn nudge_candidates(&self) -> anyhow::Result<Vec<UserSettings>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT user_id,daily_goal_min,timezone,nudges,last_nudged,tips,last_tip FROM user_settings
+             WHERE daily_goal_min IS NOT NULL AND nudges = 1",
+        )?;
+        let rows = stmt.query_map([], user_settings_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Record that `user_id` was nudged on their local `date`.
+    pub fn mark_nudged(&self, user_id: &str, date: NaiveDate) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE user_settings SET last_nudged = ?2 WHERE user_id = ?1",
+            params![user_id, date.format("%Y-%m-%d").to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// People who logged time before but none since the week starting `last_active_before`
+    /// (nor this week), leaving out those who turned nudges off and those already sent a
+    /// re-engagement DM about the same last week.
+    pub fn inactive_users(&self, last_active_before: NaiveDate) -> anyhow::Result<Vec<InactiveUser>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT w.user_id, MAX(w.username),
+                    (SELECT week_label FROM weekly_archive WHERE user_id = w.user_id AND total_sec > 0
+                     ORDER BY week_start DESC LIMIT 1) AS last_week,
+                    (SELECT week_label FROM weekly_archive WHERE user_id = w.user_id
+                     ORDER BY total_sec DESC, week_start DESC LIMIT 1),
+                    MAX(w.total_sec) / 60
+             FROM weekly_archive w
+             LEFT JOIN user_settings u ON u.user_id = w.user_id
+             WHERE w.total_sec > 0 AND COALESCE(u.nudges, 1) = 1
+               AND w.user_id NOT IN (SELECT user_id FROM sessions)
+             GROUP BY w.user_id
+             HAVING MAX(w.week_start) < ?1 AND last_week IS NOT COALESCE(MAX(u.reengaged_for), '')
+             ORDER BY w.user_id",
+        )?;
+        let rows = stmt.query_map(params![last_active_before.to_string()], |r| {
+            Ok(InactiveUser {
+                user_id: r.get(0)?,
+                username: r.get(1)?,
+                last_week: r.get(2)?,
+                best_week: r.get(3)?,
+                best_minutes: r.get(4)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Record that `user_id` got a re-engagement DM about being away since `last_week`.
+    pub fn mark_reengaged(&self, user_id: &str, last_week: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_settings (user_id, reengaged_for) VALUES (?1, ?2)
+             ON CONFLICT(user_id) DO UPDATE SET reengaged_for = excluded.reengaged_for",
+            params![user_id, last_week],
+        )?;
+        Ok(())
+    }
+
+    /// Normalize all activity names in `sessions` and `activity_archive` tables.
+    /// Call once on startup to clean up historical data.
+    /// Uses a version flag to run only once.
+    /// Returns `false` if the names were already normalized with the current rules.
+    pub fn normalize_activities(&self) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+
+        // Skip if the stored names went through this version of the rules already.
+        // The boolean flag from before versions were kept means version 1.
+        let version: u32 = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE key = 'normalization_version'
+                 UNION ALL
+                 SELECT '1' FROM metadata WHERE key = 'activities_normalized' AND value = 'true'",
+                [],
+                |r| r.get::<_, String>(0),
+            )
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        if version >= crate::normalize::NORMALIZATION_VERSION {
+            return Ok(false);
+        }
+
+        // Use rusqlite transaction for proper RAII and rollback semantics
+        let tx = conn.transaction()?;
+
+        // Step 1: Normalize activities in sessions table
+        let mut stmt = tx.prepare("SELECT DISTINCT activity FROM sessions")?;
+        let activities: Vec<String> = stmt
+            .query_map([], |r| r.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        for original in activities {
+            let normalized = crate::normalize::normalize_activity(&original);
+            if normalized != original {
+                tx.execute(
+                    "UPDATE sessions SET activity = ?1 WHERE activity = ?2",
+                    params![normalized, original],
+                )?;
+            }
+        }
+
+        // Step 2: Normalize activities in activity_archive table
+        let mut stmt = tx.prepare("SELECT DISTINCT activity FROM activity_archive")?;
+        let activities: Vec<String> = stmt
+            .query_map([], |r| r.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        for original in activities {
+            let normalized = crate::normalize::normalize_activity(&original);
+            if normalized != original {
+                tx.execute(
+                    "UPDATE activity_archive SET activity = ?1 WHERE activity = ?2",
+                    params![normalized, original],
+                )?;
+            }
+        }
+
+        // Step 3: Merge duplicate rows in activity_archive that now have the same (user_id, week_label, activity)
+        // Find groups with duplicates
+        let mut stmt = tx.prepare(
+            "SELECT user_id, week_label, activity, COUNT(*) as cnt
+             FROM activity_archive
+             GROUP BY user_id, week_label, activity
+             HAVING cnt > 1",
+        )?;
+        let duplicates: Vec<(String, String, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        // Prepare statements once for all duplicate groups
+        let mut select_stmt = tx.prepare(
+            "SELECT id, total_sec, session_count FROM activity_archive
+             WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3
+             ORDER BY id ASC",
+        )?;
+        let mut update_stmt = tx.prepare(
+            "UPDATE activity_archive SET total_sec = ?1, session_count = ?2 WHERE id = ?3"
+        )?;
+        let mut delete_stmt = tx.prepare(
+            "DELETE FROM activity_archive WHERE id = ?1"
+        )?;
+
+        // For each duplicate group, keep the row with MIN(id), sum total_sec into it, delete rest
+        for (user_id, week_label, activity) in duplicates {
+            // Get all ids and total_sec for this group
+            let rows: Vec<(i64, i64, i64)> = select_stmt
+                .query_map(params![&user_id, &week_label, &activity], |r| {
+                    Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+                })?
+                .filter_map(|r| r.ok())
+                .collect();
+
+            if rows.len() > 1 {
+                let keep_id = rows[0].0;
+                let total_sum: i64 = rows.iter().map(|(_, secs, _)| secs).sum();
+                let count_sum: i64 = rows.iter().map(|(_, _, cnt)| cnt).sum();
+
+                // Update the kept row with the sum
+                update_stmt.execute(params![total_sum, count_sum, keep_id])?;
+
+                // Delete the duplicate rows
+                for (id, _, _) in rows.iter().skip(1) {
+                    delete_stmt.execute(params![id])?;
+                }
+            }
+        }
+
+        drop(select_stmt);
+        drop(update_stmt);
+        drop(delete_stmt);
+
+        rebuild_aggregates(&tx)?;
+
+        // Record which rules the names now follow
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('normalization_version', ?1)",
+            params![crate::normalize::NORMALIZATION_VERSION.to_string()],
+        )?;
+        tx.execute("DELETE FROM metadata WHERE key = 'activities_normalized'", [])?;
+
+        // Commit transaction
+        tx.commit()?;
+        self.cache.invalidate();
+
+        Ok(true)
+    }
+
+    /// Rename all of a user's sessions with `old_activity` to `new_activity`.
+    /// In `sessions`: UPDATE activity for all rows matching (user_id, old_activity).
+    /// In `activity_archive`: UPDATE activity, then merge any resulting duplicates
+    /// by summing total_sec for the same (user_id, week_label, new_activity).
+    /// Returns (sessions_updated, archive_rows_merged) counts, `None` if the user has
+    /// nothing under `old_activity`.
+    pub fn rename_activity(&self, user_id: &str, old_activity: &str, new_activity: &str) -> anyhow::Result<Option<(usize, usize)>> {
+        let mut conn = self.conn.lock().unwrap();
+
+        // Check that the user actually has sessions or archive entries with old_activity
+        let has_sessions: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sessions WHERE user_id = ?1 AND activity = ?2",
+            params![user_id, old_activity],
+            |r| r.get(0),
+        )?;
+
+        let has_archive: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM activity_archive WHERE user_id = ?1 AND activity = ?2",
+            params![user_id, old_activity],
+            |r| r.get(0),
+        )?;
+
+        if !has_sessions && !has_archive {
+            return Ok(None);
+        }
+
+        // Start transaction
+        let tx = conn.transaction()?;
+
+        // Update sessions table
+        let sessions_updated = tx.execute(
+            "UPDATE sessions SET activity = ?1 WHERE user_id = ?2 AND activity = ?3",
+            params![new_activity, user_id, old_activity],
+        )?;
+
+        // Update activity_archive table
+        tx.execute(
+            "UPDATE activity_archive SET activity = ?1 WHERE user_id = ?2 AND activity = ?3",
+            params![new_activity, user_id, old_activity],
+        )?;
+
+        // Merge duplicate archive rows for this user
+        // Find groups with duplicates after the rename
+        let mut stmt = tx.prepare(
+            "SELECT user_id, week_label, activity, COUNT(*) as cnt
+             FROM activity_archive
+             WHERE user_id = ?1 AND activity = ?2
+             GROUP BY user_id, week_label, activity
+             HAVING cnt > 1",
+        )?;
+        let duplicates: Vec<(String, String, String)> = stmt
+            .query_map(params![user_id, new_activity], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        let mut archive_rows_merged = 0;
+
+        // Prepare statements for merging duplicates
+        let mut select_stmt = tx.prepare(
+            "SELECT id, total_sec, session_count FROM activity_archive
+             WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3
+             ORDER BY id ASC",
+        )?;
+        let mut update_stmt = tx.prepare(
+            "UPDATE activity_archive SET total_sec = ?1, session_count = ?2 WHERE id = ?3"
+        )?;
+        let mut delete_stmt = tx.prepare(
+            "DELETE FROM activity_archive WHERE id = ?1"
+        )?;
+
+        // For each duplicate group, keep the row with MIN(id), sum total_sec into it, delete rest
+        for (uid, week_label, activity) in duplicates {
+            let rows: Vec<(i64, i64, i64)> = select_stmt
+                .query_map(params![&uid, &week_label, &activity], |r| {
+                    Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+                })?
+                .filter_map(|r| r.ok())
+                .collect();
+
+            if rows.len() > 1 {
+                let keep_id = rows[0].0;
+                let total_sum: i64 = rows.iter().map(|(_, secs, _)| secs).sum();
+                let count_sum: i64 = rows.iter().map(|(_, _, cnt)| cnt).sum();
+
+                // Update the kept row with the sum
+                update_stmt.execute(params![total_sum, count_sum, keep_id])?;
+
+                // Delete the duplicate rows
+                for (id, _, _) in rows.iter().skip(1) {
+                    delete_stmt.execute(params![id])?;
+                    archive_rows_merged += 1;
+                }
+            }
+        }
+
+        drop(select_stmt);
+        drop(update_stmt);
+        drop(delete_stmt);
+
+        if sessions_updated > 0 {
+            rebuild_aggregates(&tx)?;
+        }
+
+        // Commit transaction
+        tx.commit()?;
+        self.cache.invalidate();
+
+        Ok(Some((sessions_updated, archive_rows_merged)))
+    }
+
+    /// How much of a user's tracked time is filed under `activity`.
+    /// Returns (completed_sessions, archive_rows, total_minutes) across `sessions`
+    /// and `activity_archive`.
+    pub fn activity_usage(&self, user_id: &str, activity: &str) -> anyhow::Result<(i64, i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+        let (sessions, session_secs): (i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(seconds),0) FROM sessions
+             WHERE user_id = ?1 AND activity = ?2 AND ended_at IS NOT NULL",
+            params![user_id, activity],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        let (archive_rows, archive_secs): (i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(total_sec),0) FROM activity_archive
+             WHERE user_id = ?1 AND activity = ?2",
+            params![user_id, activity],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        Ok((sessions, archive_rows, (session_secs + archive_secs) / 60))
+    }
+
+    /// Permanently delete all of a user's completed sessions and archive rows for `activity`.
+    /// Archived minutes are also subtracted from the matching `weekly_archive` totals so the
+    /// all-time leaderboard stays consistent; weeks that drop to zero are removed.
+    /// Fails with `Refusal::ActivityRunning` while the user is clocked in on that activity.
+    /// Returns (sessions_deleted, archive_rows_deleted) counts, `None` if the user has
+    /// nothing under `activity`.
+    pub fn delete_activity(&self, user_id: &str, activity: &str) -> anyhow::Result<Option<(usize, usize)>> {
+        let mut conn = self.conn.lock().unwrap();
+
+        let active: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sessions
+             WHERE user_id = ?1 AND activity = ?2 AND ended_at IS NULL",
+            params![user_id, activity],
+            |r| r.get(0),
+        )?;
+        if active {
+            return Err(Refusal::ActivityRunning.into());
+        }
+
+        let tx = conn.transaction()?;
+
+        // Collect archived seconds per week before dropping the activity rows
+        let mut stmt = tx.prepare(
+            "SELECT week_label, SUM(total_sec) FROM activity_archive
+             WHERE user_id = ?1 AND activity = ?2
+             GROUP BY week_label",
+        )?;
+        let per_week: Vec<(String, i64)> = stmt
+            .query_map(params![user_id, activity], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        let sessions_deleted = tx.execute(
+            "DELETE FROM sessions WHERE user_id = ?1 AND activity = ?2 AND ended_at IS NOT NULL",
+            params![user_id, activity],
+        )?;
+        let archive_rows_deleted = tx.execute(
+            "DELETE FROM activity_archive WHERE user_id = ?1 AND activity = ?2",
+            params![user_id, activity],
+        )?;
+
+        if sessions_deleted == 0 && archive_rows_deleted == 0 {
+            return Ok(None);
+        }
+
+        for (week_label, seconds) in per_week {
+            tx.execute(
+                "UPDATE weekly_archive SET total_sec = total_sec - ?1
+                 WHERE user_id = ?2 AND week_label = ?3",
+                params![seconds, user_id, week_label],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM weekly_archive WHERE user_id = ?1 AND total_sec <= 0",
+            params![user_id],
+        )?;
+        refresh_week_totals(&tx)?;
+        if sessions_deleted > 0 {
+            rebuild_aggregates(&tx)?;
+        }
+
+        tx.commit()?;
+        self.cache.invalidate();
+
+        Ok(Some((sessions_deleted, archive_rows_deleted)))
+    }
+
+    /// Every known activity name with all-time totals (archived + current), both for
+    /// `user_id` and server-wide. Ordered by server-wide minutes (descending).
+    pub fn activity_totals(&self, user_id: &str) -> anyhow::Result<Vec<ActivityTotals>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT activity,
+                    SUM(CASE WHEN user_id = ?1 THEN secs ELSE 0 END) / 60 as mine,
+                    SUM(secs) / 60 as total FROM (
+                SELECT user_id, activity, SUM(seconds) as secs
+                    FROM sessions WHERE ended_at IS NOT NULL
+                    GROUP BY user_id, activity
+                UNION ALL
+                SELECT user_id, activity, SUM(total_sec) as secs
+                    FROM activity_archive
+                    GROUP BY user_id, activity
+             ) GROUP BY activity ORDER BY total DESC",
+        )?;
+        let rows = stmt.query_map(params![user_id], |r| {
+            Ok(ActivityTotals {
+                activity: r.get(0)?,
+                user_minutes: r.get(1)?,
+                total_minutes: r.get(2)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Per-week, per-user, per-activity minutes: the archive plus the current week's
+    /// finished sessions. Weeks are the finest granularity kept for past data.
+    pub fn week_activities(&self) -> anyhow::Result<Vec<WeekActivity>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT user_id, username, ?1, activity, SUM(seconds) / 60 FROM sessions
+                 WHERE ended_at IS NOT NULL GROUP BY user_id, activity
+             UNION ALL
+             SELECT user_id, username, week_label, activity, total_sec / 60 FROM activity_archive",
+        )?;
+        let rows = stmt.query_map(params![self.clock.week().label()], |r| {
+            Ok(WeekActivity {
+                user_id: r.get(0)?,
+                username: r.get(1)?,
+                week_label: r.get(2)?,
+                activity: r.get(3)?,
+                minutes: r.get(4)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// The `limit` activities with the most time logged server-wide (archived + current).
+    pub fn top_activities(&self, limit: usize) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT activity FROM (
+                SELECT activity, seconds as secs FROM sessions WHERE ended_at IS NOT NULL
+                UNION ALL
+                SELECT activity, total_sec as secs FROM activity_archive
+             ) GROUP BY activity ORDER BY SUM(secs) DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |r| r.get(0))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Admin-curated quick-start activities, in the order they were added.
+    pub fn activity_templates(&self) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT activity FROM activity_templates ORDER BY rowid")?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Returns `false` if the activity was already a template.
+    pub fn add_activity_template(&self, activity: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO activity_templates (activity) VALUES (?1)",
+            params![activity],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// Returns `false` if the activity wasn't a template.
+    pub fn remove_activity_template(&self, activity: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM activity_templates WHERE activity = ?1",
+            params![activity],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Activities left out of leaderboards and awards, alphabetically.
+    pub fn non_competitive_activities(&self) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        non_competitive(&conn)
+    }
+
+    /// Returns `false` if the activity was already non-competitive.
+    pub fn add_non_competitive(&self, activity: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO non_competitive (activity) VALUES (?1)",
+            params![activity],
+        )?;
+        self.cache.invalidate();
+        Ok(added > 0)
+    }
+
+    pub fn remove_non_competitive(&self, activity: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM non_competitive WHERE activity = ?1", params![activity])?;
+        self.cache.invalidate();
+        Ok(removed > 0)
+    }
+
+    /// Score weights set by admins, by activity. Unlisted activities weigh 1.0.
+    pub fn activity_weights(&self) -> anyhow::Result<Vec<(String, f64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT activity, weight FROM activity_weights ORDER BY weight DESC, activity")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Set the score weight of `activity`, or with `None` reset it to 1.0.
+    pub fn set_activity_weight(&self, activity: &str, weight: Option<f64>) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match weight {
+            Some(weight) => conn.execute(
+                "INSERT OR REPLACE INTO activity_weights (activity, weight) VALUES (?1, ?2)",
+                params![activity, weight],
+            )?,
+            None => conn.execute("DELETE FROM activity_weights WHERE activity = ?1", params![activity])?,
+        };
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    /// File `activity` under `client`, or with `None` take it out of any client.
+    pub fn set_activity_client(&self, activity: &str, client: Option<&str>) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = match client {
+            Some(client) => conn.execute(
+                "INSERT OR REPLACE INTO activity_clients (activity, client) VALUES (?1, ?2)",
+                params![activity, client],
+            )?,
+            None => conn.execute("DELETE FROM activity_clients WHERE activity = ?1", params![activity])?,
+        };
+        self.cache.invalidate();
+        Ok(changed > 0)
+    }
+
+    /// Every activity filed under a client with its finished minutes this week and all
+    /// time (archive included), ordered by client, then all-time minutes.
+    pub fn client_activities(&self) -> anyhow::Result<Vec<ClientActivity>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT c.client, c.activity,
+                    COALESCE((SELECT SUM(seconds) FROM sessions
+                              WHERE activity = c.activity AND ended_at IS NOT NULL), 0) as week,
+                    COALESCE((SELECT SUM(total_sec) FROM activity_archive WHERE activity = c.activity), 0)
+             FROM activity_clients c",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            let week: i64 = r.get(2)?;
+            let archived: i64 = r.get(3)?;
+            Ok(ClientActivity {
+                client: r.get(0)?,
+                activity: r.get(1)?,
+                week_minutes: week / 60,
+                total_minutes: (week + archived) / 60,
+            })
+        })?;
+        let mut activities: Vec<ClientActivity> = rows.filter_map(|r| r.ok()).collect();
+        activities.sort_by(|a, b| {
+            a.client
+                .to_lowercase()
+                .cmp(&b.client.to_lowercase())
+                .then(b.total_minutes.cmp(&a.total_minutes))
+        });
+        Ok(activities)
+    }
+
+    /// Set the hourly rate billed to `client`, or with `None` remove it.
+    pub fn set_client_rate(&self, client: &str, rate: Option<f64>) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match rate {
+            Some(rate) => conn.execute(
+                "INSERT OR REPLACE INTO client_rates (client, rate) VALUES (?1, ?2)",
+                params![client, rate],
+            )?,
+            None => conn.execute("DELETE FROM client_rates WHERE client = ?1", params![client])?,
+        };
+        Ok(())
+    }
+
+    pub fn client_rate(&self, client: &str) -> anyhow::Result<Option<f64>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row("SELECT rate FROM client_rates WHERE client = ?1", params![client], |r| r.get(0)) {
+            Ok(rate) => Ok(Some(rate)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Set the hour budget of `activity`, or with `None` remove it. Changing the
+    /// amount rearms its burn alerts.
+    pub fn set_activity_budget(&self, activity: &str, budget_min: Option<i64>) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match budget_min {
+            Some(budget_min) => conn.execute(
+                "INSERT OR REPLACE INTO activity_budgets (activity, budget_min, alerted_pct)
+                 VALUES (?1, ?2, COALESCE((SELECT alerted_pct FROM activity_budgets WHERE activity = ?1 AND budget_min = ?2), 0))",
+                params![activity, budget_min],
+            )?,
+            None => conn.execute("DELETE FROM activity_budgets WHERE activity = ?1", params![activity])?,
+        };
+        self.cache.invalidate();
+        Ok(())
+    }
+
+    /// Describe `activity`, or with `None` remove its description and owner.
+    /// Returns whether anything changed.
+    pub fn set_activity_info(&self, activity: &str, info: Option<&ActivityInfo>) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = match info {
+            Some(info) => conn.execute(
+                "INSERT OR REPLACE INTO activity_info (activity, description, owner_id) VALUES (?1, ?2, ?3)",
+                params![activity, info.description, info.owner_id],
+            )?,
+            None => conn.execute("DELETE FROM activity_info WHERE activity = ?1", params![activity])?,
+        };
+        Ok(changed > 0)
+    }
+
+    pub fn activity_info(&self, activity: &str) -> anyhow::Result<Option<ActivityInfo>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT description, owner_id FROM activity_info WHERE activity = ?1",
+            params![activity],
+            |r| Ok(ActivityInfo { description: r.get(0)?, owner_id: r.get(1)? }),
+        ) {
+            Ok(info) => Ok(Some(info)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Every budget with the time used so far, by activity name.
+    pub fn activity_budgets(&self) -> anyhow::Result<Vec<ActivityBudget>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT b.activity, b.budget_min, b.alerted_pct,
+                    (COALESCE((SELECT SUM(seconds) FROM sessions
+                               WHERE activity = b.activity AND ended_at IS NOT NULL), 0)
+                   + COALESCE((SELECT SUM(total_sec) FROM activity_archive WHERE activity = b.activity), 0)) / 60
+             FROM activity_budgets b ORDER BY b.activity",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            Ok(ActivityBudget {
+                activity: r.get(0)?,
+                budget_min: r.get(1)?,
+                alerted_pct: r.get(2)?,
+                used_min: r.get(3)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    pub fn activity_budget(&self, activity: &str) -> anyhow::Result<Option<ActivityBudget>> {
+        Ok(self.activity_budgets()?.into_iter().find(|b| b.activity == activity))
+    }
+
+    pub fn mark_budget_alerted(&self, activity: &str, pct: i64) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE activity_budgets SET alerted_pct = ?2 WHERE activity = ?1",
+            params![activity, pct],
+        )?;
+        Ok(())
+    }
+
+    /// Server-wide drill-down for one activity across `sessions` and `activity_archive`:
+    /// totals, session count, per-week trend over the last `weeks` weeks and contributors.
+    /// `None` if nobody has logged time on it.
+    pub fn activity_detail(&self, activity: &str, weeks: u32) -> anyhow::Result<Option<ActivityDetail>> {
+        let week_labels = self.clock.week().last_n(weeks).iter().map(WeekId::label).collect::<Vec<_>>();
+        let current_week_label = self.clock.week().label();
+        let monday = self.monday_of_current_week();
+        let conn = self.conn.lock().unwrap();
+
+        // Contributors (live + archived), which also yield the overall totals
+        let mut stmt = conn.prepare(
+            "SELECT username, SUM(secs) as total, SUM(cnt), DENSE_RANK() OVER (ORDER BY SUM(secs) / 60 DESC) FROM (
+                SELECT user_id, username, SUM(seconds) as secs, COUNT(*) as cnt
+                    FROM sessions WHERE ended_at IS NOT NULL AND activity = ?1
+                    GROUP BY user_id
+                UNION ALL
+                SELECT user_id, username, SUM(total_sec) as secs, SUM(session_count) as cnt
+                    FROM activity_archive WHERE activity = ?1
+                    GROUP BY user_id
+             ) GROUP BY user_id ORDER BY total DESC",
+        )?;
+        let rows: Vec<(String, i64, i64, i64)> = stmt
+            .query_map(params![activity], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        drop(stmt);
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let total_minutes = rows.iter().map(|(_, secs, _, _)| secs).sum::<i64>() / 60;
+        let session_count = rows.iter().map(|(_, _, cnt, _)| cnt).sum();
+        let contributors = rows
+            .into_iter()
+            .map(|(username, seconds, _, rank)| LeaderboardEntry {
+                username,
+                total_minutes: seconds / 60,
+                rank,
+                live: false,
+            })
+            .collect();
+
+        // Weekly trend: archived weeks plus the running week from sessions
+        let mut per_week: HashMap<String, i64> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT week_label, SUM(total_sec) FROM activity_archive
+             WHERE activity = ?1 GROUP BY week_label",
+        )?;
+        let rows = stmt.query_map(params![activity], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+        })?;
+        for (week_label, seconds) in rows.filter_map(|r| r.ok()) {
+            *per_week.entry(week_label).or_insert(0) += seconds;
+        }
+        let current: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(seconds),0) FROM sessions
+             WHERE ended_at IS NOT NULL AND activity = ?1 AND started_at >= ?2",
+            params![activity, monday],
+            |r| r.get(0),
+        )?;
+        *per_week.entry(current_week_label).or_insert(0) += current;
+
+        let minutes_per_week = week_labels
+            .iter()
+            .map(|wl| per_week.get(wl).unwrap_or(&0) / 60)
+            .collect();
+
+        Ok(Some(ActivityDetail {
+            total_minutes,
+            session_count,
+            week_labels,
+            minutes_per_week,
+            contributors,
+        }))
+    }
+
+    /// Return per-user weekly minutes for the last `weeks` weeks, capped to the top 5 users
+    /// by total minutes in that window.  Week labels are in chronological order (oldest first).
+    /// Non-competitive activities are left out, like on the leaderboards.
+    pub fn weekly_hours_for_chart(&self, weeks: u32) -> anyhow::Result<ChartData> {
+        if weeks == 0 {
+            anyhow::bail!("weeks must be at least 1");
+        }
+
+        let week_labels = self.clock.week().last_n(weeks).iter().map(WeekId::label).collect::<Vec<_>>();
+        let current_week_label = self.clock.week().label();
+        let monday = self.monday_of_current_week();
+        let conn = self.conn.lock().unwrap();
+
+        // user_id -> (username, week_label -> seconds)
+        let mut user_data: HashMap<String, (String, HashMap<String, i64>)> = HashMap::new();
+
+        // ── Past weeks: pull from weekly_archive ──────────────────────────
+        let past_labels: Vec<&str> = week_labels
+            .iter()
+            .filter(|wl| wl.as_str() != current_week_label.as_str())
+            .map(String::as_str)
+            .collect();
+
+        if !past_labels.is_empty() {
+            let placeholders = past_labels.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let sql = format!(
+                "SELECT user_id, username, week_label, SUM(total_sec) as total FROM ( \
+                    SELECT user_id, username, week_label, total_sec FROM weekly_archive \
+                    WHERE week_label IN ({0}) \
+                    UNION ALL \
+                    SELECT user_id, username, week_label, -total_sec FROM activity_archive \
+                    WHERE week_label IN ({0}) AND activity IN (SELECT activity FROM non_competitive) \
+                 ) GROUP BY user_id, week_label",
+                placeholders
+            );
+            let mut stmt = conn.prepare(&sql)?;
+            let params = past_labels.iter().chain(past_labels.iter());
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, i64>(3)?,
+                ))
+            })?;
+            for row in rows.filter_map(|r| r.ok()) {
+                let (uid, username, week_label, seconds) = row;
+                let entry = user_data
+                    .entry(uid)
+                    .or_insert_with(|| (username, HashMap::new()));
+                *entry.1.entry(week_label).or_insert(0) += seconds;
+            }
+        }
+
+        // ── Current week: pull from sessions ──────────────────────────────
+        let mut stmt = conn.prepare(
+            "SELECT user_id, username, SUM(seconds) as total FROM sessions \
+             WHERE ended_at IS NOT NULL AND started_at >= ?1 \
+               AND activity NOT IN (SELECT activity FROM non_competitive) \
+             GROUP BY user_id",
+        )?;
+        let rows = stmt.query_map(params![monday], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, i64>(2)?,
+            ))
+        })?;
+        for row in rows.filter_map(|r| r.ok()) {
+            let (uid, username, seconds) = row;
+            let entry = user_data
+                .entry(uid)
+                .or_insert_with(|| (username, HashMap::new()));
+            *entry.1.entry(current_week_label.clone()).or_insert(0) += seconds;
+        }
+
+        // ── Find top 5 by total minutes in the window ─────────────────────
+        let mut user_totals: Vec<(String, String, i64)> = user_data
+            .iter()
+            .map(|(uid, (name, weeks_map))| {
+                let total: i64 = weeks_map.values().sum();
+                (uid.clone(), name.clone(), total)
+            })
+            .filter(|(_, _, total)| *total > 0)
+            .collect();
+        user_totals.sort_by_key(|(_, _, total)| std::cmp::Reverse(*total));
+        user_totals.truncate(5);
+
+        // ── Build ordered output ───────────────────────────────────────────
+        let users: Vec<UserWeeklyData> = user_totals
+            .into_iter()
+            .map(|(uid, username, _)| {
+                let weeks_map = user_data
+                    .remove(&uid)
+                    .map(|(_, m)| m)
+                    .unwrap_or_default();
+                let minutes_per_week = week_labels
+                    .iter()
+                    .map(|wl| weeks_map.get(wl).unwrap_or(&0) / 60)
+                    .collect();
                 UserWeeklyData {
                     username,
                     minutes_per_week,
@@ -726,172 +4002,1234 @@ impl Db {
             })
             .collect();
 
-        Ok(ChartData { week_labels, users })
+        Ok(ChartData { week_labels, users })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{test_db, test_db_at, test_db_file, test_db_file_at};
+    use tempfile::TempDir;
+
+    /// 2026-03-`d` at `h`:`m`, Swiss local time; the 16th is the Monday of KW12/2026.
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_rename_activity_basic() {
+        let db = test_db();
+        let user_id = "user123";
+        let username = "TestUser";
+
+        // Clock in and out for "boring work"
+        db.clock_in(user_id, username, "boring work").unwrap();
+        let session = db.active_session(user_id).unwrap().unwrap();
+        assert_eq!(session.activity, "boring work");
+        
+        // Clock out
+        db.clock_out(user_id).unwrap();
+
+        // Rename "boring work" to "work"
+        let (sessions_updated, archive_merged) = db.rename_activity(user_id, "boring work", "work").unwrap().unwrap();
+        assert_eq!(sessions_updated, 1);
+        assert_eq!(archive_merged, 0);
+
+        // Verify the rename worked
+        let conn = db.conn.lock().unwrap();
+        let activity: String = conn
+            .query_row(
+                "SELECT activity FROM sessions WHERE user_id = ?1",
+                params![user_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(activity, "work");
+    }
+
+    #[test]
+    fn test_rename_activity_not_found() {
+        let db = test_db();
+        let user_id = "user123";
+
+        // Try to rename a non-existent activity
+        assert_eq!(db.rename_activity(user_id, "nonexistent", "work").unwrap(), None);
+    }
+
+    #[test]
+    fn test_rename_activity_merge_archives() {
+        let db = test_db();
+        let user_id = "user123";
+        let username = "TestUser";
+        let week_label = "KW07/2026";
+
+        // Manually insert archive entries for the same user and week but different activities
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, username, week_label, "work", 3600],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, username, week_label, "boring work", 1800],
+            ).unwrap();
+        }
+
+        // Rename "boring work" to "work" - should merge the archives
+        let (sessions_updated, archive_merged) = db.rename_activity(user_id, "boring work", "work").unwrap().unwrap();
+        assert_eq!(sessions_updated, 0); // No sessions to update
+        assert_eq!(archive_merged, 1); // One duplicate row merged
+
+        // Verify the archives were merged
+        let conn = db.conn.lock().unwrap();
+        let total_sec: i64 = conn
+            .query_row(
+                "SELECT total_sec FROM activity_archive WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3",
+                params![user_id, week_label, "work"],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(total_sec, 5400); // 60 + 30 minutes
+
+        // Verify only one row exists for this user/week/activity
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM activity_archive WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3",
+                params![user_id, week_label, "work"],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_rename_activity_active_session() {
+        let db = test_db();
+        let user_id = "user123";
+        let username = "TestUser";
+
+        // Clock in to "boring work"
+        db.clock_in(user_id, username, "boring work").unwrap();
+        
+        // Rename while still clocked in
+        let (sessions_updated, _) = db.rename_activity(user_id, "boring work", "work").unwrap().unwrap();
+        assert_eq!(sessions_updated, 1);
+
+        // Verify the active session was renamed
+        let session = db.active_session(user_id).unwrap().unwrap();
+        assert_eq!(session.activity, "work");
+    }
+
+    #[test]
+    fn test_rename_activity_per_user() {
+        let db = test_db();
+        let user1 = "user123";
+        let user2 = "user456";
+        let username1 = "User1";
+        let username2 = "User2";
+
+        // Both users have "boring work" sessions
+        db.clock_in(user1, username1, "boring work").unwrap();
+        db.clock_out(user1).unwrap();
+        
+        db.clock_in(user2, username2, "boring work").unwrap();
+        db.clock_out(user2).unwrap();
+
+        // User1 renames their activity
+        let (sessions_updated, _) = db.rename_activity(user1, "boring work", "work").unwrap().unwrap();
+        assert_eq!(sessions_updated, 1);
+
+        // Verify user1's activity was renamed but user2's wasn't
+        let conn = db.conn.lock().unwrap();
+        let user1_activity: String = conn
+            .query_row(
+                "SELECT activity FROM sessions WHERE user_id = ?1",
+                params![user1],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(user1_activity, "work");
+
+        let user2_activity: String = conn
+            .query_row(
+                "SELECT activity FROM sessions WHERE user_id = ?1",
+                params![user2],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(user2_activity, "boring work");
+    }
+
+    #[test]
+    fn test_delete_activity_basic() {
+        let db = test_db();
+        let user_id = "user123";
+
+        db.clock_in(user_id, "TestUser", "test entry").unwrap();
+        db.clock_out(user_id).unwrap();
+        db.clock_in(user_id, "TestUser", "work").unwrap();
+        db.clock_out(user_id).unwrap();
+
+        let (sessions, archive_rows, _) = db.activity_usage(user_id, "test entry").unwrap();
+        assert_eq!((sessions, archive_rows), (1, 0));
+
+        let (sessions_deleted, archive_deleted) = db.delete_activity(user_id, "test entry").unwrap().unwrap();
+        assert_eq!(sessions_deleted, 1);
+        assert_eq!(archive_deleted, 0);
+
+        // Only the "work" session is left
+        let conn = db.conn.lock().unwrap();
+        let remaining: Vec<String> = conn
+            .prepare("SELECT activity FROM sessions WHERE user_id = ?1")
+            .unwrap()
+            .query_map(params![user_id], |r| r.get(0))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        assert_eq!(remaining, vec!["work".to_string()]);
+    }
+
+    #[test]
+    fn test_delete_activity_adjusts_weekly_archive() {
+        let db = test_db();
+        let user_id = "user123";
+        let username = "TestUser";
+
+        {
+            let conn = db.conn.lock().unwrap();
+            for (week, total) in [("KW06/2026", 5400i64), ("KW07/2026", 1800i64)] {
+                conn.execute(
+                    "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES (?1, ?2, ?3, ?4)",
+                    params![user_id, username, week, total],
+                ).unwrap();
+            }
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, username, "KW06/2026", "work", 3600],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, username, "KW06/2026", "personal", 1800],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, username, "KW07/2026", "personal", 1800],
+            ).unwrap();
+        }
+
+        let (_, archive_deleted) = db.delete_activity(user_id, "personal").unwrap().unwrap();
+        assert_eq!(archive_deleted, 2);
+
+        let conn = db.conn.lock().unwrap();
+        let kw06: i64 = conn
+            .query_row(
+                "SELECT total_sec FROM weekly_archive WHERE user_id = ?1 AND week_label = 'KW06/2026'",
+                params![user_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(kw06, 3600);
+
+        // KW07 only contained the deleted activity, so the week row is gone
+        let kw07: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM weekly_archive WHERE user_id = ?1 AND week_label = 'KW07/2026'",
+                params![user_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(kw07, 0);
+    }
+
+    #[test]
+    fn test_delete_activity_refuses_active_and_missing() {
+        let db = test_db();
+        let user_id = "user123";
+
+        assert_eq!(db.delete_activity(user_id, "nonexistent").unwrap(), None);
+
+        db.clock_in(user_id, "TestUser", "work").unwrap();
+        let err = db.delete_activity(user_id, "work").unwrap_err();
+        assert_eq!(err.downcast_ref::<Refusal>(), Some(&Refusal::ActivityRunning));
+        assert!(db.active_session(user_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_activity_detail_spans_sessions_and_archive() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let week_labels = ["KW09/2026", "KW10/2026", "KW11/2026", "KW12/2026"];
+
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec, session_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params!["a", "Alice", &week_labels[0], "backend", 120 * 60, 3],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec, session_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params!["b", "Bob", &week_labels[1], "backend", 45 * 60, 1],
+            ).unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec, session_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params!["b", "Bob", &week_labels[1], "frontend", 500 * 60, 5],
+            ).unwrap();
+        }
+        db.clock_in("b", "Bob", "backend").unwrap();
+        db.clock_out("b").unwrap();
+
+        let detail = db.activity_detail("backend", 4).unwrap().unwrap();
+        assert_eq!(detail.total_minutes, 165);
+        assert_eq!(detail.session_count, 5);
+        assert_eq!(detail.minutes_per_week, vec![120, 45, 0, 0]);
+        assert_eq!(detail.contributors.len(), 2);
+        assert_eq!(detail.contributors[0].username, "Alice");
+        assert_eq!(detail.contributors[1].total_minutes, 45);
+
+        assert!(db.activity_detail("nonexistent", 4).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_activity_totals_user_and_server() {
+        let db = test_db();
+
+        {
+            let conn = db.conn.lock().unwrap();
+            for (uid, name, activity, mins) in [
+                ("a", "Alice", "backend", 120),
+                ("b", "Bob", "backend", 60),
+                ("b", "Bob", "meetings", 30),
+            ] {
+                conn.execute(
+                    "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![uid, name, "KW07/2026", activity, mins * 60],
+                ).unwrap();
+            }
+        }
+
+        let totals = db.activity_totals("b").unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].activity, "backend");
+        assert_eq!(totals[0].user_minutes, 60);
+        assert_eq!(totals[0].total_minutes, 180);
+        assert_eq!(totals[1].activity, "meetings");
+        assert_eq!(totals[1].user_minutes, 30);
+    }
+
+    #[test]
+    fn test_week_activities_archive_and_current() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        db.conn.lock().unwrap().execute(
+            "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES ('a', 'Alice', 'KW07/2026', 'backend', 7200)",
+            [],
+        ).unwrap();
+        insert_session(&db, "a", "Alice", "docs", at(20, 17, 0), 30);
+
+        let rows = db.week_activities().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().any(|r| r.week_label == "KW07/2026" && r.minutes == 120));
+        assert!(rows.iter().any(|r| r.week_label == "KW12/2026" && r.activity == "docs" && r.minutes == 30));
+    }
+
+    #[test]
+    fn test_archived_week_total() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        insert_session(&db, "a", "Alice", "backend", at(20, 16, 0), 90);
+        insert_session(&db, "b", "Bob", "backend", at(20, 16, 0), 30);
+        db.archive_week("KW07/2026").unwrap();
+
+        assert_eq!(db.archived_week_total(Some("a"), "KW07/2026").unwrap(), Some(90));
+        assert_eq!(db.archived_week_total(None, "KW07/2026").unwrap(), Some(120));
+        assert_eq!(db.archived_week_total(Some("c"), "KW07/2026").unwrap(), None);
+        assert_eq!(db.archived_week_total(None, "KW06/2026").unwrap(), None);
+    }
+
+    #[test]
+    fn test_normalize_activities_by_version() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        insert_session(&db, "a", "Alice", "Bbb", at(20, 16, 0), 30);
+        insert_session(&db, "a", "Alice", "bb", at(20, 17, 0), 30);
+        // Normalized by the first rules, which left "Bbb" as "bbb"
+        db.set_metadata("activities_normalized", "true").unwrap();
+
+        assert!(db.normalize_activities().unwrap());
+        let breakdown = db.activity_breakdown_weekly().unwrap();
+        assert_eq!(breakdown.len(), 1);
+        assert_eq!((breakdown[0].activity.as_str(), breakdown[0].total_minutes), ("bb", 60));
+        assert_eq!(db.metadata("activities_normalized").unwrap(), None);
+        assert_eq!(
+            db.metadata("normalization_version").unwrap(),
+            Some(crate::normalize::NORMALIZATION_VERSION.to_string())
+        );
+
+        // Up to date: nothing to do
+        assert!(!db.normalize_activities().unwrap());
+    }
+
+    #[test]
+    fn test_integrity_findings() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        insert_session(&db, "a", "Alice", "backend", at(20, 15, 0), 90);
+        assert!(db.integrity_findings().unwrap().is_empty());
+
+        insert_session(&db, "a", "Alice", "ops", at(19, 12, 0), 26 * 60);
+        insert_session(&db, "b", "Bob", "docs", at(20, 16, 0), -30);
+        let forgotten = at(11, 18, 0);
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO sessions (user_id, username, activity, started_at) VALUES ('c', 'Carol', 'backend', ?1)",
+                params![forgotten.format("%Y-%m-%d %H:%M:%S").to_string()],
+            )
+            .unwrap();
+            conn.execute_batch(
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES
+                    ('a', 'Alice', 'KW05/2026', 6000), ('a', 'Alice', 'KW05/2026', 6000),
+                    ('b', 'Bob', 'KW05/2026', 3600);
+                 INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES
+                    ('a', 'Alice', 'KW05/2026', 'backend', 12000), ('b', 'Bob', 'KW05/2026', 'docs', 2700);",
+            )
+            .unwrap();
+        }
+
+        let findings = db.integrity_findings().unwrap();
+        let bad: Vec<i64> = findings
+            .iter()
+            .filter_map(|f| match f {
+                Finding::BadSession { minutes, .. } => Some(*minutes),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(bad, vec![26 * 60, -30]);
+        assert!(findings.contains(&Finding::StaleSession {
+            username: "Carol".to_string(),
+            activity: "backend".to_string(),
+            started_at: forgotten,
+        }));
+        // Alice's two total rows add up to her activities; only Bob's total is off
+        assert!(findings.contains(&Finding::ArchiveMismatch {
+            week_label: "KW05/2026".to_string(),
+            username: "Bob".to_string(),
+            total: 60,
+            activities: 45,
+        }));
+        assert!(findings.contains(&Finding::DuplicateArchive {
+            week_label: "KW05/2026".to_string(),
+            username: "Alice".to_string(),
+            activity: None,
+            rows: 2,
+        }));
+        assert_eq!(findings.len(), 5);
+    }
+
+    #[test]
+    fn test_archived_weeks() {
+        let (db, temp_dir) = test_db_file();
+        let day = |d| NaiveDate::from_ymd_opt(2026, 2, d).unwrap();
+        let feb = |d, h| day(d).and_hms_opt(h, 0, 0).unwrap();
+        insert_session(&db, "a", "Alice", "backend", feb(10, 9), 90);
+        insert_session(&db, "a", "Alice", "docs", feb(11, 9), 20);
+        insert_session(&db, "b", "Bob", "backend", feb(10, 13), 30);
+        db.archive_week("KW07/2026").unwrap();
+        insert_session(&db, "a", "Alice", "backend", feb(24, 9), 60);
+        db.archive_week("KW09/2026").unwrap();
+
+        let weeks = db.archived_weeks(day(1), day(28)).unwrap();
+        assert_eq!(weeks.len(), 2);
+        assert_eq!((weeks[0].label.as_str(), weeks[0].start, weeks[0].end), ("KW07/2026", day(9), day(15)));
+        assert_eq!((weeks[0].total_minutes, weeks[0].sessions, weeks[0].people), (140, 3, 2));
+        assert!(weeks[0].archived_at.is_some());
+        assert_eq!(db.archived_weeks(day(10), day(28)).unwrap()[0].label, "KW09/2026");
+
+        let end: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT week_end FROM activity_archive WHERE activity = 'docs'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(end, "2026-02-15");
+
+        // Deleting archived time keeps the week's totals in step
+        db.delete_activity("a", "docs").unwrap().unwrap();
+        let weeks = db.archived_weeks(day(1), day(28)).unwrap();
+        assert_eq!((weeks[0].total_minutes, weeks[0].sessions), (120, 2));
+
+        // Archives from before the dates were kept get them, and a week row, on open
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "UPDATE weekly_archive SET week_start = NULL, week_end = NULL;
+                 UPDATE activity_archive SET week_start = NULL, week_end = NULL;
+                 DELETE FROM weeks;",
+            )
+            .unwrap();
+        }
+        drop(db);
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        let weeks = db.archived_weeks(day(1), day(28)).unwrap();
+        assert_eq!(weeks.len(), 2);
+        assert_eq!((weeks[0].total_minutes, weeks[0].sessions, weeks[0].people), (120, 2, 2));
+        assert_eq!(weeks[1].archived_at, None);
+    }
+
+    #[test]
+    fn test_legacy_archive_labels_move_back_a_week() {
+        let (db, temp_dir) = test_db_file();
+        let at = |m, d| NaiveDate::from_ymd_opt(2026, m, d).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        // The old reset ran at Monday 00:00 and filed KW09's sessions under KW10, and so on
+        insert_session(&db, "a", "Alice", "backend", at(2, 24), 60);
+        db.archive_week("KW10/2026").unwrap();
+        insert_session(&db, "a", "Alice", "backend", at(3, 3), 30);
+        db.archive_week("KW11/2026").unwrap();
+        drop(db);
+
+        let labels = |db: &Db| {
+            db.archived_weeks(at(2, 1).date(), at(3, 31).date())
+                .unwrap()
+                .into_iter()
+                .map(|w| (w.label, w.start, w.total_minutes))
+                .collect::<Vec<_>>()
+        };
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(
+            labels(&db),
+            vec![("KW09/2026".to_string(), at(2, 23).date(), 60), ("KW10/2026".to_string(), at(3, 2).date(), 30)]
+        );
+        assert_eq!(db.archived_week_total(Some("a"), "KW11/2026").unwrap(), None);
+        assert!(crate::health::already_archived(&db, "KW10/2026"));
+
+        // The next reset files KW11 under its own label; later starts leave the labels alone
+        insert_session(&db, "a", "Alice", "backend", at(3, 10), 45);
+        crate::health::archive_week(&db, "KW11/2026").unwrap();
+        drop(db);
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(labels(&db).last().unwrap(), &("KW11/2026".to_string(), at(3, 9).date(), 45));
+        assert_eq!(labels(&db).len(), 3);
+        assert!(db.integrity_findings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cached_queries_see_writes() {
+        let db = test_db();
+
+        assert!(db.who_is_working().unwrap().is_empty());
+        db.clock_in("a", "Alice", "work").unwrap();
+        // Cached empty result must not survive the clock-in
+        assert_eq!(db.who_is_working().unwrap().len(), 1);
+
+        assert!(db.leaderboard_weekly(false).unwrap().is_empty());
+        db.clock_out("a").unwrap();
+        assert!(db.who_is_working().unwrap().is_empty());
+        assert_eq!(db.leaderboard_weekly(false).unwrap().len(), 1);
+
+        db.rename_activity("a", "work", "deep-work").unwrap().unwrap();
+        assert_eq!(db.activity_breakdown_weekly().unwrap()[0].activity, "deep-work");
+        db.delete_activity("a", "deep-work").unwrap().unwrap();
+        assert!(db.activity_breakdown_weekly().unwrap().is_empty());
+    }
+
+    /// Insert a completed session with explicit times (Swiss local, `%Y-%m-%d %H:%M:%S`).
+    fn insert_session(db: &Db, user_id: &str, username: &str, activity: &str, started: NaiveDateTime, minutes: i64) {
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO sessions (user_id,username,activity,started_at,ended_at,minutes,seconds)
+             VALUES (?1,?2,?3,?4,?5,?6,?7)",
+            params![
+                user_id,
+                username,
+                activity,
+                started.format("%Y-%m-%d %H:%M:%S").to_string(),
+                (started + Duration::minutes(minutes)).format("%Y-%m-%d %H:%M:%S").to_string(),
+                minutes,
+                minutes * 60
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_weekly_summary_from_aggregates() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 120);
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(13), 30);
+        insert_session(&db, "b", "Bob", "meetings", monday + Duration::hours(10), 45);
+        // Started last week: not part of this week's summary
+        insert_session(&db, "b", "Bob", "meetings", monday - Duration::hours(5), 600);
+        db.rebuild_weekly_aggregates().unwrap();
+
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.total_minutes, 195);
+        assert_eq!(summary.total_sessions, 3);
+        assert_eq!(summary.unique_workers, 2);
+        assert_eq!(summary.mvp, Some(("Alice".to_string(), 150)));
+        assert_eq!(summary.top_activity, Some(("backend".to_string(), 150)));
+        assert_eq!(summary.longest_session, Some(("Alice".to_string(), "backend".to_string(), 120)));
+        assert_eq!(summary.breakdown.len(), 2);
+        assert_eq!(summary.breakdown[0].session_count, 2);
+        assert_eq!(summary.daily_minutes, [195, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(summary.mvp_daily_minutes, [150, 0, 0, 0, 0, 0, 0]);
+        assert!(summary.previous_activities.is_empty());
+
+        // Clock-outs are folded in incrementally
+        db.clock_in("c", "Carol", "backend").unwrap();
+        db.clock_out("c").unwrap();
+        assert_eq!(db.weekly_summary(db.clock().week()).unwrap().total_sessions, 4);
+
+        // Renames keep the aggregates in sync
+        db.rename_activity("a", "backend", "api").unwrap().unwrap();
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert!(summary.breakdown.iter().any(|e| e.username == "Alice" && e.activity == "api"));
+
+        // Archiving empties them
+        db.archive_week("KW12/2026").unwrap();
+        assert_eq!(db.weekly_summary(db.clock().week()).unwrap().total_sessions, 0);
+    }
+
+    #[test]
+    fn test_weekly_summary_previous_activities() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let previous = "KW11/2026";
+        {
+            let conn = db.conn.lock().unwrap();
+            for (uid, name, activity, mins) in [("a", "Alice", "backend", 120), ("b", "Bob", "backend", 60), ("b", "Bob", "docs", 300)] {
+                conn.execute(
+                    "INSERT INTO activity_archive (user_id, username, week_label, activity, total_sec) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![uid, name, previous, activity, mins * 60],
+                ).unwrap();
+            }
+        }
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.previous_activities, vec![("docs".to_string(), 300), ("backend".to_string(), 180)]);
+    }
+
+    #[test]
+    fn test_scheduled_clock_out() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        assert!(!db.schedule_clock_out("a", Some(at(20, 18, 0))).unwrap());
+
+        db.clock_in("a", "Alice", "backend").unwrap();
+        let started = db.active_session("a").unwrap().unwrap().started_at;
+        let at = started + Duration::minutes(30);
+        assert!(db.schedule_clock_out("a", Some(at)).unwrap());
+        assert_eq!(db.scheduled_clock_out("a").unwrap(), Some(at));
+        assert!(db.due_clock_outs(at - Duration::minutes(1)).unwrap().is_empty());
+        assert_eq!(db.due_clock_outs(at).unwrap(), vec![("a".to_string(), at)]);
+
+        // Cancelling clears it
+        db.schedule_clock_out("a", None).unwrap();
+        assert!(db.due_clock_outs(at).unwrap().is_empty());
+
+        // Ending at the scheduled time counts the scheduled minutes, not the real ones
+        db.schedule_clock_out("a", Some(at)).unwrap();
+        assert_eq!(db.clock_out_at("a", at).unwrap(), (Elapsed::from_seconds(1800), "backend".to_string()));
+        assert_eq!(db.scheduled_clock_out("a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_recent_activities() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday, 30);
+        insert_session(&db, "a", "Alice", "review", monday + Duration::hours(1), 30);
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(2), 15);
+        insert_session(&db, "b", "Bob", "meetings", monday + Duration::hours(3), 60);
+        db.clock_in("a", "Alice", "docs").unwrap();
+
+        let recent = db.recent_activities("a", 5).unwrap();
+        assert_eq!(
+            recent,
+            vec![
+                ("backend".to_string(), monday + Duration::minutes(135)),
+                ("review".to_string(), monday + Duration::minutes(90)),
+            ]
+        );
+        assert_eq!(db.recent_activities("a", 1).unwrap().len(), 1);
+        assert!(db.recent_activities("c", 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_with_previous() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        assert!(!db.merge_with_previous("a").unwrap());
+
+        let start = at(20, 17, 10);
+        insert_session(&db, "a", "Alice", "backend", start, 40);
+        db.clock_in("a", "Alice", "backend").unwrap();
+        assert!(db.merge_with_previous("a").unwrap());
+        assert_eq!(db.active_session("a").unwrap().unwrap().started_at, start);
+        assert!(db.recent_activities("a", 5).unwrap().is_empty());
+        // Nothing left to merge
+        assert!(!db.merge_with_previous("a").unwrap());
+
+        let (elapsed, _) = db.clock_out("a").unwrap();
+        assert_eq!(elapsed.minutes(), 50);
+        let summary = db.weekly_summary(WeekId::containing(start.date())).unwrap();
+        assert_eq!(summary.total_sessions, 1);
+    }
+
+    #[test]
+    fn test_last_seen() {
+        let db = test_db();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap();
+        assert_eq!(db.last_seen_on("a", day).unwrap(), None);
+
+        db.record_last_seen("a", day.and_hms_opt(9, 0, 0).unwrap()).unwrap();
+        db.record_last_seen("a", day.and_hms_opt(17, 42, 0).unwrap()).unwrap();
+        db.record_last_seen("a", day.and_hms_opt(23, 59, 0).unwrap() + Duration::days(1)).unwrap();
+        assert_eq!(db.last_seen_on("a", day).unwrap(), day.and_hms_opt(17, 42, 0));
+        assert_eq!(db.last_seen_on("b", day).unwrap(), None);
+
+        // Old days are pruned
+        db.record_last_seen("a", day.and_hms_opt(8, 0, 0).unwrap() + Duration::days(20)).unwrap();
+        assert_eq!(db.last_seen_on("a", day).unwrap(), None);
+    }
+
+    #[test]
+    fn test_non_competitive_excluded_from_rankings() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 60);
+        insert_session(&db, "b", "Bob", "meetings", monday + Duration::hours(9), 300);
+        insert_session(&db, "b", "Bob", "backend", monday + Duration::hours(15), 30);
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "INSERT INTO weekly_archive (user_id,username,week_label,total_sec) VALUES ('b','Bob','KW01/2026',12000);
+                 INSERT INTO activity_archive (user_id,username,week_label,activity,total_sec) VALUES ('b','Bob','KW01/2026','meetings',9000);
+                 INSERT INTO activity_archive (user_id,username,week_label,activity,total_sec) VALUES ('b','Bob','KW01/2026','backend',3000);",
+            )
+            .unwrap();
+        }
+        db.rebuild_weekly_aggregates().unwrap();
+        assert_eq!(db.leaderboard_weekly(false).unwrap()[0].username, "Bob");
+
+        assert!(db.add_non_competitive("meetings").unwrap());
+        assert!(!db.add_non_competitive("meetings").unwrap());
+        assert_eq!(db.non_competitive_activities().unwrap(), vec!["meetings"]);
+
+        let weekly = db.leaderboard_weekly(false).unwrap();
+        assert_eq!((weekly[0].username.as_str(), weekly[0].total_minutes), ("Alice", 60));
+        assert_eq!((weekly[1].username.as_str(), weekly[1].total_minutes), ("Bob", 30));
+        let alltime = db.leaderboard_alltime(false).unwrap();
+        assert_eq!((alltime[0].username.as_str(), alltime[0].total_minutes), ("Bob", 80));
+
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.mvp, Some(("Alice".to_string(), 60)));
+        assert_eq!(summary.longest_session, Some(("Alice".to_string(), "backend".to_string(), 60)));
+        // Totals and breakdowns still include it
+        assert_eq!(summary.total_minutes, 390);
+        assert_eq!(summary.non_competitive, vec!["meetings"]);
+
+        assert!(db.remove_non_competitive("meetings").unwrap());
+        assert_eq!(db.leaderboard_weekly(false).unwrap()[0].username, "Bob");
+    }
+
+    #[test]
+    fn test_leaderboard_ranks_ties() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "backend", monday + Duration::hours(9), 120);
+        insert_session(&db, "c", "Carol", "backend", monday + Duration::hours(9), 90);
+        for i in 0..15 {
+            insert_session(&db, &format!("u{i}"), &format!("User {i:02}"), "backend", monday + Duration::hours(9), 100);
+        }
+        insert_session(&db, "d", "Dan", "backend", monday + Duration::hours(9), 30);
+
+        let board = db.leaderboard_weekly(false).unwrap();
+        assert_eq!(board.len(), 15);
+        let ranks: Vec<(&str, i64)> = board.iter().take(3).map(|e| (e.username.as_str(), e.rank)).collect();
+        assert_eq!(ranks, vec![("Alice", 1), ("Bob", 1), ("User 00", 2)]);
+
+        // Outside the top 15, but still ranked against everyone
+        let carol = db.weekly_rank("c", false).unwrap().unwrap();
+        assert_eq!((carol.rank, carol.total_minutes, carol.behind), (3, 90, Some(10)));
+        let dan = db.weekly_rank("d", false).unwrap().unwrap();
+        assert_eq!((dan.rank, dan.behind), (4, Some(60)));
+        assert_eq!(db.weekly_rank("a", false).unwrap().unwrap().behind, None);
+        assert_eq!(db.weekly_rank("nobody", false).unwrap(), None);
+
+        db.archive_week("KW12/2026").unwrap();
+        assert_eq!(db.weekly_rank("d", false).unwrap(), None);
+        assert_eq!(db.alltime_rank("d", false).unwrap().unwrap().rank, 4);
+        assert_eq!(db.leaderboard_alltime(false).unwrap()[1].rank, 1);
+    }
+
+    #[test]
+    fn test_live_leaderboards() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday, 30);
+        db.clock_in("b", "Bob", "backend").unwrap();
+        // Since Sunday 22:00
+        let started = at(15, 22, 0);
+        db.conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE sessions SET started_at = ?1 WHERE user_id = 'b'", params![started.format("%Y-%m-%d %H:%M:%S").to_string()])
+            .unwrap();
+
+        let board = db.leaderboard_weekly(false).unwrap();
+        assert_eq!(board.iter().map(|e| e.username.as_str()).collect::<Vec<_>>(), vec!["Alice"]);
+        assert!(!board[0].live);
+
+        // Running time only counts from Monday on the weekly board
+        let board = db.leaderboard_weekly(true).unwrap();
+        let bob = board.iter().find(|e| e.username == "Bob").unwrap();
+        assert!(bob.live);
+        assert_eq!(bob.total_minutes, (at(20, 18, 0) - monday).num_minutes());
+        assert!(!board.iter().find(|e| e.username == "Alice").unwrap().live);
+
+        let alltime = db.leaderboard_alltime(true).unwrap();
+        assert_eq!((alltime[0].username.as_str(), alltime[0].rank, alltime[0].live), ("Bob", 1, true));
+        assert_eq!(alltime[0].total_minutes, (at(20, 18, 0) - started).num_minutes());
+        assert_eq!(db.alltime_rank("a", true).unwrap().unwrap().rank, 2);
+        assert_eq!(db.alltime_rank("b", false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_short_sessions_keep_seconds() {
+        let (db, _clock, temp_dir) = test_db_file_at(at(20, 18, 0));
+        db.clock_in("a", "Alice", "backend").unwrap();
+        let started = db.active_session("a").unwrap().unwrap().started_at;
+        let (elapsed, _) = db.clock_out_at("a", started + Duration::seconds(95)).unwrap();
+        assert_eq!((elapsed.minutes(), elapsed.seconds()), (1, 95));
+
+        insert_session(&db, "b", "Bob", "backend", at(16, 9, 0), 20);
+        let records = db.session_records(None).unwrap();
+        assert_eq!(records.iter().map(|r| r.elapsed.seconds()).collect::<Vec<_>>(), vec![1200, 95]);
+        assert_eq!(db.leaderboard_weekly(false).unwrap()[1].total_minutes, 1);
+
+        // Sessions from before seconds were kept get them from their timestamps
+        db.conn.lock().unwrap().execute("UPDATE sessions SET seconds = NULL", []).unwrap();
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(db.session_records(Some("a")).unwrap()[0].elapsed.seconds(), 95);
+    }
+
+    #[test]
+    fn test_short_sessions_add_up() {
+        let monday = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap();
+        let (db, clock) = test_db_at(monday.and_hms_opt(9, 0, 0).unwrap());
+        let week = db.clock().week();
+        // Three 40-second sessions: no whole minute each, two together
+        for _ in 0..3 {
+            db.clock_in("a", "Alice", "backend").unwrap();
+            clock.advance(Duration::seconds(40));
+            db.clock_out("a").unwrap();
+            clock.advance(Duration::minutes(5));
+        }
+        assert_eq!(db.leaderboard_weekly(false).unwrap()[0].total_minutes, 2);
+        assert_eq!(db.score_leaderboard_weekly().unwrap()[0].total_minutes, 2);
+        assert_eq!(db.competitive_minutes("a", week).unwrap(), 2);
+        let summary = db.weekly_summary(week).unwrap();
+        assert_eq!((summary.total_minutes, summary.breakdown[0].total_minutes), (2, 2));
+
+        db.archive_week(&week.label()).unwrap();
+        assert_eq!(db.archived_week_total(Some("a"), &week.label()).unwrap(), Some(2));
+        assert_eq!(db.leaderboard_alltime(false).unwrap()[0].total_minutes, 2);
+        assert_eq!(db.activity_totals("a").unwrap()[0].total_minutes, 2);
+    }
+
+    #[test]
+    fn test_minute_totals_become_seconds() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("old.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE weekly_archive (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id TEXT NOT NULL,
+                    username TEXT NOT NULL, week_label TEXT NOT NULL, total_min INTEGER NOT NULL);
+                 CREATE TABLE activity_archive (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id TEXT NOT NULL,
+                    username TEXT NOT NULL, week_label TEXT NOT NULL, activity TEXT NOT NULL, total_min INTEGER NOT NULL);
+                 INSERT INTO weekly_archive (user_id, username, week_label, total_min) VALUES ('a', 'Alice', 'KW10/2026', 90);
+                 INSERT INTO activity_archive (user_id, username, week_label, activity, total_min)
+                    VALUES ('a', 'Alice', 'KW10/2026', 'backend', 90);
+                 CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO metadata (key, value) VALUES ('last_archived_week', 'KW10/2026');",
+            )
+            .unwrap();
+
+        let db = Db::open(&path).unwrap();
+        assert_eq!(db.archived_week_total(Some("a"), "KW10/2026").unwrap(), Some(90));
+        assert_eq!(db.activity_totals("a").unwrap()[0].total_minutes, 90);
+        let seconds: i64 = db.conn.lock().unwrap().query_row("SELECT total_sec FROM weekly_archive", [], |r| r.get(0)).unwrap();
+        assert_eq!(seconds, 5400);
+        // Opening again doesn't convert twice
+        drop(db);
+        assert_eq!(Db::open(&path).unwrap().archived_week_total(None, "KW10/2026").unwrap(), Some(90));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+    #[test]
+    fn test_one_running_session_per_user() {
+        let (db, temp_dir) = test_db_file();
+        db.clock_in("a", "Alice", "backend").unwrap();
+        let err = db.clock_in("a", "Alice", "frontend").unwrap_err();
+        assert_eq!(err.downcast_ref::<Refusal>(), Some(&Refusal::ClockedIn));
+        assert_eq!(db.active_session("a").unwrap().unwrap().activity, "backend");
+        db.clock_in("b", "Bob", "backend").unwrap();
 
-    fn setup_test_db() -> (Db, TempDir) {
-        let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let db = Db::open(&db_path).unwrap();
-        (db, temp_dir)
+        // A second connection, like clockctl, can't sneak one in either
+        let other = Connection::open(temp_dir.path().join("test.db")).unwrap();
+        assert!(other
+            .execute("INSERT INTO sessions (user_id,username,activity,started_at) VALUES ('a','Alice','docs','2026-01-01 09:00:00')", [])
+            .is_err());
+
+        db.clock_out("a").unwrap();
+        db.clock_in("a", "Alice", "frontend").unwrap();
+
+        // Databases from before the index keep only the newest running session
+        other.execute_batch(
+            "DROP INDEX idx_sess_one_running;
+             INSERT INTO sessions (user_id,username,activity,started_at) VALUES ('a','Alice','docs','2026-01-01 09:00:00');",
+        )
+        .unwrap();
+        drop(db);
+        let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(db.active_session("a").unwrap().unwrap().activity, "docs");
+        assert_eq!(db.who_is_working().unwrap().len(), 2);
     }
 
     #[test]
-    fn test_rename_activity_basic() {
-        let (db, _temp_dir) = setup_test_db();
-        let user_id = "user123";
-        let username = "TestUser";
+    fn test_score_leaderboard() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "deep work", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "admin", monday + Duration::hours(9), 200);
+        assert_eq!(db.score_leaderboard_weekly().unwrap()[0].username, "Bob");
 
-        // Clock in and out for "boring work"
-        db.clock_in(user_id, username, "boring work").unwrap();
-        let session = db.active_session(user_id).unwrap().unwrap();
-        assert_eq!(session.activity, "boring work");
-        
-        // Clock out
-        db.clock_out(user_id).unwrap();
+        db.set_activity_weight("admin", Some(0.5)).unwrap();
+        db.set_activity_weight("deep work", Some(1.0)).unwrap();
+        assert_eq!(
+            db.activity_weights().unwrap(),
+            vec![("deep work".to_string(), 1.0), ("admin".to_string(), 0.5)]
+        );
+        let board = db.score_leaderboard_weekly().unwrap();
+        assert_eq!((board[0].username.as_str(), board[0].total_minutes), ("Alice", 120));
+        assert_eq!((board[1].username.as_str(), board[1].total_minutes), ("Bob", 100));
+        // Raw hours are unaffected
+        assert_eq!(db.leaderboard_weekly(false).unwrap()[0].username, "Bob");
 
-        // Rename "boring work" to "work"
-        let (sessions_updated, archive_merged) = db.rename_activity(user_id, "boring work", "work").unwrap();
-        assert_eq!(sessions_updated, 1);
-        assert_eq!(archive_merged, 0);
+        db.set_activity_weight("admin", None).unwrap();
+        assert_eq!(db.score_leaderboard_weekly().unwrap()[0].total_minutes, 200);
+    }
 
-        // Verify the rename worked
-        let conn = db.conn.lock().unwrap();
-        let activity: String = conn
-            .query_row(
-                "SELECT activity FROM sessions WHERE user_id = ?1",
-                params![user_id],
-                |r| r.get(0),
-            )
-            .unwrap();
-        assert_eq!(activity, "work");
+    #[test]
+    fn test_link_accounts() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        let week = "KW12/2026";
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 60);
+        insert_session(&db, "b", "Alice (work)", "backend", monday + Duration::hours(12), 30);
+        insert_session(&db, "b", "Alice (work)", "docs", monday + Duration::hours(14), 20);
+        db.archive_week(week).unwrap();
+        insert_session(&db, "b", "Alice (work)", "api", monday + Duration::hours(16), 15);
+        db.clock_in("c", "Carol", "backend").unwrap();
+
+        let err = db.link_accounts("c", "a", "Alice").unwrap_err();
+        assert_eq!(err.downcast_ref::<Refusal>(), Some(&Refusal::ClockedIn));
+        db.link_accounts("b", "a", "Alice").unwrap();
+        assert_eq!(db.primary_account("b"), "a");
+        assert_eq!(db.linked_accounts("a").unwrap(), vec!["b"]);
+        assert!(db.link_accounts("a", "b", "Alice").is_err());
+
+        // Archived weeks add up instead of showing the same person twice
+        assert_eq!(db.archived_week_total(Some("a"), week).unwrap(), Some(110));
+        assert_eq!(db.archived_week_total(Some("b"), week).unwrap(), None);
+        assert_eq!(db.activity_usage("a", "backend").unwrap(), (0, 1, 90));
+        assert_eq!(db.activity_usage("a", "docs").unwrap(), (0, 1, 20));
+        let board = db.leaderboard_weekly(false).unwrap();
+        assert_eq!((board[0].username.as_str(), board[0].total_minutes), ("Alice", 15));
+
+        assert!(db.unlink_account("b").unwrap());
+        assert_eq!(db.primary_account("b"), "b");
+        assert!(!db.unlink_account("b").unwrap());
     }
 
     #[test]
-    fn test_rename_activity_not_found() {
-        let (db, _temp_dir) = setup_test_db();
-        let user_id = "user123";
+    fn test_clients() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "platform", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "website", monday + Duration::hours(9), 60);
+        insert_session(&db, "b", "Bob", "admin", monday + Duration::hours(12), 30);
 
-        // Try to rename a non-existent activity
-        let result = db.rename_activity(user_id, "nonexistent", "work");
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "no sessions found with that activity");
+        assert!(db.set_activity_client("platform", Some("Acme")).unwrap());
+        assert!(db.set_activity_client("website", Some("Acme")).unwrap());
+        db.rebuild_weekly_aggregates().unwrap();
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.clients, vec![("Acme".to_string(), 180)]);
+
+        db.archive_week("KW12/2026").unwrap();
+        insert_session(&db, "a", "Alice", "website", monday + Duration::hours(14), 45);
+        let activities = db.client_activities().unwrap();
+        assert_eq!(
+            activities,
+            vec![
+                ClientActivity { client: "Acme".into(), activity: "platform".into(), week_minutes: 0, total_minutes: 120 },
+                ClientActivity { client: "Acme".into(), activity: "website".into(), week_minutes: 45, total_minutes: 105 },
+            ]
+        );
+
+        assert!(db.set_activity_client("platform", None).unwrap());
+        assert!(!db.set_activity_client("platform", None).unwrap());
+        assert_eq!(db.client_activities().unwrap().len(), 1);
+
+        assert_eq!(db.client_rate("Acme").unwrap(), None);
+        db.set_client_rate("Acme", Some(150.0)).unwrap();
+        assert_eq!(db.client_rate("acme").unwrap(), Some(150.0));
+        db.set_client_rate("ACME", None).unwrap();
+        assert_eq!(db.client_rate("Acme").unwrap(), None);
     }
 
     #[test]
-    fn test_rename_activity_merge_archives() {
-        let (db, _temp_dir) = setup_test_db();
-        let user_id = "user123";
-        let username = "TestUser";
-        let week_label = "KW07/2026";
+    fn test_activity_budgets() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "platform", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "platform", monday + Duration::hours(12), 60);
+        insert_session(&db, "b", "Bob", "admin", monday + Duration::hours(14), 60);
+        assert_eq!(db.activity_budget("platform").unwrap(), None);
 
-        // Manually insert archive entries for the same user and week but different activities
+        db.set_activity_budget("platform", Some(600)).unwrap();
+        let budget = db.activity_budget("platform").unwrap().unwrap();
+        assert_eq!((budget.budget_min, budget.used_min, budget.alerted_pct), (600, 180, 0));
+
+        // Archived weeks still count against the budget
+        db.archive_week("KW12/2026").unwrap();
+        insert_session(&db, "a", "Alice", "platform", monday + Duration::hours(16), 60);
+        assert_eq!(db.activity_budget("platform").unwrap().unwrap().used_min, 240);
+
+        db.mark_budget_alerted("platform", 50).unwrap();
+        db.set_activity_budget("platform", Some(600)).unwrap();
+        assert_eq!(db.activity_budget("platform").unwrap().unwrap().alerted_pct, 50);
+        // A new budget starts over
+        db.set_activity_budget("platform", Some(1200)).unwrap();
+        assert_eq!(db.activity_budget("platform").unwrap().unwrap().alerted_pct, 0);
+
+        db.set_activity_budget("platform", None).unwrap();
+        assert!(db.activity_budgets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_inactive_users() {
+        let db = test_db();
         {
             let conn = db.conn.lock().unwrap();
-            conn.execute(
-                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_min) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![user_id, username, week_label, "work", 60],
-            ).unwrap();
-            conn.execute(
-                "INSERT INTO activity_archive (user_id, username, week_label, activity, total_min) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![user_id, username, week_label, "boring work", 30],
-            ).unwrap();
+            conn.execute_batch(
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec, week_start) VALUES
+                    ('a', 'Alice', 'KW02/2026', 36000, '2026-01-05'), ('a', 'Alice', 'KW04/2026', 18000, '2026-01-19'),
+                    ('b', 'Bob', 'KW04/2026', 7200, '2026-01-19'), ('b', 'Bob', 'KW08/2026', 3600, '2026-02-16'),
+                    ('c', 'Carol', 'KW03/2026', 3600, '2026-01-12'), ('d', 'Dan', 'KW03/2026', 3600, '2026-01-12');",
+            )
+            .unwrap();
         }
+        // Carol is back this week, Dan doesn't want DMs
+        db.clock_in("c", "Carol", "backend").unwrap();
+        db.set_nudges("d", false).unwrap();
 
-        // Rename "boring work" to "work" - should merge the archives
-        let (sessions_updated, archive_merged) = db.rename_activity(user_id, "boring work", "work").unwrap();
-        assert_eq!(sessions_updated, 0); // No sessions to update
-        assert_eq!(archive_merged, 1); // One duplicate row merged
+        let cutoff = NaiveDate::from_ymd_opt(2026, 2, 2).unwrap();
+        let inactive = db.inactive_users(cutoff).unwrap();
+        assert_eq!(
+            inactive,
+            vec![InactiveUser {
+                user_id: "a".to_string(),
+                username: "Alice".to_string(),
+                last_week: "KW04/2026".to_string(),
+                best_week: "KW02/2026".to_string(),
+                best_minutes: 600,
+            }]
+        );
 
-        // Verify the archives were merged
-        let conn = db.conn.lock().unwrap();
-        let total_min: i64 = conn
-            .query_row(
-                "SELECT total_min FROM activity_archive WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3",
-                params![user_id, week_label, "work"],
-                |r| r.get(0),
+        // One DM per stretch away, until they come back and leave again
+        db.mark_reengaged("a", "KW04/2026").unwrap();
+        assert!(db.inactive_users(cutoff).unwrap().is_empty());
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec, week_start)
+                 VALUES ('a', 'Alice', 'KW05/2026', 1800, '2026-01-26')",
+                [],
             )
             .unwrap();
-        assert_eq!(total_min, 90); // 60 + 30
+        assert_eq!(db.inactive_users(cutoff).unwrap()[0].last_week, "KW05/2026");
+        assert!(db.user_settings("a").unwrap().nudges);
+    }
 
-        // Verify only one row exists for this user/week/activity
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM activity_archive WHERE user_id = ?1 AND week_label = ?2 AND activity = ?3",
-                params![user_id, week_label, "work"],
-                |r| r.get(0),
-            )
-            .unwrap();
-        assert_eq!(count, 1);
+    #[test]
+    fn test_activity_info() {
+        let db = test_db();
+        assert_eq!(db.activity_info("platform").unwrap(), None);
+        let info = ActivityInfo { description: "The provisioning engine".to_string(), owner_id: Some("42".to_string()) };
+        assert!(db.set_activity_info("platform", Some(&info)).unwrap());
+        assert_eq!(db.activity_info("platform").unwrap(), Some(info));
+
+        let info = ActivityInfo { description: "Provisioning".to_string(), owner_id: None };
+        db.set_activity_info("platform", Some(&info)).unwrap();
+        assert_eq!(db.activity_info("platform").unwrap().unwrap().owner_id, None);
+
+        assert!(db.set_activity_info("platform", None).unwrap());
+        assert!(!db.set_activity_info("platform", None).unwrap());
+        assert_eq!(db.activity_info("platform").unwrap(), None);
     }
 
     #[test]
-    fn test_rename_activity_active_session() {
-        let (db, _temp_dir) = setup_test_db();
-        let user_id = "user123";
-        let username = "TestUser";
+    fn test_goal_leaderboard() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "backend", monday + Duration::hours(9), 600);
+        insert_session(&db, "b", "Bob", "break", monday + Duration::hours(20), 60);
+        assert!(db.goal_leaderboard_weekly().unwrap().is_empty());
 
-        // Clock in to "boring work"
-        db.clock_in(user_id, username, "boring work").unwrap();
-        
-        // Rename while still clocked in
-        let (sessions_updated, _) = db.rename_activity(user_id, "boring work", "work").unwrap();
-        assert_eq!(sessions_updated, 1);
+        db.set_daily_goal("a", Some(240)).unwrap();
+        db.set_daily_goal("b", Some(480)).unwrap();
+        db.add_non_competitive("break").unwrap();
+        let mut board = db.goal_leaderboard_weekly().unwrap();
+        board.sort();
+        assert_eq!(board, vec![("Alice".to_string(), 120, 240), ("Bob".to_string(), 600, 480)]);
+    }
 
-        // Verify the active session was renamed
-        let session = db.active_session(user_id).unwrap().unwrap();
-        assert_eq!(session.activity, "work");
+    #[test]
+    fn test_event_standings_survive_archive() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        let start = monday + Duration::hours(10);
+        let end = monday + Duration::hours(14);
+        assert!(db.create_event("Hackathon", start, end).unwrap());
+        assert!(!db.create_event("hackathon", start, end).unwrap());
+        let event = db.event("HACKATHON").unwrap().unwrap();
+        assert_eq!((event.starts_at, event.ends_at), (start, end));
+
+        // Clipped to the window: 9:00–11:00 counts 60 minutes
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(9), 120);
+        insert_session(&db, "b", "Bob", "backend", monday + Duration::hours(11), 120);
+        // Outside the window
+        insert_session(&db, "a", "Alice", "backend", monday + Duration::hours(15), 300);
+        let standings = db.event_standings(&event, end).unwrap();
+        assert_eq!(
+            standings.iter().map(|e| (e.username.as_str(), e.total_minutes)).collect::<Vec<_>>(),
+            vec![("Bob", 120), ("Alice", 60)]
+        );
+
+        assert!(db.events_to_announce(end - Duration::minutes(1)).unwrap().is_empty());
+        assert_eq!(db.events_to_announce(end).unwrap(), vec![event.clone()]);
+        db.mark_event_announced(event.id).unwrap();
+        assert!(db.events_to_announce(end).unwrap().is_empty());
+
+        // The weekly reset deletes sessions, the event keeps its minutes
+        db.archive_week("KW07/2026").unwrap();
+        assert_eq!(db.event_standings(&event, end).unwrap(), standings);
+
+        assert!(db.delete_event("hackathon").unwrap());
+        assert!(db.events().unwrap().is_empty());
     }
 
     #[test]
-    fn test_rename_activity_per_user() {
-        let (db, _temp_dir) = setup_test_db();
-        let user1 = "user123";
-        let user2 = "user456";
-        let username1 = "User1";
-        let username2 = "User2";
+    fn test_challenges() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let week = db.clock().week();
+        let challenge = Challenge {
+            id: 0,
+            week_label: week.label(),
+            channel_id: "c".to_string(),
+            challenger_id: "a".to_string(),
+            challenger_name: "Alice".to_string(),
+            opponent_id: "b".to_string(),
+            opponent_name: "Bob".to_string(),
+            target_min: 600,
+        };
+        assert!(db.create_challenge(&challenge, false).unwrap());
+        assert!(!db.create_challenge(&challenge, false).unwrap());
 
-        // Both users have "boring work" sessions
-        db.clock_in(user1, username1, "boring work").unwrap();
-        db.clock_out(user1).unwrap();
-        
-        db.clock_in(user2, username2, "boring work").unwrap();
-        db.clock_out(user2).unwrap();
+        let stored = db.challenges(&week.label()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].opponent_name, "Bob");
+        assert_eq!(db.challenges_awaiting_progress(&week.label()).unwrap().len(), 1);
+        db.mark_challenge_progress_posted(stored[0].id).unwrap();
+        assert!(db.challenges_awaiting_progress(&week.label()).unwrap().is_empty());
 
-        // User1 renames their activity
-        let (sessions_updated, _) = db.rename_activity(user1, "boring work", "work").unwrap();
-        assert_eq!(sessions_updated, 1);
+        insert_session(&db, "a", "Alice", "backend", week.start() + Duration::hours(9), 90);
+        insert_session(&db, "a", "Alice", "break", week.start() + Duration::hours(12), 30);
+        db.add_non_competitive("break").unwrap();
+        assert_eq!(db.competitive_minutes("a", week).unwrap(), 90);
+        assert_eq!(db.competitive_minutes("b", week).unwrap(), 0);
 
-        // Verify user1's activity was renamed but user2's wasn't
-        let conn = db.conn.lock().unwrap();
-        let user1_activity: String = conn
-            .query_row(
-                "SELECT activity FROM sessions WHERE user_id = ?1",
-                params![user1],
-                |r| r.get(0),
-            )
-            .unwrap();
-        assert_eq!(user1_activity, "work");
+        assert_eq!(db.settle_challenges(&week.label()).unwrap().len(), 1);
+        assert!(db.settle_challenges(&week.label()).unwrap().is_empty());
+    }
 
-        let user2_activity: String = conn
-            .query_row(
-                "SELECT activity FROM sessions WHERE user_id = ?1",
-                params![user2],
-                |r| r.get(0),
-            )
-            .unwrap();
-        assert_eq!(user2_activity, "boring work");
+    #[test]
+    fn test_kudos() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let week = "KW12/2026";
+        db.add_kudos_target("m1", "a", "Alice", week).unwrap();
+        db.add_kudos_target("m2", "b", "Bob", week).unwrap();
+
+        assert!(db.give_kudos("m1", "b").unwrap());
+        assert!(!db.give_kudos("m1", "b").unwrap());
+        assert!(db.give_kudos("m1", "c").unwrap());
+        // Own posts and unknown messages don't count
+        assert!(!db.give_kudos("m1", "a").unwrap());
+        assert!(!db.give_kudos("other", "b").unwrap());
+        assert!(db.give_kudos("m2", "a").unwrap());
+
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.most_appreciated, Some(("Alice".to_string(), 2)));
+
+        assert!(db.take_back_kudos("m1", "b").unwrap());
+        assert!(db.take_back_kudos("m1", "c").unwrap());
+        let summary = db.weekly_summary(db.clock().week()).unwrap();
+        assert_eq!(summary.most_appreciated, Some(("Bob".to_string(), 1)));
+
+        db.archive_week(week).unwrap();
+        assert!(!db.give_kudos("m2", "c").unwrap());
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let db = test_db();
+        db.ping().unwrap();
+        assert_eq!(db.metadata("k").unwrap(), None);
+        db.set_metadata("k", "v1").unwrap();
+        db.set_metadata("k", "v2").unwrap();
+        assert_eq!(db.metadata("k").unwrap(), Some("v2".to_string()));
+    }
+
+    #[test]
+    fn test_active_count() {
+        let db = test_db();
+        assert_eq!(db.active_count().unwrap(), 0);
+        db.clock_in("a", "Alice", "work").unwrap();
+        db.clock_in("b", "Bob", "work").unwrap();
+        db.clock_out("a").unwrap();
+        assert_eq!(db.active_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_focus_roles_tracking() {
+        let db = test_db();
+
+        db.clock_in("a", "Alice", "work").unwrap();
+        db.record_focus_role("a", "g1").unwrap();
+        db.record_focus_role("b", "g1").unwrap();
+
+        // Bob holds the role without an open session → stale
+        assert_eq!(db.stale_focus_roles().unwrap(), vec![("b".to_string(), "g1".to_string())]);
+
+        assert_eq!(db.take_focus_role("b").unwrap(), Some("g1".to_string()));
+        assert_eq!(db.take_focus_role("b").unwrap(), None);
+
+        db.clock_out("a").unwrap();
+        assert_eq!(db.stale_focus_roles().unwrap().len(), 1);
     }
 
     #[test]
     fn test_weekly_hours_for_chart_empty() {
-        let (db, _temp_dir) = setup_test_db();
+        let db = test_db();
         // No data at all → returns empty users list (no error)
         let chart = db.weekly_hours_for_chart(4).unwrap();
         assert_eq!(chart.week_labels.len(), 4);
@@ -900,10 +5238,10 @@ mod tests {
 
     #[test]
     fn test_weekly_hours_for_chart_archive_data() {
-        let (db, _temp_dir) = setup_test_db();
+        let (db, _clock) = test_db_at(at(20, 18, 0));
 
         // Insert archive rows for two past weeks and two users.
-        let week_labels = generate_week_labels(4);
+        let week_labels = ["KW09/2026", "KW10/2026", "KW11/2026", "KW12/2026"];
         // Use the oldest two weeks (index 0 and 1) for past data.
         let week_a = &week_labels[0];
         let week_b = &week_labels[1];
@@ -912,17 +5250,17 @@ mod tests {
             let conn = db.conn.lock().unwrap();
             // User A: 120 min in week_a, 60 min in week_b  → total 180
             conn.execute(
-                "INSERT INTO weekly_archive (user_id, username, week_label, total_min) VALUES (?1,?2,?3,?4)",
-                params!["a", "Alice", week_a, 120i64],
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES (?1,?2,?3,?4)",
+                params!["a", "Alice", week_a, 120i64 * 60],
             ).unwrap();
             conn.execute(
-                "INSERT INTO weekly_archive (user_id, username, week_label, total_min) VALUES (?1,?2,?3,?4)",
-                params!["a", "Alice", week_b, 60i64],
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES (?1,?2,?3,?4)",
+                params!["a", "Alice", week_b, 60i64 * 60],
             ).unwrap();
             // User B: 90 min in week_a only → total 90
             conn.execute(
-                "INSERT INTO weekly_archive (user_id, username, week_label, total_min) VALUES (?1,?2,?3,?4)",
-                params!["b", "Bob", week_a, 90i64],
+                "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES (?1,?2,?3,?4)",
+                params!["b", "Bob", week_a, 90i64 * 60],
             ).unwrap();
         }
 
@@ -946,9 +5284,9 @@ mod tests {
 
     #[test]
     fn test_weekly_hours_for_chart_top5_cap() {
-        let (db, _temp_dir) = setup_test_db();
+        let (db, _clock) = test_db_at(at(20, 18, 0));
 
-        let week_labels = generate_week_labels(2);
+        let week_labels = ["KW11/2026", "KW12/2026"];
         let week_a = &week_labels[0];
 
         {
@@ -956,8 +5294,8 @@ mod tests {
             // Insert 6 users with distinct totals.
             for i in 1u32..=6 {
                 conn.execute(
-                    "INSERT INTO weekly_archive (user_id, username, week_label, total_min) VALUES (?1,?2,?3,?4)",
-                    params![format!("u{}", i), format!("User{}", i), week_a, (i * 10) as i64],
+                    "INSERT INTO weekly_archive (user_id, username, week_label, total_sec) VALUES (?1,?2,?3,?4)",
+                    params![format!("u{}", i), format!("User{}", i), week_a, (i * 10 * 60) as i64],
                 ).unwrap();
             }
         }
@@ -968,4 +5306,396 @@ mod tests {
         // Top user should be User6 (60 min).
         assert_eq!(chart.users[0].username, "User6");
     }
+
+    #[test]
+    fn test_sessions_on_day_overlap() {
+        let db = test_db();
+        let day = NaiveDate::from_ymd_opt(2026, 2, 10).unwrap();
+        let at = |d: NaiveDate, h: u32, m: u32| d.and_hms_opt(h, m, 0).unwrap();
+        let prev = day.pred_opt().unwrap();
+        let next = day.succ_opt().unwrap();
+
+        insert_session(&db, "u1", "Alice", "backend", at(prev, 23, 0), 90); // crosses midnight in
+        insert_session(&db, "u1", "Alice", "frontend", at(day, 14, 0), 60);
+        insert_session(&db, "u1", "Alice", "docs", at(prev, 10, 0), 60); // previous day only
+        insert_session(&db, "u1", "Alice", "ops", at(next, 0, 0), 30); // next day only
+        insert_session(&db, "u2", "Bob", "backend", at(day, 9, 0), 60); // someone else
+
+        let sessions = db.sessions_on_day("u1", day).unwrap();
+        let activities: Vec<&str> = sessions.iter().map(|s| s.activity.as_str()).collect();
+        assert_eq!(activities, vec!["backend", "frontend"]);
+        assert_eq!(sessions[0].ended_at, at(day, 0, 30));
+    }
+
+    #[test]
+    fn test_sessions_on_day_includes_running_session() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        db.clock_in("u1", "Alice", "backend").unwrap();
+
+        let sessions = db.sessions_on_day("u1", at(20, 0, 0).date()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].ended_at >= sessions[0].started_at);
+    }
+
+    #[test]
+    fn test_user_settings_defaults_and_updates() {
+        let db = test_db();
+
+        let s = db.user_settings("u1").unwrap();
+        assert_eq!(s.daily_goal_min, None);
+        assert_eq!(s.timezone, DEFAULT_TIMEZONE);
+        assert!(s.nudges);
+        assert!(db.nudge_candidates().unwrap().is_empty());
+
+        db.set_daily_goal("u1", Some(240)).unwrap();
+        db.set_timezone("u1", "America/New_York").unwrap();
+        let s = db.user_settings("u1").unwrap();
+        assert_eq!(s.daily_goal_min, Some(240));
+        assert_eq!(s.timezone, "America/New_York");
+        assert_eq!(db.nudge_candidates().unwrap().len(), 1);
+
+        let day = NaiveDate::from_ymd_opt(2026, 2, 10).unwrap();
+        db.mark_nudged("u1", day).unwrap();
+        assert_eq!(db.user_settings("u1").unwrap().last_nudged, Some(day));
+
+        db.set_nudges("u1", false).unwrap();
+        assert!(db.nudge_candidates().unwrap().is_empty());
+        // Turning nudges off keeps the goal
+        assert_eq!(db.user_settings("u1").unwrap().daily_goal_min, Some(240));
+
+        db.set_nudges("u1", true).unwrap();
+        db.set_daily_goal("u1", None).unwrap();
+        assert!(db.nudge_candidates().unwrap().is_empty());
+
+        assert!(s.tips);
+        db.mark_tip_shown("u1", day).unwrap();
+        db.set_tips("u1", false).unwrap();
+        let s = db.user_settings("u1").unwrap();
+        assert!(!s.tips);
+        assert_eq!(s.last_tip, Some(day));
+    }
+
+    #[test]
+    fn test_consecutive_work_days() {
+        let db = test_db();
+        let today = NaiveDate::from_ymd_opt(2026, 2, 12).unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            for day in ["2026-02-12", "2026-02-11", "2026-02-10", "2026-02-09", "2026-02-07"] {
+                conn.execute(
+                    "INSERT INTO work_days (user_id, day) VALUES ('u1', ?1)",
+                    params![day],
+                )
+                .unwrap();
+            }
+        }
+        // Today itself doesn't count; the gap on the 8th ends the run
+        assert_eq!(db.consecutive_work_days_before("u1", today).unwrap(), 3);
+        assert_eq!(db.consecutive_work_days_before("u1", today + Duration::days(2)).unwrap(), 0);
+        assert_eq!(db.consecutive_work_days_before("u2", today).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clock_out_records_work_days() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let today = at(20, 0, 0).date();
+        db.clock_in("u1", "Alice", "backend").unwrap();
+        db.clock_out("u1").unwrap();
+        assert_eq!(db.consecutive_work_days_before("u1", today + Duration::days(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_holidays_and_short_week_summary() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let week = db.clock().week();
+        let monday = week.monday();
+
+        db.add_holiday(monday, "Whit Monday").unwrap();
+        db.add_holiday(monday + Duration::days(5), "Saturday party").unwrap();
+        assert!(db.is_holiday(monday).unwrap());
+        assert!(!db.is_holiday(monday + Duration::days(1)).unwrap());
+
+        // Weekend holidays don't shorten the week
+        let summary = db.weekly_summary(week).unwrap();
+        assert_eq!(summary.holidays, vec![(monday, "Whit Monday".to_string())]);
+
+        db.add_holiday(monday, "Pentecost Monday").unwrap();
+        assert_eq!(db.holidays_between(monday, monday).unwrap()[0].1, "Pentecost Monday");
+
+        assert!(db.remove_holiday(monday).unwrap());
+        assert!(!db.remove_holiday(monday).unwrap());
+        assert!(db.weekly_summary(week).unwrap().holidays.is_empty());
+    }
+
+    #[test]
+    fn test_top_activities_and_templates() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "a", "Alice", "backend", monday, 30);
+        insert_session(&db, "b", "Bob", "meetings", monday, 90);
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO activity_archive (user_id,username,week_label,activity,total_sec) VALUES ('a','Alice','KW01/2026','backend',7200)",
+                [],
+            )
+            .unwrap();
+        }
+        assert_eq!(db.top_activities(25).unwrap(), vec!["backend", "meetings"]);
+        assert_eq!(db.top_activities(1).unwrap(), vec!["backend"]);
+
+        assert!(db.activity_templates().unwrap().is_empty());
+        assert!(db.add_activity_template("standup").unwrap());
+        assert!(db.add_activity_template("backend").unwrap());
+        assert!(!db.add_activity_template("standup").unwrap());
+        assert_eq!(db.activity_templates().unwrap(), vec!["standup", "backend"]);
+        assert!(db.remove_activity_template("standup").unwrap());
+        assert!(!db.remove_activity_template("standup").unwrap());
+        assert_eq!(db.activity_templates().unwrap(), vec!["backend"]);
+    }
+
+    #[test]
+    fn test_calendar_feed_settings() {
+        let db = test_db();
+        assert!(db.calendar_feed("u1").unwrap().is_none());
+        assert!(!db.set_calendar_keywords("u1", &["standup".to_string()]).unwrap());
+
+        db.set_calendar_feed("u1", "Alice", "https://example.com/a.ics").unwrap();
+        db.set_calendar_keywords("u1", &["standup".to_string(), "review".to_string()]).unwrap();
+        db.set_calendar_activity("u1", "syncs").unwrap();
+        db.set_calendar_feed("u1", "Alice", "https://example.com/b.ics").unwrap();
+
+        let feed = db.calendar_feed("u1").unwrap().unwrap();
+        assert_eq!(feed.url, "https://example.com/b.ics");
+        assert_eq!(feed.keywords, vec!["standup", "review"]);
+        assert_eq!(feed.activity, "syncs");
+        assert_eq!(db.calendar_feeds().unwrap().len(), 1);
+
+        db.set_calendar_keywords("u1", &[]).unwrap();
+        assert!(db.calendar_feed("u1").unwrap().unwrap().keywords.is_empty());
+        assert!(db.remove_calendar_feed("u1").unwrap());
+        assert!(db.calendar_feeds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_import_calendar_event_skips_overlap_with_manual_session() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "u1", "Alice", "backend", monday + Duration::hours(9), 60);
+
+        let overlapping = (monday + Duration::minutes(9 * 60 + 30), monday + Duration::hours(11));
+        assert!(!db.import_calendar_event("u1", "Alice", "meetings", "a@1", overlapping.0, overlapping.1).unwrap());
+
+        let later = (monday + Duration::hours(14), monday + Duration::minutes(14 * 60 + 45));
+        assert!(db.import_calendar_event("u1", "Alice", "meetings", "b@1", later.0, later.1).unwrap());
+        assert!(!db.import_calendar_event("u1", "Alice", "meetings", "b@1", later.0, later.1).unwrap());
+
+        let sessions = db.sessions_between("u1", monday, monday + Duration::days(1)).unwrap();
+        let activities: Vec<&str> = sessions.iter().map(|s| s.activity.as_str()).collect();
+        assert_eq!(activities, vec!["backend", "meetings"]);
+    }
+
+    #[test]
+    fn test_import_session_records_source() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "u1", "Alice", "backend", monday + Duration::hours(9), 60);
+        let toil = (monday + Duration::hours(11), monday + Duration::minutes(11 * 60 + 45));
+        assert!(db.import_session("u1", "Alice", "deployments", toil.0, toil.1, SOURCE_BOT).unwrap());
+
+        let conn = db.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT activity, source FROM sessions ORDER BY started_at").unwrap();
+        let sources: Vec<(String, String)> =
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            sources,
+            vec![("backend".to_string(), "manual".to_string()), ("deployments".to_string(), "bot".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_clock_out_across_week_boundary() {
+        use crate::fixtures::make_user;
+        let sunday = NaiveDate::from_ymd_opt(2026, 3, 22).unwrap();
+        let (db, clock) = test_db_at(sunday.and_hms_opt(23, 30, 0).unwrap());
+        let alice = make_user(&db, "Alice");
+
+        db.clock_in(&alice.id, &alice.name, "backend").unwrap();
+        clock.advance(Duration::minutes(90));
+        assert_eq!(db.clock().week(), WeekId::containing(sunday).next());
+        let (elapsed, _) = db.clock_out(&alice.id).unwrap();
+        assert_eq!(elapsed.minutes(), 90);
+
+        // The session counts towards the week it started in
+        let conn = db.conn.lock().unwrap();
+        let week: String = conn.query_row("SELECT week_label FROM weekly_aggregates", [], |r| r.get(0)).unwrap();
+        assert_eq!(week, WeekId::containing(sunday).label());
+    }
+
+    #[test]
+    fn test_import_sessions_in_bulk() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "u1", "Alice", "backend", monday + Duration::hours(9), 60);
+        db.rebuild_weekly_aggregates().unwrap();
+        let row = |user: &str, activity: &str, start: NaiveDateTime, minutes| ImportRow {
+            user_id: user.to_string(),
+            username: user.to_uppercase(),
+            activity: activity.to_string(),
+            started_at: start,
+            ended_at: start + Duration::minutes(minutes),
+        };
+
+        // Enough rows for several chunks: 30-minute sessions every 40 minutes for u2
+        let mut rows: Vec<ImportRow> = (0..250)
+            .map(|i| row("u2", if i % 2 == 0 { "docs" } else { "ops" }, monday + Duration::minutes(40 * i), 30))
+            .collect();
+        // Overlaps the existing session, then an earlier row of the batch
+        rows.push(row("u1", "docs", monday + Duration::minutes(9 * 60 + 30), 60));
+        rows.push(row("u1", "docs", monday + Duration::hours(12), 60));
+        rows.push(row("u1", "ops", monday + Duration::minutes(12 * 60 + 30), 60));
+        assert_eq!(db.import_sessions(&rows, SOURCE_IMPORT).unwrap(), 251);
+        assert_eq!(db.import_sessions(&rows, SOURCE_IMPORT).unwrap(), 0);
+
+        // Aggregates match a rebuild from the sessions
+        let aggregates = |db: &Db| {
+            let conn = db.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT user_id, activity, total_sec, session_count, longest_sec FROM weekly_aggregates ORDER BY user_id, activity")
+                .unwrap();
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect::<Vec<(String, String, i64, i64, i64)>>()
+        };
+        let bulk = aggregates(&db);
+        db.rebuild_weekly_aggregates().unwrap();
+        assert_eq!(bulk, aggregates(&db));
+        assert_eq!(bulk[0], ("u1".to_string(), "backend".to_string(), 3600, 1, 3600));
+        assert_eq!(bulk[1], ("u1".to_string(), "docs".to_string(), 3600, 1, 3600));
+        let u2: i64 = bulk.iter().filter(|a| a.0 == "u2").map(|a| a.2).sum();
+        assert_eq!(u2, 250 * 30 * 60);
+    }
+
+    #[test]
+    fn test_weekly_stats_by_source() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let monday = at(16, 0, 0);
+        insert_session(&db, "u1", "Alice", "backend", monday + Duration::hours(9), 60);
+        let meeting = (monday + Duration::hours(11), monday + Duration::minutes(11 * 60 + 30));
+        assert!(db.import_calendar_event("u1", "Alice", "meetings", "m@1", meeting.0, meeting.1).unwrap());
+
+        let auto = db.activity_breakdown_weekly_from(&[SOURCE_CALENDAR, SOURCE_API]).unwrap();
+        assert_eq!(auto.len(), 1);
+        assert_eq!((auto[0].activity.as_str(), auto[0].total_minutes), ("meetings", 30));
+        assert_eq!(
+            db.source_totals_weekly().unwrap(),
+            vec![(SOURCE_MANUAL.to_string(), 60), (SOURCE_CALENDAR.to_string(), 30)]
+        );
+
+        let records = db.session_records(Some("u1")).unwrap();
+        let sources: Vec<&str> = records.iter().map(|r| r.source.as_str()).collect();
+        assert_eq!(sources, vec![SOURCE_MANUAL, SOURCE_CALENDAR]);
+        assert!(db.session_records(Some("u2")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_github_links_and_rules() {
+        let db = test_db();
+        db.link_github("Octocat", "u1", "Alice", "suggest").unwrap();
+        assert_eq!(db.github_link_by_login("octocat").unwrap().unwrap().user_id, "u1");
+
+        // Relinking replaces the user's previous login
+        db.link_github("alice-gh", "u1", "Alice", "log").unwrap();
+        assert!(db.github_link_by_login("octocat").unwrap().is_none());
+        assert_eq!(db.github_link_for_user("u1").unwrap().unwrap().mode, "log");
+
+        db.set_github_repo_rule("Org/API", "backend").unwrap();
+        db.set_github_repo_rule("org/web*", "frontend").unwrap();
+        db.set_github_repo_rule("org/api", "api").unwrap();
+        assert_eq!(
+            db.github_repo_rules().unwrap(),
+            vec![("org/api".to_string(), "api".to_string()), ("org/web*".to_string(), "frontend".to_string())]
+        );
+        assert!(db.remove_github_repo_rule("ORG/WEB*").unwrap());
+
+        assert!(db.unlink_github("u1").unwrap());
+        assert!(!db.unlink_github("u1").unwrap());
+
+        let link = GithubLink { login: "Octocat".into(), user_id: "u2".into(), username: "Bob".into(), mode: "log".into() };
+        db.set_github_pending(&link, "clockbot-ABC").unwrap();
+        let (pending, code) = db.github_pending("u2").unwrap().unwrap();
+        assert_eq!((pending.login.as_str(), code.as_str()), ("octocat", "clockbot-ABC"));
+        db.delete_github_pending("u2").unwrap();
+        assert!(db.github_pending("u2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_github_bursts() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let t = |h: u32, m: u32| at(16, 0, 0).date().and_hms_opt(h, m, 0).unwrap();
+        let gap = Duration::minutes(45);
+
+        let (started, closed) = db.record_github_activity("u1", "Alice", "backend", t(9, 0), t(9, 20), gap).unwrap();
+        assert!(started && closed.is_none());
+        // Within the gap: extends the burst
+        let (started, closed) = db.record_github_activity("u1", "Alice", "backend", t(10, 0), t(10, 0), gap).unwrap();
+        assert!(!started && closed.is_none());
+        // Different activity: closes it
+        let (started, closed) = db.record_github_activity("u1", "Alice", "frontend", t(10, 10), t(10, 10), gap).unwrap();
+        assert!(started);
+        let closed = closed.unwrap();
+        assert_eq!((closed.activity.as_str(), closed.first_at, closed.last_at), ("backend", t(9, 0), t(10, 0)));
+
+        assert!(db.take_idle_github_bursts(t(10, 10)).unwrap().is_empty());
+        let idle = db.take_idle_github_bursts(t(11, 0)).unwrap();
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0].activity, "frontend");
+        assert!(db.take_idle_github_bursts(t(12, 0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_heartbeat_spans_and_api_tokens() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let t = |h: u32, m: u32| at(16, 0, 0).date().and_hms_opt(h, m, 0).unwrap();
+        let gap = Duration::minutes(15);
+
+        assert!(db.record_heartbeat("u1", "Alice", "clockbot", t(9, 0), gap).unwrap().is_none());
+        assert!(db.record_heartbeat("u1", "Alice", "clockbot", t(9, 10), gap).unwrap().is_none());
+        // Heartbeat spans are independent of GitHub bursts
+        assert!(db.take_idle_github_bursts(t(12, 0)).unwrap().is_empty());
+        let closed = db.record_heartbeat("u1", "Alice", "clockbot", t(9, 40), gap).unwrap().unwrap();
+        assert_eq!((closed.first_at, closed.last_at), (t(9, 0), t(9, 10)));
+        assert_eq!(db.take_idle_heartbeat_spans(t(10, 0)).unwrap().len(), 1);
+
+        db.set_api_token("u1", "Alice", "hash-a").unwrap();
+        db.set_api_token("u1", "Alice", "hash-b").unwrap();
+        assert!(db.api_token_user("hash-a").unwrap().is_none());
+        assert_eq!(db.api_token_user("hash-b").unwrap(), Some(("u1".to_string(), "Alice".to_string())));
+        assert!(db.revoke_api_token("u1").unwrap());
+        assert!(db.api_token_user("hash-b").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_digest_subscriptions_and_archived_activities() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        db.subscribe_digest("u1", "Alice", "alice@example.com").unwrap();
+        db.subscribe_digest("u1", "Alice", "alice@work.example").unwrap();
+        db.subscribe_digest("u2", "Bob", "bob@example.com").unwrap();
+        assert_eq!(db.digest_email("u1").unwrap().as_deref(), Some("alice@work.example"));
+        assert_eq!(db.digest_subscribers().unwrap().len(), 2);
+        assert!(db.unsubscribe_digest("u2").unwrap());
+        assert!(!db.unsubscribe_digest("u2").unwrap());
+
+        let monday = at(16, 0, 0).date().and_hms_opt(9, 0, 0).unwrap();
+        insert_session(&db, "u1", "Alice", "backend", monday, 120);
+        insert_session(&db, "u1", "Alice", "docs", monday + Duration::hours(3), 60);
+        insert_session(&db, "u2", "Bob", "backend", monday, 60);
+        db.archive_week("KW07/2026").unwrap();
+
+        let mine = db.archived_activities("u1", "KW07/2026").unwrap();
+        assert_eq!(mine.len(), 2);
+        assert_eq!((mine[0].activity.as_str(), mine[0].total_minutes), ("backend", 120));
+        assert!(db.archived_activities("u1", "KW08/2026").unwrap().is_empty());
+    }
 }
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keys seen within the last `window`, to drop repeats of something already handled:
+/// a message Discord delivers twice or a command sent twice in a row.
+pub struct Debounce {
+    window: Duration,
+    seen: Mutex<HashMap<String, Instant>>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `true` the first time `key` comes up within the window, `false` for repeats.
+    /// Repeats don't extend the window, so a key is accepted again `window` after it
+    /// was first seen.
+    pub fn first(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, at| now.duration_since(*at) < self.window);
+        if seen.contains_key(key) {
+            return false;
+        }
+        seen.insert(key.to_string(), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeats_within_window() {
+        let debounce = Debounce::new(Duration::from_secs(60));
+        assert!(debounce.first("42:/clock in backend"));
+        assert!(!debounce.first("42:/clock in backend"));
+        assert!(debounce.first("42:/clock out"));
+        assert!(debounce.first("43:/clock in backend"));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let debounce = Debounce::new(Duration::ZERO);
+        assert!(debounce.first("42:/clock in backend"));
+        assert!(debounce.first("42:/clock in backend"));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::dashboard::{escape, ranking, table, totals, Totals};
+use crate::db::{ActivityEntry, Db, WeeklySummary};
+use crate::display::DisplayFormat;
+use crate::chart::{render_report_card, ReportCard};
+use lettre::message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+
+const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// SMTP settings from `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD` and
+/// `SMTP_FROM`. Digests are off unless host and sender are set.
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    pub from: Mailbox,
+}
+
+pub fn smtp_config() -> Option<SmtpConfig> {
+    let var = |name| env::var(name).ok().filter(|s: &String| !s.is_empty());
+    let host = var("SMTP_HOST")?;
+    let from = match var("SMTP_FROM")?.parse() {
+        Ok(from) => from,
+        Err(e) => {
+            eprintln!("[clock] Invalid SMTP_FROM: {e}");
+            return None;
+        }
+    };
+    Some(SmtpConfig {
+        host,
+        port: var("SMTP_PORT").and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_SMTP_PORT),
+        credentials: var("SMTP_USERNAME").map(|u| (u, var("SMTP_PASSWORD").unwrap_or_default())),
+        from,
+    })
+}
+
+pub fn enabled() -> bool {
+    smtp_config().is_some()
+}
+
+/// Addresses that always get the full report, from the comma-separated `DIGEST_ADMIN_EMAILS`.
+pub fn admin_emails() -> Vec<String> {
+    env::var("DIGEST_ADMIN_EMAILS")
+        .map(|list| {
+            list.split(',')
+                .map(|e| e.trim().to_string())
+                .filter(|e| valid_email(e))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A plausible `local@domain.tld` address; the SMTP server has the final say.
+pub fn valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    email.len() <= 254
+        && !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(|c| c.is_whitespace() || c == '<' || c == '>' || c == ',')
+        && !domain.contains('@')
+}
+
+fn page(week_label: &str, summary: &WeeklySummary, sections: &str, fmt: &DisplayFormat) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+body {{ font-family: system-ui, sans-serif; color: #1e1f22; max-width: 640px; }}
+table {{ width: 100%; border-collapse: collapse; }}
+td {{ padding: .25rem .5rem; border-bottom: 1px solid #e3e5e8; }}
+td:last-child {{ text-align: right; white-space: nowrap; }}
+td:nth-child(3) {{ width: 35%; }}
+.bar {{ height: .5rem; background: #4c9aff; border-radius: 3px; }}
+.muted {{ color: #6b7178; }}
+</style>
+</head>
+<body>
+<h1>📊 Weekly Report — {week}</h1>
+<p class="muted">{total} total · {sessions} sessions · {people} people</p>
+{awards}{sections}</body>
+</html>
+"#,
+        week = escape(&fmt.week(week_label)),
+        total = fmt.duration(summary.total_minutes),
+        sessions = summary.total_sessions,
+        people = summary.unique_workers,
+        awards = awards(summary, fmt),
+        sections = sections,
+    )
+}
+
+fn awards(summary: &WeeklySummary, fmt: &DisplayFormat) -> String {
+    let mut items = Vec::new();
+    if let Some((ref name, mins)) = summary.mvp {
+        items.push(format!("🏅 <b>MVP</b> — {} with {}", escape(name), fmt.duration(mins)));
+    }
+    if let Some((ref activity, mins)) = summary.top_activity {
+        items.push(format!("🔥 <b>Hot Topic</b> — {} ({})", escape(activity), fmt.duration(mins)));
+    }
+    if let Some((ref name, ref activity, mins)) = summary.longest_session {
+        items.push(format!(
+            "🏋️ <b>Marathon</b> — {} on {} ({})",
+            escape(name),
+            escape(activity),
+            fmt.duration(mins)
+        ));
+    }
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("<p>{}</p>\n", items.join("<br>\n"))
+}
+
+/// The weekly summary plus the subscriber's own per-activity breakdown.
+pub fn render_personal(week_label: &str, summary: &WeeklySummary, mine: &[ActivityEntry], fmt: &DisplayFormat) -> String {
+    let users = ranking(summary);
+    let personal = if mine.is_empty() {
+        "<section><h2>Your week</h2><p>No sessions logged.</p></section>\n".to_string()
+    } else {
+        let rows: Totals = mine.iter().map(|e| (e.activity.clone(), e.total_minutes)).collect();
+        let total: i64 = mine.iter().map(|e| e.total_minutes).sum();
+        table(&format!("Your week — {}", fmt.duration(total)), &rows, fmt)
+    };
+    page(week_label, summary, &format!("{}{}", personal, table("Leaderboard", &users, fmt)), fmt)
+}
+
+/// The weekly summary with every person's breakdown, for admins.
+pub fn render_admin(week_label: &str, summary: &WeeklySummary, fmt: &DisplayFormat) -> String {
+    let (users, activities) = totals(summary);
+    let mut sections = table("Leaderboard", &ranking(summary), fmt) + &table("Activities", &activities, fmt);
+    for (username, _) in &users {
+        let rows: Totals = summary
+            .breakdown
+            .iter()
+            .filter(|e| &e.username == username)
+            .map(|e| (e.activity.clone(), e.total_minutes))
+            .collect();
+        sections += &table(&escape(username), &rows, fmt);
+    }
+    page(week_label, summary, &sections, fmt)
+}
+
+fn strip_tags(html: &str) -> String {
+    let body = html.split_once("<body>").map(|(_, b)| b).unwrap_or(html);
+    let body = body.replace("</td>", "  ").replace("<br>", "\n");
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Attachment name for a week's report card, e.g. `report-card-KW07-2026.png`.
+pub fn card_filename(week_label: &str) -> String {
+    format!("report-card-{}.png", week_label.replace('/', "-"))
+}
+
+fn mailer(config: &SmtpConfig) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    // Port 465 is implicit TLS, anything else upgrades with STARTTLS
+    let mut builder = if config.port == 465 {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+    }
+    .port(config.port);
+    if let Some((user, password)) = &config.credentials {
+        builder = builder.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+    Ok(builder.build())
+}
+
+async fn send(
+    mailer: &AsyncSmtpTransport<Tokio1Executor>,
+    from: &Mailbox,
+    to: &str,
+    subject: &str,
+    html: String,
+    card: Option<(String, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    let body = MultiPart::alternative()
+        .singlepart(SinglePart::builder().header(ContentType::TEXT_PLAIN).body(strip_tags(&html)))
+        .singlepart(SinglePart::builder().header(ContentType::TEXT_HTML).body(html));
+    let body = match card {
+        Some((filename, png)) => MultiPart::mixed()
+            .multipart(body)
+            .singlepart(Attachment::new(filename).body(png, ContentType::parse("image/png")?)),
+        None => body,
+    };
+    let message = Message::builder()
+        .from(from.clone())
+        .to(to.parse()?)
+        .subject(subject)
+        .multipart(body)?;
+    mailer.send(message).await?;
+    Ok(())
+}
+
+/// Email the closed week's report to subscribers and admins. Runs after archiving, so
+/// personal breakdowns come from the archive. Returns how many emails went out.
+pub async fn send_digests(db: &Db, week_label: &str, summary: &WeeklySummary) -> anyhow::Result<usize> {
+    let Some(config) = smtp_config() else {
+        return Ok(0);
+    };
+    let mailer = mailer(&config)?;
+    let fmt = DisplayFormat::load(db);
+    let subject = format!("Weekly Report — {}", fmt.week(week_label));
+    let leaderboard = ranking(summary);
+    // Everyone's own email says who they are; the leaderboard in it doesn't name others
+    let public = crate::anonymous::public_summary(db, summary);
+    let today = db.clock().now().date();
+
+    let mut sent = 0;
+    for (user_id, username, email) in db.digest_subscribers()? {
+        let mine = db.archived_activities(&user_id, week_label).unwrap_or_default();
+        let html = render_personal(week_label, &public, &mine, &fmt);
+        let card = if mine.is_empty() {
+            None
+        } else {
+            let streak = db.consecutive_work_days_before(&user_id, today).unwrap_or(0);
+            let card = ReportCard::new(&username, week_label, &mine, &leaderboard, streak);
+            render_report_card(&card, &fmt)
+                .inspect_err(|e| eprintln!("[clock] Failed to render report card for {username}: {e}"))
+                .ok()
+                .map(|png| (card_filename(week_label), png))
+        };
+        match send(&mailer, &config.from, &email, &subject, html, card).await {
+            Ok(()) => sent += 1,
+            Err(e) => eprintln!("[clock] Failed to email digest to {username}: {e}"),
+        }
+    }
+    let admin_html = render_admin(week_label, summary, &fmt);
+    for email in admin_emails() {
+        match send(&mailer, &config.from, &email, &subject, admin_html.clone(), None).await {
+            Ok(()) => sent += 1,
+            Err(e) => eprintln!("[clock] Failed to email digest to {email}: {e}"),
+        }
+    }
+    Ok(sent)
+}
+
+/// Email `DIGEST_ADMIN_EMAILS` outside the weekly report, e.g. about the bot being
+/// removed. Returns how many emails went out; none without SMTP settings.
+pub async fn email_admins(subject: &str, html: &str) -> anyhow::Result<usize> {
+    let Some(config) = smtp_config() else {
+        return Ok(0);
+    };
+    let mailer = mailer(&config)?;
+    let mut sent = 0;
+    for email in admin_emails() {
+        match send(&mailer, &config.from, &email, subject, html.to_string(), None).await {
+            Ok(()) => sent += 1,
+            Err(e) => eprintln!("[clock] Failed to email {email}: {e}"),
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(username: &str, activity: &str, total_minutes: i64) -> ActivityEntry {
+        ActivityEntry {
+            username: username.to_string(),
+            activity: activity.to_string(),
+            total_minutes,
+            session_count: 1,
+        }
+    }
+
+    fn summary() -> WeeklySummary {
+        WeeklySummary {
+            total_minutes: 420,
+            total_sessions: 3,
+            unique_workers: 2,
+            mvp: Some(("Alice".to_string(), 300)),
+            top_activity: Some(("backend".to_string(), 360)),
+            longest_session: Some(("Alice".to_string(), "backend".to_string(), 240)),
+            most_appreciated: None,
+            breakdown: vec![
+                entry("Alice", "backend", 240),
+                entry("Alice", "docs", 60),
+                entry("Bob & Co", "backend", 120),
+            ],
+            holidays: Vec::new(),
+            daily_minutes: [0; 7],
+            mvp_daily_minutes: [0; 7],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_valid_email() {
+        assert!(valid_email("alice@example.com"));
+        assert!(valid_email("a.b+clock@mail.example.ch"));
+        assert!(!valid_email("alice"));
+        assert!(!valid_email("alice@localhost"));
+        assert!(!valid_email("@example.com"));
+        assert!(!valid_email("alice@@example.com"));
+        assert!(!valid_email("alice smith@example.com"));
+        assert!(!valid_email("alice@example.com, bob@example.com"));
+    }
+
+    #[test]
+    fn test_render_personal_and_admin() {
+        let mine = vec![entry("Alice", "backend", 240), entry("Alice", "docs", 60)];
+        let html = render_personal("KW07/2026", &summary(), &mine, &DisplayFormat::default());
+        assert!(html.contains("Your week — 5h 0m"));
+        assert!(html.contains("🏅 <b>MVP</b> — Alice with 5h 0m"));
+        assert!(html.contains("Bob &amp; Co"));
+        assert!(render_personal("KW07/2026", &summary(), &[], &DisplayFormat::default()).contains("No sessions logged."));
+
+        let admin = render_admin("KW07/2026", &summary(), &DisplayFormat::default());
+        assert!(admin.contains("<h2>Activities</h2>"));
+        assert!(admin.contains("<h2>Bob &amp; Co</h2>"));
+    }
+
+    #[test]
+    fn test_card_filename() {
+        assert_eq!(card_filename("KW07/2026"), "report-card-KW07-2026.png");
+    }
+
+    #[test]
+    fn test_plain_text_alternative() {
+        let text = strip_tags(&render_personal("KW07/2026", &summary(), &[], &DisplayFormat::default()));
+        assert!(text.starts_with("📊 Weekly Report — KW07/2026"));
+        assert!(text.contains("Bob & Co"));
+        assert!(!text.contains('<'));
+        assert!(!text.contains("font-family"));
+        assert!(text.contains("1  Alice    5h 0m"));
+    }
+}
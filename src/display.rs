@@ -0,0 +1,129 @@
+use crate::config;
+use crate::db::{Db, Elapsed};
+use crate::weeks::WeekId;
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+
+/// How durations, times and week labels are shown, from the `duration-format`,
+/// `time-format` and `week-format` settings. The default matches the built-in formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DisplayFormat {
+    /// `7.5h` instead of `7h 30m`.
+    pub decimal_hours: bool,
+    /// `9:30 PM` instead of `21:30`.
+    pub twelve_hour: bool,
+    /// `Week 7, 2026` instead of `KW07/2026`.
+    pub week_words: bool,
+}
+
+impl DisplayFormat {
+    pub fn load(db: &Db) -> Self {
+        Self {
+            decimal_hours: config::get(db, config::DURATION_FORMAT).as_deref() == Some("decimal"),
+            twelve_hour: config::get(db, config::TIME_FORMAT).as_deref() == Some("12h"),
+            week_words: config::get(db, config::WEEK_FORMAT).as_deref() == Some("week"),
+        }
+    }
+
+    pub fn duration(&self, minutes: i64) -> String {
+        if self.decimal_hours {
+            format!("{:.1}h", minutes as f64 / 60.0)
+        } else {
+            format_duration(minutes)
+        }
+    }
+
+    /// A single session's length: `12m 5s` under an hour, like `duration` above.
+    pub fn elapsed(&self, elapsed: Elapsed) -> String {
+        if elapsed.minutes() < 60 {
+            format_elapsed(elapsed)
+        } else {
+            self.duration(elapsed.minutes())
+        }
+    }
+
+    pub fn time(&self, time: NaiveTime) -> String {
+        if self.twelve_hour {
+            time.format("%-I:%M %p").to_string()
+        } else {
+            time.format("%H:%M").to_string()
+        }
+    }
+
+    /// Date and time, e.g. `16.02.2026 21:30`.
+    pub fn timestamp(&self, at: NaiveDateTime) -> String {
+        format!("{} {}", at.format("%d.%m.%Y"), self.time(at.time()))
+    }
+
+    /// A stored `KW07/2026` label for display; labels that don't parse are shown as is.
+    pub fn week(&self, label: &str) -> String {
+        match WeekId::parse(label) {
+            Some(week) if self.week_words => {
+                let iso = week.monday().iso_week();
+                format!("Week {}, {}", iso.week(), iso.year())
+            }
+            _ => label.to_string(),
+        }
+    }
+}
+
+fn format_duration(minutes: i64) -> String {
+    let h = minutes / 60;
+    let m = minutes % 60;
+    if h > 0 {
+        format!("{}h {}m", h, m)
+    } else {
+        format!("{}m", m)
+    }
+}
+
+/// `12m 5s`, or `40s` under a minute, for sessions too short to show in whole minutes.
+fn format_elapsed(elapsed: Elapsed) -> String {
+    let (m, s) = (elapsed.minutes(), elapsed.seconds() % 60);
+    if elapsed.seconds() >= 3600 {
+        format_duration(m)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+
+    #[test]
+    fn test_default_matches_builtin_formats() {
+        let fmt = DisplayFormat::default();
+        let at = NaiveDateTime::parse_from_str("2026-02-16 21:05:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(fmt.duration(125), "2h 5m");
+        assert_eq!(fmt.elapsed(Elapsed::from_seconds(40)), "40s");
+        assert_eq!(fmt.elapsed(Elapsed::from_seconds(725)), "12m 5s");
+        assert_eq!(fmt.elapsed(Elapsed::from_seconds(7530)), "2h 5m");
+        assert_eq!(fmt.timestamp(at), "16.02.2026 21:05");
+        assert_eq!(fmt.week("KW07/2026"), "KW07/2026");
+    }
+
+    #[test]
+    fn test_load_applies_settings() {
+        let db = test_db();
+        for (key, value) in [
+            (config::DURATION_FORMAT, "decimal"),
+            (config::TIME_FORMAT, "12h"),
+            (config::WEEK_FORMAT, "week"),
+        ] {
+            config::set(&db, config::setting(key).unwrap(), value).unwrap();
+        }
+
+        let fmt = DisplayFormat::load(&db);
+        assert_eq!(fmt.duration(125), "2.1h");
+        assert_eq!(fmt.duration(20), "0.3h");
+        // Short sessions keep their seconds either way
+        assert_eq!(fmt.elapsed(Elapsed::from_seconds(725)), "12m 5s");
+        assert_eq!(fmt.time(NaiveTime::from_hms_opt(21, 5, 0).unwrap()), "9:05 PM");
+        assert_eq!(fmt.time(NaiveTime::from_hms_opt(0, 30, 0).unwrap()), "12:30 AM");
+        assert_eq!(fmt.week("KW07/2026"), "Week 7, 2026");
+        assert_eq!(fmt.week("imported"), "imported");
+    }
+}
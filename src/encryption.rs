@@ -0,0 +1,127 @@
+//! Optional encryption at rest. Built with the `encryption` feature, the database file is
+//! encrypted with SQLCipher, so a leaked file or backup shows neither activity names nor
+//! anything else. Each server's database gets its own key, derived from one master secret.
+
+use hmac::{Hmac, Mac};
+use rusqlite::Connection;
+use sha2::Sha256;
+use std::env;
+use std::path::Path;
+
+/// The master secret from `DB_MASTER_KEY`; unset leaves the database unencrypted.
+pub fn master_key() -> Option<String> {
+    env::var("DB_MASTER_KEY").ok().filter(|k| !k.is_empty())
+}
+
+/// The server the database belongs to, from `GUILD_ID`, for deriving its key.
+pub fn guild_id() -> Option<String> {
+    env::var("GUILD_ID").ok().map(|g| g.trim().to_string()).filter(|g| !g.is_empty())
+}
+
+/// The 256-bit key of `guild_id`'s database as hex: knowing one server's key (or its
+/// database) says nothing about another's.
+pub fn database_key(master: &str, guild_id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(master.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("clockbot-db/{guild_id}").as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The key to open the database with, if encryption is configured. Fails rather than
+/// silently writing plaintext when the build can't encrypt, and rather than sharing one
+/// key between servers when `GUILD_ID` is missing.
+pub fn key_from_env() -> anyhow::Result<Option<String>> {
+    key_for(master_key(), guild_id())
+}
+
+fn key_for(master: Option<String>, guild_id: Option<String>) -> anyhow::Result<Option<String>> {
+    let Some(master) = master else {
+        return Ok(None);
+    };
+    if !cfg!(feature = "encryption") {
+        anyhow::bail!("DB_MASTER_KEY is set, but this build has no encryption support (feature `encryption`)");
+    }
+    let Some(guild_id) = guild_id else {
+        anyhow::bail!("DB_MASTER_KEY is set, but GUILD_ID is not; it is needed to derive this server's own key");
+    };
+    Ok(Some(database_key(&master, &guild_id)))
+}
+
+/// Unlock an encrypted database (or set the key of a new one) before its first use.
+pub fn unlock(conn: &Connection, key: &str) -> anyhow::Result<()> {
+    conn.pragma_update(None, "key", format!("x'{key}'"))?;
+    // SQLCipher only reads the file on first access; fail here on a wrong key or plaintext
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |r| r.get::<_, i64>(0))
+        .map_err(|e| anyhow::anyhow!("cannot open the database with the configured key (wrong key or not encrypted?): {e}"))?;
+    Ok(())
+}
+
+/// Write an encrypted copy of the unencrypted database at `plain` to `encrypted`, which
+/// must not exist.
+pub fn encrypt_file(plain: &Path, encrypted: &Path, key: &str) -> anyhow::Result<()> {
+    if !cfg!(feature = "encryption") {
+        anyhow::bail!("this build has no encryption support (feature `encryption`)");
+    }
+    if encrypted.exists() {
+        anyhow::bail!("{} already exists", encrypted.display());
+    }
+    let conn = Connection::open(plain)?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        rusqlite::params![encrypted.to_string_lossy(), format!("x'{key}'")],
+    )?;
+    conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+    conn.execute("DETACH DATABASE encrypted", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_key() {
+        let key = database_key("master secret", "4711");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, database_key("master secret", "4711"));
+        assert_ne!(key, database_key("master secret", "4712"));
+        assert_ne!(key, database_key("other secret", "4711"));
+    }
+
+    #[test]
+    fn test_key_for() {
+        assert_eq!(key_for(None, None).unwrap(), None);
+        assert_eq!(key_for(None, Some("4711".to_string())).unwrap(), None);
+        let err = key_for(Some("master secret".to_string()), None).unwrap_err().to_string();
+        if cfg!(feature = "encryption") {
+            assert!(err.contains("GUILD_ID"));
+            assert_eq!(
+                key_for(Some("master secret".to_string()), Some("4711".to_string())).unwrap(),
+                Some(database_key("master secret", "4711"))
+            );
+        } else {
+            assert!(err.contains("no encryption support"));
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypt_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (plain, encrypted) = (dir.path().join("plain.db"), dir.path().join("encrypted.db"));
+        let db = crate::db::Db::open(&plain).unwrap();
+        db.clock_in("u1", "Alice", "secret project").unwrap();
+        drop(db);
+
+        let key = database_key("master secret", "4711");
+        encrypt_file(&plain, &encrypted, &key).unwrap();
+        let bytes = std::fs::read(&encrypted).unwrap();
+        assert!(!bytes.windows(14).any(|w| w == b"secret project"));
+        assert!(Connection::open(&encrypted).unwrap().query_row("SELECT 1 FROM sessions", [], |_| Ok(())).is_err());
+
+        let conn = Connection::open(&encrypted).unwrap();
+        unlock(&conn, &key).unwrap();
+        let activity: String = conn.query_row("SELECT activity FROM sessions", [], |r| r.get(0)).unwrap();
+        assert_eq!(activity, "secret project");
+        assert!(unlock(&Connection::open(&encrypted).unwrap(), &database_key("master secret", "4712")).is_err());
+    }
+}
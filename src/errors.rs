@@ -0,0 +1,133 @@
+//! What people see when a command fails. The user gets a short apology with a reference
+//! like `#K7Q2XD`; the details go to stderr and, with `ERROR_CHANNEL` set, to that channel
+//! under the same reference, so a report in chat leads straight to the error.
+
+use crate::commands::truncate_for_embed;
+use rand::Rng;
+use serenity::all::*;
+use std::env;
+
+const COLOR_RED: u32 = 0xe74c3c;
+/// Letters and digits that can't be mistaken for each other when read out.
+const REFERENCE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const REFERENCE_LEN: usize = 6;
+
+/// Channel detailed errors are posted to, from `ERROR_CHANNEL`.
+pub fn error_channel_id() -> Option<ChannelId> {
+    env::var("ERROR_CHANNEL")
+        .ok()
+        .and_then(|s| s.parse().ok())
+}
+
+/// A fresh reference for one failure, e.g. `K7Q2XD`.
+pub fn reference() -> String {
+    let mut rng = rand::thread_rng();
+    (0..REFERENCE_LEN)
+        .map(|_| REFERENCE_CHARS[rng.gen_range(0..REFERENCE_CHARS.len())] as char)
+        .collect()
+}
+
+/// The reply to the user: no details, just the reference to quote.
+pub fn user_embed(reference: &str) -> CreateEmbed {
+    CreateEmbed::new()
+        .color(COLOR_RED)
+        .title("⚠️ Something went wrong")
+        .description(user_text(reference))
+}
+
+pub fn user_text(reference: &str) -> String {
+    format!(
+        "That didn't work, sorry. Please try again in a moment; if it keeps failing, \
+         tell an admin (ref #{reference})."
+    )
+}
+
+/// The error with its causes, one per line.
+pub fn detail_text(error: &anyhow::Error) -> String {
+    error.chain().map(|cause| cause.to_string()).collect::<Vec<_>>().join("\ncaused by: ")
+}
+
+/// The details of a failed `command` by `user` in `channel_id` at `at`.
+fn detail_embed(
+    reference: &str,
+    command: &str,
+    user: &User,
+    channel_id: ChannelId,
+    at: Timestamp,
+    error: &anyhow::Error,
+) -> CreateEmbed {
+    CreateEmbed::new()
+        .color(COLOR_RED)
+        .title(format!("⚠️ Error ref #{reference}"))
+        .description(truncate_for_embed(&format!("```\n{}\n```", detail_text(error)), 4000))
+        .field("Command", truncate_for_embed(command, 1024), false)
+        .field("User", format!("<@{}> ({})", user.id, user.name), true)
+        .field("Channel", format!("<#{}>", channel_id), true)
+        .timestamp(at)
+}
+
+async fn post_details(http: &Http, reference: &str, details: CreateEmbed) {
+    if let Some(channel_id) = error_channel_id() {
+        let details = CreateMessage::new().embed(details);
+        if let Err(e) = channel_id.send_message(http, details).await {
+            eprintln!("[clock] Failed to post error ref #{reference} to the error channel: {e}");
+        }
+    }
+}
+
+/// Report a command on `msg` that failed with `error`: log it, reply to the user with a
+/// reference and post the details to the error channel. Returns the reference.
+pub async fn report(http: &Http, msg: &Message, error: &anyhow::Error) -> String {
+    let reference = reference();
+    eprintln!("[clock] Error ref #{reference} on `{}` by {}: {error:#}", msg.content, msg.author.name);
+    let reply = CreateMessage::new().embed(user_embed(&reference)).reference_message(msg);
+    if let Err(e) = msg.channel_id.send_message(http, reply).await {
+        eprintln!("[clock] Failed to tell the user about error ref #{reference}: {e}");
+    }
+    let details = detail_embed(&reference, &msg.content, &msg.author, msg.channel_id, msg.timestamp, error);
+    post_details(http, &reference, details).await;
+    reference
+}
+
+/// Like `report`, for a button or menu pick that failed: logs it and posts the details,
+/// but leaves answering the interaction to the caller, e.g. with `user_text`.
+pub async fn report_interaction(http: &Http, interaction: &ComponentInteraction, error: &anyhow::Error) -> String {
+    let reference = reference();
+    let (custom_id, user) = (&interaction.data.custom_id, &interaction.user);
+    eprintln!("[clock] Error ref #{reference} on `{custom_id}` by {}: {error:#}", user.name);
+    let command = format!("component `{custom_id}`");
+    let details = detail_embed(&reference, &command, user, interaction.channel_id, Timestamp::now(), error);
+    post_details(http, &reference, details).await;
+    reference
+}
+
+/// The value of `result`, or `None` after reporting its error on `msg`.
+pub async fn or_report<T>(http: &Http, msg: &Message, result: anyhow::Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            report(http, msg, &e).await;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_reference() {
+        let a = reference();
+        assert_eq!(a.len(), REFERENCE_LEN);
+        assert!(a.bytes().all(|c| REFERENCE_CHARS.contains(&c)));
+        assert!((0..10).map(|_| reference()).any(|b| b != a));
+    }
+
+    #[test]
+    fn test_detail_text() {
+        let error = Err::<(), _>(anyhow::anyhow!("database is locked")).context("loading the leaderboard").unwrap_err();
+        assert_eq!(detail_text(&error), "loading the leaderboard\ncaused by: database is locked");
+    }
+}
@@ -0,0 +1,129 @@
+use crate::db::{Db, LeaderboardEntry};
+use crate::display::DisplayFormat;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often finished events are checked for a winners announcement.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+/// `2026-03-20T09:00` or `2026-03-20`, which is midnight at the start of the day, or at
+/// its end when `end_of_day` is set.
+pub fn parse_time(arg: &str, end_of_day: bool) -> Option<NaiveDateTime> {
+    if let Ok(at) = NaiveDateTime::parse_from_str(arg, "%Y-%m-%dT%H:%M") {
+        return Some(at);
+    }
+    let day = NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok()?;
+    let day = if end_of_day { day + Duration::days(1) } else { day };
+    day.and_hms_opt(0, 0, 0)
+}
+
+/// `"Spring Hackathon" 2026-03-20T09:00 2026-03-22` (quotes optional for one-word names)
+/// into name, start and end.
+pub fn parse_create(args: &str) -> Option<(String, NaiveDateTime, NaiveDateTime)> {
+    let args = args.trim();
+    let (name, rest) = match args.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => args.split_once(' ')?,
+    };
+    let name = name.trim();
+    let mut times = rest.split_whitespace();
+    let start = parse_time(times.next()?, false)?;
+    let end = parse_time(times.next()?, true)?;
+    if name.is_empty() || times.next().is_some() || end <= start {
+        return None;
+    }
+    Some((name.to_string(), start, end))
+}
+
+/// `🥇 Alice — 5h 0m` lines.
+pub fn format_standings(standings: &[LeaderboardEntry], fmt: &DisplayFormat) -> String {
+    if standings.is_empty() {
+        return "*No time logged in the window yet*".into();
+    }
+    standings
+        .iter()
+        .map(|e| {
+            let place = MEDALS.get(e.rank as usize - 1).map(|m| m.to_string()).unwrap_or_else(|| format!("{}.", e.rank));
+            format!("{} **{}** — {}", place, e.username, fmt.duration(e.total_minutes))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `20.03.2026 09:00 – 22.03.2026 00:00`
+pub fn format_window(start: NaiveDateTime, end: NaiveDateTime, fmt: &DisplayFormat) -> String {
+    format!("{} – {}", fmt.timestamp(start), fmt.timestamp(end))
+}
+
+/// Announce the winners of events once they end. Without a summary channel they are
+/// only marked as done; standings stay available through `/clock event`.
+pub async fn announce_loop(db: Arc<Db>, http: Arc<Http>, channel_id: Option<ChannelId>) {
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let events = match db.events_to_announce(db.clock().now()) {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("[clock] Event check failed: {e}");
+                continue;
+            }
+        };
+        for event in events {
+            if let Some(channel_id) = channel_id {
+                let fmt = DisplayFormat::load(&db);
+                let standings = crate::anonymous::public_board(&db, db.event_standings(&event, event.ends_at).unwrap_or_default());
+                let embed = CreateEmbed::new()
+                    .color(0xf1c40f)
+                    .title(format!("🏁 {} is over!", event.name))
+                    .description(format_standings(&standings, &fmt))
+                    .footer(CreateEmbedFooter::new(format_window(event.starts_at, event.ends_at, &fmt)));
+                if let Err(e) = channel_id.send_message(&http, CreateMessage::new().embed(embed)).await {
+                    eprintln!("[clock] Failed to announce event {}: {e}", event.name);
+                    continue;
+                }
+            }
+            if let Err(e) = db.mark_event_announced(event.id) {
+                eprintln!("[clock] Failed to mark event {} announced: {e}", event.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_create() {
+        assert_eq!(
+            parse_create("\"Spring Hackathon\" 2026-03-20T09:30 2026-03-22"),
+            Some(("Spring Hackathon".to_string(), at(20, 9, 30), at(23, 0, 0)))
+        );
+        assert_eq!(
+            parse_create("Sprint 2026-03-20 2026-03-20"),
+            Some(("Sprint".to_string(), at(20, 0, 0), at(21, 0, 0)))
+        );
+        // End before start, missing end, unclosed quote, trailing junk
+        assert_eq!(parse_create("Sprint 2026-03-20T10:00 2026-03-20T09:00"), None);
+        assert_eq!(parse_create("Sprint 2026-03-20"), None);
+        assert_eq!(parse_create("\"Sprint 2026-03-20 2026-03-21"), None);
+        assert_eq!(parse_create("Sprint 2026-03-20 2026-03-21 extra"), None);
+    }
+
+    #[test]
+    fn test_format_standings() {
+        let fmt = DisplayFormat::default();
+        let entry = |name: &str, minutes, rank| LeaderboardEntry { username: name.to_string(), total_minutes: minutes, rank, live: false };
+        let standings = vec![entry("Bob", 300, 1), entry("Alice", 120, 2), entry("Carol", 60, 3), entry("Dan", 30, 4)];
+        assert_eq!(
+            format_standings(&standings, &fmt),
+            "🥇 **Bob** — 5h 0m\n🥈 **Alice** — 2h 0m\n🥉 **Carol** — 1h 0m\n4. **Dan** — 30m"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Shared test fixtures: throwaway databases (optionally on a fixed clock), users and a
+//! week of sessions.
+
+use crate::clock::{FixedClock, SystemClock};
+use crate::db::{self, Db, ImportRow};
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDateTime};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// A fresh in-memory database.
+pub fn test_db() -> Db {
+    Db::open_in_memory(Arc::new(SystemClock)).unwrap()
+}
+
+/// A fresh database on disk, for tests that reopen it; keep the `TempDir` alive for as
+/// long as the `Db`.
+pub fn test_db_file() -> (Db, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Db::open(&temp_dir.path().join("test.db")).unwrap();
+    (db, temp_dir)
+}
+
+/// `test_db_file` with the clock standing at `at`, like `test_db_at`.
+pub fn test_db_file_at(at: NaiveDateTime) -> (Db, Arc<FixedClock>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(FixedClock::at(at));
+    let db = Db::open_with_clock(&temp_dir.path().join("test.db"), clock.clone()).unwrap();
+    (db, clock, temp_dir)
+}
+
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub id: String,
+    pub name: String,
+}
+
+/// A user with default settings, id `u-<name>` in lowercase.
+pub fn make_user(db: &Db, name: &str) -> TestUser {
+    let user = TestUser {
+        id: format!("u-{}", name.to_lowercase()),
+        name: name.to_string(),
+    };
+    db.set_nudges(&user.id, true).unwrap();
+    user
+}
+
+/// Finished `activity` sessions for `user` from 09:00, `minutes_per_day` long, Monday
+/// to Friday of `week`. Goes through the import path, so aggregates are kept up to date.
+pub fn make_week_of_sessions(db: &Db, user: &TestUser, week: WeekId, activity: &str, minutes_per_day: i64) -> usize {
+    let rows: Vec<ImportRow> = (0..5)
+        .map(|day| {
+            let start = (week.monday() + Duration::days(day)).and_hms_opt(9, 0, 0).unwrap();
+            ImportRow {
+                user_id: user.id.clone(),
+                username: user.name.clone(),
+                activity: activity.to_string(),
+                started_at: start,
+                ended_at: start + Duration::minutes(minutes_per_day),
+            }
+        })
+        .collect();
+    db.import_sessions(&rows, db::SOURCE_MANUAL).unwrap()
+}
+
+/// A fresh in-memory database whose clock stands at `at`, Swiss local time; move it
+/// with the returned `FixedClock`.
+pub fn test_db_at(at: NaiveDateTime) -> (Db, Arc<FixedClock>) {
+    let clock = Arc::new(FixedClock::at(at));
+    (Db::open_in_memory(clock.clone()).unwrap(), clock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_db_at_fixed_time() {
+        let (db, clock) = test_db_at(at(29, 1, 30));
+        assert_eq!(db.clock().now(), at(29, 1, 30));
+        clock.set(at(30, 9, 0));
+        assert_eq!(db.clock().week(), WeekId::containing(at(29, 0, 0).date()).next());
+    }
+
+    #[test]
+    fn test_make_week_of_sessions() {
+        let (db, _clock) = test_db_at(at(20, 18, 0));
+        let alice = make_user(&db, "Alice");
+        assert_eq!(alice.id, "u-alice");
+        assert_eq!(make_week_of_sessions(&db, &alice, db.clock().week(), "backend", 240), 5);
+        let board = db.leaderboard_weekly(false).unwrap();
+        assert_eq!((board[0].username.as_str(), board[0].total_minutes), ("Alice", 1200));
+    }
+}
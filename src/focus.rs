@@ -0,0 +1,78 @@
+use crate::db::Db;
+use serenity::all::*;
+use std::env;
+use std::sync::Arc;
+
+/// Role granted while a user is clocked in, from `FOCUS_ROLE_ID`.
+/// Unset disables focus mode entirely.
+pub fn focus_role_id() -> Option<RoleId> {
+    env::var("FOCUS_ROLE_ID")
+        .ok()
+        .and_then(|s| s.parse().ok())
+}
+
+/// Give `user_id` the focus role in the guild they clocked in from.
+pub async fn grant(http: &Http, db: &Arc<Db>, guild_id: Option<GuildId>, user_id: UserId) {
+    let (Some(role_id), Some(guild_id)) = (focus_role_id(), guild_id) else {
+        return;
+    };
+
+    match http
+        .add_member_role(guild_id, user_id, role_id, Some("Clocked in"))
+        .await
+    {
+        Ok(()) => {
+            if let Err(e) = db.record_focus_role(&user_id.to_string(), &guild_id.to_string()) {
+                eprintln!("[clock] Failed to record focus role: {e}");
+            }
+        }
+        Err(e) => eprintln!("[clock] Failed to grant focus role: {e}"),
+    }
+}
+
+/// Take the focus role away from `user_id` if we granted it earlier.
+pub async fn revoke(http: &Http, db: &Arc<Db>, user_id: UserId) {
+    let Some(role_id) = focus_role_id() else {
+        return;
+    };
+    let guild_id = match db.take_focus_role(&user_id.to_string()) {
+        Ok(Some(g)) => g,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("[clock] Failed to look up focus role: {e}");
+            return;
+        }
+    };
+    let Ok(guild_id) = guild_id.parse::<GuildId>() else {
+        return;
+    };
+
+    if let Err(e) = http
+        .remove_member_role(guild_id, user_id, role_id, Some("Clocked out"))
+        .await
+    {
+        eprintln!("[clock] Failed to remove focus role: {e}");
+    }
+}
+
+/// Remove focus roles left behind by sessions that ended while the bot was offline.
+pub async fn cleanup_stale(http: &Http, db: &Arc<Db>) {
+    if focus_role_id().is_none() {
+        return;
+    }
+    let stale = match db.stale_focus_roles() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[clock] Focus role cleanup failed: {e}");
+            return;
+        }
+    };
+    for (user_id, _) in &stale {
+        if let Ok(user_id) = user_id.parse::<UserId>() {
+            revoke(http, db, user_id).await;
+        }
+    }
+    if !stale.is_empty() {
+        println!("[clock] Removed {} stale focus role(s)", stale.len());
+    }
+}
@@ -0,0 +1,381 @@
+use crate::db::{self, Db, ActivityBurst, GithubLink};
+use crate::http::{HttpState, Request, Response};
+use crate::normalize::normalize_activity;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use chrono_tz::Europe::Zurich;
+use hmac::{Hmac, Mac};
+use serenity::all::*;
+use sha2::Sha256;
+use std::env;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// A pause longer than this between pushes ends a burst of coding.
+const BURST_GAP_MINUTES: i64 = 45;
+/// Time assumed to have gone into the first commit of a burst.
+const BURST_LEAD_MINUTES: i64 = 15;
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+pub const MODE_SUGGEST: &str = "suggest";
+pub const MODE_LOG: &str = "log";
+
+/// Shared secret configured on the GitHub webhook, from `GITHUB_WEBHOOK_SECRET`.
+/// Unset disables the `/github` endpoint.
+pub fn webhook_secret() -> Option<String> {
+    env::var("GITHUB_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+/// Check an `X-Hub-Signature-256` header (`sha256=<hex>`) against the body.
+pub fn verify_signature(secret: &str, body: &[u8], header: &str) -> bool {
+    let Some(expected) = header.strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// GitHub logins are 1–39 letters, digits or single inner hyphens.
+pub fn valid_login(login: &str) -> bool {
+    (1..=39).contains(&login.len())
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !login.starts_with('-')
+        && !login.ends_with('-')
+        && !login.contains("--")
+}
+
+/// A code for `/clock github link` to be put on the GitHub account, e.g.
+/// `clockbot-verify-7KQ2XD`.
+pub fn verification_code() -> String {
+    format!("clockbot-verify-{}", crate::errors::reference())
+}
+
+/// Whether the code is in the profile's bio or in the description of one of its public
+/// gists, i.e. whoever asked to link the login controls it.
+pub fn proves_ownership(code: &str, profile: &serde_json::Value, gists: &serde_json::Value) -> bool {
+    let in_bio = profile["bio"].as_str().is_some_and(|bio| bio.contains(code));
+    let in_gist = gists
+        .as_array()
+        .is_some_and(|gists| gists.iter().any(|g| g["description"].as_str().is_some_and(|d| d.contains(code))));
+    in_bio || in_gist
+}
+
+async fn get_json(url: &str) -> anyhow::Result<serde_json::Value> {
+    let body = crate::calendar::http_client()
+        .get(url)
+        .header("User-Agent", "clockbot")
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Look for `code` on `login`'s public profile and gists.
+pub async fn owns_login(login: &str, code: &str) -> anyhow::Result<bool> {
+    let profile = get_json(&format!("https://api.github.com/users/{login}")).await?;
+    let gists = get_json(&format!("https://api.github.com/users/{login}/gists")).await?;
+    Ok(proves_ownership(code, &profile, &gists))
+}
+
+/// Coding activity extracted from a webhook delivery, in Swiss local time.
+#[derive(Debug, PartialEq)]
+pub struct GithubEvent {
+    pub login: String,
+    pub repo: String,
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+}
+
+/// Pull `push` and `pull_request` activity out of a payload; other events are ignored.
+/// Pushes are timed by their commits, pull requests by when they arrive.
+pub fn parse_event(kind: &str, payload: &serde_json::Value, received: NaiveDateTime) -> Option<GithubEvent> {
+    let login = payload["sender"]["login"].as_str()?.to_lowercase();
+    let repo = payload["repository"]["full_name"].as_str()?.to_lowercase();
+    let (first, last) = match kind {
+        "push" => {
+            let times: Vec<NaiveDateTime> = payload["commits"]
+                .as_array()?
+                .iter()
+                .filter_map(|c| c["timestamp"].as_str())
+                .filter_map(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&Zurich).naive_local())
+                .collect();
+            (*times.iter().min()?, *times.iter().max()?)
+        }
+        "pull_request" => {
+            let action = payload["action"].as_str()?;
+            if !matches!(action, "opened" | "synchronize" | "reopened" | "closed") {
+                return None;
+            }
+            (received, received)
+        }
+        _ => return None,
+    };
+    Some(GithubEvent { login, repo, first, last })
+}
+
+/// Activity for `repo` (`owner/name`): the first rule matching the full name, the bare
+/// name, or a `prefix*` pattern wins; otherwise the normalized repository name.
+pub fn activity_for_repo(rules: &[(String, String)], repo: &str) -> String {
+    let name = repo.rsplit('/').next().unwrap_or(repo);
+    rules
+        .iter()
+        .find(|(pattern, _)| match pattern.strip_suffix('*') {
+            Some(prefix) => repo.starts_with(prefix) || name.starts_with(prefix),
+            None => pattern == repo || pattern == name,
+        })
+        .map(|(_, activity)| activity.clone())
+        .unwrap_or_else(|| normalize_activity(name))
+}
+
+/// The session a finished burst stands for, `None` if it started before the running week.
+/// Short bursts still count as a quarter of an hour.
+pub fn burst_session(burst: &ActivityBurst, week_start: NaiveDateTime) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let start = (burst.first_at - Duration::minutes(BURST_LEAD_MINUTES)).max(week_start);
+    let end = burst.last_at.max(start + Duration::minutes(BURST_LEAD_MINUTES));
+    (burst.first_at >= week_start).then_some((start, end))
+}
+
+pub async fn handle_webhook(req: &Request, state: &HttpState) -> Response {
+    let Some(secret) = webhook_secret() else {
+        return Response::text(404, "not found");
+    };
+    let signed = req
+        .header("X-Hub-Signature-256")
+        .is_some_and(|sig| verify_signature(&secret, &req.body, sig));
+    if !signed {
+        return Response::text(401, "bad signature");
+    }
+
+    let kind = req.header("X-GitHub-Event").unwrap_or_default();
+    if kind == "ping" {
+        return Response::text(200, "pong");
+    }
+    let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&req.body) else {
+        return Response::text(400, "bad payload");
+    };
+    let Some(event) = parse_event(kind, &payload, state.db.clock().now()) else {
+        return Response::text(200, "ignored");
+    };
+    match state.db.github_link_by_login(&event.login) {
+        Ok(Some(link)) => {
+            if let Err(e) = record(&state.db, &state.http, &link, &event).await {
+                eprintln!("[clock] GitHub activity failed for {}: {e}", link.username);
+            }
+            Response::text(200, "ok")
+        }
+        Ok(None) => Response::text(200, "unlinked"),
+        Err(e) => {
+            eprintln!("[clock] GitHub link lookup failed: {e}");
+            Response::text(503, "unavailable")
+        }
+    }
+}
+
+async fn record(db: &Db, http: &Http, link: &GithubLink, event: &GithubEvent) -> anyhow::Result<()> {
+    let activity = activity_for_repo(&db.github_repo_rules()?, &event.repo);
+    let (started, closed) = db.record_github_activity(
+        &link.user_id,
+        &link.username,
+        &activity,
+        event.first,
+        event.last,
+        Duration::minutes(BURST_GAP_MINUTES),
+    )?;
+    if let Some(burst) = closed {
+        log_burst(db, link, &burst);
+    }
+    if started && link.mode == MODE_SUGGEST {
+        suggest(db, http, link, &activity, &event.repo).await;
+    }
+    Ok(())
+}
+
+/// In `log` mode, turn a finished burst into a session.
+fn log_burst(db: &Db, link: &GithubLink, burst: &ActivityBurst) {
+    if link.mode != MODE_LOG {
+        return;
+    }
+    let Some((start, end)) = burst_session(burst, db.clock().week().start()) else {
+        return;
+    };
+    match db.import_session(&burst.user_id, &burst.username, &burst.activity, start, end, db::SOURCE_API) {
+        Ok(true) => println!("[clock] Logged GitHub activity for {} ({})", burst.username, burst.activity),
+        Ok(false) => {}
+        Err(e) => eprintln!("[clock] Failed to log GitHub activity for {}: {e}", burst.username),
+    }
+}
+
+/// DM a clock-in hint when a burst starts and the user isn't already clocked in.
+async fn suggest(db: &Db, http: &Http, link: &GithubLink, activity: &str, repo: &str) {
+    if matches!(db.active_session(&link.user_id), Ok(Some(_))) || crate::quiet::is_quiet(db, db.clock().now()) {
+        return;
+    }
+    let Ok(user_id) = link.user_id.parse::<UserId>() else {
+        return;
+    };
+    let embed = CreateEmbed::new()
+        .color(0x24292e)
+        .title("💻 Looks like you're coding")
+        .description(format!(
+            "New activity on **{}**. Clock in? `/clock in {}`",
+            repo, activity
+        ))
+        .footer(CreateEmbedFooter::new("/clock github unlink to stop these"));
+    if let Err(e) = user_id.direct_message(http, CreateMessage::new().embed(embed)).await {
+        eprintln!("[clock] Failed to DM GitHub suggestion to {}: {e}", link.user_id);
+    }
+}
+
+/// Close bursts that have gone quiet and log them for users in `log` mode.
+pub async fn burst_flush_loop(db: Arc<Db>) {
+    loop {
+        sleep(FLUSH_INTERVAL).await;
+        let idle_before = db.clock().now() - Duration::minutes(BURST_GAP_MINUTES);
+        match db.take_idle_github_bursts(idle_before) {
+            Ok(bursts) => {
+                for burst in &bursts {
+                    if let Ok(Some(link)) = db.github_link_for_user(&burst.user_id) {
+                        log_burst(&db, &link, burst);
+                    }
+                }
+            }
+            Err(e) => eprintln!("[clock] GitHub burst flush failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 2, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let secret = "It's a Secret to Everybody";
+        let sig = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature(secret, b"Hello, World!", sig));
+        assert!(!verify_signature(secret, b"Hello, World?", sig));
+        assert!(!verify_signature("other", b"Hello, World!", sig));
+        assert!(!verify_signature(secret, b"Hello, World!", "sha1=abc"));
+        assert!(!verify_signature(secret, b"Hello, World!", "sha256=zz"));
+    }
+
+    #[test]
+    fn test_parse_push_and_pull_request() {
+        let push = serde_json::json!({
+            "sender": { "login": "Octocat" },
+            "repository": { "full_name": "Org/API" },
+            "commits": [
+                { "timestamp": "2026-02-10T10:30:00+01:00" },
+                { "timestamp": "2026-02-10T08:15:00Z" }
+            ]
+        });
+        assert_eq!(
+            parse_event("push", &push, at(10, 12, 0)),
+            Some(GithubEvent {
+                login: "octocat".to_string(),
+                repo: "org/api".to_string(),
+                first: at(10, 9, 15),
+                last: at(10, 10, 30),
+            })
+        );
+        // Branch deletions carry no commits
+        let empty = serde_json::json!({
+            "sender": { "login": "octocat" },
+            "repository": { "full_name": "org/api" },
+            "commits": []
+        });
+        assert_eq!(parse_event("push", &empty, at(10, 12, 0)), None);
+
+        let mut pr = serde_json::json!({
+            "action": "opened",
+            "sender": { "login": "octocat" },
+            "repository": { "full_name": "org/web" }
+        });
+        assert_eq!(parse_event("pull_request", &pr, at(10, 12, 0)).unwrap().first, at(10, 12, 0));
+        pr["action"] = "labeled".into();
+        assert_eq!(parse_event("pull_request", &pr, at(10, 12, 0)), None);
+        assert_eq!(parse_event("issues", &pr, at(10, 12, 0)), None);
+    }
+
+    #[test]
+    fn test_proves_ownership() {
+        let code = "clockbot-verify-7KQ2XD";
+        let profile = serde_json::json!({"login": "octocat", "bio": "Cat. clockbot-verify-7KQ2XD"});
+        assert!(proves_ownership(code, &profile, &serde_json::json!([])));
+        let gists = serde_json::json!([{"description": null}, {"description": "clockbot-verify-7KQ2XD"}]);
+        assert!(proves_ownership(code, &serde_json::json!({"bio": null}), &gists));
+        assert!(!proves_ownership(code, &serde_json::json!({"bio": "clockbot-verify-OTHER"}), &serde_json::json!([])));
+        assert!(verification_code().starts_with("clockbot-verify-"));
+    }
+
+    #[test]
+    fn test_valid_login() {
+        assert!(valid_login("octocat"));
+        assert!(valid_login("Mona-Lisa42"));
+        assert!(!valid_login(""));
+        assert!(!valid_login("-octocat"));
+        assert!(!valid_login("octo--cat"));
+        assert!(!valid_login("octo cat"));
+        assert!(!valid_login(&"a".repeat(40)));
+    }
+
+    #[test]
+    fn test_activity_for_repo() {
+        let rules = vec![
+            ("org/api".to_string(), "backend".to_string()),
+            ("web*".to_string(), "frontend".to_string()),
+            ("docs".to_string(), "writing".to_string()),
+        ];
+        assert_eq!(activity_for_repo(&rules, "org/api"), "backend");
+        assert_eq!(activity_for_repo(&rules, "org/web-admin"), "frontend");
+        assert_eq!(activity_for_repo(&rules, "other/docs"), "writing");
+        assert_eq!(activity_for_repo(&rules, "fork/api"), "api");
+        assert_eq!(activity_for_repo(&rules, "org/MobileApp"), "mobile-app");
+    }
+
+    #[test]
+    fn test_burst_session() {
+        let burst = |first, last| ActivityBurst {
+            user_id: "u1".to_string(),
+            username: "Alice".to_string(),
+            activity: "backend".to_string(),
+            first_at: first,
+            last_at: last,
+        };
+        let monday = at(9, 0, 0);
+        assert_eq!(
+            burst_session(&burst(at(10, 9, 0), at(10, 11, 0)), monday),
+            Some((at(10, 8, 45), at(10, 11, 0)))
+        );
+        // A single push still counts for a quarter of an hour
+        assert_eq!(
+            burst_session(&burst(at(10, 9, 0), at(10, 9, 0)), monday),
+            Some((at(10, 8, 45), at(10, 9, 0)))
+        );
+        // Clipped to the week start, or dropped if it began last week
+        assert_eq!(
+            burst_session(&burst(at(9, 0, 5), at(9, 0, 5)), monday),
+            Some((at(9, 0, 0), at(9, 0, 15)))
+        );
+        assert_eq!(burst_session(&burst(at(8, 23, 0), at(9, 1, 0)), monday), None);
+    }
+}
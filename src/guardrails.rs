@@ -0,0 +1,129 @@
+use crate::config;
+use crate::db::Db;
+use crate::display::DisplayFormat;
+use serenity::all::*;
+
+/// What happens when a clock-in would break a limit, from the `guardrails` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Warn,
+    Refuse,
+}
+
+pub fn mode(db: &Db) -> Mode {
+    match config::get(db, config::GUARDRAILS).as_deref() {
+        Some("refuse") => Mode::Refuse,
+        _ => Mode::Warn,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Violation {
+    DailyHours { worked: i64, limit: i64 },
+    Streak { days: u32, limit: u32 },
+}
+
+impl Violation {
+    fn describe(&self, fmt: &DisplayFormat) -> String {
+        match self {
+            Violation::DailyHours { worked, limit } => format!(
+                "You've tracked **{}** today, past the **{}** daily limit.",
+                fmt.duration(*worked),
+                fmt.duration(*limit)
+            ),
+            Violation::Streak { days, limit } => format!(
+                "You've worked **{} days in a row** without a day off (limit: {}).",
+                days, limit
+            ),
+        }
+    }
+}
+
+fn daily_limit(db: &Db) -> Option<i64> {
+    config::get(db, config::DAILY_LIMIT)?.parse().ok()
+}
+
+fn max_streak(db: &Db) -> Option<u32> {
+    config::get(db, config::MAX_STREAK)?.parse().ok()
+}
+
+/// Limits `user_id` has already reached, checked before a clock-in.
+pub fn check_clock_in(db: &Db, user_id: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if let Some(limit) = daily_limit(db) {
+        let worked = db
+            .user_settings(user_id)
+            .and_then(|s| crate::nudge::worked_minutes_today(db, &s))
+            .unwrap_or(0);
+        if worked >= limit {
+            violations.push(Violation::DailyHours { worked, limit });
+        }
+    }
+
+    if let Some(limit) = max_streak(db) {
+        let days = db
+            .consecutive_work_days_before(user_id, db.clock().now().date())
+            .unwrap_or(0);
+        if days >= limit {
+            violations.push(Violation::Streak { days, limit });
+        }
+    }
+
+    violations
+}
+
+/// The daily limit, if the session of `session_minutes` that just ended crossed it.
+pub fn check_clock_out(db: &Db, user_id: &str, session_minutes: i64) -> Option<Violation> {
+    let limit = daily_limit(db)?;
+    let worked = db
+        .user_settings(user_id)
+        .and_then(|s| crate::nudge::worked_minutes_today(db, &s))
+        .ok()?;
+    crossed(worked - session_minutes, worked, limit).then_some(Violation::DailyHours { worked, limit })
+}
+
+/// Whether going from `before` to `after` minutes crossed `limit`, so each day warns once.
+fn crossed(before: i64, after: i64, limit: i64) -> bool {
+    before < limit && after >= limit
+}
+
+/// Tell the user privately about `violations`; `refused` when their clock-in was blocked.
+pub async fn notify(http: &Http, user_id: UserId, violations: &[Violation], refused: bool, fmt: &DisplayFormat) {
+    let mut desc = violations
+        .iter()
+        .map(|v| v.describe(fmt))
+        .collect::<Vec<_>>()
+        .join("\n");
+    desc += if refused {
+        "\n\nYour clock-in was not recorded. Time for a break 🌿"
+    } else {
+        "\n\nConsider taking a break 🌿"
+    };
+
+    let embed = CreateEmbed::new()
+        .color(0xe67e22)
+        .title(if refused { "⛔ Clock-in blocked" } else { "🌿 Wellbeing check" })
+        .description(desc)
+        .footer(CreateEmbedFooter::new("Limits are set by your server admins"));
+    if let Err(e) = user_id
+        .direct_message(http, CreateMessage::new().embed(embed))
+        .await
+    {
+        eprintln!("[clock] Failed to DM guardrail notice: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_only_once() {
+        assert!(crossed(500, 620, 600));
+        assert!(crossed(599, 600, 600));
+        // Already past the limit before this session: warned earlier
+        assert!(!crossed(610, 700, 600));
+        assert!(!crossed(100, 200, 600));
+    }
+}
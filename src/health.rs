@@ -0,0 +1,157 @@
+use crate::db::Db;
+use crate::display::DisplayFormat;
+use crate::http::Response;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDateTime};
+use serenity::all::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration as TokioDuration};
+
+/// Metadata key holding the timestamp of the last completed weekly reset.
+pub const LAST_WEEKLY_RUN_KEY: &str = "last_weekly_run";
+/// Metadata key holding the label of the last archived week, guarding against double runs.
+pub const LAST_ARCHIVED_WEEK_KEY: &str = "last_archived_week";
+/// Metadata key remembering which missed reset we already alerted about.
+const WATCHDOG_ALERTED_KEY: &str = "watchdog_alerted_for";
+/// How late the weekly reset may be before the watchdog raises an alert.
+const WATCHDOG_GRACE_MINUTES: i64 = 15;
+const WATCHDOG_INTERVAL: TokioDuration = TokioDuration::from_secs(5 * 60);
+
+/// Liveness information shared between the gateway handler and the HTTP server.
+#[derive(Default)]
+pub struct HealthState {
+    gateway_connected: AtomicBool,
+}
+
+impl HealthState {
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn gateway_connected(&self) -> bool {
+        self.gateway_connected.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether the reset due at `expected` is overdue given the last recorded run.
+/// Without any recorded run (fresh install) there is nothing to compare against.
+fn reset_overdue(now: NaiveDateTime, expected: NaiveDateTime, last_run: Option<NaiveDateTime>) -> bool {
+    match last_run {
+        Some(last) => last < expected && now - expected > Duration::minutes(WATCHDOG_GRACE_MINUTES),
+        None => false,
+    }
+}
+
+fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+/// `GET /health`: 200 when the database answers and the gateway is connected, 503 otherwise.
+pub fn report(db: &Db, state: &HealthState) -> Response {
+    let db_ok = db.ping().is_ok();
+    let gateway_ok = state.gateway_connected();
+    let last_run = db.metadata(LAST_WEEKLY_RUN_KEY).ok().flatten();
+
+    let healthy = db_ok && gateway_ok;
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "degraded" },
+        "db": db_ok,
+        "gateway": gateway_ok,
+        "last_weekly_run": last_run,
+    });
+    Response::json(if healthy { 200 } else { 503 }, &body)
+}
+
+/// Whether `week_label` has already been archived by a reset.
+pub fn already_archived(db: &Db, week_label: &str) -> bool {
+    db.metadata(LAST_ARCHIVED_WEEK_KEY).ok().flatten().as_deref() == Some(week_label)
+}
+
+/// Archive the finished sessions as `week_label` and record the run for the watchdog.
+pub fn archive_week(db: &Db, week_label: &str) -> anyhow::Result<()> {
+    db.archive_week(week_label)?;
+    let ran_at = db.clock().now().format("%Y-%m-%d %H:%M:%S").to_string();
+    if let Err(e) = db.set_metadata(LAST_WEEKLY_RUN_KEY, &ran_at) {
+        eprintln!("[clock] Failed to record weekly run: {e}");
+    }
+    if let Err(e) = db.set_metadata(LAST_ARCHIVED_WEEK_KEY, week_label) {
+        eprintln!("[clock] Failed to record archived week: {e}");
+    }
+    Ok(())
+}
+
+/// Periodically check that the weekly reset actually ran and alert the summary
+/// channel (once per missed run) if it did not.
+pub async fn watchdog_loop(db: Arc<Db>, http: Arc<Http>, channel_id: Option<ChannelId>) {
+    loop {
+        sleep(WATCHDOG_INTERVAL).await;
+
+        let now = db.clock().now();
+        // Most recent Monday 00:00 — when the weekly reset should have fired
+        let expected = WeekId::containing(now.date()).start();
+        let last_run = db
+            .metadata(LAST_WEEKLY_RUN_KEY)
+            .ok()
+            .flatten()
+            .and_then(|s| parse_timestamp(&s));
+
+        if !reset_overdue(now, expected, last_run) {
+            continue;
+        }
+
+        let expected_str = expected.format("%Y-%m-%d %H:%M:%S").to_string();
+        if db.metadata(WATCHDOG_ALERTED_KEY).ok().flatten().as_deref() == Some(expected_str.as_str()) {
+            continue;
+        }
+
+        eprintln!("[clock] Watchdog: weekly reset due {expected_str} has not run");
+        if let Some(channel_id) = channel_id {
+            let fmt = DisplayFormat::load(&db);
+            let last = last_run
+                .map(|t| fmt.timestamp(t))
+                .unwrap_or_else(|| "never".into());
+            let embed = CreateEmbed::new()
+                .color(0xe74c3c)
+                .title("🚨 Weekly Reset Missed")
+                .description(format!(
+                    "The reset scheduled for **{}** has not run.\nLast successful run: {}",
+                    fmt.timestamp(expected),
+                    last
+                ))
+                .footer(CreateEmbedFooter::new(fmt.timestamp(now)));
+            if let Err(e) = channel_id
+                .send_message(&http, CreateMessage::new().embed(embed))
+                .await
+            {
+                eprintln!("[clock] Failed to post watchdog alert: {e}");
+            }
+        }
+        let _ = db.set_metadata(WATCHDOG_ALERTED_KEY, &expected_str);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_reset_overdue() {
+        let expected = at(2026, 2, 16, 0, 0);
+        let previous = Some(at(2026, 2, 9, 0, 0));
+
+        // Within the grace window
+        assert!(!reset_overdue(at(2026, 2, 16, 0, 10), expected, previous));
+        // Past the grace window without a new run
+        assert!(reset_overdue(at(2026, 2, 16, 0, 30), expected, previous));
+        // Ran on time
+        assert!(!reset_overdue(at(2026, 2, 18, 9, 0), expected, Some(at(2026, 2, 16, 0, 0))));
+        // Never ran: nothing to compare against
+        assert!(!reset_overdue(at(2026, 2, 18, 9, 0), expected, None));
+    }
+}
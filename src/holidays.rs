@@ -0,0 +1,70 @@
+use chrono::{Duration, NaiveDate};
+
+/// Easter Sunday (Gregorian), via the anonymous Gregorian computus.
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+/// Public holidays observed in the canton of Zurich, ordered by date.
+/// Other cantons differ; `/clock holiday add|remove` adjusts after an import.
+pub fn swiss_public_holidays(year: i32) -> Vec<(NaiveDate, &'static str)> {
+    let fixed = |m, d| NaiveDate::from_ymd_opt(year, m, d).unwrap();
+    let easter = easter_sunday(year);
+    let mut days = vec![
+        (fixed(1, 1), "New Year's Day"),
+        (fixed(1, 2), "Berchtold's Day"),
+        (easter - Duration::days(2), "Good Friday"),
+        (easter + Duration::days(1), "Easter Monday"),
+        (fixed(5, 1), "Labour Day"),
+        (easter + Duration::days(39), "Ascension Day"),
+        (easter + Duration::days(50), "Whit Monday"),
+        (fixed(8, 1), "Swiss National Day"),
+        (fixed(12, 25), "Christmas Day"),
+        (fixed(12, 26), "St. Stephen's Day"),
+    ];
+    days.sort_by_key(|(d, _)| *d);
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_easter_sunday() {
+        assert_eq!(easter_sunday(2024), date(2024, 3, 31));
+        assert_eq!(easter_sunday(2025), date(2025, 4, 20));
+        assert_eq!(easter_sunday(2026), date(2026, 4, 5));
+        assert_eq!(easter_sunday(2027), date(2027, 3, 28));
+        assert_eq!(easter_sunday(2038), date(2038, 4, 25));
+    }
+
+    #[test]
+    fn test_swiss_public_holidays_2026() {
+        let days = swiss_public_holidays(2026);
+        assert_eq!(days.len(), 10);
+        assert!(days.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(days.contains(&(date(2026, 4, 3), "Good Friday")));
+        assert!(days.contains(&(date(2026, 4, 6), "Easter Monday")));
+        assert!(days.contains(&(date(2026, 5, 14), "Ascension Day")));
+        assert!(days.contains(&(date(2026, 5, 25), "Whit Monday")));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::db::Db;
+use crate::health::HealthState;
+use serenity::all::Http;
+use std::env;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+
+/// Largest request (headers + body) we are willing to buffer.
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Address for the built-in HTTP server, from `HTTP_ADDR` (e.g. `0.0.0.0:8080`).
+/// Unset disables the server.
+pub fn http_addr() -> Option<String> {
+    env::var("HTTP_ADDR").ok().filter(|s| !s.is_empty())
+}
+
+/// Shared state handed to every request.
+pub struct HttpState {
+    pub db: Arc<Db>,
+    pub health: Arc<HealthState>,
+    /// For handlers that message users (e.g. webhook-driven suggestions).
+    pub http: Arc<Http>,
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// First header named `name`, case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    pub fn html(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Status line and headers of `response`.
+fn head(response: &Response) -> String {
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )
+}
+
+/// Accept connections until the process exits. One request per connection.
+pub async fn serve(addr: String, state: Arc<HttpState>) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[clock] Failed to bind HTTP server on {addr}: {e}");
+            return;
+        }
+    };
+    println!("[clock] HTTP server listening on {addr}");
+
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[clock] HTTP accept failed: {e}");
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            handle_connection(stream, &state).await;
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &HttpState) {
+    let response = match timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(req))) => route(&req, state).await,
+        Ok(Ok(None)) => Response::text(413, "request too large"),
+        Ok(Err(_)) | Err(_) => Response::text(400, "bad request"),
+    };
+
+    let _ = stream.write_all(head(&response).as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Read and parse one HTTP/1.1 request. `Ok(None)` means it exceeded `MAX_REQUEST_BYTES`.
+async fn read_request(stream: &mut TcpStream) -> anyhow::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before headers");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = std::str::from_utf8(&buf[..header_end])?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    if method.is_empty() || path.is_empty() {
+        anyhow::bail!("malformed request line");
+    }
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length: usize = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    if header_end + 4 + content_length > MAX_REQUEST_BYTES {
+        return Ok(None);
+    }
+
+    let mut body = buf.split_off(header_end + 4);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before body");
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+async fn route(req: &Request, state: &HttpState) -> Response {
+    // Ignore query strings for routing purposes
+    let path = req.path.split('?').next().unwrap_or_default();
+    match (req.method.as_str(), path) {
+        ("GET", "/health") => crate::health::report(&state.db, &state.health),
+        ("GET", "/dashboard") => crate::dashboard::serve(&state.db),
+        ("GET", "/api/v1/sessions") => crate::api::sessions(req, state),
+        ("POST", "/api/v1/kiosk") => crate::kiosk::handle(req, state),
+        ("POST", "/github") => crate::github::handle_webhook(req, state).await,
+        ("POST", "/attendance") => crate::attendance::handle_webhook(req, state).await,
+        ("POST", "/api/v1/users/current/heartbeats") => crate::ingest::handle_heartbeats(req, state, false).await,
+        ("POST", "/api/v1/users/current/heartbeats.bulk") => crate::ingest::handle_heartbeats(req, state, true).await,
+        _ => Response::text(404, "not found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_head() {
+        let head = head(&Response::text(403, "read-only token"));
+        assert!(head.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        assert!(head.contains("Content-Length: 15\r\n"));
+        for status in [200, 201, 400, 401, 403, 404, 409, 413, 503] {
+            assert!(!reason(status).is_empty(), "no reason phrase for {status}");
+        }
+    }
+}
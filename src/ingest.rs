@@ -0,0 +1,316 @@
+use crate::db::{self, ActivityBurst, Db, TimelineSession};
+use crate::http::{HttpState, Request, Response};
+use crate::normalize::normalize_activity;
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use chrono_tz::Europe::Zurich;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// Heartbeats further apart than this start a new span (WakaTime's default keystroke timeout).
+const HEARTBEAT_TIMEOUT_MINUTES: i64 = 15;
+/// Spans (or the parts of them not already covered by sessions) shorter than this are dropped.
+const MIN_SESSION_MINUTES: i64 = 5;
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+/// Bulk requests larger than this are rejected.
+const MAX_HEARTBEATS: usize = 1000;
+
+/// Activity for heartbeats that don't name a project.
+pub const DEFAULT_ACTIVITY: &str = "coding";
+
+/// A fresh API key, formatted like a UUIDv4 so WakaTime clients accept it.
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Keys are only stored hashed.
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.trim().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The API key of a request: `Authorization: Basic base64(key)` as WakaTime clients send
+/// it, `Authorization: Bearer <key>`, or an `api_key` query parameter.
+pub fn api_key(req: &Request) -> Option<String> {
+    if let Some(auth) = req.header("Authorization") {
+        let (scheme, value) = auth.split_once(' ')?;
+        return match scheme.to_ascii_lowercase().as_str() {
+            "basic" => {
+                let decoded = base64::engine::general_purpose::STANDARD.decode(value.trim()).ok()?;
+                let decoded = String::from_utf8(decoded).ok()?;
+                // `key` or `key:` (empty password)
+                let key = decoded.split(':').next().unwrap_or_default();
+                (!key.is_empty()).then(|| key.to_string())
+            }
+            "bearer" => Some(value.trim().to_string()).filter(|k| !k.is_empty()),
+            _ => None,
+        };
+    }
+    let (_, query) = req.path.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == "api_key")
+        .map(|(_, v)| v.to_string())
+        .filter(|k| !k.is_empty())
+}
+
+/// One editor heartbeat, in Swiss local time.
+#[derive(Debug, PartialEq)]
+pub struct Heartbeat {
+    pub at: NaiveDateTime,
+    pub project: Option<String>,
+}
+
+/// Parse a heartbeat object or an array of them. `time` is Unix seconds (fractions allowed)
+/// and defaults to `received`; everything but `time` and `project` is ignored.
+pub fn parse_heartbeats(payload: &serde_json::Value, received: NaiveDateTime) -> Option<Vec<Heartbeat>> {
+    let items = match payload {
+        serde_json::Value::Array(items) => items.iter().collect(),
+        serde_json::Value::Object(_) => vec![payload],
+        _ => return None,
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let at = match item.get("time") {
+                None | Some(serde_json::Value::Null) => received,
+                Some(time) => {
+                    let secs = time.as_f64()?;
+                    let utc = DateTime::from_timestamp(secs.trunc() as i64, (secs.fract() * 1e9) as u32)?;
+                    utc.with_timezone(&Zurich).naive_local()
+                }
+            };
+            let project = item["project"]
+                .as_str()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string);
+            Some(Heartbeat { at, project })
+        })
+        .collect()
+}
+
+pub fn activity_for_project(project: Option<&str>) -> String {
+    project
+        .map(normalize_activity)
+        .filter(|a| !a.is_empty())
+        .unwrap_or_else(|| DEFAULT_ACTIVITY.to_string())
+}
+
+/// Parts of `[start, end)` not covered by `sessions` (ordered by start), so detected time
+/// never double-counts a manual clock-in.
+pub fn uncovered(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    sessions: &[TimelineSession],
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut pieces = Vec::new();
+    let mut cursor = start;
+    for s in sessions {
+        if s.started_at > cursor {
+            pieces.push((cursor, s.started_at.min(end)));
+        }
+        cursor = cursor.max(s.ended_at);
+        if cursor >= end {
+            break;
+        }
+    }
+    if cursor < end {
+        pieces.push((cursor, end));
+    }
+    pieces.retain(|(a, b)| *b - *a >= Duration::minutes(MIN_SESSION_MINUTES));
+    pieces
+}
+
+pub async fn handle_heartbeats(req: &Request, state: &HttpState, bulk: bool) -> Response {
+    let (user_id, username) = match crate::api::authenticate(req, &state.db, crate::api::Scope::Write) {
+        Ok(user) => user,
+        Err(response) => return response,
+    };
+    let now = state.db.clock().now();
+    let heartbeats = serde_json::from_slice::<serde_json::Value>(&req.body)
+        .ok()
+        .and_then(|payload| parse_heartbeats(&payload, now));
+    let Some(heartbeats) = heartbeats.filter(|h| h.len() <= MAX_HEARTBEATS) else {
+        return Response::text(400, "bad payload");
+    };
+
+    if let Err(e) = ingest(&state.db, &user_id, &username, &heartbeats, now) {
+        eprintln!("[clock] Heartbeat ingest failed for {username}: {e}");
+        return Response::text(503, "unavailable");
+    }
+
+    if bulk {
+        let responses: Vec<serde_json::Value> = heartbeats
+            .iter()
+            .map(|_| serde_json::json!([{ "data": {} }, 201]))
+            .collect();
+        Response::json(201, &serde_json::json!({ "responses": responses }))
+    } else {
+        Response::json(201, &serde_json::json!({ "data": {} }))
+    }
+}
+
+/// Fold heartbeats into the user's span. Heartbeats sent while clocked in are already
+/// tracked and are dropped; so are ones outside the running week or from the future.
+fn ingest(db: &Db, user_id: &str, username: &str, heartbeats: &[Heartbeat], now: NaiveDateTime) -> anyhow::Result<()> {
+    if db.active_session(user_id)?.is_some() {
+        return Ok(());
+    }
+    let week_start = db.clock().week().start();
+    let mut ordered: Vec<&Heartbeat> = heartbeats
+        .iter()
+        .filter(|h| h.at >= week_start && h.at <= now + Duration::minutes(1))
+        .collect();
+    ordered.sort_by_key(|h| h.at);
+    for heartbeat in ordered {
+        let activity = activity_for_project(heartbeat.project.as_deref());
+        let gap = Duration::minutes(HEARTBEAT_TIMEOUT_MINUTES);
+        if let Some(span) = db.record_heartbeat(user_id, username, &activity, heartbeat.at, gap)? {
+            log_span(db, &span);
+        }
+    }
+    Ok(())
+}
+
+/// Log the parts of a finished span that no existing session covers.
+fn log_span(db: &Db, span: &ActivityBurst) {
+    let start = span.first_at.max(db.clock().week().start());
+    if span.last_at <= start {
+        return;
+    }
+    let sessions = match db.sessions_between(&span.user_id, start, span.last_at) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[clock] Failed to load sessions for {}: {e}", span.username);
+            return;
+        }
+    };
+    for (from, to) in uncovered(start, span.last_at, &sessions) {
+        if let Err(e) = db.import_session(&span.user_id, &span.username, &span.activity, from, to, db::SOURCE_API) {
+            eprintln!("[clock] Failed to log heartbeat session for {}: {e}", span.username);
+        }
+    }
+}
+
+/// Close spans whose editor has gone quiet and log them as sessions.
+pub async fn ingest_flush_loop(db: Arc<Db>) {
+    loop {
+        sleep(FLUSH_INTERVAL).await;
+        let idle_before = db.clock().now() - Duration::minutes(HEARTBEAT_TIMEOUT_MINUTES);
+        match db.take_idle_heartbeat_spans(idle_before) {
+            Ok(spans) => spans.iter().for_each(|span| log_span(&db, span)),
+            Err(e) => eprintln!("[clock] Heartbeat flush failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::fixtures::test_db_at;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 2, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn request(path: &str, headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_api_key_from_request() {
+        let key = "0f8b3c6e-2a4d-4e7b-9c1a-5d6e7f8a9b0c";
+        let basic = base64::engine::general_purpose::STANDARD.encode(key);
+        let basic_colon = base64::engine::general_purpose::STANDARD.encode(format!("{}:", key));
+        let p = "/api/v1/users/current/heartbeats";
+        assert_eq!(api_key(&request(p, &[("Authorization", &format!("Basic {}", basic))])).as_deref(), Some(key));
+        assert_eq!(api_key(&request(p, &[("authorization", &format!("Basic {}", basic_colon))])).as_deref(), Some(key));
+        assert_eq!(api_key(&request(p, &[("Authorization", &format!("Bearer {}", key))])).as_deref(), Some(key));
+        assert_eq!(api_key(&request(&format!("{}?api_key={}", p, key), &[])).as_deref(), Some(key));
+        assert_eq!(api_key(&request(p, &[("Authorization", "Digest abc")])), None);
+        assert_eq!(api_key(&request(p, &[])), None);
+    }
+
+    #[test]
+    fn test_generated_keys_look_like_uuids() {
+        let key = generate_api_key();
+        assert_eq!(key.len(), 36);
+        assert_eq!(&key[14..15], "4");
+        assert_ne!(key, generate_api_key());
+        assert_eq!(hash_api_key(&key), hash_api_key(&format!(" {} ", key)));
+    }
+
+    #[test]
+    fn test_parse_heartbeats() {
+        // 2026-02-10 09:00:00 UTC is 10:00 in Zurich
+        let single = serde_json::json!({ "entity": "src/main.rs", "time": 1770714000.25, "project": "ClockBot" });
+        assert_eq!(
+            parse_heartbeats(&single, at(10, 12, 0)).unwrap(),
+            vec![Heartbeat { at: at(10, 10, 0) + Duration::milliseconds(250), project: Some("ClockBot".to_string()) }]
+        );
+        let bulk = serde_json::json!([{ "project": "" }, { "time": 1770714000, "project": "api" }]);
+        let parsed = parse_heartbeats(&bulk, at(10, 12, 0)).unwrap();
+        assert_eq!(parsed[0], Heartbeat { at: at(10, 12, 0), project: None });
+        assert_eq!(parsed[1].project.as_deref(), Some("api"));
+        assert!(parse_heartbeats(&serde_json::json!({ "time": "noon" }), at(10, 12, 0)).is_none());
+        assert!(parse_heartbeats(&serde_json::json!("ping"), at(10, 12, 0)).is_none());
+
+        assert_eq!(activity_for_project(Some("ClockBot")), "clock-bot");
+        assert_eq!(activity_for_project(None), DEFAULT_ACTIVITY);
+    }
+
+    #[test]
+    fn test_uncovered_skips_manual_sessions() {
+        let session = |from, to| TimelineSession { activity: "work".to_string(), started_at: from, ended_at: to };
+        assert_eq!(uncovered(at(10, 9, 0), at(10, 11, 0), &[]), vec![(at(10, 9, 0), at(10, 11, 0))]);
+        assert_eq!(
+            uncovered(
+                at(10, 9, 0),
+                at(10, 12, 0),
+                &[session(at(10, 8, 0), at(10, 9, 30)), session(at(10, 10, 0), at(10, 10, 58))]
+            ),
+            vec![(at(10, 9, 30), at(10, 10, 0)), (at(10, 10, 58), at(10, 12, 0))]
+        );
+        // The 2-minute gap is too short to log
+        assert_eq!(
+            uncovered(at(10, 9, 0), at(10, 10, 0), &[session(at(10, 9, 2), at(10, 11, 0))]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_ingest_reconciles_with_clock_ins() {
+        let now = at(16, 10, 0);
+        let (db, _clock) = test_db_at(now);
+        let beat = |minutes_ago: i64| Heartbeat { at: now - Duration::minutes(minutes_ago), project: Some("api".to_string()) };
+
+        // While clocked in, heartbeats are ignored
+        db.clock_in("u1", "Alice", "work").unwrap();
+        ingest(&db, "u1", "Alice", &[beat(1)], now).unwrap();
+        db.clock_out("u1").unwrap();
+        assert!(db.take_idle_heartbeat_spans(now + Duration::hours(1)).unwrap().is_empty());
+
+        ingest(&db, "u1", "Alice", &[beat(0)], now).unwrap();
+        assert_eq!(db.take_idle_heartbeat_spans(now + Duration::hours(1)).unwrap().len(), 1);
+        // Heartbeats from the future are dropped
+        ingest(&db, "u1", "Alice", &[beat(-10)], now).unwrap();
+        assert!(db.take_idle_heartbeat_spans(now + Duration::hours(1)).unwrap().is_empty());
+    }
+}
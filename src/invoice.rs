@@ -0,0 +1,163 @@
+use crate::ask::Period;
+use crate::commands::csv_field;
+use crate::db::WeekActivity;
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDate};
+
+/// One week of a person's time on an activity. Past weeks are archived as totals,
+/// so a week is the finest line an invoice can have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceLine {
+    pub week: WeekId,
+    pub activity: String,
+    pub username: String,
+    pub minutes: i64,
+}
+
+/// `2026-03` into year and month.
+pub fn parse_month(arg: &str) -> Option<(i32, u32)> {
+    let (year, month) = arg.trim().split_once('-')?;
+    let year: i32 = year.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    NaiveDate::from_ymd_opt(year, month, 1).map(|_| (year, month))
+}
+
+/// The lines of `activities` in the weeks of `year`-`month` (a week belongs to the month
+/// its Thursday falls in), ordered by week, activity and person.
+pub fn lines(rows: &[WeekActivity], activities: &[String], year: i32, month: u32, today: NaiveDate) -> Vec<InvoiceLine> {
+    let weeks = Period::Month(year, month).weeks(today).unwrap_or_default();
+    let mut lines: Vec<InvoiceLine> = rows
+        .iter()
+        .filter(|r| r.minutes > 0 && activities.contains(&r.activity))
+        .filter_map(|r| {
+            let week = WeekId::parse(&r.week_label).filter(|w| weeks.contains(w))?;
+            Some(InvoiceLine {
+                week,
+                activity: r.activity.clone(),
+                username: r.username.clone(),
+                minutes: r.minutes,
+            })
+        })
+        .collect();
+    lines.sort_by(|a, b| {
+        (a.week.monday(), &a.activity, &a.username).cmp(&(b.week.monday(), &b.activity, &b.username))
+    });
+    lines
+}
+
+pub fn amount(minutes: i64, rate: f64) -> f64 {
+    minutes as f64 / 60.0 * rate
+}
+
+/// Spreadsheet-ready lines; `rate` and `amount` stay empty without an hourly rate.
+pub fn csv(lines: &[InvoiceLine], rate: Option<f64>) -> String {
+    let mut out = String::from("week,from,to,activity,user,hours,rate,amount\n");
+    for line in lines {
+        let (rate, amount) = match rate {
+            Some(rate) => (format!("{:.2}", rate), format!("{:.2}", amount(line.minutes, rate))),
+            None => (String::new(), String::new()),
+        };
+        out += &format!(
+            "{},{},{},{},{},{:.2},{},{}\n",
+            line.week.label(),
+            line.week.monday(),
+            line.week.monday() + Duration::days(6),
+            csv_field(&line.activity),
+            csv_field(&line.username),
+            line.minutes as f64 / 60.0,
+            rate,
+            amount
+        );
+    }
+    out
+}
+
+/// `**platform** — 12h 0m · CHF 1800.00` per activity, most time first.
+pub fn format_totals(lines: &[InvoiceLine], rate: Option<f64>, currency: &str, fmt: &DisplayFormat) -> String {
+    let mut totals: Vec<(&str, i64)> = Vec::new();
+    for line in lines {
+        match totals.iter_mut().find(|(activity, _)| *activity == line.activity) {
+            Some((_, total)) => *total += line.minutes,
+            None => totals.push((&line.activity, line.minutes)),
+        }
+    }
+    totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+    totals
+        .iter()
+        .map(|(activity, minutes)| match rate {
+            Some(rate) => format!("**{}** — {} · {} {:.2}\n", activity, fmt.duration(*minutes), currency, amount(*minutes, rate)),
+            None => format!("**{}** — {}\n", activity, fmt.duration(*minutes)),
+        })
+        .collect()
+}
+
+/// Attachment name, e.g. `invoice-acme-2026-03.csv`.
+pub fn filename(client: &str, year: i32, month: u32) -> String {
+    let client: String = client
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("invoice-{}-{}-{:02}.csv", client, year, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(week_label: &str, username: &str, activity: &str, minutes: i64) -> WeekActivity {
+        WeekActivity {
+            user_id: username.to_lowercase(),
+            username: username.into(),
+            week_label: week_label.into(),
+            activity: activity.into(),
+            minutes,
+        }
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(parse_month("2026-03"), Some((2026, 3)));
+        assert_eq!(parse_month("2026-13"), None);
+        assert_eq!(parse_month("march"), None);
+    }
+
+    #[test]
+    fn test_lines_and_csv() {
+        let today = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        let rows = vec![
+            // KW09 has its Thursday on 26.02, so it belongs to February
+            row("KW09/2026", "Alice", "platform", 600),
+            row("KW10/2026", "Bob", "website", 90),
+            row("KW10/2026", "Alice", "platform", 120),
+            row("KW10/2026", "Alice", "admin", 60),
+            row("KW13/2026", "Alice", "platform", 30),
+            // KW14 starts on 30.03 but its Thursday is in April
+            row("KW14/2026", "Alice", "platform", 45),
+        ];
+        let activities = vec!["platform".to_string(), "website".to_string()];
+        let lines = lines(&rows, &activities, 2026, 3, today);
+        assert_eq!(
+            lines.iter().map(|l| (l.week.label(), l.activity.as_str(), l.minutes)).collect::<Vec<_>>(),
+            vec![
+                ("KW10/2026".to_string(), "platform", 120),
+                ("KW10/2026".to_string(), "website", 90),
+                ("KW13/2026".to_string(), "platform", 30),
+            ]
+        );
+
+        assert_eq!(
+            csv(&lines[..1], Some(150.0)),
+            "week,from,to,activity,user,hours,rate,amount\nKW10/2026,2026-03-02,2026-03-08,platform,Alice,2.00,150.00,300.00\n"
+        );
+        assert!(csv(&lines[..1], None).ends_with(",Alice,2.00,,\n"));
+
+        let fmt = DisplayFormat::default();
+        assert_eq!(
+            format_totals(&lines, Some(100.0), "CHF", &fmt),
+            "**platform** — 2h 30m · CHF 250.00\n**website** — 1h 30m · CHF 150.00\n"
+        );
+        assert_eq!(filename("Acme Corp", 2026, 3), "invoice-acme-corp-2026-03.csv");
+    }
+}
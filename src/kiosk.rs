@@ -0,0 +1,92 @@
+use crate::api::{authenticate, Scope};
+use crate::attendance::{parse_action, respond};
+use crate::http::{HttpState, Request, Response};
+use crate::ingest::{api_key, hash_api_key};
+use rand::Rng;
+use std::env;
+
+/// Letters and digits that can't be mistaken for each other on a keypad or screen.
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 6;
+
+/// Key the shared kiosk device sends, from `KIOSK_KEY`. Unset disables short codes.
+pub fn kiosk_key() -> Option<String> {
+    env::var("KIOSK_KEY").ok().filter(|s| !s.is_empty())
+}
+
+/// A fresh personal short code, e.g. `K7P2QX`.
+pub fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// A typed code as stored: uppercase, without spaces or dashes.
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// `POST /api/v1/kiosk`: clock someone in or out. A shared kiosk sends `KIOSK_KEY` and
+/// the person's short code in `{"code": "K7P2QX"}`; a phone shortcut can instead send
+/// a personal write token and no code. `action` and `activity` work like `/attendance`.
+pub fn handle(req: &Request, state: &HttpState) -> Response {
+    let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&req.body) else {
+        return Response::text(400, "bad payload");
+    };
+    let Some((action, activity)) = parse_action(&payload) else {
+        return Response::text(400, "bad payload");
+    };
+
+    let user = match payload["code"].as_str() {
+        Some(code) => {
+            let Some(key) = kiosk_key() else {
+                return Response::text(404, "not found");
+            };
+            if api_key(req).is_none_or(|k| hash_api_key(&k) != hash_api_key(&key)) {
+                return Response::text(401, "invalid kiosk key");
+            }
+            match state.db.kiosk_code_user(&hash_api_key(&normalize_code(code))) {
+                Ok(Some(user)) => user,
+                Ok(None) => return Response::text(404, "unknown code"),
+                Err(e) => {
+                    eprintln!("[clock] Kiosk code lookup failed: {e}");
+                    return Response::text(503, "unavailable");
+                }
+            }
+        }
+        None => match authenticate(req, &state.db, Scope::Write) {
+            Ok(user) => user,
+            Err(response) => return response,
+        },
+    };
+    respond(&state.db, &user.0, &user.1, action, activity.as_deref(), "kiosk")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+
+    #[test]
+    fn test_codes() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|b| CODE_ALPHABET.contains(&b)));
+        assert_eq!(normalize_code(" k7p-2qx "), "K7P2QX");
+
+        let db = test_db();
+        db.set_kiosk_code("u1", "Alice", &hash_api_key("K7P2QX")).unwrap();
+        let lookup = |typed| db.kiosk_code_user(&hash_api_key(&normalize_code(typed))).unwrap();
+        assert_eq!(lookup("k7p 2qx"), Some(("u1".to_string(), "Alice".to_string())));
+
+        // A new code replaces the old one
+        db.set_kiosk_code("u1", "Alice", &hash_api_key("ABCDEF")).unwrap();
+        assert_eq!(lookup("K7P2QX"), None);
+        assert!(db.revoke_kiosk_code("u1").unwrap());
+        assert_eq!(lookup("ABCDEF"), None);
+    }
+}
@@ -0,0 +1,48 @@
+pub mod anonymous;
+pub mod api;
+pub mod attendance;
+pub mod ask;
+pub mod auto_out;
+pub mod budgets;
+pub mod cache;
+pub mod calendar;
+pub mod challenges;
+pub mod chart;
+pub mod clock;
+pub mod commands;
+pub mod config;
+pub mod dashboard;
+pub mod db;
+pub mod debounce;
+pub mod digest;
+pub mod display;
+pub mod encryption;
+pub mod errors;
+pub mod events;
+#[cfg(test)]
+mod fixtures;
+pub mod focus;
+pub mod github;
+pub mod guardrails;
+pub mod health;
+pub mod holidays;
+pub mod http;
+pub mod ingest;
+pub mod invoice;
+pub mod kiosk;
+pub mod midweek;
+pub mod mirror;
+pub mod normalize;
+pub mod nudge;
+pub mod pace;
+pub mod parquet;
+pub mod quiet;
+pub mod reengage;
+pub mod removal;
+pub mod reporting;
+pub mod sentry;
+pub mod status;
+pub mod storage;
+pub mod supervisor;
+pub mod tips;
+pub mod weeks;
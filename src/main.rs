@@ -1,10 +1,11 @@
-mod chart;
-mod commands;
-mod db;
-mod normalize;
-
+use clockbot::{
+    anonymous, auto_out, budgets, calendar, challenges, chart, commands, config, dashboard, db, digest, display, events, focus,
+    github, health, http, ingest, midweek, mirror, nudge, quiet, reengage, removal, reporting, status, storage, supervisor,
+    weeks,
+};
 use db::Db;
 use dotenv::dotenv;
+use health::HealthState;
 use plotters::style::{register_font, FontStyle};
 use serenity::all::*;
 use serenity::async_trait;
@@ -16,35 +17,93 @@ static EMBEDDED_FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
 
 struct Handler {
     db: Arc<Db>,
+    health: Arc<HealthState>,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
         if msg.author.bot {
+            if config::is_trusted_bot(&msg) {
+                commands::handle_bot_command(&ctx, &msg, &self.db).await;
+            }
             return;
         }
         commands::handle_command(&ctx, &msg, &self.db).await;
+        if let Err(e) = self.db.record_last_seen(&commands::account(&self.db, msg.author.id), self.db.clock().now()) {
+            eprintln!("[clock] Failed to record last message: {e}");
+        }
+    }
+
+    async fn reaction_add(&self, _ctx: Context, reaction: Reaction) {
+        commands::handle_kudos_reaction(&self.db, &reaction, true);
+    }
+
+    async fn reaction_remove(&self, _ctx: Context, reaction: Reaction) {
+        commands::handle_kudos_reaction(&self.db, &reaction, false);
     }
 
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("[clock] {} is online", ready.user.name);
+        self.health.set_gateway_connected(true);
+        if let Some(guild) = ready.guilds.first()
+            && let Err(e) = self.db.set_metadata(storage::GUILD_KEY, &guild.id.to_string())
+        {
+            eprintln!("[clock] Failed to record the server id: {e}");
+        }
+
+        focus::cleanup_stale(&ctx.http, &self.db).await;
 
-        if let Some(channel_id) = summary_channel_id() {
+        let quiet = quiet::is_quiet(&self.db, self.db.clock().now());
+        if let Some(channel_id) = summary_channel_id().filter(|_| !quiet) {
             let embed = CreateEmbed::new()
                 .color(0x2ecc71)
                 .title("✅ ClockBot Online")
-                .description(format!(
+                .description(
                     "Summary channel verified.\nWeekly reports will post here every Monday 00:00.",
-                ))
+                )
                 .footer(CreateEmbedFooter::new(
-                    db::now_ch().format("%d.%m.%Y %H:%M").to_string(),
+                    display::DisplayFormat::load(&self.db).timestamp(self.db.clock().now()),
                 ));
             let _ = channel_id
                 .send_message(&ctx.http, CreateMessage::new().embed(embed))
                 .await;
         }
     }
+
+    async fn guild_create(&self, _ctx: Context, guild: Guild, _is_new: Option<bool>) {
+        removal::on_joined(&self.db, &guild.id.to_string()).await;
+    }
+
+    async fn guild_delete(&self, ctx: Context, incomplete: UnavailableGuild, _full: Option<Guild>) {
+        // Unavailable means a Discord outage, not a removal
+        if incomplete.unavailable {
+            return;
+        }
+        let remaining = ctx.cache.guilds().into_iter().filter(|g| *g != incomplete.id).count();
+        removal::on_removed(&self.db, &incomplete.id.to_string(), remaining).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+        let custom_id = component.data.custom_id.as_str();
+        if custom_id == commands::MENU_CUSTOM_ID {
+            commands::handle_menu_select(&ctx, &component, &self.db).await;
+        } else if custom_id.starts_with(commands::STALE_CUSTOM_ID) {
+            commands::handle_stale_button(&ctx, &component, &self.db).await;
+        } else if custom_id.starts_with(commands::MERGE_CUSTOM_ID)
+            || custom_id.starts_with(commands::KEEP_CUSTOM_ID)
+        {
+            commands::handle_merge_button(&ctx, &component, &self.db).await;
+        }
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        self.health
+            .set_gateway_connected(event.new == ConnectionStage::Connected);
+    }
 }
 
 fn summary_channel_id() -> Option<ChannelId> {
@@ -67,7 +126,7 @@ async fn main() -> anyhow::Result<()> {
         FontStyle::Italic,
         FontStyle::Oblique,
     ] {
-        if let Err(_) = register_font("sans-serif", style, EMBEDDED_FONT) {
+        if register_font("sans-serif", style, EMBEDDED_FONT).is_err() {
             eprintln!("[clock] Warning: failed to register embedded font for a style");
         }
     }
@@ -75,19 +134,71 @@ async fn main() -> anyhow::Result<()> {
     let token = env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN missing");
     let db = Arc::new(Db::open(Path::new("/data/clock.db"))?);
 
-    // Normalize all existing activity names in the database
-    db.normalize_activities()?;
-    println!("[clock] Activity names normalized");
+    // Normalize existing activity names whenever the rules changed since the last run
+    if db.normalize_activities()? {
+        println!("[clock] Activity names normalized");
+    }
 
-    let db_clone = Arc::clone(&db);
+    // Background loops restart if they panic; see `supervisor`
+    let reporter = Arc::new(supervisor::Reporter::from_env(Arc::new(Http::new(&token))));
     let token_clone = token.clone();
-    tokio::spawn(async move {
-        weekly_reset_loop(&db_clone, &token_clone).await;
+    spawn_loop("weekly-reset", &reporter, &db, &token, move |db, _| {
+        let token = token_clone.clone();
+        async move { weekly_reset_loop(&db, &token).await }
+    });
+
+    let health = Arc::new(HealthState::default());
+    if let Some(addr) = http::http_addr() {
+        let state = Arc::new(http::HttpState {
+            db: Arc::clone(&db),
+            health: Arc::clone(&health),
+            http: Arc::new(Http::new(&token)),
+        });
+        supervisor::spawn("http", &reporter, move || http::serve(addr.clone(), Arc::clone(&state)));
+    }
+    spawn_loop("watchdog", &reporter, &db, &token, |db, http| {
+        health::watchdog_loop(db, http, summary_channel_id())
     });
 
-    let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+    spawn_loop("status-channel", &reporter, &db, &token, status::status_channel_loop);
+
+    spawn_loop("nudge", &reporter, &db, &token, nudge::nudge_loop);
+
+    spawn_loop("reengage", &reporter, &db, &token, reengage::reengage_loop);
+
+    spawn_loop("auto-out", &reporter, &db, &token, auto_out::auto_out_loop);
+
+    spawn_loop("budget-alerts", &reporter, &db, &token, |db, http| {
+        budgets::alert_loop(db, http, summary_channel_id())
+    });
+
+    spawn_loop("event-announcements", &reporter, &db, &token, |db, http| {
+        events::announce_loop(db, http, summary_channel_id())
+    });
+
+    spawn_loop("challenge-progress", &reporter, &db, &token, challenges::progress_loop);
+
+    spawn_loop("midweek-standings", &reporter, &db, &token, |db, http| {
+        midweek::standings_loop(db, http, summary_channel_id())
+    });
+
+    spawn_loop("calendar-sync", &reporter, &db, &token, |db, _| calendar::calendar_sync_loop(db));
+
+    spawn_loop("github-flush", &reporter, &db, &token, |db, _| github::burst_flush_loop(db));
+
+    spawn_loop("ingest-flush", &reporter, &db, &token, |db, _| ingest::ingest_flush_loop(db));
+
+    spawn_loop("removal-cleanup", &reporter, &db, &token, |db, _| removal::cleanup_loop(db));
+
+    // Direct messages let users share private links (e.g. calendar URLs) with the bot;
+    // guild events tell it when it was removed from a server
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::DIRECT_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT;
     let mut client = Client::builder(&token, intents)
-        .event_handler(Handler { db })
+        .event_handler(Handler { db, health })
         .await
         .expect("Failed to build client");
 
@@ -95,72 +206,208 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Every Monday 00:00 Swiss time:
-/// 1. Post weekly summary to SUMMARY_CHANNEL
-/// 2. Archive the week
-/// 3. Clear completed sessions
-async fn weekly_reset_loop(db: &Arc<Db>, token: &str) {
-    use chrono::{Datelike, Duration, Weekday};
-    use tokio::time::{sleep, Duration as TokioDuration};
+/// Run a background loop under the supervisor, handing it the database and its own
+/// Discord client again on every restart.
+fn spawn_loop<F, Fut>(name: &'static str, reporter: &Arc<supervisor::Reporter>, db: &Arc<Db>, token: &str, task: F)
+where
+    F: Fn(Arc<Db>, Arc<Http>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (db, http) = (Arc::clone(db), Arc::new(Http::new(token)));
+    supervisor::spawn(name, reporter, move || task(Arc::clone(&db), Arc::clone(&http)));
+}
 
-    let summary_channel: Option<ChannelId> = env::var("SUMMARY_CHANNEL")
+/// Attach the PDF report to the first summary message. Runs before archiving so the
+/// chart still includes the closed week.
+fn attach_pdf_report(db: &Db, messages: &mut [CreateMessage], summary: &db::WeeklySummary, week_label: &str) {
+    let chart = db
+        .weekly_hours_for_chart(12)
         .ok()
-        .and_then(|s| s.parse().ok());
+        .and_then(|data| chart::render_chart(&anonymous::public_chart(db, data), chart::ChartMode::Totals).ok());
+    match reporting::render_pdf(summary, week_label, chart.as_deref(), &display::DisplayFormat::load(db)) {
+        Ok(pdf) => {
+            if let Some(first) = messages.first_mut() {
+                let attachment = CreateAttachment::bytes(pdf, reporting::pdf_filename(week_label));
+                *first = std::mem::take(first).add_file(attachment);
+            }
+        }
+        Err(e) => eprintln!("[clock] PDF report failed: {e}"),
+    }
+}
 
+/// Attach the 12-week chart to the last appendix message, if there's enough history to
+/// draw one. Runs before archiving, like `attach_pdf_report`.
+fn attach_chart(db: &Db, appendix: &mut [CreateMessage]) {
+    let chart = db
+        .weekly_hours_for_chart(12)
+        .and_then(|data| chart::render_chart(&anonymous::public_chart(db, data), chart::ChartMode::Totals));
+    if let (Ok(png), Some(last)) = (chart, appendix.last_mut()) {
+        *last = std::mem::take(last).add_file(CreateAttachment::bytes(png, "weekly-hours.png"));
+    }
+}
+
+/// Post the appendix in a thread under the weekly report, or below it in the channel
+/// if the thread can't be created.
+async fn post_appendix(
+    http: &Http,
+    channel_id: ChannelId,
+    report: MessageId,
+    appendix: Vec<CreateMessage>,
+    week_label: &str,
+    fmt: &display::DisplayFormat,
+) {
+    let thread = CreateThread::new(commands::summary_thread_name(week_label, fmt))
+        .auto_archive_duration(AutoArchiveDuration::OneWeek);
+    let target = match channel_id.create_thread_from_message(http, report, thread).await {
+        Ok(thread) => thread.id,
+        Err(e) => {
+            eprintln!("[clock] Failed to create summary thread, posting details in the channel: {e}");
+            channel_id
+        }
+    };
+    for msg in appendix {
+        if let Err(e) = target.send_message(http, msg).await {
+            eprintln!("[clock] Failed to post summary details: {e}");
+            break;
+        }
+    }
+}
+
+/// Every Monday 00:00 Swiss time, for the week that just ended:
+/// 1. Build the weekly summary
+/// 2. Archive the week and clear completed sessions
+/// 3. Post the summary to SUMMARY_CHANNEL (after quiet hours, if configured)
+async fn weekly_reset_loop(db: &Arc<Db>, token: &str) {
+    use chrono::Duration;
+    use tokio::time::sleep;
+    use weeks::WeekId;
+
+    let summary_channel = summary_channel_id();
     let http = Arc::new(Http::new(token));
+    let mut first_run = true;
 
     loop {
-        let now = db::now_ch();
-
-        let days_until_monday = match now.weekday() {
-            Weekday::Mon if now.time().hour() == 0 && now.time().minute() < 1 => 0,
-            Weekday::Mon => 7,
-            Weekday::Tue => 6,
-            Weekday::Wed => 5,
-            Weekday::Thu => 4,
-            Weekday::Fri => 3,
-            Weekday::Sat => 2,
-            Weekday::Sun => 1,
+        let now = db.clock().now();
+
+        // A (re)start within the first minute after a reset still handles that reset
+        let current_start = WeekId::containing(now.date()).start();
+        let reset = if first_run && now - current_start < Duration::minutes(1) {
+            current_start
+        } else {
+            weeks::next_reset_after(now)
         };
+        first_run = false;
 
-        let next_monday = (now + Duration::days(days_until_monday))
-            .date()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
+        sleep(weeks::duration_until(reset, db.clock().now_utc())).await;
 
-        let wait_secs = (next_monday - now).num_seconds().max(1) as u64;
-        sleep(TokioDuration::from_secs(wait_secs)).await;
+        let week = weeks::week_closed_by(reset);
+        let week_label = week.label();
 
-        let week_label = db::swiss_week_label();
+        if health::already_archived(db, &week_label) {
+            println!("[clock] {week_label} already archived, skipping");
+            continue;
+        }
 
-        // Post weekly summary before archiving
-        if let Some(channel_id) = summary_channel {
-            match db.weekly_summary() {
-                Ok(summary) if summary.total_sessions > 0 => {
-                    let embeds = commands::build_weekly_summary_embeds(&summary, &week_label);
-                    let mut msg = CreateMessage::new();
-                    for embed in embeds {
-                        msg = msg.embed(embed);
-                    }
-                    if let Err(e) = channel_id.send_message(&http, msg).await {
-                        eprintln!("[clock] Failed to post summary: {e}");
-                    } else {
-                        println!("[clock] Posted weekly summary for {week_label}");
+        // Build the summary before archiving clears the week's aggregates
+        let mut messages = Vec::new();
+        let mut appendix = Vec::new();
+        let mut summary = None;
+        if summary_channel.is_some() || dashboard::enabled() || digest::enabled() || !mirror::notifiers().is_empty() {
+            match db.weekly_summary(week) {
+                Ok(s) if s.total_sessions > 0 => {
+                    if summary_channel.is_some() {
+                        let public = anonymous::public_summary(db, &s);
+                        let fmt = display::DisplayFormat::load(db);
+                        let sections = commands::report_sections(db);
+                        if config::is_on(db, config::SUMMARY_THREAD) {
+                            let (report, details) = commands::build_weekly_summary_thread(db, &public, &week_label, &fmt, &sections);
+                            messages = vec![report];
+                            appendix = details;
+                            attach_chart(db, &mut appendix);
+                        } else {
+                            messages = commands::build_weekly_summary_messages(db, &public, &week_label, &fmt, &sections);
+                        }
+                        if config::is_on(db, config::PDF_REPORT) {
+                            attach_pdf_report(db, &mut messages, &public, &week_label);
+                        }
                     }
+                    summary = Some(s);
                 }
                 Ok(_) => println!("[clock] No sessions to summarize for {week_label}"),
                 Err(e) => eprintln!("[clock] Summary query failed: {e}"),
             }
         }
 
+        // Challenges are decided on the same sessions, so also before archiving
+        if let Some(results) = challenges::settle(db, week, &display::DisplayFormat::load(db))
+            && summary_channel.is_some()
+        {
+            messages.push(results);
+        }
+
         // Archive and clear
-        match db.archive_week(&week_label) {
+        match health::archive_week(db, &week_label) {
             Ok(()) => println!("[clock] Archived {week_label}"),
             Err(e) => eprintln!("[clock] Archive failed: {e}"),
         }
 
-        sleep(TokioDuration::from_secs(120)).await;
+        if let Some(store) = storage::ObjectStore::from_env() {
+            match storage::backup(db, &store, week).await {
+                Ok(keys) => println!("[clock] Backed up {week_label} to {} object(s)", keys.len()),
+                Err(e) => eprintln!("[clock] Backup upload failed: {e}"),
+            }
+        }
+
+        if let (Some(summary), true) = (&summary, dashboard::enabled()) {
+            match dashboard::publish(db, &week_label, summary).await {
+                Ok(()) => println!("[clock] Published dashboard for {week_label}"),
+                Err(e) => eprintln!("[clock] Dashboard publish failed: {e}"),
+            }
+        }
+
+        // The reset itself runs on time; only posts and emails wait for quiet hours to end
+        let emailing = summary.is_some() && digest::enabled();
+        let mirroring = summary.is_some() && !mirror::notifiers().is_empty();
+        if ((summary_channel.is_some() && !messages.is_empty()) || emailing || mirroring)
+            && let Some(until) = quiet::quiet_until(db, db.clock().now())
+        {
+            println!("[clock] Quiet hours, sending summary for {week_label} at {until}");
+            sleep(weeks::duration_until(until, db.clock().now_utc())).await;
+        }
+
+        if let (Some(channel_id), false) = (summary_channel, messages.is_empty()) {
+            let mut posted = true;
+            let mut report = None;
+            for msg in messages {
+                match channel_id.send_message(&http, msg).await {
+                    Ok(sent) => {
+                        report.get_or_insert(sent.id);
+                    }
+                    Err(e) => {
+                        eprintln!("[clock] Failed to post summary: {e}");
+                        posted = false;
+                        break;
+                    }
+                }
+            }
+            if let (Some(report), false) = (report, appendix.is_empty()) {
+                post_appendix(&http, channel_id, report, appendix, &week_label, &display::DisplayFormat::load(db)).await;
+            }
+            if posted {
+                println!("[clock] Posted weekly summary for {week_label}");
+            }
+        }
+
+        if let Some(summary) = &summary {
+            let fmt = display::DisplayFormat::load(db);
+            mirror::send(&mirror::summary_text(&anonymous::public_summary(db, summary), &week_label, &fmt)).await;
+        }
+
+        if let (Some(summary), true) = (&summary, emailing) {
+            match digest::send_digests(db, &week_label, summary).await {
+                Ok(n) => println!("[clock] Emailed {n} weekly digest(s) for {week_label}"),
+                Err(e) => eprintln!("[clock] Digest emails failed: {e}"),
+            }
+        }
     }
 }
-
-use chrono::Timelike;
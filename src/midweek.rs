@@ -0,0 +1,126 @@
+use crate::config;
+use crate::db::Db;
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDateTime, NaiveTime, Weekday};
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often the schedule is checked.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+/// Metadata key holding the label of the week whose standings were posted.
+const POSTED_KEY: &str = "midweek_posted";
+
+/// When in the week the standings go out, Swiss local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    pub weekday: Weekday,
+    pub time: NaiveTime,
+}
+
+impl Schedule {
+    /// Parse `wed 12:00`, `Thursday 9` or `fri`, which means noon.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split_whitespace();
+        let weekday: Weekday = parts.next()?.parse().ok()?;
+        let time = match parts.next() {
+            Some(t) => NaiveTime::parse_from_str(t, "%H:%M")
+                .ok()
+                .or_else(|| t.parse::<u32>().ok().and_then(|h| NaiveTime::from_hms_opt(h, 0, 0)))?,
+            None => NaiveTime::from_hms_opt(12, 0, 0)?,
+        };
+        parts.next().is_none().then_some(Self { weekday, time })
+    }
+
+    /// When the standings of `week` go out.
+    pub fn time_in(&self, week: WeekId) -> NaiveDateTime {
+        (week.monday() + Duration::days(self.weekday.num_days_from_monday() as i64)).and_time(self.time)
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let day = format!("{:?}", self.weekday).to_lowercase();
+        write!(f, "{} {}", day, self.time.format("%H:%M"))
+    }
+}
+
+/// The configured `midweek-post` schedule, `None` while it's off.
+pub fn schedule(db: &Db) -> Option<Schedule> {
+    config::get(db, config::MIDWEEK_POST).and_then(|v| Schedule::parse(&v))
+}
+
+/// Whether this week's standings are due at `now`: on the scheduled day, from the
+/// scheduled time, and not posted yet. A bot that was down all that day skips the week.
+pub fn due(schedule: Schedule, now: NaiveDateTime, posted_week: Option<&str>) -> bool {
+    let week = WeekId::containing(now.date());
+    let at = schedule.time_in(week);
+    now >= at && now.date() == at.date() && posted_week != Some(week.label().as_str())
+}
+
+/// Post the week's standings so far to the summary channel at the `midweek-post` time.
+pub async fn standings_loop(db: Arc<Db>, http: Arc<Http>, channel_id: Option<ChannelId>) {
+    let Some(channel_id) = channel_id else {
+        return;
+    };
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let Some(schedule) = schedule(&db) else {
+            continue;
+        };
+        let now = db.clock().now();
+        let posted = db.metadata(POSTED_KEY).ok().flatten();
+        if !due(schedule, now, posted.as_deref()) || crate::quiet::is_quiet(&db, now) {
+            continue;
+        }
+        let embed = match crate::commands::midweek_standings_embed(&db, &DisplayFormat::load(&db)) {
+            Ok(embed) => embed,
+            Err(e) => {
+                eprintln!("[clock] Failed to load mid-week standings: {e}");
+                continue;
+            }
+        };
+        match channel_id.send_message(&http, CreateMessage::new().embed(embed)).await {
+            Ok(_) => println!("[clock] Posted mid-week standings"),
+            Err(e) => eprintln!("[clock] Failed to post mid-week standings: {e}"),
+        }
+        if let Err(e) = db.set_metadata(POSTED_KEY, &WeekId::containing(now.date()).label()) {
+            eprintln!("[clock] Failed to record mid-week standings: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        // 2026-03-16 is a Monday
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let wed_noon = Schedule { weekday: Weekday::Wed, time: NaiveTime::from_hms_opt(12, 0, 0).unwrap() };
+        assert_eq!(Schedule::parse("wed 12:00"), Some(wed_noon));
+        assert_eq!(Schedule::parse("Wednesday"), Some(wed_noon));
+        assert_eq!(Schedule::parse("thu 9").unwrap().to_string(), "thu 09:00");
+        assert_eq!(Schedule::parse("someday 12:00"), None);
+        assert_eq!(Schedule::parse("wed 25:00"), None);
+        assert_eq!(Schedule::parse("wed 12:00 extra"), None);
+    }
+
+    #[test]
+    fn test_due() {
+        let schedule = Schedule::parse("wed 12:00").unwrap();
+        let week = WeekId::containing(at(16, 0, 0).date()).label();
+        assert!(!due(schedule, at(18, 11, 55), None));
+        assert!(due(schedule, at(18, 12, 0), None));
+        assert!(due(schedule, at(18, 21, 0), Some("KW11/2026")));
+        assert!(!due(schedule, at(18, 12, 5), Some(week.as_str())));
+        // Missed the day entirely
+        assert!(!due(schedule, at(19, 9, 0), None));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::db::{Elapsed, WeeklySummary};
+use crate::display::DisplayFormat;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Another chat platform that clock events and weekly summaries are mirrored to.
+/// Implementations only describe the HTTP call; sending and error handling are shared.
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn request(&self, client: &reqwest::Client, text: &str) -> reqwest::RequestBuilder;
+}
+
+/// Telegram Bot API, from `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`.
+pub struct Telegram {
+    token: String,
+    chat_id: String,
+}
+
+impl Telegram {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            token: env_var("TELEGRAM_BOT_TOKEN")?,
+            chat_id: env_var("TELEGRAM_CHAT_ID")?,
+        })
+    }
+}
+
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "Telegram"
+    }
+
+    fn request(&self, client: &reqwest::Client, text: &str) -> reqwest::RequestBuilder {
+        let body = serde_json::json!({ "chat_id": self.chat_id, "text": text });
+        client
+            .post(format!("https://api.telegram.org/bot{}/sendMessage", self.token))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+    }
+}
+
+/// Matrix client-server API, from `MATRIX_HOMESERVER`, `MATRIX_ACCESS_TOKEN` and
+/// `MATRIX_ROOM_ID`. The account must already have joined the room.
+pub struct Matrix {
+    homeserver: String,
+    access_token: String,
+    room_id: String,
+}
+
+impl Matrix {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            homeserver: env_var("MATRIX_HOMESERVER")?.trim_end_matches('/').to_string(),
+            access_token: env_var("MATRIX_ACCESS_TOKEN")?,
+            room_id: env_var("MATRIX_ROOM_ID")?,
+        })
+    }
+}
+
+/// Matrix deduplicates sends by transaction id, so each message needs a fresh one.
+fn transaction_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "clock-{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Percent-encode a room id (`!abc:example.org`) for use in a URL path.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl Notifier for Matrix {
+    fn name(&self) -> &'static str {
+        "Matrix"
+    }
+
+    fn request(&self, client: &reqwest::Client, text: &str) -> reqwest::RequestBuilder {
+        let body = serde_json::json!({ "msgtype": "m.text", "body": text });
+        client
+            .put(format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                self.homeserver,
+                encode_path_segment(&self.room_id),
+                transaction_id()
+            ))
+            .bearer_auth(&self.access_token)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|s| !s.is_empty())
+}
+
+/// Every platform configured in the environment.
+pub fn notifiers() -> Vec<Box<dyn Notifier>> {
+    let mut all: Vec<Box<dyn Notifier>> = Vec::new();
+    if let Some(telegram) = Telegram::from_env() {
+        all.push(Box::new(telegram));
+    }
+    if let Some(matrix) = Matrix::from_env() {
+        all.push(Box::new(matrix));
+    }
+    all
+}
+
+/// Send `text` to every configured platform. Failures are logged, never surfaced.
+pub async fn send(text: &str) {
+    let notifiers = notifiers();
+    if notifiers.is_empty() {
+        return;
+    }
+    let client = crate::calendar::http_client();
+    for notifier in &notifiers {
+        let result = notifier.request(&client, text).send().await.and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            eprintln!("[clock] Failed to mirror to {}: {e}", notifier.name());
+        }
+    }
+}
+
+/// Mirror in the background so slow platforms never delay a Discord reply.
+pub fn spawn(text: String) {
+    if notifiers().is_empty() {
+        return;
+    }
+    tokio::spawn(async move { send(&text).await });
+}
+
+pub fn clock_in_text(username: &str, activity: &str) -> String {
+    format!("🟢 {} clocked in — {}", username, activity)
+}
+
+pub fn clock_out_text(username: &str, activity: &str, elapsed: Elapsed, fmt: &DisplayFormat) -> String {
+    format!("🔴 {} clocked out — {} ({})", username, activity, fmt.elapsed(elapsed))
+}
+
+/// Plain-text weekly report: totals, awards and the top five people.
+pub fn summary_text(summary: &WeeklySummary, week_label: &str, fmt: &DisplayFormat) -> String {
+    let mut lines = vec![
+        format!("📊 Weekly Report — {}", fmt.week(week_label)),
+        format!(
+            "{} total · {} sessions · {} people",
+            fmt.duration(summary.total_minutes),
+            summary.total_sessions,
+            summary.unique_workers
+        ),
+    ];
+    if let Some((ref name, mins)) = summary.mvp {
+        lines.push(format!("🏅 MVP — {} with {}", name, fmt.duration(mins)));
+    }
+    if let Some((ref activity, mins)) = summary.top_activity {
+        lines.push(format!("🔥 Hot Topic — {} ({})", activity, fmt.duration(mins)));
+    }
+    if let Some((ref name, ref activity, mins)) = summary.longest_session {
+        lines.push(format!("🏋️ Marathon — {} on {} ({})", name, activity, fmt.duration(mins)));
+    }
+
+    let users = crate::dashboard::ranking(summary);
+    for (i, (name, minutes)) in users.iter().take(5).enumerate() {
+        if i == 0 {
+            lines.push(String::new());
+        }
+        lines.push(format!("{}. {} — {}", i + 1, name, fmt.duration(*minutes)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ActivityEntry;
+
+    #[test]
+    fn test_matrix_request_encodes_room_and_varies_txn() {
+        let matrix = Matrix {
+            homeserver: "https://matrix.example.org".to_string(),
+            access_token: "secret".to_string(),
+            room_id: "!abc123:example.org".to_string(),
+        };
+        let client = reqwest::Client::new();
+        let first = matrix.request(&client, "hi").build().unwrap();
+        let second = matrix.request(&client, "hi").build().unwrap();
+        assert_eq!(first.method(), reqwest::Method::PUT);
+        assert!(first
+            .url()
+            .as_str()
+            .starts_with("https://matrix.example.org/_matrix/client/v3/rooms/%21abc123%3Aexample.org/send/m.room.message/clock-"));
+        assert_ne!(first.url(), second.url());
+        assert_eq!(first.headers()["authorization"], "Bearer secret");
+    }
+
+    #[test]
+    fn test_telegram_request() {
+        let telegram = Telegram { token: "123:abc".to_string(), chat_id: "-10042".to_string() };
+        let request = telegram.request(&reqwest::Client::new(), "hi").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.telegram.org/bot123:abc/sendMessage");
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body, serde_json::json!({ "chat_id": "-10042", "text": "hi" }));
+    }
+
+    #[test]
+    fn test_texts() {
+        assert_eq!(clock_out_text("Alice", "backend", Elapsed::from_seconds(7500), &DisplayFormat::default()), "🔴 Alice clocked out — backend (2h 5m)");
+        assert_eq!(clock_out_text("Alice", "backend", Elapsed::from_seconds(45), &DisplayFormat::default()), "🔴 Alice clocked out — backend (45s)");
+        let entry = |username: &str, minutes| ActivityEntry {
+            username: username.to_string(),
+            activity: "backend".to_string(),
+            total_minutes: minutes,
+            session_count: 1,
+        };
+        let summary = WeeklySummary {
+            total_minutes: 180,
+            total_sessions: 3,
+            unique_workers: 2,
+            mvp: Some(("Bob".to_string(), 120)),
+            top_activity: None,
+            longest_session: None,
+            most_appreciated: None,
+            breakdown: vec![entry("Alice", 60), entry("Bob", 90), entry("Bob", 30)],
+            holidays: Vec::new(),
+            daily_minutes: [0; 7],
+            mvp_daily_minutes: [0; 7],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        };
+        assert_eq!(
+            summary_text(&summary, "KW07/2026", &DisplayFormat::default()),
+            "📊 Weekly Report — KW07/2026\n3h 0m total · 3 sessions · 2 people\n🏅 MVP — Bob with 2h 0m\n\n1. Bob — 2h 0m\n2. Alice — 1h 0m"
+        );
+    }
+}
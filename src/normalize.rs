@@ -4,35 +4,152 @@ use once_cell::sync::Lazy;
 static RE_SPACES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
 static RE_HYPHENS: Lazy<Regex> = Lazy::new(|| Regex::new(r"-+").unwrap());
 
+/// Version of `normalize_activity`. Bump it whenever the rules change, so stored names
+/// are normalized again on the next start (see `Db::normalize_activities`).
+/// 2: repetition is collapsed again after lowercasing; only letters with a lowercase form split camelCase.
+pub const NORMALIZATION_VERSION: u32 = 2;
+
 /// Normalize an activity name:
 /// 1. Collapse excessive character repetition:
 ///    - Exactly 3 consecutive identical characters → keep 2
 ///    - 4+ consecutive identical characters → keep 1
 /// 2. Split PascalCase/camelCase into hyphenated lowercase (e.g., "WorkSchool" → "work-school")
 /// 3. Lowercase everything
+/// 4. Collapse repetition again, so the result is a fixed point
 pub fn normalize_activity(raw: &str) -> String {
-    let trimmed = raw.trim();
+    normalize_with_steps(raw).0
+}
+
+/// The steps of `normalize_activity` that change `raw`, each with the text after it,
+/// for `/clock normalize preview`.
+pub fn normalization_steps(raw: &str) -> Vec<(&'static str, String)> {
+    normalize_with_steps(raw).1
+}
+
+fn normalize_with_steps(raw: &str) -> (String, Vec<(&'static str, String)>) {
+    let mut steps = Vec::new();
+    let mut record = |step: &'static str, before: &str, after: String| {
+        if after != before {
+            steps.push((step, after.clone()));
+        }
+        after
+    };
+
+    let trimmed = record("trim", raw, raw.trim().to_string());
     if trimmed.is_empty() {
-        return String::new();
+        return (String::new(), steps);
     }
 
     // Step 1: Collapse 3+ consecutive identical characters to 1
-    let collapsed = collapse_repeated_chars(trimmed);
+    let collapsed = record("collapse repeated characters", &trimmed, collapse_repeated_chars(&trimmed));
 
     // Step 2: Detect and split PascalCase/camelCase boundaries with hyphens
-    let hyphenated = split_camel_case(&collapsed);
+    let hyphenated = record("split camelCase", &collapsed, split_camel_case(&collapsed));
 
     // Step 3: Lowercase and normalize whitespace/hyphens
-    let lowercased = hyphenated.to_lowercase();
-    
-    // Normalize multiple spaces to single space
+    let lowercased = record("lowercase", &hyphenated, hyphenated.to_lowercase());
+
+    // Multiple spaces to a single space, multiple hyphens to a single hyphen, and no
+    // leading/trailing spaces or hyphens
     let normalized_spaces = RE_SPACES.replace_all(&lowercased, " ");
-    
-    // Normalize multiple hyphens to single hyphen
     let normalized_hyphens = RE_HYPHENS.replace_all(&normalized_spaces, "-");
-    
-    // Trim any leading/trailing spaces or hyphens
-    normalized_hyphens.trim_matches(|c| c == ' ' || c == '-').to_string()
+    let tidied = normalized_hyphens.trim_matches(|c| c == ' ' || c == '-').to_string();
+    let tidied = record("tidy spaces and hyphens", &lowercased, tidied);
+
+    // Step 4: Lowercasing can line up new repeats ("Bbb" → "bbb"), so collapse
+    // once more to keep normalizing an already normalized name a no-op
+    let result = record("collapse repeated characters again", &tidied, collapse_repeated_chars(&tidied));
+    (result, steps)
+}
+
+/// Score how well an activity name matches a search term (lower is better).
+/// Substring matches score 0; otherwise the edit distance to the closest
+/// hyphen/space-separated word (or the whole name) is used, accepting up to
+/// one typo per three characters of the term. Returns `None` for no match.
+pub fn search_score(term: &str, activity: &str) -> Option<usize> {
+    let term = normalize_activity(term);
+    if term.is_empty() {
+        return None;
+    }
+    if activity.contains(&term) {
+        return Some(0);
+    }
+
+    let max_distance = (term.chars().count() / 3).max(1);
+    activity
+        .split(['-', ' '])
+        .chain(std::iter::once(activity))
+        .map(|word| levenshtein(&term, word))
+        .min()
+        .filter(|&d| d <= max_distance)
+}
+
+/// Whether two activity names look like spellings of the same thing: ignoring
+/// hyphens and spaces they are at most one edit per four characters apart.
+/// Names shorter than four characters never match, so `qa` and `ux` stay apart.
+pub fn similar_names(a: &str, b: &str) -> bool {
+    let compact = |s: &str| s.replace(['-', ' '], "");
+    let (a, b) = (compact(a), compact(b));
+    let shorter = a.chars().count().min(b.chars().count());
+    shorter >= 4 && levenshtein(&a, &b) <= shorter / 4
+}
+
+/// Group activity names that are similar to each other, directly or through a
+/// chain of similar names. `names` should be ordered by preference (e.g. most time
+/// logged first); each group keeps that order, so its first name is the one to keep.
+/// Only groups with at least two names are returned.
+pub fn cluster_activities(names: &[String]) -> Vec<Vec<String>> {
+    // Union-find over the indices of `names`
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut parent: Vec<usize> = (0..names.len()).collect();
+    for i in 0..names.len() {
+        for j in i + 1..names.len() {
+            if similar_names(&names[i], &names[j]) {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                // The earlier (preferred) name stays the root
+                parent[ri.max(rj)] = ri.min(rj);
+            }
+        }
+    }
+
+    let mut clusters: Vec<(usize, Vec<String>)> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let r = root(&mut parent, i);
+        match clusters.iter_mut().find(|(root, _)| *root == r) {
+            Some((_, members)) => members.push(name.clone()),
+            None => clusters.push((r, vec![name.clone()])),
+        }
+    }
+    clusters
+        .into_iter()
+        .map(|(_, members)| members)
+        .filter(|members| members.len() > 1)
+        .collect()
+}
+
+/// Classic Levenshtein edit distance over chars.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
 }
 
 /// Collapse 3+ consecutive identical characters
@@ -73,6 +190,12 @@ fn collapse_repeated_chars(s: &str) -> String {
     result
 }
 
+/// Uppercase letters that lowercasing changes. Symbols like `𝚨` count as uppercase
+/// but stay as they are, so they can't start a new word or the split wouldn't be stable.
+fn has_lowercase(c: char) -> bool {
+    c.is_uppercase() && c.to_lowercase().ne(std::iter::once(c))
+}
+
 /// Split camelCase/PascalCase into hyphenated words
 fn split_camel_case(s: &str) -> String {
     let mut result = String::with_capacity(s.len() + 10);
@@ -84,7 +207,7 @@ fn split_camel_case(s: &str) -> String {
         // Insert hyphen before uppercase letter if:
         // 1. Previous char is lowercase (e.g., "workSchool" -> "work-School")
         // 2. Previous char is uppercase and next char is lowercase (e.g., "MyApp" -> "My-App")
-        if i > 0 && current.is_uppercase() {
+        if i > 0 && has_lowercase(current) {
             let prev = chars[i - 1];
             let next = if i + 1 < chars.len() {
                 Some(chars[i + 1])
@@ -93,11 +216,8 @@ fn split_camel_case(s: &str) -> String {
             };
 
             // Case 1: lowercase followed by uppercase
-            if prev.is_lowercase() {
-                result.push('-');
-            }
             // Case 2: uppercase followed by uppercase then lowercase (e.g., "MyApp" -> "My-App")
-            else if prev.is_uppercase() && next.map_or(false, |n| n.is_lowercase()) {
+            if prev.is_lowercase() || (has_lowercase(prev) && next.is_some_and(|n| n.is_lowercase())) {
                 result.push('-');
             }
         }
@@ -111,6 +231,22 @@ fn split_camel_case(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_normalization_steps() {
+        assert_eq!(
+            normalization_steps("  Meeeeting--Notes "),
+            vec![
+                ("trim", "Meeeeting--Notes".to_string()),
+                ("collapse repeated characters", "Meting--Notes".to_string()),
+                ("lowercase", "meting--notes".to_string()),
+                ("tidy spaces and hyphens", "meting-notes".to_string()),
+            ]
+        );
+        assert!(normalization_steps("backend").is_empty());
+        assert_eq!(normalization_steps("Bbb").last().unwrap(), &("collapse repeated characters again", "bb".to_string()));
+    }
 
     #[test]
     fn test_collapse_repeated_chars() {
@@ -151,6 +287,51 @@ mod tests {
         assert_eq!(normalize_activity("work-School"), "work-school");
     }
 
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("work", "work"), 0);
+        assert_eq!(levenshtein("wrok", "work"), 2);
+        assert_eq!(levenshtein("backend", "backed"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_search_score() {
+        assert_eq!(search_score("end", "backend"), Some(0));
+        assert_eq!(search_score("BACKEND", "backend"), Some(0));
+        assert_eq!(search_score("BackEnd", "backend"), Some(1)); // "back-end"
+        assert_eq!(search_score("meetng", "team-meeting"), Some(1));
+        assert_eq!(search_score("school", "work-school"), Some(0));
+        assert_eq!(search_score("gaming", "backend"), None);
+        assert_eq!(search_score("   ", "backend"), None);
+    }
+
+    #[test]
+    fn test_cluster_activities() {
+        let names: Vec<String> = ["backend", "code-review", "back-end", "codereview", "backnd", "qa", "ux", "docs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            cluster_activities(&names),
+            vec![
+                vec!["backend".to_string(), "back-end".to_string(), "backnd".to_string()],
+                vec!["code-review".to_string(), "codereview".to_string()],
+            ]
+        );
+        assert!(!similar_names("docs", "ops"));
+        assert!(!similar_names("frontend", "backend"));
+    }
+
+    #[test]
+    fn test_normalization_version() {
+        // Names the version 2 rules normalize differently; when rules change, bump the
+        // version and add the names they change here
+        assert_eq!(NORMALIZATION_VERSION, 2);
+        assert_eq!(normalize_activity("Bbb"), "bb");
+        assert_eq!(normalize_activity("a𝚨b"), "a𝚨b");
+    }
+
     #[test]
     fn test_edge_cases() {
         assert_eq!(normalize_activity(""), "");
@@ -160,5 +341,29 @@ mod tests {
         assert_eq!(normalize_activity("aaa"), "aa");  // 3 consecutive → 2
         assert_eq!(normalize_activity("aabbcc"), "aabbcc");  // all doubles, no change
         assert_eq!(normalize_activity("aaabbbccc"), "aabbcc");  // 3 of each → 2 of each
+        assert_eq!(normalize_activity("Bbb"), "bb");  // repeats that only line up once lowercased
+    }
+
+    proptest! {
+        #[test]
+        fn prop_normalize_is_idempotent(raw in "[aAbBäÄ0 _-]{0,24}|\\PC{0,24}") {
+            let once = normalize_activity(&raw);
+            prop_assert_eq!(normalize_activity(&once), once);
+        }
+
+        #[test]
+        fn prop_normalize_output_is_clean(raw in "\\PC{0,24}") {
+            let out = normalize_activity(&raw);
+            prop_assert!(!out.starts_with([' ', '-']) && !out.ends_with([' ', '-']));
+            prop_assert!(!out.contains("  ") && !out.contains("--"));
+            prop_assert_eq!(out.to_lowercase(), out.clone());
+        }
+
+        #[test]
+        fn prop_normalize_keeps_meaningful_input(raw in "\\PC{0,24}") {
+            if raw.chars().any(char::is_alphanumeric) {
+                prop_assert!(!normalize_activity(&raw).is_empty());
+            }
+        }
     }
 }
@@ -0,0 +1,179 @@
+use crate::db::{Db, UserSettings};
+use crate::display::DisplayFormat;
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use chrono_tz::{Europe::Zurich, Tz};
+use serenity::all::*;
+use std::env;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often goals are checked against the clock.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+const DEFAULT_NUDGE_HOUR: u32 = 18;
+
+/// Local hour (0–23) from which users below their daily goal get nudged, from `NUDGE_HOUR`.
+pub fn nudge_hour() -> u32 {
+    env::var("NUDGE_HOUR")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|h| *h < 24)
+        .unwrap_or(DEFAULT_NUDGE_HOUR)
+}
+
+/// The user's timezone, falling back to Swiss time for names that no longer parse.
+pub fn user_timezone(settings: &UserSettings) -> Tz {
+    settings.timezone.parse().unwrap_or(Zurich)
+}
+
+/// Swiss local bounds of the calendar day `date` in `tz`.
+fn day_bounds_ch(tz: Tz, date: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+    let start = |d: NaiveDate| {
+        let midnight = d.and_hms_opt(0, 0, 0).unwrap();
+        // A few zones skip midnight on DST days; the day then starts an hour later
+        tz.from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+            .map(|t| t.with_timezone(&Zurich).naive_local())
+            .unwrap_or(midnight)
+    };
+    (start(date), start(date + Duration::days(1)))
+}
+
+/// Minutes `settings.user_id` has logged so far on their local calendar day.
+pub fn worked_minutes_today(db: &Db, settings: &UserSettings) -> anyhow::Result<i64> {
+    let tz = user_timezone(settings);
+    let today = db.clock().now_utc().with_timezone(&tz).date_naive();
+    let (from, to) = day_bounds_ch(tz, today);
+    let sessions = db.sessions_between(&settings.user_id, from, to)?;
+    Ok(sessions
+        .iter()
+        .map(|s| (s.ended_at.min(to) - s.started_at.max(from)).num_minutes().max(0))
+        .sum())
+}
+
+/// Whether the nudge window is open at `local_now` and today's nudge has not gone out yet.
+fn due(settings: &UserSettings, local_now: NaiveDateTime, hour: u32) -> bool {
+    local_now.hour() >= hour && settings.last_nudged != Some(local_now.date())
+}
+
+/// DM users who are below their daily goal by the evening, at most once per local day.
+pub async fn nudge_loop(db: Arc<Db>, http: Arc<Http>) {
+    let hour = nudge_hour();
+    loop {
+        // Skipped nudges aren't marked, so they go out once the window closes (same day)
+        if crate::quiet::is_quiet(&db, db.clock().now()) {
+            sleep(CHECK_INTERVAL).await;
+            continue;
+        }
+        match db.nudge_candidates() {
+            Ok(candidates) => {
+                for settings in &candidates {
+                    check_user(&http, &db, settings, hour).await;
+                }
+            }
+            Err(e) => eprintln!("[clock] Nudge query failed: {e}"),
+        }
+        sleep(CHECK_INTERVAL).await;
+    }
+}
+
+async fn check_user(http: &Arc<Http>, db: &Db, settings: &UserSettings, hour: u32) {
+    let Some(goal) = settings.daily_goal_min else {
+        return;
+    };
+    let local_now = db.clock().now_utc().with_timezone(&user_timezone(settings)).naive_local();
+    if !due(settings, local_now, hour) {
+        return;
+    }
+    // Someone clocked in right now doesn't need reminding, nor does anyone on a holiday
+    if matches!(db.active_session(&settings.user_id), Ok(Some(_)))
+        || db.is_holiday(local_now.date()).unwrap_or(false)
+    {
+        return;
+    }
+    let worked = match worked_minutes_today(db, settings) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("[clock] Nudge progress query failed: {e}");
+            return;
+        }
+    };
+    if worked >= goal {
+        return;
+    }
+    let Ok(user_id) = settings.user_id.parse::<UserId>() else {
+        return;
+    };
+
+    let fmt = DisplayFormat::load(db);
+    let embed = CreateEmbed::new()
+        .color(0xe67e22)
+        .title("⏰ Daily goal check")
+        .description(format!(
+            "You've logged **{}** of your **{}** goal today.\nStill time for a session? `/clock in <activity>`",
+            fmt.duration(worked),
+            fmt.duration(goal)
+        ))
+        .footer(CreateEmbedFooter::new("/clock nudge off to stop these"));
+    if let Err(e) = user_id
+        .direct_message(http, CreateMessage::new().embed(embed))
+        .await
+    {
+        // Closed DMs fail every time; still count the day so we don't retry every few minutes
+        eprintln!("[clock] Failed to DM nudge to {}: {e}", settings.user_id);
+    }
+    if let Err(e) = db.mark_nudged(&settings.user_id, local_now.date()) {
+        eprintln!("[clock] Failed to record nudge: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(last_nudged: Option<NaiveDate>) -> UserSettings {
+        UserSettings {
+            user_id: "u1".to_string(),
+            daily_goal_min: Some(240),
+            timezone: "Europe/Zurich".to_string(),
+            nudges: true,
+            last_nudged,
+            tips: true,
+            last_tip: None,
+        }
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 2, d).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_due_once_per_day_after_hour() {
+        let today = at(10, 0, 0).date();
+        assert!(!due(&settings(None), at(10, 17, 59), 18));
+        assert!(due(&settings(None), at(10, 18, 0), 18));
+        assert!(due(&settings(None), at(10, 23, 30), 18));
+        assert!(!due(&settings(Some(today)), at(10, 19, 0), 18));
+        // Yesterday's nudge doesn't block today's
+        assert!(due(&settings(today.pred_opt()), at(10, 19, 0), 18));
+    }
+
+    #[test]
+    fn test_day_bounds_in_other_timezones() {
+        let day = at(10, 0, 0).date();
+        assert_eq!(day_bounds_ch(Zurich, day), (at(10, 0, 0), at(11, 0, 0)));
+        // New York is six hours behind Zurich in February
+        let ny: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(day_bounds_ch(ny, day), (at(10, 6, 0), at(11, 6, 0)));
+        // Tokyo is eight hours ahead
+        let tokyo: Tz = "Asia/Tokyo".parse().unwrap();
+        assert_eq!(day_bounds_ch(tokyo, day), (at(9, 16, 0), at(10, 16, 0)));
+    }
+
+    #[test]
+    fn test_user_timezone_falls_back_to_zurich() {
+        let mut s = settings(None);
+        s.timezone = "Mars/Olympus_Mons".to_string();
+        assert_eq!(user_timezone(&s), Zurich);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::db::{Db, TimelineSession};
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+/// Minutes per day of `week` (Monday first), splitting sessions at midnight.
+pub fn daily_minutes(sessions: &[TimelineSession], week: WeekId) -> [i64; 7] {
+    let mut days = [0; 7];
+    for (i, day) in days.iter_mut().enumerate() {
+        let from = week.start() + Duration::days(i as i64);
+        let to = from + Duration::days(1);
+        *day = sessions
+            .iter()
+            .map(|s| (s.ended_at.min(to) - s.started_at.max(from)).num_minutes().max(0))
+            .sum();
+    }
+    days
+}
+
+/// Working days (Monday–Friday, minus `holidays`) of the week containing `now`:
+/// `(elapsed, total)`, where today counts with the share of it that has passed.
+pub fn working_days(now: NaiveDateTime, holidays: &[NaiveDate]) -> (f64, f64) {
+    let week = WeekId::containing(now.date());
+    let mut elapsed = 0.0;
+    let mut total = 0.0;
+    for i in 0..5 {
+        let day = week.monday() + Duration::days(i);
+        if holidays.contains(&day) {
+            continue;
+        }
+        total += 1.0;
+        if day < now.date() {
+            elapsed += 1.0;
+        } else if day == now.date() {
+            elapsed += (now - day.and_hms_opt(0, 0, 0).unwrap()).num_minutes() as f64 / (24.0 * 60.0);
+        }
+    }
+    (elapsed, total)
+}
+
+/// End-of-week total if the average per elapsed working day holds for the rest
+/// of the week. `None` before any working time has passed.
+pub fn project(so_far: i64, elapsed: f64, total: f64) -> Option<i64> {
+    if elapsed < 0.25 {
+        return None;
+    }
+    let remaining = (total - elapsed).max(0.0);
+    Some(so_far + (so_far as f64 / elapsed * remaining).round() as i64)
+}
+
+/// `📈 3h 0m so far · on pace for ~15h 0m · goal 20h 0m (5h 0m short) · last week 12h 0m (▲3h 0m)`
+pub fn pace_line(so_far: i64, projected: Option<i64>, goal: Option<i64>, last_week: Option<i64>, fmt: &DisplayFormat) -> String {
+    let mut parts = vec![format!("📈 {} so far", fmt.duration(so_far))];
+    let Some(projected) = projected else {
+        parts.push("too early to project".to_string());
+        return parts.join(" · ");
+    };
+    parts.push(format!("on pace for ~{}", fmt.duration(projected)));
+    if let Some(goal) = goal {
+        let status = if projected >= goal {
+            "on track".to_string()
+        } else {
+            format!("{} short", fmt.duration(goal - projected))
+        };
+        parts.push(format!("goal {} ({})", fmt.duration(goal), status));
+    }
+    if let Some(last) = last_week {
+        let diff = projected - last;
+        let arrow = if diff >= 0 { "▲" } else { "▼" };
+        parts.push(format!("last week {} ({}{})", fmt.duration(last), arrow, fmt.duration(diff.abs())));
+    }
+    parts.join(" · ")
+}
+
+fn holidays(db: &Db, week: WeekId) -> Vec<NaiveDate> {
+    db.holidays_between(week.monday(), week.monday() + Duration::days(4))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(day, _)| day)
+        .collect()
+}
+
+/// A daily goal over the working days of the week containing `now`.
+pub fn week_goal(db: &Db, daily_goal: i64, now: NaiveDateTime) -> i64 {
+    let (_, total) = working_days(now, &holidays(db, WeekId::containing(now.date())));
+    daily_goal * total as i64
+}
+
+/// Pace of `user_id` this week, compared with their daily goal on every working day
+/// and with last week's total.
+pub fn user_pace(db: &Db, user_id: &str, now: NaiveDateTime, fmt: &DisplayFormat) -> String {
+    let week = WeekId::containing(now.date());
+    let sessions = db.sessions_between(user_id, week.start(), now).unwrap_or_default();
+    let so_far: i64 = daily_minutes(&sessions, week).iter().sum();
+    let (elapsed, total) = working_days(now, &holidays(db, week));
+    let goal = db
+        .user_settings(user_id)
+        .ok()
+        .and_then(|s| s.daily_goal_min)
+        .map(|daily| week_goal(db, daily, now));
+    let last_week = db
+        .archived_week_total(Some(user_id), &week.previous().label())
+        .ok()
+        .flatten();
+    pace_line(so_far, project(so_far, elapsed, total), goal, last_week, fmt)
+}
+
+/// Pace of the whole server this week, `so_far` being everyone's minutes.
+pub fn server_pace(db: &Db, so_far: i64, now: NaiveDateTime, fmt: &DisplayFormat) -> String {
+    let week = WeekId::containing(now.date());
+    let (elapsed, total) = working_days(now, &holidays(db, week));
+    let last_week = db.archived_week_total(None, &week.previous().label()).ok().flatten();
+    pace_line(so_far, project(so_far, elapsed, total), None, last_week, fmt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(d: u32, h: u32) -> NaiveDateTime {
+        // 2026-03-16 is a Monday
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(h, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_daily_minutes_split_at_midnight() {
+        let week = WeekId::containing(at(16, 0).date());
+        let sessions = vec![
+            TimelineSession { activity: "a".into(), started_at: at(16, 9), ended_at: at(16, 11) },
+            TimelineSession { activity: "b".into(), started_at: at(17, 23), ended_at: at(18, 1) },
+        ];
+        assert_eq!(daily_minutes(&sessions, week), [120, 60, 60, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_working_days_and_projection() {
+        // Wednesday noon: Monday and Tuesday plus half of Wednesday
+        assert_eq!(working_days(at(18, 12), &[]), (2.5, 5.0));
+        // Good Friday-style holiday shortens the week
+        assert_eq!(working_days(at(18, 12), &[at(20, 0).date()]), (2.5, 4.0));
+        // The weekend has no working time left
+        assert_eq!(working_days(at(21, 12), &[]), (5.0, 5.0));
+
+        assert_eq!(project(600, 2.5, 5.0), Some(1200));
+        assert_eq!(project(600, 5.0, 5.0), Some(600));
+        assert_eq!(project(30, 0.1, 5.0), None);
+    }
+
+    #[test]
+    fn test_pace_line() {
+        let fmt = DisplayFormat::default();
+        assert_eq!(
+            pace_line(600, Some(1200), Some(1500), Some(1260), &fmt),
+            "📈 10h 0m so far · on pace for ~20h 0m · goal 25h 0m (5h 0m short) · last week 21h 0m (▼1h 0m)"
+        );
+        assert_eq!(pace_line(600, Some(1200), Some(1200), None, &fmt), "📈 10h 0m so far · on pace for ~20h 0m · goal 20h 0m (on track)");
+        assert_eq!(pace_line(5, None, Some(1200), Some(60), &fmt), "📈 5m so far · too early to project");
+    }
+}
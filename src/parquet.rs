@@ -0,0 +1,376 @@
+//! Minimal Parquet writer for analytics exports: one row group, required columns,
+//! PLAIN encoding, uncompressed. Enough for DuckDB, pandas and Spark to read the
+//! history without a Parquet dependency in the bot.
+
+use crate::db::Db;
+use crate::weeks::WeekId;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// Values of one column, all rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    Int(Vec<i64>),
+    Text(Vec<String>),
+    Date(Vec<NaiveDate>),
+    /// Swiss local times, stored as milliseconds and marked as not adjusted to UTC.
+    Timestamp(Vec<NaiveDateTime>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Int(v) => v.len(),
+            Values::Text(v) => v.len(),
+            Values::Date(v) => v.len(),
+            Values::Timestamp(v) => v.len(),
+        }
+    }
+
+    /// Parquet physical type: INT32, INT64 or BYTE_ARRAY.
+    fn physical_type(&self) -> i32 {
+        match self {
+            Values::Date(_) => 1,
+            Values::Int(_) | Values::Timestamp(_) => 2,
+            Values::Text(_) => 6,
+        }
+    }
+
+    fn plain(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Values::Int(v) => v.iter().for_each(|n| out.extend(n.to_le_bytes())),
+            Values::Text(v) => v.iter().for_each(|s| {
+                out.extend((s.len() as u32).to_le_bytes());
+                out.extend(s.as_bytes());
+            }),
+            Values::Date(v) => v.iter().for_each(|d| {
+                let days = (*d - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() as i32;
+                out.extend(days.to_le_bytes());
+            }),
+            Values::Timestamp(v) => v.iter().for_each(|t| out.extend(t.and_utc().timestamp_millis().to_le_bytes())),
+        }
+        out
+    }
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub values: Values,
+}
+
+impl Column {
+    pub fn new(name: &'static str, values: Values) -> Self {
+        Self { name, values }
+    }
+}
+
+/// Thrift compact protocol, as used by the Parquet footer and page headers.
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    /// Last field id of each open struct.
+    last: Vec<i16>,
+}
+
+const T_TRUE: u8 = 1;
+const T_FALSE: u8 = 2;
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+impl Compact {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.out.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.out.push(n as u8);
+    }
+
+    fn zigzag(&mut self, n: i64) {
+        self.varint(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self.last.last_mut().expect("a field outside a struct");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.out.push(((delta as u8) << 4) | kind);
+        } else {
+            self.out.push(kind);
+            self.zigzag(id as i64);
+        }
+    }
+
+    fn begin(&mut self) {
+        self.last.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.field(id, T_I32);
+        self.zigzag(n as i64);
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.field(id, T_I64);
+        self.zigzag(n);
+    }
+
+    fn bool(&mut self, id: i16, b: bool) {
+        self.field(id, if b { T_TRUE } else { T_FALSE });
+    }
+
+    fn binary(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.out.extend(bytes);
+    }
+
+    fn string(&mut self, id: i16, s: &str) {
+        self.field(id, T_BINARY);
+        self.binary(s.as_bytes());
+    }
+
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.begin();
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.out.push(((len as u8) << 4) | kind);
+        } else {
+            self.out.push(0xF0 | kind);
+            self.varint(len as u64);
+        }
+    }
+}
+
+/// Where a written column chunk ended up.
+struct Chunk {
+    offset: i64,
+    size: i64,
+}
+
+/// A Parquet file with `columns` as its only row group. All columns must have the same
+/// number of rows.
+pub fn write(columns: &[Column]) -> anyhow::Result<Vec<u8>> {
+    let rows = columns.first().map_or(0, |c| c.values.len());
+    if let Some(c) = columns.iter().find(|c| c.values.len() != rows) {
+        anyhow::bail!("column {} has {} rows, expected {rows}", c.name, c.values.len());
+    }
+
+    let mut file = MAGIC.to_vec();
+    let mut chunks = Vec::new();
+    for column in columns {
+        let data = column.values.plain();
+        let mut header = Compact::default();
+        header.begin();
+        header.i32(1, 0); // DATA_PAGE
+        header.i32(2, data.len() as i32);
+        header.i32(3, data.len() as i32);
+        header.struct_field(5);
+        header.i32(1, rows as i32);
+        header.i32(2, 0); // PLAIN
+        header.i32(3, 3); // RLE, unused for required columns
+        header.i32(4, 3);
+        header.end();
+        header.end();
+
+        let offset = file.len() as i64;
+        file.extend(&header.out);
+        file.extend(&data);
+        chunks.push(Chunk { offset, size: file.len() as i64 - offset });
+    }
+
+    let mut meta = Compact::default();
+    meta.begin();
+    meta.i32(1, 1);
+    meta.list(2, T_STRUCT, columns.len() + 1);
+    meta.begin();
+    meta.string(4, "schema");
+    meta.i32(5, columns.len() as i32);
+    meta.end();
+    for column in columns {
+        meta.begin();
+        meta.i32(1, column.values.physical_type());
+        meta.i32(3, 0); // REQUIRED
+        meta.string(4, column.name);
+        match column.values {
+            Values::Text(_) => {
+                meta.i32(6, 0); // UTF8
+                meta.struct_field(10);
+                meta.struct_field(1); // STRING
+                meta.end();
+                meta.end();
+            }
+            Values::Date(_) => {
+                meta.i32(6, 6); // DATE
+                meta.struct_field(10);
+                meta.struct_field(6);
+                meta.end();
+                meta.end();
+            }
+            Values::Timestamp(_) => {
+                // No converted type: TIMESTAMP_MILLIS would mean UTC
+                meta.struct_field(10);
+                meta.struct_field(8);
+                meta.bool(1, false); // local time, not UTC
+                meta.struct_field(2);
+                meta.struct_field(1); // MILLIS
+                meta.end();
+                meta.end();
+                meta.end();
+                meta.end();
+            }
+            Values::Int(_) => {}
+        }
+        meta.end();
+    }
+    meta.i64(3, rows as i64);
+    if rows == 0 {
+        meta.list(4, T_STRUCT, 0);
+    } else {
+        meta.list(4, T_STRUCT, 1);
+        meta.begin();
+        meta.list(1, T_STRUCT, columns.len());
+        for (column, chunk) in columns.iter().zip(&chunks) {
+            meta.begin();
+            meta.i64(2, chunk.offset);
+            meta.struct_field(3);
+            meta.i32(1, column.values.physical_type());
+            meta.list(2, T_I32, 2);
+            meta.zigzag(0); // PLAIN
+            meta.zigzag(3); // RLE
+            meta.list(3, T_BINARY, 1);
+            meta.binary(column.name.as_bytes());
+            meta.i32(4, 0); // UNCOMPRESSED
+            meta.i64(5, rows as i64);
+            meta.i64(6, chunk.size);
+            meta.i64(7, chunk.size);
+            meta.i64(9, chunk.offset);
+            meta.end();
+            meta.end();
+        }
+        meta.i64(2, chunks.iter().map(|c| c.size).sum());
+        meta.i64(3, rows as i64);
+        meta.end();
+    }
+    meta.string(6, concat!("clockbot version ", env!("CARGO_PKG_VERSION")));
+    meta.end();
+
+    file.extend(&meta.out);
+    file.extend((meta.out.len() as u32).to_le_bytes());
+    file.extend(MAGIC);
+    Ok(file)
+}
+
+/// The tables of an export, by file name: finished sessions of the current week, minutes
+/// per week, person and activity (archive and current week), and the archived weeks.
+pub fn export_tables(db: &Db) -> anyhow::Result<Vec<(&'static str, Vec<Column>)>> {
+    let sessions = db.session_records(None)?;
+    let sessions = vec![
+        Column::new("user_id", Values::Text(sessions.iter().map(|s| s.user_id.clone()).collect())),
+        Column::new("username", Values::Text(sessions.iter().map(|s| s.username.clone()).collect())),
+        Column::new("activity", Values::Text(sessions.iter().map(|s| s.activity.clone()).collect())),
+        Column::new("started_at", Values::Timestamp(sessions.iter().map(|s| s.started_at).collect())),
+        Column::new("ended_at", Values::Timestamp(sessions.iter().map(|s| s.ended_at).collect())),
+        Column::new("seconds", Values::Int(sessions.iter().map(|s| s.elapsed.seconds()).collect())),
+        Column::new("source", Values::Text(sessions.iter().map(|s| s.source.clone()).collect())),
+    ];
+
+    let activities: Vec<_> = db
+        .week_activities()?
+        .into_iter()
+        .filter_map(|a| Some((WeekId::parse(&a.week_label)?, a)))
+        .collect();
+    let activities = vec![
+        Column::new("week", Values::Text(activities.iter().map(|(_, a)| a.week_label.clone()).collect())),
+        Column::new("week_start", Values::Date(activities.iter().map(|(w, _)| w.monday()).collect())),
+        Column::new("user_id", Values::Text(activities.iter().map(|(_, a)| a.user_id.clone()).collect())),
+        Column::new("username", Values::Text(activities.iter().map(|(_, a)| a.username.clone()).collect())),
+        Column::new("activity", Values::Text(activities.iter().map(|(_, a)| a.activity.clone()).collect())),
+        Column::new("minutes", Values::Int(activities.iter().map(|(_, a)| a.minutes).collect())),
+    ];
+
+    let weeks = db.archived_weeks(
+        NaiveDate::from_ymd_opt(1, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(9999, 12, 31).unwrap(),
+    )?;
+    let weeks = vec![
+        Column::new("week", Values::Text(weeks.iter().map(|w| w.label.clone()).collect())),
+        Column::new("week_start", Values::Date(weeks.iter().map(|w| w.start).collect())),
+        Column::new("week_end", Values::Date(weeks.iter().map(|w| w.end).collect())),
+        Column::new("minutes", Values::Int(weeks.iter().map(|w| w.total_minutes).collect())),
+        Column::new("sessions", Values::Int(weeks.iter().map(|w| w.sessions).collect())),
+        Column::new("people", Values::Int(weeks.iter().map(|w| w.people).collect())),
+    ];
+
+    Ok(vec![("sessions.parquet", sessions), ("activities.parquet", activities), ("weeks.parquet", weeks)])
+}
+
+/// Write `export_tables` into `dir`, creating it if needed. Returns each file and its rows.
+pub fn export(db: &Db, dir: &Path) -> anyhow::Result<Vec<(PathBuf, usize)>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for (name, columns) in export_tables(db)? {
+        let path = dir.join(name);
+        std::fs::write(&path, write(&columns)?)?;
+        written.push((path, columns.first().map_or(0, |c| c.values.len())));
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{make_user, make_week_of_sessions, test_db_at};
+
+    #[test]
+    fn test_write_layout() {
+        let at = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let file = write(&[
+            Column::new("user", Values::Text(vec!["Alice".into(), "Bob".into()])),
+            Column::new("minutes", Values::Int(vec![90, 1])),
+            Column::new("started_at", Values::Timestamp(vec![at, at])),
+        ])
+        .unwrap();
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let footer = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        let meta = &file[file.len() - 8 - footer..file.len() - 8];
+        assert!(meta.windows(10).any(|w| w == b"started_at"));
+        // PLAIN values: length-prefixed strings and little-endian integers
+        assert!(file.windows(9).any(|w| w == b"\x05\0\0\0Alice"));
+        assert!(file.windows(16).any(|w| w == [90, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]));
+        let millis = at.and_utc().timestamp_millis().to_le_bytes();
+        assert!(file.windows(8).any(|w| w == millis));
+
+        assert!(write(&[Column::new("a", Values::Int(vec![1])), Column::new("b", Values::Int(vec![]))]).is_err());
+        assert!(write(&[Column::new("a", Values::Int(vec![]))]).unwrap().ends_with(MAGIC));
+    }
+
+    #[test]
+    fn test_export() {
+        let (db, _clock) = test_db_at(NaiveDate::from_ymd_opt(2026, 3, 20).unwrap().and_hms_opt(18, 0, 0).unwrap());
+        let alice = make_user(&db, "Alice");
+        make_week_of_sessions(&db, &alice, db.clock().week(), "backend", 60);
+        let dir = tempfile::TempDir::new().unwrap();
+        let written = export(&db, dir.path()).unwrap();
+        let rows: Vec<(String, usize)> = written
+            .iter()
+            .map(|(p, n)| (p.file_name().unwrap().to_string_lossy().into_owned(), *n))
+            .collect();
+        assert_eq!(rows, [("sessions.parquet".into(), 5), ("activities.parquet".into(), 1), ("weeks.parquet".into(), 0)]);
+        assert!(std::fs::read(&written[0].0).unwrap().starts_with(MAGIC));
+    }
+}
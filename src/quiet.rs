@@ -0,0 +1,116 @@
+use crate::config;
+use crate::db::Db;
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+/// A daily quiet window in Swiss local time. Wraps past midnight when `start > end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parse `22-7`, `22:00-07:00` or `12:30-13:15`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let time = |s: &str| {
+            let s = s.trim();
+            NaiveTime::parse_from_str(s, "%H:%M")
+                .ok()
+                .or_else(|| s.parse::<u32>().ok().and_then(|h| NaiveTime::from_hms_opt(h, 0, 0)))
+        };
+        let (start, end) = (time(start)?, time(end)?);
+        (start != end).then_some(Self { start, end })
+    }
+
+    pub fn contains(&self, t: NaiveTime) -> bool {
+        if self.start < self.end {
+            t >= self.start && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+
+    /// When the window containing `now` ends, `None` if `now` is outside it.
+    pub fn end_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.contains(now.time()) {
+            return None;
+        }
+        let end = now.date().and_time(self.end);
+        Some(if end > now { end } else { end + Duration::days(1) })
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+/// The configured `quiet-hours` window, if any.
+pub fn quiet_hours(db: &Db) -> Option<QuietHours> {
+    config::get(db, config::QUIET_HOURS).and_then(|v| QuietHours::parse(&v))
+}
+
+/// Whether reminders and non-essential posts should hold off at Swiss local time `now`.
+pub fn is_quiet(db: &Db, now: NaiveDateTime) -> bool {
+    quiet_hours(db).is_some_and(|q| q.contains(now.time()))
+}
+
+/// When the current quiet window ends, `None` outside quiet hours.
+pub fn quiet_until(db: &Db, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    quiet_hours(db)?.end_after(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn t(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 2, d).unwrap().and_time(t(h, m))
+    }
+
+    #[test]
+    fn test_parse() {
+        let night = QuietHours { start: t(22, 0), end: t(7, 0) };
+        assert_eq!(QuietHours::parse("22-7"), Some(night));
+        assert_eq!(QuietHours::parse("22:00-07:00"), Some(night));
+        assert_eq!(QuietHours::parse(" 22:00 - 07:00 "), Some(night));
+        assert_eq!(night.to_string(), "22:00-07:00");
+        assert_eq!(
+            QuietHours::parse("12:30-13:15"),
+            Some(QuietHours { start: t(12, 30), end: t(13, 15) })
+        );
+        assert_eq!(QuietHours::parse("22-22"), None);
+        assert_eq!(QuietHours::parse("25-7"), None);
+        assert_eq!(QuietHours::parse("night"), None);
+    }
+
+    #[test]
+    fn test_contains_wrapping_and_plain() {
+        let night = QuietHours::parse("22-7").unwrap();
+        assert!(night.contains(t(22, 0)));
+        assert!(night.contains(t(0, 0)));
+        assert!(night.contains(t(6, 59)));
+        assert!(!night.contains(t(7, 0)));
+        assert!(!night.contains(t(21, 59)));
+
+        let lunch = QuietHours::parse("12-13").unwrap();
+        assert!(lunch.contains(t(12, 30)));
+        assert!(!lunch.contains(t(13, 0)));
+        assert!(!lunch.contains(t(11, 0)));
+    }
+
+    #[test]
+    fn test_end_after() {
+        let night = QuietHours::parse("22-7").unwrap();
+        assert_eq!(night.end_after(at(9, 23, 0)), Some(at(10, 7, 0)));
+        assert_eq!(night.end_after(at(10, 0, 0)), Some(at(10, 7, 0)));
+        assert_eq!(night.end_after(at(10, 12, 0)), None);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::config;
+use crate::db::{Db, InactiveUser};
+use crate::display::DisplayFormat;
+use crate::weeks::WeekId;
+use chrono::{Duration, NaiveDate};
+use serenity::all::*;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// How often the archive is scanned for people who stopped logging time.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Weeks without time before the DM, from `inactive-weeks`; `None` while it's off.
+pub fn inactive_weeks(db: &Db) -> Option<i64> {
+    config::get(db, config::INACTIVE_WEEKS).and_then(|w| w.parse().ok())
+}
+
+/// Monday of the earliest of the last `weeks` full weeks before `today`'s: whoever has
+/// no archived time from then on has been away all of them.
+pub fn inactive_cutoff(today: NaiveDate, weeks: i64) -> NaiveDate {
+    WeekId::containing(today).monday() - Duration::weeks(weeks)
+}
+
+pub fn reengage_text(user: &InactiveUser, weeks: i64, fmt: &DisplayFormat) -> String {
+    format!(
+        "We miss you, {}! It's been over {} weeks since you last clocked any time.\n\
+         Your best week was **{}** with **{}**. Whenever you're back: `/clock in <activity>`",
+        user.username,
+        weeks,
+        fmt.week(&user.best_week),
+        fmt.duration(user.best_minutes)
+    )
+}
+
+/// With `inactive-weeks` set, DM everyone who used to log time but stopped that many
+/// weeks ago, once per stretch away.
+pub async fn reengage_loop(db: Arc<Db>, http: Arc<Http>) {
+    loop {
+        sleep(CHECK_INTERVAL).await;
+        let Some(weeks) = inactive_weeks(&db) else {
+            continue;
+        };
+        let now = db.clock().now();
+        // Unsent DMs stay due and go out on the first check after quiet hours
+        if crate::quiet::is_quiet(&db, now) {
+            continue;
+        }
+        let users = match db.inactive_users(inactive_cutoff(now.date(), weeks)) {
+            Ok(users) => users,
+            Err(e) => {
+                eprintln!("[clock] Inactive users query failed: {e}");
+                continue;
+            }
+        };
+        for user in &users {
+            send(&http, &db, user, weeks).await;
+        }
+    }
+}
+
+async fn send(http: &Http, db: &Db, user: &InactiveUser, weeks: i64) {
+    let Ok(user_id) = user.user_id.parse::<UserId>() else {
+        return;
+    };
+    let embed = CreateEmbed::new()
+        .color(0x5865f2)
+        .title("👋 Long time no see")
+        .description(reengage_text(user, weeks, &DisplayFormat::load(db)))
+        .footer(CreateEmbedFooter::new("/clock nudge off to stop these"));
+    if let Err(e) = user_id.direct_message(http, CreateMessage::new().embed(embed)).await {
+        // Closed DMs fail every time; still mark it so they aren't retried every hour
+        eprintln!("[clock] Failed to DM {} about being away: {e}", user.user_id);
+    }
+    if let Err(e) = db.mark_reengaged(&user.user_id, &user.last_week) {
+        eprintln!("[clock] Failed to record re-engagement DM: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inactive_cutoff() {
+        // Wednesday of KW08/2026: four full weeks back is KW04's Monday
+        let today = NaiveDate::from_ymd_opt(2026, 2, 18).unwrap();
+        assert_eq!(inactive_cutoff(today, 4), NaiveDate::from_ymd_opt(2026, 1, 19).unwrap());
+        assert_eq!(inactive_cutoff(today, 1), NaiveDate::from_ymd_opt(2026, 2, 9).unwrap());
+    }
+
+    #[test]
+    fn test_reengage_text() {
+        let user = InactiveUser {
+            user_id: "1".to_string(),
+            username: "Alice".to_string(),
+            last_week: "KW04/2026".to_string(),
+            best_week: "KW02/2026".to_string(),
+            best_minutes: 750,
+        };
+        let text = reengage_text(&user, 4, &DisplayFormat::default());
+        assert!(text.starts_with("We miss you, Alice! It's been over 4 weeks"));
+        assert!(text.contains("Your best week was **KW02/2026** with **12h 30m**."));
+        let fmt = DisplayFormat { decimal_hours: true, week_words: true, ..DisplayFormat::default() };
+        assert!(reengage_text(&user, 4, &fmt).contains("Your best week was **Week 2, 2026** with **12.5h**."));
+    }
+}
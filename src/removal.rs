@@ -0,0 +1,159 @@
+//! What happens to the data when the bot is removed from its server. The database holds
+//! one server's data, so it is only deleted once the bot has left every server, after
+//! `REMOVAL_GRACE_DAYS` in case the removal was a mistake.
+
+use crate::db::Db;
+use chrono::{Duration, NaiveDateTime};
+use std::env;
+use std::sync::Arc;
+use tokio::time::sleep;
+
+/// Metadata key of a pending deletion: `<guild id>|<delete at>`.
+pub const REMOVAL_KEY: &str = "removal_scheduled";
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Days to keep the data after the bot left its last server, from `REMOVAL_GRACE_DAYS`.
+/// Unset keeps it until an operator deletes it.
+pub fn grace_days() -> Option<i64> {
+    env::var("REMOVAL_GRACE_DAYS").ok().and_then(|d| d.trim().parse().ok()).filter(|d| *d >= 0)
+}
+
+/// A pending deletion: the server that removed the bot and when the data goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheduled {
+    pub guild_id: String,
+    pub delete_at: NaiveDateTime,
+}
+
+pub fn scheduled(db: &Db) -> Option<Scheduled> {
+    let value = db.metadata(REMOVAL_KEY).ok().flatten()?;
+    let (guild_id, at) = value.split_once('|')?;
+    Some(Scheduled {
+        guild_id: guild_id.to_string(),
+        delete_at: NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M:%S").ok()?,
+    })
+}
+
+/// Delete everything `grace_days` from now, Swiss time.
+pub fn schedule(db: &Db, guild_id: &str, grace_days: i64) -> anyhow::Result<Scheduled> {
+    let delete_at = db.clock().now() + Duration::days(grace_days);
+    db.set_metadata(REMOVAL_KEY, &format!("{guild_id}|{}", delete_at.format("%Y-%m-%d %H:%M:%S")))?;
+    Ok(Scheduled { guild_id: guild_id.to_string(), delete_at })
+}
+
+/// Drop the pending deletion if `guild_id` is the server that removed the bot. Joining
+/// another server doesn't cancel it: that server must not see the old one's data.
+pub fn cancel(db: &Db, guild_id: &str) -> anyhow::Result<bool> {
+    match scheduled(db) {
+        Some(s) if s.guild_id == guild_id => {
+            db.delete_metadata(REMOVAL_KEY)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+async fn notify(subject: &str, text: &str) {
+    match crate::digest::email_admins(subject, &format!("<p>{}</p>", crate::dashboard::escape(text))).await {
+        Ok(0) => {}
+        Ok(sent) => println!("[clock] Emailed {sent} admin(s): {subject}"),
+        Err(e) => eprintln!("[clock] Failed to email admins: {e}"),
+    }
+}
+
+/// The bot was removed from `guild_id` (kicked or banned, not an outage) and is still in
+/// `remaining` servers.
+pub async fn on_removed(db: &Db, guild_id: &str, remaining: usize) {
+    println!("[clock] Removed from server {guild_id}");
+    if remaining > 0 {
+        println!("[clock] Still in {remaining} server(s), keeping the data");
+        return;
+    }
+    let text = match grace_days() {
+        Some(days) => match schedule(db, guild_id, days) {
+            Ok(s) => format!(
+                "ClockBot was removed from server {guild_id}. All its data will be deleted on {} \
+                 unless the bot is added back to that server before then.",
+                crate::display::DisplayFormat::load(db).timestamp(s.delete_at)
+            ),
+            Err(e) => {
+                eprintln!("[clock] Failed to schedule data deletion: {e}");
+                return;
+            }
+        },
+        None => format!(
+            "ClockBot was removed from server {guild_id}. Its data is kept; set REMOVAL_GRACE_DAYS \
+             to have it deleted automatically."
+        ),
+    };
+    println!("[clock] {text}");
+    notify("ClockBot removed from server", &text).await;
+}
+
+/// The bot is in `guild_id`, newly added or on connect.
+pub async fn on_joined(db: &Db, guild_id: &str) {
+    match cancel(db, guild_id) {
+        Ok(true) => {
+            let text = format!("ClockBot was added back to server {guild_id}; the data will not be deleted.");
+            println!("[clock] {text}");
+            notify("ClockBot data deletion cancelled", &text).await;
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("[clock] Failed to cancel data deletion: {e}"),
+    }
+}
+
+/// Delete the data once a scheduled deletion is due.
+pub async fn cleanup_loop(db: Arc<Db>) {
+    loop {
+        if let Some(s) = scheduled(&db).filter(|s| db.clock().now() >= s.delete_at) {
+            match db.delete_all_data() {
+                Ok(()) => {
+                    let text = format!("All data of server {} was deleted as scheduled.", s.guild_id);
+                    println!("[clock] {text}");
+                    notify("ClockBot data deleted", &text).await;
+                }
+                Err(e) => eprintln!("[clock] Scheduled data deletion failed: {e}"),
+            }
+        }
+        sleep(CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{make_user, make_week_of_sessions, test_db_at};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_schedule_and_cancel() {
+        let start = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let (db, _clock) = test_db_at(start);
+        assert_eq!(scheduled(&db), None);
+
+        let s = schedule(&db, "4711", 30).unwrap();
+        assert_eq!(s.delete_at, start + Duration::days(30));
+        assert_eq!(scheduled(&db), Some(s));
+        // Another server doesn't cancel it, the same one does
+        assert!(!cancel(&db, "42").unwrap());
+        assert!(cancel(&db, "4711").unwrap());
+        assert_eq!(scheduled(&db), None);
+    }
+
+    #[test]
+    fn test_delete_all_data() {
+        let (db, _clock) = test_db_at(NaiveDate::from_ymd_opt(2026, 3, 20).unwrap().and_hms_opt(18, 0, 0).unwrap());
+        let alice = make_user(&db, "Alice");
+        make_week_of_sessions(&db, &alice, db.clock().week(), "backend", 60);
+        schedule(&db, "4711", 0).unwrap();
+
+        db.delete_all_data().unwrap();
+        assert!(db.session_records(None).unwrap().is_empty());
+        assert!(db.leaderboard_weekly(false).unwrap().is_empty());
+        assert_eq!(scheduled(&db), None);
+        // Still usable
+        db.clock_in(&alice.id, &alice.name, "backend").unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use crate::dashboard::{ranking, totals, Totals};
+use crate::db::WeeklySummary;
+use crate::display::DisplayFormat;
+use printpdf::{
+    Color, Image, ImageTransform, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Rect, Rgb,
+};
+
+static FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 18.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const LINE: f32 = 6.0;
+/// Widest bar in the tables.
+const BAR_WIDTH: f32 = 70.0;
+
+/// Writes top to bottom, starting a new page when the current one is full.
+struct Writer {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    y: f32,
+    pages: usize,
+}
+
+impl Writer {
+    fn new(title: &str) -> anyhow::Result<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+        let font = doc.add_external_font(FONT)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self { doc, layer, font, y: PAGE_HEIGHT - MARGIN, pages: 1 })
+    }
+
+    /// Make room for `height` mm, breaking the page if needed.
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+            self.pages += 1;
+        }
+    }
+
+    fn text_at(&self, text: &str, size: f32, x: f32) {
+        self.layer.use_text(text, size, Mm(x), Mm(self.y), &self.font);
+    }
+
+    fn line(&mut self, text: &str, size: f32) {
+        let height = size * 0.5;
+        self.reserve(height);
+        self.y -= height;
+        self.text_at(text, size, MARGIN);
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn heading(&mut self, text: &str) {
+        // Keep a heading together with its first rows
+        self.reserve(LINE * 4.0);
+        self.gap(LINE * 0.5);
+        self.line(text, 14.0);
+        self.gap(LINE * 0.5);
+    }
+
+    /// Rank, name, proportional bar and duration per row.
+    fn table(&mut self, rows: &Totals, fmt: &DisplayFormat) {
+        let max = rows.iter().map(|(_, m)| *m).max().unwrap_or(0).max(1);
+        for (i, (name, minutes)) in rows.iter().enumerate() {
+            self.reserve(LINE);
+            self.y -= LINE;
+            self.text_at(&format!("{}.", i + 1), 10.0, MARGIN);
+            self.text_at(name, 10.0, MARGIN + 10.0);
+            let bar = BAR_WIDTH * *minutes as f32 / max as f32;
+            self.layer.set_fill_color(Color::Rgb(Rgb::new(0.30, 0.60, 1.0, None)));
+            self.layer.add_rect(Rect::new(
+                Mm(MARGIN + 80.0),
+                Mm(self.y),
+                Mm(MARGIN + 80.0 + bar.max(0.5)),
+                Mm(self.y + 3.0),
+            ));
+            self.layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+            self.text_at(&fmt.duration(*minutes), 10.0, MARGIN + 80.0 + BAR_WIDTH + 5.0);
+        }
+    }
+
+    /// A PNG scaled to the content width.
+    fn image(&mut self, png: &[u8]) -> anyhow::Result<()> {
+        let image = image::load_from_memory(png)?;
+        let (width_px, height_px) = (image.width() as f32, image.height() as f32);
+        let dpi = width_px * 25.4 / CONTENT_WIDTH;
+        let height = CONTENT_WIDTH * height_px / width_px;
+        self.reserve(height + LINE);
+        self.y -= height + LINE * 0.5;
+        Image::from_dynamic_image(&image).add_to_layer(
+            self.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(MARGIN)),
+                translate_y: Some(Mm(self.y)),
+                dpi: Some(dpi),
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Printable PDF of the weekly report: totals, awards, the weekly-hours chart (if any),
+/// leaderboard, activity totals and everyone's breakdown.
+pub fn render_pdf(
+    summary: &WeeklySummary,
+    week_label: &str,
+    chart_png: Option<&[u8]>,
+    fmt: &DisplayFormat,
+) -> anyhow::Result<Vec<u8>> {
+    Ok(layout(summary, week_label, chart_png, fmt)?.doc.save_to_bytes()?)
+}
+
+fn layout(summary: &WeeklySummary, week_label: &str, chart_png: Option<&[u8]>, fmt: &DisplayFormat) -> anyhow::Result<Writer> {
+    let title = format!("Weekly Report — {}", fmt.week(week_label));
+    let mut w = Writer::new(&title)?;
+
+    w.line(&title, 20.0);
+    w.gap(2.0);
+    w.line(
+        &format!(
+            "{} total · {} sessions · {} people",
+            fmt.duration(summary.total_minutes),
+            summary.total_sessions,
+            summary.unique_workers
+        ),
+        11.0,
+    );
+    if !summary.holidays.is_empty() {
+        let names: Vec<&str> = summary.holidays.iter().map(|(_, name)| name.as_str()).collect();
+        w.line(
+            &format!("Short week — {} working days ({})", 5 - summary.holidays.len(), names.join(", ")),
+            11.0,
+        );
+    }
+
+    w.heading("Awards");
+    if let Some((ref name, mins)) = summary.mvp {
+        w.line(&format!("MVP — {} with {}", name, fmt.duration(mins)), 11.0);
+    }
+    if let Some((ref activity, mins)) = summary.top_activity {
+        w.line(&format!("Hot Topic — {} ({})", activity, fmt.duration(mins)), 11.0);
+    }
+    if let Some((ref name, ref activity, mins)) = summary.longest_session {
+        w.line(&format!("Marathon — {} on {} ({})", name, activity, fmt.duration(mins)), 11.0);
+    }
+
+    if let Some(png) = chart_png {
+        w.heading("Weekly hours");
+        w.image(png)?;
+    }
+
+    let (users, activities) = totals(summary);
+    w.heading("Leaderboard");
+    w.table(&ranking(summary), fmt);
+    w.heading("Activities");
+    w.table(&activities, fmt);
+    if !summary.clients.is_empty() {
+        w.heading("Clients");
+        w.table(&summary.clients, fmt);
+    }
+
+    w.heading("Who worked on what");
+    for (username, minutes) in &users {
+        w.reserve(LINE * 3.0);
+        w.gap(LINE * 0.5);
+        w.line(&format!("{} — {}", username, fmt.duration(*minutes)), 12.0);
+        let rows: Totals = summary
+            .breakdown
+            .iter()
+            .filter(|e| &e.username == username)
+            .map(|e| (e.activity.clone(), e.total_minutes))
+            .collect();
+        w.table(&rows, fmt);
+    }
+    Ok(w)
+}
+
+/// Attachment name for a week's report, e.g. `weekly-report-KW07-2026.pdf`.
+pub fn pdf_filename(week_label: &str) -> String {
+    format!("weekly-report-{}.pdf", week_label.replace('/', "-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ActivityEntry;
+
+    fn summary(people: usize) -> WeeklySummary {
+        let breakdown = (0..people)
+            .flat_map(|i| {
+                ["backend", "docs", "review"].into_iter().map(move |activity| ActivityEntry {
+                    username: format!("Person {} — Zoë", i),
+                    activity: activity.to_string(),
+                    total_minutes: 60 + i as i64,
+                    session_count: 1,
+                })
+            })
+            .collect();
+        WeeklySummary {
+            total_minutes: 600,
+            total_sessions: 10,
+            unique_workers: people as i64,
+            mvp: Some(("Person 0 — Zoë".to_string(), 180)),
+            top_activity: Some(("backend".to_string(), 300)),
+            longest_session: None,
+            most_appreciated: None,
+            breakdown,
+            holidays: vec![(chrono::NaiveDate::from_ymd_opt(2026, 4, 6).unwrap(), "Easter Monday".to_string())],
+            daily_minutes: [0; 7],
+            mvp_daily_minutes: [0; 7],
+            previous_activities: Vec::new(),
+            non_competitive: Vec::new(),
+            clients: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_pdf() {
+        let png = {
+            let mut bytes = Vec::new();
+            image::DynamicImage::new_rgb8(120, 60)
+                .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+                .unwrap();
+            bytes
+        };
+        let small = render_pdf(&summary(2), "KW15/2026", Some(&png), &DisplayFormat::default()).unwrap();
+        assert!(small.starts_with(b"%PDF-"));
+        // The font is subset, not embedded whole
+        assert!(small.len() < FONT.len());
+
+        // Long breakdowns flow onto more pages
+        let pages = |people| layout(&summary(people), "KW15/2026", None, &DisplayFormat::default()).unwrap().pages;
+        assert_eq!(pages(1), 1);
+        assert!(pages(40) > 3);
+    }
+
+    #[test]
+    fn test_pdf_filename() {
+        assert_eq!(pdf_filename("KW07/2026"), "weekly-report-KW07-2026.pdf");
+    }
+}
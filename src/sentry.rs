@@ -0,0 +1,98 @@
+//! Optional error reporting to Sentry (or anything speaking its store API, like
+//! GlitchTip), configured with `SENTRY_DSN`.
+
+use chrono::Utc;
+use rand::Rng;
+use std::env;
+
+/// Where and how to send events, from a DSN like `https://<key>@o1.ingest.sentry.io/42`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dsn {
+    pub store_url: String,
+    pub public_key: String,
+}
+
+impl Dsn {
+    pub fn parse(dsn: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(dsn.trim()).ok()?;
+        let public_key = url.username();
+        let host = url.host_str()?;
+        let (path, project) = url.path().trim_end_matches('/').rsplit_once('/')?;
+        if public_key.is_empty() || project.is_empty() {
+            return None;
+        }
+        let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+        Some(Self {
+            store_url: format!("{}://{host}{port}{path}/api/{project}/store/", url.scheme()),
+            public_key: public_key.to_string(),
+        })
+    }
+
+    /// `None` without a (valid) `SENTRY_DSN`.
+    pub fn from_env() -> Option<Self> {
+        Self::parse(&env::var("SENTRY_DSN").ok()?)
+    }
+}
+
+/// A Sentry event for `message` at `level` (`error`, `fatal`, …), tagged with `task`.
+pub fn event(level: &str, task: &str, message: &str) -> serde_json::Value {
+    let event_id: String = (0..16).map(|_| format!("{:02x}", rand::thread_rng().r#gen::<u8>())).collect();
+    serde_json::json!({
+        "event_id": event_id,
+        "timestamp": Utc::now().to_rfc3339(),
+        "platform": "other",
+        "level": level,
+        "logger": "clockbot",
+        "release": concat!("clockbot@", env!("CARGO_PKG_VERSION")),
+        "message": { "formatted": message },
+        "tags": { "task": task },
+    })
+}
+
+pub async fn send(dsn: &Dsn, event: &serde_json::Value) -> anyhow::Result<()> {
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_client=clockbot/{}, sentry_key={}",
+        env!("CARGO_PKG_VERSION"),
+        dsn.public_key
+    );
+    crate::calendar::http_client()
+        .post(&dsn.store_url)
+        .header("X-Sentry-Auth", auth)
+        .header("Content-Type", "application/json")
+        .body(event.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dsn() {
+        assert_eq!(
+            Dsn::parse("https://abc123@o99.ingest.sentry.io/4711"),
+            Some(Dsn {
+                store_url: "https://o99.ingest.sentry.io/api/4711/store/".to_string(),
+                public_key: "abc123".to_string(),
+            })
+        );
+        assert_eq!(
+            Dsn::parse("http://key@localhost:8000/glitchtip/3").unwrap().store_url,
+            "http://localhost:8000/glitchtip/api/3/store/"
+        );
+        assert_eq!(Dsn::parse("https://o99.ingest.sentry.io/4711"), None);
+        assert_eq!(Dsn::parse("not a dsn"), None);
+    }
+
+    #[test]
+    fn test_event() {
+        let event = event("fatal", "weekly-reset", "boom");
+        assert_eq!(event["event_id"].as_str().unwrap().len(), 32);
+        assert_eq!(event["level"], "fatal");
+        assert_eq!(event["message"]["formatted"], "boom");
+        assert_eq!(event["tags"]["task"], "weekly-reset");
+    }
+}
@@ -0,0 +1,53 @@
+use crate::db::Db;
+use serenity::all::*;
+use std::env;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration, Instant};
+
+/// How often the active worker count is sampled.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Discord allows two channel renames per ten minutes; stay under that.
+const MIN_RENAME_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Channel renamed to show the active worker count, from `STATUS_CHANNEL`.
+pub fn status_channel_id() -> Option<ChannelId> {
+    env::var("STATUS_CHANNEL")
+        .ok()
+        .and_then(|s| s.parse().ok())
+}
+
+fn channel_name(active: i64) -> String {
+    format!("🔨 working: {}", active)
+}
+
+/// Keep the status channel name in sync with the number of clocked-in users.
+/// Changes within one rename window are coalesced into a single rename.
+pub async fn status_channel_loop(db: Arc<Db>, http: Arc<Http>) {
+    let Some(channel_id) = status_channel_id() else {
+        return;
+    };
+
+    let mut applied: Option<i64> = None;
+    let mut last_rename: Option<Instant> = None;
+
+    loop {
+        let rate_limited = last_rename.is_some_and(|t| t.elapsed() < MIN_RENAME_INTERVAL);
+
+        if !rate_limited {
+            match db.active_count() {
+                Ok(count) if applied != Some(count) => {
+                    let edit = EditChannel::new().name(channel_name(count));
+                    match channel_id.edit(&http, edit).await {
+                        Ok(_) => applied = Some(count),
+                        Err(e) => eprintln!("[clock] Failed to rename status channel: {e}"),
+                    }
+                    last_rename = Some(Instant::now());
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[clock] Active count query failed: {e}"),
+            }
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
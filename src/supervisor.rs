@@ -0,0 +1,145 @@
+//! Keeps the background loops running. A loop that panics is logged, reported to
+//! `ERROR_CHANNEL` and Sentry (see `sentry`) and restarted after a growing delay, so a bad
+//! row can't silently stop the weekly reset for good.
+
+use serenity::all::*;
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Delays between restarts: doubling from `initial` up to `max`, and back to `initial`
+/// once a run lasted `healthy_after`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub healthy_after: Duration,
+}
+
+impl Backoff {
+    pub const DEFAULT: Backoff = Backoff {
+        initial: Duration::from_secs(5),
+        max: Duration::from_secs(10 * 60),
+        healthy_after: Duration::from_secs(60 * 60),
+    };
+}
+
+/// Where crashes are reported besides stderr.
+#[derive(Default)]
+pub struct Reporter {
+    http: Option<Arc<Http>>,
+    sentry: Option<crate::sentry::Dsn>,
+}
+
+impl Reporter {
+    pub fn from_env(http: Arc<Http>) -> Self {
+        Self { http: Some(http), sentry: crate::sentry::Dsn::from_env() }
+    }
+
+    async fn crashed(&self, task: &str, message: &str, restart_in: Duration) {
+        if let (Some(http), Some(channel_id)) = (&self.http, crate::errors::error_channel_id()) {
+            let embed = CreateEmbed::new()
+                .color(0xe74c3c)
+                .title(format!("💥 Background task `{task}` crashed"))
+                .description(crate::commands::truncate_for_embed(&format!("```\n{message}\n```"), 4000))
+                .footer(CreateEmbedFooter::new(format!("Restarting in {}s", restart_in.as_secs())));
+            if let Err(e) = channel_id.send_message(http, CreateMessage::new().embed(embed)).await {
+                eprintln!("[clock] Failed to post crash of {task} to the error channel: {e}");
+            }
+        }
+        if let Some(dsn) = &self.sentry
+            && let Err(e) = crate::sentry::send(dsn, &crate::sentry::event("fatal", task, message)).await
+        {
+            eprintln!("[clock] Failed to report crash of {task} to Sentry: {e}");
+        }
+    }
+}
+
+/// The text a panic was raised with.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "panic without a message".to_string()
+    }
+}
+
+/// Run `task()` until it returns, starting it over whenever it panics.
+pub async fn supervise<F, Fut>(name: &'static str, backoff: Backoff, reporter: Arc<Reporter>, task: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut delay = backoff.initial;
+    loop {
+        let started = Instant::now();
+        match tokio::spawn(task()).await {
+            Ok(()) => {
+                println!("[clock] Background task {name} finished");
+                return;
+            }
+            Err(e) if e.is_panic() => {
+                if started.elapsed() >= backoff.healthy_after {
+                    delay = backoff.initial;
+                }
+                let message = panic_message(&*e.into_panic());
+                eprintln!("[clock] Background task {name} panicked: {message}; restarting in {}s", delay.as_secs());
+                reporter.crashed(name, &message, delay).await;
+                sleep(delay).await;
+                delay = (delay * 2).min(backoff.max);
+            }
+            Err(e) => {
+                eprintln!("[clock] Background task {name} was cancelled: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Spawn a supervised background task with the default backoff.
+pub fn spawn<F, Fut>(name: &'static str, reporter: &Arc<Reporter>, task: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(supervise(name, Backoff::DEFAULT, Arc::clone(reporter), task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("row {} is broken", 7)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "row 7 is broken");
+        let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "static");
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_panic() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let backoff = Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(2),
+            healthy_after: Duration::from_secs(60),
+        };
+        let counter = Arc::clone(&runs);
+        supervise("test", backoff, Arc::new(Reporter::default()), move || {
+            let counter = Arc::clone(&counter);
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("crash");
+                }
+            }
+        })
+        .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
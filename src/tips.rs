@@ -0,0 +1,135 @@
+use crate::db::{ActivityTotals, Db, UserSettings};
+use crate::normalize::similar_names;
+use chrono::{NaiveDateTime, Timelike};
+
+/// A hint about a command the user would likely find useful, from their own data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tip {
+    /// The user logged time under several spellings of the activity they just used.
+    Rename { names: Vec<String> },
+    /// No daily goal is set.
+    Goal,
+}
+
+impl Tip {
+    pub fn text(&self) -> String {
+        match self {
+            Tip::Rename { names } => format!(
+                "You've used {} names for this ({}) — `/clock rename <old> > <new>` merges them.",
+                names.len(),
+                names.iter().map(|n| format!("**{}**", n)).collect::<Vec<_>>().join(", ")
+            ),
+            Tip::Goal => "Set a daily goal with `/clock goal 4h` to see your progress each day.".to_string(),
+        }
+    }
+}
+
+/// `Good morning` and friends for a local time.
+pub fn greeting(local: NaiveDateTime) -> &'static str {
+    match local.hour() {
+        5..=11 => "Good morning",
+        12..=17 => "Good afternoon",
+        18..=22 => "Good evening",
+        _ => "Burning the midnight oil",
+    }
+}
+
+/// Tips that apply to `settings.user_id` having just used `activity`, most useful first.
+/// `totals` are the all-time totals from `Db::activity_totals` for that user.
+pub fn applicable(settings: &UserSettings, activity: &str, totals: &[ActivityTotals]) -> Vec<Tip> {
+    let mut tips = Vec::new();
+    let mut names: Vec<String> = totals
+        .iter()
+        .filter(|t| t.user_minutes > 0 && (t.activity == activity || similar_names(activity, &t.activity)))
+        .map(|t| t.activity.clone())
+        .collect();
+    if !names.iter().any(|n| n == activity) {
+        names.insert(0, activity.to_string());
+    }
+    if names.len() > 1 {
+        tips.push(Tip::Rename { names });
+    }
+    if settings.daily_goal_min.is_none() {
+        tips.push(Tip::Goal);
+    }
+    tips
+}
+
+/// The user's greeting for a clock-in post, `None` if they turned tips off.
+pub fn greeting_for(db: &Db, user_id: &str) -> Option<&'static str> {
+    let settings = db.user_settings(user_id).ok()?;
+    let tz = crate::nudge::user_timezone(&settings);
+    settings.tips.then(|| greeting(db.clock().now_utc().with_timezone(&tz).naive_local()))
+}
+
+/// At most one tip per local day for `user_id` after using `activity`, marked as shown.
+/// `None` if tips are off, one was already shown today, or none applies.
+pub fn next_tip(db: &Db, user_id: &str, activity: &str) -> Option<Tip> {
+    let settings = db.user_settings(user_id).ok()?;
+    let today = db.clock().now_utc().with_timezone(&crate::nudge::user_timezone(&settings)).date_naive();
+    if !settings.tips || settings.last_tip == Some(today) {
+        return None;
+    }
+    let totals = db.activity_totals(user_id).ok()?;
+    let tip = applicable(&settings, activity, &totals).into_iter().next()?;
+    if let Err(e) = db.mark_tip_shown(user_id, today) {
+        eprintln!("[clock] Failed to record tip: {e}");
+    }
+    Some(tip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::test_db;
+    use chrono::NaiveDate;
+
+    fn totals(entries: &[(&str, i64)]) -> Vec<ActivityTotals> {
+        entries
+            .iter()
+            .map(|&(activity, minutes)| ActivityTotals {
+                activity: activity.to_string(),
+                user_minutes: minutes,
+                total_minutes: minutes + 60,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_greeting() {
+        let at = |h| NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(h, 30, 0).unwrap();
+        assert_eq!(greeting(at(8)), "Good morning");
+        assert_eq!(greeting(at(13)), "Good afternoon");
+        assert_eq!(greeting(at(20)), "Good evening");
+        assert_eq!(greeting(at(2)), "Burning the midnight oil");
+    }
+
+    #[test]
+    fn test_applicable() {
+        let db = test_db();
+        let mut settings = db.user_settings("u1").unwrap();
+        let all = totals(&[("backend", 300), ("backed", 20), ("back-end", 0), ("frontend", 90)]);
+
+        let tips = applicable(&settings, "backend", &all);
+        assert_eq!(tips[0], Tip::Rename { names: vec!["backend".to_string(), "backed".to_string()] });
+        assert_eq!(tips[1], Tip::Goal);
+        assert!(tips[0].text().starts_with("You've used 2 names"));
+
+        settings.daily_goal_min = Some(240);
+        assert!(applicable(&settings, "frontend", &all).is_empty());
+        // A brand-new spelling counts too
+        assert_eq!(applicable(&settings, "frontnd", &all).len(), 1);
+    }
+
+    #[test]
+    fn test_next_tip_once_a_day() {
+        let db = test_db();
+        assert_eq!(next_tip(&db, "u1", "backend"), Some(Tip::Goal));
+        assert_eq!(next_tip(&db, "u1", "backend"), None);
+
+        db.set_tips("u2", false).unwrap();
+        assert_eq!(next_tip(&db, "u2", "backend"), None);
+        assert_eq!(greeting_for(&db, "u2"), None);
+        assert!(greeting_for(&db, "u1").is_some());
+    }
+}
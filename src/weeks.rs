@@ -0,0 +1,295 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Europe::Zurich;
+use std::fmt;
+
+/// An ISO-8601 week (Monday–Sunday), e.g. `KW07/2026`.
+///
+/// Weeks belong to their ISO year, which differs from the calendar year around
+/// New Year: 2026-12-31 is in `KW53/2026` and 2027-01-01 still is too, while
+/// 2024-12-30 is already in `KW01/2025`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WeekId {
+    year: i32,
+    week: u32,
+}
+
+impl WeekId {
+    /// `None` if `week` does not exist in ISO year `year` (e.g. week 53 of a 52-week year).
+    pub fn new(year: i32, week: u32) -> Option<Self> {
+        NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).map(|_| Self { year, week })
+    }
+
+    /// The week a calendar date falls into.
+    pub fn containing(date: NaiveDate) -> Self {
+        let iso = date.iso_week();
+        Self {
+            year: iso.year(),
+            week: iso.week(),
+        }
+    }
+
+    /// The current week in Swiss local time by the real clock; prefer `db.clock().week()`.
+    pub fn current() -> Self {
+        Self::containing(crate::db::now_ch().date())
+    }
+
+    pub fn monday(&self) -> NaiveDate {
+        NaiveDate::from_isoywd_opt(self.year, self.week, Weekday::Mon).unwrap()
+    }
+
+    pub fn sunday(&self) -> NaiveDate {
+        self.monday() + Duration::days(6)
+    }
+
+    /// Monday 00:00 (inclusive start, Swiss local time).
+    pub fn start(&self) -> NaiveDateTime {
+        self.monday().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    /// Following Monday 00:00 (exclusive end, Swiss local time).
+    pub fn end(&self) -> NaiveDateTime {
+        self.next().start()
+    }
+
+    pub fn previous(&self) -> Self {
+        Self::containing(self.monday() - Duration::weeks(1))
+    }
+
+    pub fn next(&self) -> Self {
+        Self::containing(self.monday() + Duration::weeks(1))
+    }
+
+    /// The `n` weeks ending with this one, oldest first.
+    pub fn last_n(&self, n: u32) -> Vec<Self> {
+        (0..n as i64)
+            .rev()
+            .map(|i| Self::containing(self.monday() - Duration::weeks(i)))
+            .collect()
+    }
+
+    /// `KW07/2026` — the format stored in the archive tables.
+    pub fn label(&self) -> String {
+        format!("KW{:02}/{}", self.week, self.year)
+    }
+
+    /// Parse a `KW07/2026` label (the week number may omit the leading zero).
+    pub fn parse(label: &str) -> Option<Self> {
+        let (week, year) = label.strip_prefix("KW")?.split_once('/')?;
+        Self::new(year.parse().ok()?, week.parse().ok()?)
+    }
+}
+
+impl fmt::Display for WeekId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KW{:02}/{}", self.week, self.year)
+    }
+}
+
+/// Label of the current week, e.g. `KW07/2026`.
+pub fn swiss_week_label() -> String {
+    WeekId::current().label()
+}
+
+/// Labels of the last `weeks` weeks including the current one, oldest first.
+pub fn recent_week_labels(weeks: u32) -> Vec<String> {
+    WeekId::current()
+        .last_n(weeks)
+        .iter()
+        .map(WeekId::label)
+        .collect()
+}
+
+/// The first weekly reset (Monday 00:00 Swiss time) strictly after `now`.
+pub fn next_reset_after(now: NaiveDateTime) -> NaiveDateTime {
+    WeekId::containing(now.date()).end()
+}
+
+/// The week a reset at `reset` closes: the one that ended at that instant.
+pub fn week_closed_by(reset: NaiveDateTime) -> WeekId {
+    WeekId::containing(reset.date()).previous()
+}
+
+/// Real time left until the Swiss local time `target`, measured from `now`.
+/// Converting through the timezone keeps DST weeks exact: naive local arithmetic
+/// would be an hour off in the weeks the clocks change.
+pub fn duration_until(target: NaiveDateTime, now: DateTime<Utc>) -> std::time::Duration {
+    let target_utc = Zurich
+        .from_local_datetime(&target)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        // Only reachable for times inside a spring-forward gap, which midnight never is
+        .unwrap_or_else(|| Utc.from_utc_datetime(&target));
+    (target_utc - now).to_std().unwrap_or(std::time::Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        date(y, m, d).and_hms_opt(h, min, 0).unwrap()
+    }
+
+    fn wk(year: i32, week: u32) -> WeekId {
+        WeekId::new(year, week).unwrap()
+    }
+
+    #[test]
+    fn test_new_validates_week_53() {
+        // 2026 has 53 ISO weeks (it starts on a Thursday), 2025 does not
+        assert!(WeekId::new(2026, 53).is_some());
+        assert!(WeekId::new(2025, 53).is_none());
+        assert!(WeekId::new(2020, 53).is_some());
+        assert!(WeekId::new(2026, 0).is_none());
+        assert!(WeekId::new(2026, 54).is_none());
+    }
+
+    #[test]
+    fn test_containing_year_boundaries() {
+        // Early January belonging to the previous ISO year
+        assert_eq!(WeekId::containing(date(2027, 1, 1)), wk(2026, 53));
+        assert_eq!(WeekId::containing(date(2027, 1, 3)), wk(2026, 53));
+        assert_eq!(WeekId::containing(date(2027, 1, 4)), wk(2027, 1));
+        assert_eq!(WeekId::containing(date(2021, 1, 3)), wk(2020, 53));
+        assert_eq!(WeekId::containing(date(2023, 1, 1)), wk(2022, 52));
+        // Late December belonging to the next ISO year
+        assert_eq!(WeekId::containing(date(2024, 12, 30)), wk(2025, 1));
+        assert_eq!(WeekId::containing(date(2025, 12, 29)), wk(2026, 1));
+        assert_eq!(WeekId::containing(date(2025, 12, 28)), wk(2025, 52));
+    }
+
+    #[test]
+    fn test_previous_next_across_years() {
+        assert_eq!(wk(2027, 1).previous(), wk(2026, 53));
+        assert_eq!(wk(2026, 53).next(), wk(2027, 1));
+        assert_eq!(wk(2026, 1).previous(), wk(2025, 52));
+        assert_eq!(wk(2025, 52).next(), wk(2026, 1));
+        assert_eq!(wk(2026, 7).next().previous(), wk(2026, 7));
+    }
+
+    #[test]
+    fn test_date_range() {
+        let w = wk(2026, 7);
+        assert_eq!(w.monday(), date(2026, 2, 9));
+        assert_eq!(w.sunday(), date(2026, 2, 15));
+        assert_eq!(w.start(), at(2026, 2, 9, 0, 0));
+        assert_eq!(w.end(), at(2026, 2, 16, 0, 0));
+
+        // Week 1 of 2025 starts in December 2024
+        assert_eq!(wk(2025, 1).monday(), date(2024, 12, 30));
+    }
+
+    #[test]
+    fn test_every_day_of_a_decade_is_in_its_week() {
+        let mut day = date(2019, 12, 1);
+        while day < date(2031, 2, 1) {
+            let w = WeekId::containing(day);
+            assert!(w.monday() <= day && day <= w.sunday(), "{day} not in {w}");
+            assert_eq!(w.next().previous(), w);
+            assert_eq!(WeekId::parse(&w.label()), Some(w));
+            day += Duration::days(1);
+        }
+    }
+
+    #[test]
+    fn test_last_n() {
+        let weeks = wk(2027, 2).last_n(4);
+        assert_eq!(weeks, vec![wk(2026, 52), wk(2026, 53), wk(2027, 1), wk(2027, 2)]);
+        assert!(wk(2027, 2).last_n(0).is_empty());
+    }
+
+    #[test]
+    fn test_label_and_parse() {
+        assert_eq!(wk(2026, 7).label(), "KW07/2026");
+        assert_eq!(wk(2026, 7).to_string(), "KW07/2026");
+        assert_eq!(WeekId::parse("KW07/2026"), Some(wk(2026, 7)));
+        assert_eq!(WeekId::parse("KW7/2026"), Some(wk(2026, 7)));
+        assert_eq!(WeekId::parse("KW53/2025"), None);
+        assert_eq!(WeekId::parse("W07/2026"), None);
+        assert_eq!(WeekId::parse("KW07-2026"), None);
+    }
+
+    #[test]
+    fn test_next_reset_and_closed_week() {
+        // Midweek → coming Monday
+        assert_eq!(next_reset_after(at(2026, 2, 18, 13, 5)), at(2026, 2, 23, 0, 0));
+        // Exactly at a reset → the one after, never the same instant again
+        assert_eq!(next_reset_after(at(2026, 2, 16, 0, 0)), at(2026, 2, 23, 0, 0));
+        // Across the year boundary
+        assert_eq!(next_reset_after(at(2026, 12, 31, 12, 0)), at(2027, 1, 4, 0, 0));
+
+        assert_eq!(week_closed_by(at(2026, 2, 23, 0, 0)), wk(2026, 8));
+        assert_eq!(week_closed_by(at(2027, 1, 4, 0, 0)), wk(2026, 53));
+        assert_eq!(week_closed_by(at(2026, 1, 5, 0, 0)), wk(2026, 1));
+    }
+
+    #[test]
+    fn test_duration_until_across_dst() {
+        // Spring forward: Sunday 2026-03-29 02:00 CET → 03:00 CEST.
+        // Saturday 12:00 CET (11:00 UTC) to Monday 00:00 CEST (Sunday 22:00 UTC) is 35h.
+        let now = Utc.with_ymd_and_hms(2026, 3, 28, 11, 0, 0).unwrap();
+        let reset = next_reset_after(at(2026, 3, 28, 12, 0));
+        assert_eq!(duration_until(reset, now).as_secs(), 35 * 3600);
+
+        // Fall back: Sunday 2026-10-25 03:00 CEST → 02:00 CET.
+        // Saturday 12:00 CEST (10:00 UTC) to Monday 00:00 CET (Sunday 23:00 UTC) is 37h.
+        let now = Utc.with_ymd_and_hms(2026, 10, 24, 10, 0, 0).unwrap();
+        let reset = next_reset_after(at(2026, 10, 24, 12, 0));
+        assert_eq!(duration_until(reset, now).as_secs(), 37 * 3600);
+
+        // Ordinary week: naive and real time agree
+        let now = Utc.with_ymd_and_hms(2026, 2, 21, 11, 0, 0).unwrap();
+        let reset = next_reset_after(at(2026, 2, 21, 12, 0));
+        assert_eq!(duration_until(reset, now).as_secs(), 36 * 3600);
+
+        // Past targets clamp to zero
+        assert_eq!(duration_until(at(2020, 1, 1, 0, 0), now).as_secs(), 0);
+    }
+
+    /// Any minute between 1990 and 2100.
+    fn any_time() -> impl Strategy<Value = NaiveDateTime> {
+        (631_152_000i64..4_102_444_800).prop_map(|secs| DateTime::from_timestamp(secs - secs % 60, 0).unwrap().naive_utc())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_week_contains_its_dates(t in any_time()) {
+            let week = WeekId::containing(t.date());
+            prop_assert!(week.start() <= t && t < week.end());
+            prop_assert_eq!(week.monday().weekday(), Weekday::Mon);
+            prop_assert_eq!(week.end() - week.start(), Duration::weeks(1));
+        }
+
+        #[test]
+        fn prop_week_navigation_round_trips(t in any_time()) {
+            let week = WeekId::containing(t.date());
+            prop_assert_eq!(week.previous().next(), week);
+            prop_assert_eq!(week.next().previous(), week);
+            prop_assert!(week.previous() < week && week < week.next());
+            prop_assert_eq!(WeekId::parse(&week.label()), Some(week));
+        }
+
+        #[test]
+        fn prop_reset_closes_the_current_week(t in any_time()) {
+            let reset = next_reset_after(t);
+            prop_assert!(reset > t && reset - t <= Duration::weeks(1));
+            prop_assert_eq!(week_closed_by(reset), WeekId::containing(t.date()));
+        }
+
+        #[test]
+        fn prop_duration_until_matches_the_clock(t in any_time(), minutes in 0i64..14 * 24 * 60) {
+            // Real time, not local time: across a DST change the two differ by an hour
+            let now = Zurich.from_local_datetime(&t).earliest().unwrap_or_else(|| Zurich.from_utc_datetime(&t));
+            let target = (now + Duration::minutes(minutes)).naive_local();
+            let left = duration_until(target, now.with_timezone(&Utc)).as_secs() as i64;
+            // Fall-back hours are ambiguous and resolve to the earlier instant
+            prop_assert!(left <= minutes * 60 && left >= minutes * 60 - 3600, "{} minutes, {} seconds left", minutes, left);
+            prop_assert_eq!(duration_until(now.naive_local() - Duration::minutes(minutes), now.with_timezone(&Utc)).as_secs(), 0);
+        }
+    }
+}